@@ -0,0 +1,342 @@
+//! Conditioning applied to each channel of the audio input stream before its samples are forwarded
+//! to realtime sounds.
+//!
+//! Each input channel has a trim, a noise gate and a simple feed-forward compressor which are
+//! applied in that order. The levels of each channel are measured after conditioning so that they
+//! reflect what is actually sent to the realtime sounds.
+
+use audio::SAMPLE_RATE;
+use time_calc::Ms;
+
+/// The level in decibels considered to be silence.
+const SILENCE_DB: f32 = -120.0;
+
+/// The release of the level detector used to open and close the gate.
+const GATE_DETECTOR_RELEASE: Ms = Ms(50.0);
+
+/// Conditioning parameters for every channel of the audio input stream.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct Input {
+    /// The parameters for each input channel, indexed by channel.
+    ///
+    /// Channels beyond the end of the list use the default parameters, i.e. no conditioning.
+    #[serde(default)]
+    pub channels: Vec<Channel>,
+}
+
+/// Conditioning parameters for a single input channel.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Channel {
+    /// The gain applied to the channel before the gate and compressor.
+    #[serde(default = "default::trim_db")]
+    pub trim_db: f32,
+    #[serde(default)]
+    pub gate: Gate,
+    #[serde(default)]
+    pub compressor: Compressor,
+}
+
+/// A noise gate that silences the channel while its level remains below the threshold.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Gate {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default::gate_threshold_db")]
+    pub threshold_db: f32,
+    /// The time taken for the gate to open once the level exceeds the threshold.
+    #[serde(default = "default::gate_attack")]
+    pub attack: Ms,
+    /// The time taken for the gate to close once the level falls below the threshold.
+    #[serde(default = "default::gate_release")]
+    pub release: Ms,
+}
+
+/// A feed-forward compressor reducing the gain of the channel while above the threshold.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Compressor {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default::compressor_threshold_db")]
+    pub threshold_db: f32,
+    /// The ratio of the input level above the threshold to the output level above the threshold.
+    #[serde(default = "default::ratio")]
+    pub ratio: f32,
+    #[serde(default = "default::compressor_attack")]
+    pub attack: Ms,
+    #[serde(default = "default::compressor_release")]
+    pub release: Ms,
+    /// The gain applied after compression to make up for the reduction in level.
+    #[serde(default = "default::makeup_db")]
+    pub makeup_db: f32,
+}
+
+/// The levels of a single input channel over a single buffer.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Levels {
+    pub peak: f32,
+    pub rms: f32,
+}
+
+/// Applies the conditioning of a single input channel on the audio input thread.
+#[derive(Clone, Debug)]
+pub struct Processor {
+    trim: f32,
+    gate: Option<GateProcessor>,
+    compressor: Option<CompressorProcessor>,
+    // Accumulated for metering.
+    peak: f32,
+    sum_sq: f32,
+    frames: usize,
+}
+
+#[derive(Clone, Debug)]
+struct GateProcessor {
+    threshold: f32,
+    attack_coeff: f32,
+    release_coeff: f32,
+    detector_release_coeff: f32,
+    level: f32,
+    gain: f32,
+}
+
+#[derive(Clone, Debug)]
+struct CompressorProcessor {
+    threshold_db: f32,
+    slope: f32,
+    attack_coeff: f32,
+    release_coeff: f32,
+    makeup: f32,
+    level_db: f32,
+}
+
+impl Input {
+    /// The parameters for the given input channel.
+    pub fn channel(&self, channel: usize) -> Channel {
+        self.channels.get(channel).cloned().unwrap_or_default()
+    }
+
+    /// Mutable access to the parameters for the given input channel.
+    ///
+    /// Extends the list of channels with the default parameters as necessary.
+    pub fn channel_mut(&mut self, channel: usize) -> &mut Channel {
+        while self.channels.len() <= channel {
+            self.channels.push(Default::default());
+        }
+        &mut self.channels[channel]
+    }
+}
+
+impl Processor {
+    /// Create a processor for the given parameters.
+    pub fn new(channel: &Channel) -> Self {
+        let mut processor = Processor {
+            trim: 1.0,
+            gate: None,
+            compressor: None,
+            peak: 0.0,
+            sum_sq: 0.0,
+            frames: 0,
+        };
+        processor.set_params(channel);
+        processor
+    }
+
+    /// Update the parameters of the processor.
+    ///
+    /// The state of the gate and compressor envelopes is retained so that changes are smooth.
+    pub fn set_params(&mut self, channel: &Channel) {
+        self.trim = db_to_amp(channel.trim_db);
+
+        self.gate = match channel.gate.enabled {
+            false => None,
+            true => {
+                let (level, gain) = match self.gate {
+                    Some(ref gate) => (gate.level, gate.gain),
+                    None => (0.0, 1.0),
+                };
+                Some(GateProcessor {
+                    threshold: db_to_amp(channel.gate.threshold_db),
+                    attack_coeff: smoothing_coeff(channel.gate.attack),
+                    release_coeff: smoothing_coeff(channel.gate.release),
+                    detector_release_coeff: smoothing_coeff(GATE_DETECTOR_RELEASE),
+                    level,
+                    gain,
+                })
+            },
+        };
+
+        self.compressor = match channel.compressor.enabled {
+            false => None,
+            true => {
+                let level_db = match self.compressor {
+                    Some(ref compressor) => compressor.level_db,
+                    None => SILENCE_DB,
+                };
+                let ratio = channel.compressor.ratio.max(1.0);
+                Some(CompressorProcessor {
+                    threshold_db: channel.compressor.threshold_db,
+                    slope: 1.0 - 1.0 / ratio,
+                    attack_coeff: smoothing_coeff(channel.compressor.attack),
+                    release_coeff: smoothing_coeff(channel.compressor.release),
+                    makeup: db_to_amp(channel.compressor.makeup_db),
+                    level_db,
+                })
+            },
+        };
+    }
+
+    /// Condition a single sample.
+    pub fn process(&mut self, sample: f32) -> f32 {
+        let mut sample = sample * self.trim;
+
+        if let Some(ref mut gate) = self.gate {
+            let level = sample.abs();
+            gate.level = level.max(gate.level * gate.detector_release_coeff);
+            let (target, coeff) = match gate.level >= gate.threshold {
+                true => (1.0, gate.attack_coeff),
+                false => (0.0, gate.release_coeff),
+            };
+            gate.gain = target + (gate.gain - target) * coeff;
+            sample *= gate.gain;
+        }
+
+        if let Some(ref mut compressor) = self.compressor {
+            let level_db = amp_to_db(sample.abs());
+            let coeff = match level_db > compressor.level_db {
+                true => compressor.attack_coeff,
+                false => compressor.release_coeff,
+            };
+            compressor.level_db = level_db + (compressor.level_db - level_db) * coeff;
+            let over_db = compressor.level_db - compressor.threshold_db;
+            let reduction = match over_db > 0.0 {
+                true => db_to_amp(-over_db * compressor.slope),
+                false => 1.0,
+            };
+            sample *= reduction * compressor.makeup;
+        }
+
+        self.peak = self.peak.max(sample.abs());
+        self.sum_sq += sample * sample;
+        self.frames += 1;
+        sample
+    }
+
+    /// The levels of all samples processed since the last call, resetting the meter.
+    pub fn take_levels(&mut self) -> Levels {
+        let rms = match self.frames {
+            0 => 0.0,
+            frames => (self.sum_sq / frames as f32).sqrt(),
+        };
+        let levels = Levels { peak: self.peak, rms };
+        self.peak = 0.0;
+        self.sum_sq = 0.0;
+        self.frames = 0;
+        levels
+    }
+}
+
+impl Default for Channel {
+    fn default() -> Self {
+        let trim_db = default::trim_db();
+        let gate = Default::default();
+        let compressor = Default::default();
+        Channel { trim_db, gate, compressor }
+    }
+}
+
+impl Default for Gate {
+    fn default() -> Self {
+        let enabled = false;
+        let threshold_db = default::gate_threshold_db();
+        let attack = default::gate_attack();
+        let release = default::gate_release();
+        Gate { enabled, threshold_db, attack, release }
+    }
+}
+
+impl Default for Compressor {
+    fn default() -> Self {
+        let enabled = false;
+        let threshold_db = default::compressor_threshold_db();
+        let ratio = default::ratio();
+        let attack = default::compressor_attack();
+        let release = default::compressor_release();
+        let makeup_db = default::makeup_db();
+        Compressor { enabled, threshold_db, ratio, attack, release, makeup_db }
+    }
+}
+
+impl Default for Processor {
+    fn default() -> Self {
+        Processor::new(&Default::default())
+    }
+}
+
+/// Convert the given decibels to a linear amplitude.
+pub fn db_to_amp(db: f32) -> f32 {
+    10.0f32.powf(db / 20.0)
+}
+
+/// Convert the given linear amplitude to decibels, clamped to the level considered silence.
+pub fn amp_to_db(amp: f32) -> f32 {
+    (20.0 * amp.log10()).max(SILENCE_DB)
+}
+
+// The coefficient of a one-pole smoother reaching ~63% of its target within the given duration.
+fn smoothing_coeff(duration: Ms) -> f32 {
+    let frames = duration.ms() * 0.001 * SAMPLE_RATE;
+    if frames < 1.0 {
+        return 0.0;
+    }
+    (-1.0 / frames).exp() as f32
+}
+
+pub mod default {
+    use time_calc::Ms;
+
+    pub const TRIM_DB: f32 = 0.0;
+    pub const GATE_THRESHOLD_DB: f32 = -60.0;
+    pub const GATE_ATTACK: Ms = Ms(1.0);
+    pub const GATE_RELEASE: Ms = Ms(200.0);
+    pub const COMPRESSOR_THRESHOLD_DB: f32 = -18.0;
+    pub const RATIO: f32 = 4.0;
+    pub const COMPRESSOR_ATTACK: Ms = Ms(10.0);
+    pub const COMPRESSOR_RELEASE: Ms = Ms(100.0);
+    pub const MAKEUP_DB: f32 = 0.0;
+
+    pub fn trim_db() -> f32 {
+        TRIM_DB
+    }
+
+    pub fn gate_threshold_db() -> f32 {
+        GATE_THRESHOLD_DB
+    }
+
+    pub fn gate_attack() -> Ms {
+        GATE_ATTACK
+    }
+
+    pub fn gate_release() -> Ms {
+        GATE_RELEASE
+    }
+
+    pub fn compressor_threshold_db() -> f32 {
+        COMPRESSOR_THRESHOLD_DB
+    }
+
+    pub fn ratio() -> f32 {
+        RATIO
+    }
+
+    pub fn compressor_attack() -> Ms {
+        COMPRESSOR_ATTACK
+    }
+
+    pub fn compressor_release() -> Ms {
+        COMPRESSOR_RELEASE
+    }
+
+    pub fn makeup_db() -> f32 {
+        MAKEUP_DB
+    }
+}
@@ -0,0 +1,266 @@
+//! Sidechain ducking of soundscape sounds by the sounds of other sources.
+//!
+//! Each `Rule` describes a set of "trigger" sounds along with an installation. While the level of
+//! any of the trigger sounds exceeds the rule's threshold, all soundscape sounds within the
+//! installation are attenuated by the rule's depth. This allows visitor-triggered content to cut
+//! through the ambient bed of the soundscape.
+//!
+//! Levels are measured from the actual signal of each sound on the audio output thread.
+
+use audio::{sound, source, Sound, SAMPLE_RATE};
+use audio::conditioning::{amp_to_db, db_to_amp};
+use fxhash::FxHashSet;
+use installation;
+use soundscape::group;
+use time_calc::Ms;
+
+/// The role of a source, without any of the role's associated data.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Deserialize, Serialize)]
+pub enum Role {
+    Soundscape,
+    Interactive,
+    Scribbles,
+}
+
+/// Describes the sounds whose level triggers a ducking rule.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Deserialize, Serialize)]
+pub enum Trigger {
+    /// All sounds whose source has the given role.
+    Role(Role),
+    /// All sounds of the given source.
+    Source(source::Id),
+    /// All sounds whose soundscape source is a member of the given group.
+    Group(group::Id),
+}
+
+/// A rule describing how sounds of one kind duck the soundscape sounds of an installation.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Rule {
+    /// The sounds whose level causes the ducking.
+    pub trigger: Trigger,
+    /// The installation whose soundscape sounds are ducked.
+    pub installation: installation::Id,
+    /// The attenuation applied to the ducked sounds while triggered.
+    #[serde(default = "default::depth_db")]
+    pub depth_db: f32,
+    /// The peak level of the trigger sounds above which ducking is applied.
+    #[serde(default = "default::threshold_db")]
+    pub threshold_db: f32,
+    /// The duration over which the attenuation is applied once triggered.
+    #[serde(default = "default::attack")]
+    pub attack: Ms,
+    /// The duration over which the attenuation is removed once the trigger falls silent.
+    #[serde(default = "default::release")]
+    pub release: Ms,
+}
+
+/// The properties of a sound by which it may be matched against a rule's `Trigger`.
+#[derive(Clone, Debug, Default)]
+pub struct Tags {
+    pub role: Option<Role>,
+    pub groups: FxHashSet<group::Id>,
+}
+
+/// The ducking gain applied to a sound over the duration of a single buffer.
+///
+/// The gain is interpolated from `previous` to `current` to avoid discontinuities.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Gain {
+    pub previous: f32,
+    pub current: f32,
+}
+
+/// Evaluates the ducking rules on the audio output thread.
+#[derive(Default)]
+pub struct Ducker {
+    rules: Vec<Rule>,
+    // The gain of each rule's envelope, indexed by rule.
+    gains: Vec<Gain>,
+}
+
+impl Trigger {
+    /// Whether or not the given sound triggers the rule.
+    pub fn matches(&self, source_id: source::Id, tags: &Tags) -> bool {
+        match *self {
+            Trigger::Role(role) => tags.role == Some(role),
+            Trigger::Source(id) => source_id == id,
+            Trigger::Group(id) => tags.groups.contains(&id),
+        }
+    }
+}
+
+impl<'a> From<&'a Option<source::Role>> for Tags {
+    fn from(role: &'a Option<source::Role>) -> Self {
+        match *role {
+            None => Tags::default(),
+            Some(source::Role::Soundscape(ref soundscape)) => Tags {
+                role: Some(Role::Soundscape),
+                groups: soundscape.groups.clone(),
+            },
+            Some(source::Role::Interactive) => Tags {
+                role: Some(Role::Interactive),
+                groups: Default::default(),
+            },
+            Some(source::Role::Scribbles) => Tags {
+                role: Some(Role::Scribbles),
+                groups: Default::default(),
+            },
+        }
+    }
+}
+
+impl Gain {
+    /// No attenuation.
+    pub const UNITY: Self = Gain { previous: 1.0, current: 1.0 };
+
+    /// The gain at the given normalised position through the buffer.
+    pub fn at(&self, lerp_amt: f32) -> f32 {
+        self.previous + (self.current - self.previous) * lerp_amt
+    }
+}
+
+impl Ducker {
+    /// Replace the set of rules.
+    ///
+    /// The envelopes of rules that remain unchanged at the same index are retained to avoid jumps
+    /// in gain. The envelope at any index whose rule has changed is reset to unity so that, for
+    /// example, removing a rule does not leave its attenuation applied to the rule that follows.
+    pub fn set_rules(&mut self, rules: Vec<Rule>) {
+        for ((old, new), gain) in self.rules.iter().zip(&rules).zip(&mut self.gains) {
+            if old != new {
+                *gain = Gain::UNITY;
+            }
+        }
+        self.gains.resize(rules.len(), Gain::UNITY);
+        self.rules = rules;
+    }
+
+    /// Step the envelope of every rule forward by a single buffer of the given number of frames.
+    ///
+    /// `levels` yields the peak amplitude of every sound that is currently playing.
+    pub fn update<'a, I>(&mut self, levels: I, frames: usize)
+    where
+        I: Clone + Iterator<Item = (&'a Sound, f32)>,
+    {
+        for (rule, gain) in self.rules.iter().zip(self.gains.iter_mut()) {
+            let level = levels
+                .clone()
+                .filter(|&(sound, _)| rule.trigger.matches(sound.source_id(), &sound.ducking))
+                .fold(0.0, |max, (_, level)| level.max(max));
+            let (target, duration) = match amp_to_db(level) > rule.threshold_db {
+                true => (db_to_amp(-rule.depth_db), rule.attack),
+                false => (1.0, rule.release),
+            };
+            let coeff = buffer_coeff(duration, frames);
+            gain.previous = gain.current;
+            gain.current = target + (gain.current - target) * coeff;
+        }
+    }
+
+    /// The ducking gain to apply to the given sound.
+    ///
+    /// Only soundscape sounds assigned to a rule's installation are ducked, and sounds are never
+    /// ducked by a rule that they trigger.
+    pub fn sound_gain(&self, sound: &Sound) -> Gain {
+        let installations = match sound.installations {
+            sound::Installations::Set(ref set) => set,
+            sound::Installations::All => return Gain::UNITY,
+        };
+        let mut sound_gain = Gain::UNITY;
+        for (rule, gain) in self.rules.iter().zip(&self.gains) {
+            if !installations.contains(&rule.installation) {
+                continue;
+            }
+            if rule.trigger.matches(sound.source_id(), &sound.ducking) {
+                continue;
+            }
+            sound_gain.previous = sound_gain.previous.min(gain.previous);
+            sound_gain.current = sound_gain.current.min(gain.current);
+        }
+        sound_gain
+    }
+}
+
+// The smoothing coefficient that moves a one-pole envelope ~63% of the way towards its target over
+// the given duration, when stepped once per buffer of the given number of frames.
+fn buffer_coeff(duration: Ms, frames: usize) -> f32 {
+    let duration_frames = duration.ms() * 0.001 * SAMPLE_RATE;
+    if duration_frames < 1.0 {
+        return 0.0;
+    }
+    (-(frames as f64) / duration_frames).exp() as f32
+}
+
+pub mod default {
+    use time_calc::Ms;
+
+    pub const DEPTH_DB: f32 = 12.0;
+    pub const THRESHOLD_DB: f32 = -50.0;
+    pub const ATTACK: Ms = Ms(50.0);
+    pub const RELEASE: Ms = Ms(1_000.0);
+
+    pub fn depth_db() -> f32 {
+        DEPTH_DB
+    }
+
+    pub fn threshold_db() -> f32 {
+        THRESHOLD_DB
+    }
+
+    pub fn attack() -> Ms {
+        ATTACK
+    }
+
+    pub fn release() -> Ms {
+        RELEASE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use installation;
+    use super::{default, Ducker, Gain, Role, Rule, Trigger};
+
+    fn rule(role: Role, installation: usize) -> Rule {
+        Rule {
+            trigger: Trigger::Role(role),
+            installation: installation::Id(installation),
+            depth_db: default::DEPTH_DB,
+            threshold_db: default::THRESHOLD_DB,
+            attack: default::ATTACK,
+            release: default::RELEASE,
+        }
+    }
+
+    fn ducked(ducker: &mut Ducker) {
+        for gain in &mut ducker.gains {
+            *gain = Gain { previous: 0.5, current: 0.25 };
+        }
+    }
+
+    #[test]
+    fn unchanged_rules_retain_their_gain() {
+        let rules = vec![rule(Role::Interactive, 0), rule(Role::Scribbles, 1)];
+        let mut ducker = Ducker::default();
+        ducker.set_rules(rules.clone());
+        ducked(&mut ducker);
+        ducker.set_rules(rules);
+        assert!(ducker.gains.iter().all(|g| *g == Gain { previous: 0.5, current: 0.25 }));
+    }
+
+    #[test]
+    fn changed_rules_reset_their_gain() {
+        let mut ducker = Ducker::default();
+        ducker.set_rules(vec![rule(Role::Interactive, 0), rule(Role::Scribbles, 1)]);
+        ducked(&mut ducker);
+
+        // Removing the first rule moves the second rule to its index.
+        ducker.set_rules(vec![rule(Role::Scribbles, 1)]);
+        assert_eq!(ducker.gains, vec![Gain::UNITY]);
+
+        // Changing the installation of a rule is also a new rule.
+        ducked(&mut ducker);
+        ducker.set_rules(vec![rule(Role::Scribbles, 2), rule(Role::Interactive, 0)]);
+        assert_eq!(ducker.gains, vec![Gain::UNITY, Gain::UNITY]);
+    }
+}
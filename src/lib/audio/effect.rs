@@ -0,0 +1,848 @@
+//! Pre-spatial effects processing applied to a sound's signal before it is mapped onto the output
+//! channels via DBAP.
+//!
+//! Each `Source` may describe a chain of `Effect`s. Every effect parameter is described by a range
+//! from which a concrete value is chosen each time a sound is spawned, much like the
+//! `attack_duration` of a soundscape source. The resulting `Chain` is stored within the `Sound` and
+//! processes the sound's interleaved samples on the audio output thread.
+
+use audio::SAMPLE_RATE;
+use nannou::rand::Rng;
+use std::f32::consts::PI;
+use time_calc::Ms;
+use utils::Range;
+
+/// The maximum delay time supported by the `Delay` effect.
+pub const MAX_DELAY: Ms = Ms(2_000.0);
+
+/// The longest tail for which a sound is kept alive after its signal has been exhausted.
+pub const MAX_TAIL: Ms = Ms(30_000.0);
+
+/// The frequency of the low shelf used by the `Eq` effect.
+pub const EQ_LOW_SHELF_HZ: f32 = 200.0;
+
+/// The frequency of the high shelf used by the `Eq` effect.
+pub const EQ_HIGH_SHELF_HZ: f32 = 4_000.0;
+
+/// The quality factor of the `Eq`'s mid band.
+pub const EQ_MID_Q: f32 = 0.7;
+
+// Comb and allpass lengths in frames at 44.1KHz, based on the "freeverb" tunings.
+const REVERB_COMB_LENGTHS: [usize; 4] = [1116, 1188, 1277, 1356];
+const REVERB_ALLPASS_LENGTHS: [usize; 2] = [556, 441];
+const REVERB_INPUT_GAIN: f32 = 0.015;
+
+// The amplitude (-60dB) below which a delay or reverb tail is considered to have decayed.
+const TAIL_THRESHOLD: f32 = 0.001;
+
+/// A single effect within a source's pre-spatial effects chain.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum Effect {
+    Filter(Filter),
+    Eq(Eq),
+    Delay(Delay),
+    Bitcrush(Bitcrush),
+    Tremolo(Tremolo),
+    ReverbSend(ReverbSend),
+}
+
+/// The response of a `Filter` effect.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum FilterKind {
+    LowPass,
+    HighPass,
+    BandPass,
+}
+
+/// A resonant filter.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Filter {
+    #[serde(default = "default::filter_kind")]
+    pub kind: FilterKind,
+    #[serde(default = "default::cutoff_hz")]
+    pub cutoff_hz: Range<f32>,
+    #[serde(default = "default::resonance")]
+    pub resonance: Range<f32>,
+}
+
+/// A three band equaliser with low and high shelves and a peaking mid band.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Eq {
+    #[serde(default = "default::gain_db")]
+    pub low_gain_db: Range<f32>,
+    #[serde(default = "default::gain_db")]
+    pub mid_gain_db: Range<f32>,
+    #[serde(default = "default::mid_hz")]
+    pub mid_hz: Range<f32>,
+    #[serde(default = "default::gain_db")]
+    pub high_gain_db: Range<f32>,
+}
+
+/// A feedback delay.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Delay {
+    #[serde(default = "default::delay_time")]
+    pub time: Range<Ms>,
+    #[serde(default = "default::feedback")]
+    pub feedback: Range<f32>,
+    #[serde(default = "default::mix")]
+    pub mix: Range<f32>,
+}
+
+/// Bit depth and sample rate reduction.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Bitcrush {
+    #[serde(default = "default::bits")]
+    pub bits: Range<f32>,
+    /// The number of frames for which each sample is held.
+    #[serde(default = "default::downsample")]
+    pub downsample: Range<f32>,
+}
+
+/// Periodic amplitude modulation.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Tremolo {
+    #[serde(default = "default::rate_hz")]
+    pub rate_hz: Range<f32>,
+    #[serde(default = "default::depth")]
+    pub depth: Range<f32>,
+}
+
+/// A simple reverb whose output is mixed with the dry signal by the `send` amount.
+///
+/// The reverb is processed per sound so that the reverberated signal is spatialised along with
+/// the sound itself.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct ReverbSend {
+    #[serde(default = "default::send")]
+    pub send: Range<f32>,
+    #[serde(default = "default::room_size")]
+    pub room_size: Range<f32>,
+    #[serde(default = "default::damping")]
+    pub damping: Range<f32>,
+}
+
+/// The effects chain instantiated for a single sound.
+#[derive(Debug, Default)]
+pub struct Chain {
+    processors: Vec<Processor>,
+    // The number of frames for which the chain rings on once the sound's signal is exhausted.
+    tail_frames: usize,
+    // Whether or not the sound's signal has been exhausted and only the tail remains.
+    ringing_out: bool,
+}
+
+/// The processing state of a single effect within a `Chain`.
+#[derive(Debug)]
+enum Processor {
+    // One cascade of biquads per channel, used for both `Filter` and `Eq`.
+    Biquads(Vec<Vec<Biquad>>),
+    Delay {
+        buffer: Vec<f32>,
+        frame_index: usize,
+        feedback: f32,
+        mix: f32,
+    },
+    Bitcrush {
+        step: f32,
+        hold_frames: usize,
+        frame_countdown: usize,
+        held: Vec<f32>,
+    },
+    Tremolo {
+        phase: f32,
+        phase_step: f32,
+        depth: f32,
+    },
+    Reverb {
+        send: f32,
+        channels: Vec<Reverb>,
+    },
+}
+
+/// A biquad filter in transposed direct form II.
+#[derive(Copy, Clone, Debug)]
+struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    z1: f32,
+    z2: f32,
+}
+
+/// The reverb state for a single channel.
+#[derive(Debug)]
+struct Reverb {
+    combs: Vec<Comb>,
+    allpasses: Vec<Allpass>,
+}
+
+#[derive(Debug)]
+struct Comb {
+    buffer: Vec<f32>,
+    index: usize,
+    feedback: f32,
+    damping: f32,
+    filter_store: f32,
+}
+
+#[derive(Debug)]
+struct Allpass {
+    buffer: Vec<f32>,
+    index: usize,
+}
+
+/// Choose a value within the given range.
+fn gen_within<R>(rng: &mut R, range: Range<f32>) -> f32
+where
+    R: Rng,
+{
+    if range.min < range.max {
+        rng.gen_range(range.min, range.max)
+    } else {
+        range.min
+    }
+}
+
+impl Chain {
+    /// Instantiate an effects chain for a sound with the given number of channels.
+    ///
+    /// A concrete value is chosen for every effect parameter using the given `rng`. All memory
+    /// required by the chain is allocated here so that no allocation occurs on the audio thread.
+    pub fn from_effects<R>(effects: &[Effect], channels: usize, rng: &mut R) -> Self
+    where
+        R: Rng,
+    {
+        let processors: Vec<_> = effects
+            .iter()
+            .map(|effect| Processor::from_effect(effect, channels, rng))
+            .collect();
+        let max_tail_frames = MAX_TAIL.samples(SAMPLE_RATE) as usize;
+        let tail_frames = processors
+            .iter()
+            .map(|processor| processor.tail_frames(channels))
+            .sum::<usize>()
+            .min(max_tail_frames);
+        Chain { processors, tail_frames, ringing_out: false }
+    }
+
+    /// Whether or not the chain contains any effects.
+    pub fn is_empty(&self) -> bool {
+        self.processors.is_empty()
+    }
+
+    /// Whether or not the sound's signal has been exhausted and the chain is rendering its tail.
+    pub fn is_ringing_out(&self) -> bool {
+        self.ringing_out
+    }
+
+    /// Count down the tail of the chain by the given number of frames of silence that follow the
+    /// end of the sound's signal.
+    ///
+    /// Returns `false` once the tail has decayed, at which point the sound may be removed.
+    pub fn ring_out(&mut self, silent_frames: usize) -> bool {
+        self.ringing_out = true;
+        if self.tail_frames <= silent_frames {
+            self.tail_frames = 0;
+            return false;
+        }
+        self.tail_frames -= silent_frames;
+        true
+    }
+
+    /// Process the given interleaved samples in place.
+    pub fn process(&mut self, samples: &mut [f32], channels: usize) {
+        if channels == 0 {
+            return;
+        }
+        for processor in &mut self.processors {
+            processor.process(samples, channels);
+        }
+    }
+}
+
+impl Processor {
+    fn from_effect<R>(effect: &Effect, channels: usize, rng: &mut R) -> Self
+    where
+        R: Rng,
+    {
+        match *effect {
+            Effect::Filter(ref filter) => {
+                let hz = gen_within(rng, filter.cutoff_hz);
+                let q = gen_within(rng, filter.resonance);
+                let biquad = match filter.kind {
+                    FilterKind::LowPass => Biquad::low_pass(hz, q),
+                    FilterKind::HighPass => Biquad::high_pass(hz, q),
+                    FilterKind::BandPass => Biquad::band_pass(hz, q),
+                };
+                Processor::Biquads(vec![vec![biquad]; channels])
+            },
+
+            Effect::Eq(ref eq) => {
+                let low = Biquad::low_shelf(EQ_LOW_SHELF_HZ, gen_within(rng, eq.low_gain_db));
+                let mid_hz = gen_within(rng, eq.mid_hz);
+                let mid = Biquad::peak(mid_hz, EQ_MID_Q, gen_within(rng, eq.mid_gain_db));
+                let high = Biquad::high_shelf(EQ_HIGH_SHELF_HZ, gen_within(rng, eq.high_gain_db));
+                Processor::Biquads(vec![vec![low, mid, high]; channels])
+            },
+
+            Effect::Delay(ref delay) => {
+                let time = Range { min: delay.time.min.ms() as f32, max: delay.time.max.ms() as f32 };
+                let ms = gen_within(rng, time).min(MAX_DELAY.ms() as f32).max(0.0);
+                let frames = Ms(ms as _).samples(SAMPLE_RATE).max(1) as usize;
+                let buffer = vec![0.0; frames * channels];
+                let feedback = gen_within(rng, delay.feedback);
+                let mix = gen_within(rng, delay.mix);
+                Processor::Delay { buffer, frame_index: 0, feedback, mix }
+            },
+
+            Effect::Bitcrush(ref bitcrush) => {
+                let bits = gen_within(rng, bitcrush.bits).max(1.0);
+                let step = 2.0 / 2.0f32.powf(bits);
+                let hold_frames = gen_within(rng, bitcrush.downsample).round().max(1.0) as usize;
+                let held = vec![0.0; channels];
+                Processor::Bitcrush { step, hold_frames, frame_countdown: 0, held }
+            },
+
+            Effect::Tremolo(ref tremolo) => {
+                let rate_hz = gen_within(rng, tremolo.rate_hz);
+                let phase_step = rate_hz * 2.0 * PI / SAMPLE_RATE as f32;
+                let depth = gen_within(rng, tremolo.depth);
+                Processor::Tremolo { phase: 0.0, phase_step, depth }
+            },
+
+            Effect::ReverbSend(ref reverb) => {
+                let send = gen_within(rng, reverb.send);
+                let room_size = gen_within(rng, reverb.room_size);
+                let damping = gen_within(rng, reverb.damping);
+                let channels = (0..channels)
+                    .map(|_| Reverb::new(room_size, damping))
+                    .collect();
+                Processor::Reverb { send, channels }
+            },
+        }
+    }
+
+    // The number of frames for which the processor continues to produce output after its input
+    // has fallen silent.
+    //
+    // The ringing of the filters and the held sample of the bitcrusher are short enough to ignore.
+    fn tail_frames(&self, channels: usize) -> usize {
+        match *self {
+            Processor::Delay { ref buffer, feedback, mix, .. } => {
+                if mix <= 0.0 || channels == 0 {
+                    return 0;
+                }
+                let delay_frames = buffer.len() / channels;
+                let feedback = feedback.abs();
+                let repeats = if feedback == 0.0 {
+                    1
+                } else if feedback >= 1.0 {
+                    return MAX_TAIL.samples(SAMPLE_RATE) as usize;
+                } else {
+                    let further = (TAIL_THRESHOLD / mix).ln() / feedback.ln();
+                    1 + further.max(0.0).ceil() as usize
+                };
+                delay_frames * repeats
+            },
+
+            Processor::Reverb { send, channels: ref reverbs } => match reverbs.first() {
+                Some(reverb) if send > 0.0 => reverb.tail_frames(),
+                _ => 0,
+            },
+
+            Processor::Biquads(_) | Processor::Bitcrush { .. } | Processor::Tremolo { .. } => 0,
+        }
+    }
+
+    fn process(&mut self, samples: &mut [f32], channels: usize) {
+        match *self {
+            Processor::Biquads(ref mut cascades) => {
+                for frame in samples.chunks_mut(channels) {
+                    for (sample, cascade) in frame.iter_mut().zip(cascades.iter_mut()) {
+                        for biquad in cascade.iter_mut() {
+                            *sample = biquad.process(*sample);
+                        }
+                    }
+                }
+            },
+
+            Processor::Delay { ref mut buffer, ref mut frame_index, feedback, mix } => {
+                let delay_frames = buffer.len() / channels;
+                for frame in samples.chunks_mut(channels) {
+                    let start = *frame_index * channels;
+                    for (ch, sample) in frame.iter_mut().enumerate() {
+                        let delayed = buffer[start + ch];
+                        buffer[start + ch] = *sample + delayed * feedback;
+                        *sample = *sample * (1.0 - mix) + delayed * mix;
+                    }
+                    *frame_index = (*frame_index + 1) % delay_frames;
+                }
+            },
+
+            Processor::Bitcrush { step, hold_frames, ref mut frame_countdown, ref mut held } => {
+                for frame in samples.chunks_mut(channels) {
+                    if *frame_countdown == 0 {
+                        for (h, &sample) in held.iter_mut().zip(frame.iter()) {
+                            *h = (sample / step).round() * step;
+                        }
+                        *frame_countdown = hold_frames;
+                    }
+                    *frame_countdown -= 1;
+                    for (sample, &h) in frame.iter_mut().zip(held.iter()) {
+                        *sample = h;
+                    }
+                }
+            },
+
+            Processor::Tremolo { ref mut phase, phase_step, depth } => {
+                for frame in samples.chunks_mut(channels) {
+                    let gain = 1.0 - depth * (0.5 - 0.5 * phase.cos());
+                    for sample in frame.iter_mut() {
+                        *sample *= gain;
+                    }
+                    *phase = (*phase + phase_step) % (2.0 * PI);
+                }
+            },
+
+            Processor::Reverb { send, channels: ref mut reverbs } => {
+                for frame in samples.chunks_mut(channels) {
+                    for (sample, reverb) in frame.iter_mut().zip(reverbs.iter_mut()) {
+                        let wet = reverb.process(*sample);
+                        *sample += wet * send;
+                    }
+                }
+            },
+        }
+    }
+}
+
+impl Biquad {
+    fn new(b0: f32, b1: f32, b2: f32, a0: f32, a1: f32, a2: f32) -> Self {
+        Biquad {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    // The angular frequency and its cosine and sine for the given frequency.
+    fn omega(hz: f32) -> (f32, f32) {
+        let nyquist = SAMPLE_RATE as f32 * 0.5;
+        let hz = hz.max(1.0).min(nyquist * 0.99);
+        let w0 = 2.0 * PI * hz / SAMPLE_RATE as f32;
+        (w0.cos(), w0.sin())
+    }
+
+    fn low_pass(hz: f32, q: f32) -> Self {
+        let (cos, sin) = Biquad::omega(hz);
+        let alpha = sin / (2.0 * q.max(0.01));
+        let b1 = 1.0 - cos;
+        Biquad::new(b1 * 0.5, b1, b1 * 0.5, 1.0 + alpha, -2.0 * cos, 1.0 - alpha)
+    }
+
+    fn high_pass(hz: f32, q: f32) -> Self {
+        let (cos, sin) = Biquad::omega(hz);
+        let alpha = sin / (2.0 * q.max(0.01));
+        let b1 = -(1.0 + cos);
+        Biquad::new(-b1 * 0.5, b1, -b1 * 0.5, 1.0 + alpha, -2.0 * cos, 1.0 - alpha)
+    }
+
+    fn band_pass(hz: f32, q: f32) -> Self {
+        let (cos, sin) = Biquad::omega(hz);
+        let alpha = sin / (2.0 * q.max(0.01));
+        Biquad::new(alpha, 0.0, -alpha, 1.0 + alpha, -2.0 * cos, 1.0 - alpha)
+    }
+
+    fn peak(hz: f32, q: f32, gain_db: f32) -> Self {
+        let (cos, sin) = Biquad::omega(hz);
+        let alpha = sin / (2.0 * q.max(0.01));
+        let a = 10.0f32.powf(gain_db / 40.0);
+        Biquad::new(
+            1.0 + alpha * a,
+            -2.0 * cos,
+            1.0 - alpha * a,
+            1.0 + alpha / a,
+            -2.0 * cos,
+            1.0 - alpha / a,
+        )
+    }
+
+    fn low_shelf(hz: f32, gain_db: f32) -> Self {
+        let (cos, sin) = Biquad::omega(hz);
+        let a = 10.0f32.powf(gain_db / 40.0);
+        let beta = 2.0 * a.sqrt() * sin / 2.0f32.sqrt();
+        Biquad::new(
+            a * ((a + 1.0) - (a - 1.0) * cos + beta),
+            2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+            a * ((a + 1.0) - (a - 1.0) * cos - beta),
+            (a + 1.0) + (a - 1.0) * cos + beta,
+            -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+            (a + 1.0) + (a - 1.0) * cos - beta,
+        )
+    }
+
+    fn high_shelf(hz: f32, gain_db: f32) -> Self {
+        let (cos, sin) = Biquad::omega(hz);
+        let a = 10.0f32.powf(gain_db / 40.0);
+        let beta = 2.0 * a.sqrt() * sin / 2.0f32.sqrt();
+        Biquad::new(
+            a * ((a + 1.0) + (a - 1.0) * cos + beta),
+            -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+            a * ((a + 1.0) + (a - 1.0) * cos - beta),
+            (a + 1.0) - (a - 1.0) * cos + beta,
+            2.0 * ((a - 1.0) - (a + 1.0) * cos),
+            (a + 1.0) - (a - 1.0) * cos - beta,
+        )
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let output = self.b0 * input + self.z1;
+        self.z1 = self.b1 * input - self.a1 * output + self.z2;
+        self.z2 = self.b2 * input - self.a2 * output;
+        output
+    }
+}
+
+impl Reverb {
+    fn new(room_size: f32, damping: f32) -> Self {
+        let scale = SAMPLE_RATE as f32 / 44_100.0;
+        let feedback = 0.7 + room_size.max(0.0).min(1.0) * 0.28;
+        let damping = damping.max(0.0).min(1.0) * 0.4;
+        let combs = REVERB_COMB_LENGTHS
+            .iter()
+            .map(|&len| Comb {
+                buffer: vec![0.0; (len as f32 * scale) as usize],
+                index: 0,
+                feedback,
+                damping,
+                filter_store: 0.0,
+            })
+            .collect();
+        let allpasses = REVERB_ALLPASS_LENGTHS
+            .iter()
+            .map(|&len| Allpass {
+                buffer: vec![0.0; (len as f32 * scale) as usize],
+                index: 0,
+            })
+            .collect();
+        Reverb { combs, allpasses }
+    }
+
+    // The number of frames until the longest comb has decayed below the tail threshold.
+    fn tail_frames(&self) -> usize {
+        let allpass_frames: usize = self.allpasses.iter().map(|a| a.buffer.len()).sum();
+        let comb_frames = self
+            .combs
+            .iter()
+            .map(|comb| {
+                let feedback = comb.feedback.abs();
+                if feedback >= 1.0 {
+                    return MAX_TAIL.samples(SAMPLE_RATE) as usize;
+                }
+                let repeats = (TAIL_THRESHOLD.ln() / feedback.ln()).max(0.0).ceil() as usize;
+                comb.buffer.len() * (1 + repeats)
+            })
+            .max()
+            .unwrap_or(0);
+        comb_frames + allpass_frames
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let input = input * REVERB_INPUT_GAIN;
+        let mut output = 0.0;
+        for comb in &mut self.combs {
+            let delayed = comb.buffer[comb.index];
+            comb.filter_store = delayed * (1.0 - comb.damping) + comb.filter_store * comb.damping;
+            comb.buffer[comb.index] = input + comb.filter_store * comb.feedback;
+            comb.index = (comb.index + 1) % comb.buffer.len();
+            output += delayed;
+        }
+        for allpass in &mut self.allpasses {
+            let delayed = allpass.buffer[allpass.index];
+            allpass.buffer[allpass.index] = output + delayed * 0.5;
+            allpass.index = (allpass.index + 1) % allpass.buffer.len();
+            output = delayed - output;
+        }
+        output
+    }
+}
+
+pub mod default {
+    use super::FilterKind;
+    use time_calc::Ms;
+    use utils::Range;
+
+    pub const FILTER_KIND: FilterKind = FilterKind::LowPass;
+    pub const CUTOFF_HZ: Range<f32> = Range { min: 2_000.0, max: 2_000.0 };
+    pub const RESONANCE: Range<f32> = Range { min: 0.707, max: 0.707 };
+    pub const GAIN_DB: Range<f32> = Range { min: 0.0, max: 0.0 };
+    pub const MID_HZ: Range<f32> = Range { min: 1_000.0, max: 1_000.0 };
+    pub const DELAY_TIME: Range<Ms> = Range { min: Ms(250.0), max: Ms(250.0) };
+    pub const FEEDBACK: Range<f32> = Range { min: 0.3, max: 0.3 };
+    pub const MIX: Range<f32> = Range { min: 0.3, max: 0.3 };
+    pub const BITS: Range<f32> = Range { min: 8.0, max: 8.0 };
+    pub const DOWNSAMPLE: Range<f32> = Range { min: 1.0, max: 1.0 };
+    pub const RATE_HZ: Range<f32> = Range { min: 4.0, max: 4.0 };
+    pub const DEPTH: Range<f32> = Range { min: 0.5, max: 0.5 };
+    pub const SEND: Range<f32> = Range { min: 0.3, max: 0.3 };
+    pub const ROOM_SIZE: Range<f32> = Range { min: 0.5, max: 0.5 };
+    pub const DAMPING: Range<f32> = Range { min: 0.5, max: 0.5 };
+
+    pub fn filter_kind() -> FilterKind {
+        FILTER_KIND
+    }
+
+    pub fn cutoff_hz() -> Range<f32> {
+        CUTOFF_HZ
+    }
+
+    pub fn resonance() -> Range<f32> {
+        RESONANCE
+    }
+
+    pub fn gain_db() -> Range<f32> {
+        GAIN_DB
+    }
+
+    pub fn mid_hz() -> Range<f32> {
+        MID_HZ
+    }
+
+    pub fn delay_time() -> Range<Ms> {
+        DELAY_TIME
+    }
+
+    pub fn feedback() -> Range<f32> {
+        FEEDBACK
+    }
+
+    pub fn mix() -> Range<f32> {
+        MIX
+    }
+
+    pub fn bits() -> Range<f32> {
+        BITS
+    }
+
+    pub fn downsample() -> Range<f32> {
+        DOWNSAMPLE
+    }
+
+    pub fn rate_hz() -> Range<f32> {
+        RATE_HZ
+    }
+
+    pub fn depth() -> Range<f32> {
+        DEPTH
+    }
+
+    pub fn send() -> Range<f32> {
+        SEND
+    }
+
+    pub fn room_size() -> Range<f32> {
+        ROOM_SIZE
+    }
+
+    pub fn damping() -> Range<f32> {
+        DAMPING
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use audio::SAMPLE_RATE;
+    use nannou::rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+    use super::{Biquad, Chain, Delay, Effect, Eq, ReverbSend, Tremolo, MAX_TAIL, TAIL_THRESHOLD};
+    use time_calc::Ms;
+    use utils::Range;
+
+    fn fixed<T: Copy>(value: T) -> Range<T> {
+        Range { min: value, max: value }
+    }
+
+    fn chain(effects: &[Effect], channels: usize) -> Chain {
+        let mut rng = XorShiftRng::from_seed([1; 16]);
+        Chain::from_effects(effects, channels, &mut rng)
+    }
+
+    fn delay(ms: f64, feedback: f32, mix: f32) -> Effect {
+        Effect::Delay(Delay { time: fixed(Ms(ms)), feedback: fixed(feedback), mix: fixed(mix) })
+    }
+
+    // A deterministic test signal within [-1, 1].
+    fn signal(len: usize) -> Vec<f32> {
+        (0..len).map(|i| ((i * 7919) % 2001) as f32 / 1000.0 - 1.0).collect()
+    }
+
+    fn impulse(len: usize) -> Vec<f32> {
+        let mut samples = vec![0.0; len];
+        samples[0] = 1.0;
+        samples
+    }
+
+    // Check the stability triangle for the poles of a normalised biquad.
+    fn is_stable(biquad: &Biquad) -> bool {
+        biquad.a2.abs() < 1.0 && biquad.a1.abs() < 1.0 + biquad.a2
+    }
+
+    #[test]
+    fn empty_chain_bypasses() {
+        let input = signal(512);
+        let mut samples = input.clone();
+        let mut chain = chain(&[], 2);
+        assert!(chain.is_empty());
+        chain.process(&mut samples, 2);
+        assert_eq!(samples, input);
+    }
+
+    #[test]
+    fn flat_eq_and_shallow_tremolo_are_unity() {
+        let eq = Eq {
+            low_gain_db: fixed(0.0),
+            mid_gain_db: fixed(0.0),
+            mid_hz: fixed(1_000.0),
+            high_gain_db: fixed(0.0),
+        };
+        let tremolo = Tremolo { rate_hz: fixed(4.0), depth: fixed(0.0) };
+        let effects = [Effect::Eq(eq), Effect::Tremolo(tremolo)];
+        let input = signal(4_096);
+        let mut samples = input.clone();
+        chain(&effects, 2).process(&mut samples, 2);
+        for (a, b) in samples.iter().zip(&input) {
+            assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+        }
+    }
+
+    #[test]
+    fn low_pass_passes_dc() {
+        let mut biquad = Biquad::low_pass(1_000.0, 0.707);
+        let mut output = 0.0;
+        for _ in 0..4_800 {
+            output = biquad.process(1.0);
+        }
+        assert!((output - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn filter_coefficients_are_stable() {
+        let hzs = [20.0, 50.0, 200.0, 1_000.0, 5_000.0, 15_000.0, 23_000.0, 40_000.0];
+        let qs = [0.01, 0.1, 0.707, 2.0, 10.0];
+        let gains = [-24.0, -6.0, 0.0, 6.0, 24.0];
+        for &hz in &hzs {
+            for &q in &qs {
+                assert!(is_stable(&Biquad::low_pass(hz, q)), "low pass {} {}", hz, q);
+                assert!(is_stable(&Biquad::high_pass(hz, q)), "high pass {} {}", hz, q);
+                assert!(is_stable(&Biquad::band_pass(hz, q)), "band pass {} {}", hz, q);
+                for &db in &gains {
+                    assert!(is_stable(&Biquad::peak(hz, q, db)), "peak {} {} {}", hz, q, db);
+                }
+            }
+            for &db in &gains {
+                assert!(is_stable(&Biquad::low_shelf(hz, db)), "low shelf {} {}", hz, db);
+                assert!(is_stable(&Biquad::high_shelf(hz, db)), "high shelf {} {}", hz, db);
+            }
+        }
+    }
+
+    #[test]
+    fn filter_impulse_response_decays() {
+        let biquads = [
+            Biquad::low_pass(1_000.0, 10.0),
+            Biquad::high_pass(1_000.0, 10.0),
+            Biquad::band_pass(1_000.0, 10.0),
+            Biquad::peak(1_000.0, 10.0, 24.0),
+        ];
+        for &biquad in &biquads {
+            let mut biquad = biquad;
+            let mut output = biquad.process(1.0);
+            for _ in 0..48_000 {
+                output = biquad.process(0.0);
+            }
+            assert!(output.abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn delay_repeats_after_delay_time() {
+        // 10ms at 48KHz is 480 frames.
+        let frames = 480;
+        let mut samples = impulse(frames * 4);
+        chain(&[delay(10.0, 0.5, 0.5)], 1).process(&mut samples, 1);
+        for (i, &sample) in samples.iter().enumerate() {
+            let expected = match i {
+                0 => 0.5,
+                i if i == frames => 0.5,
+                i if i == frames * 2 => 0.25,
+                i if i == frames * 3 => 0.125,
+                _ => 0.0,
+            };
+            assert!((sample - expected).abs() < 1e-6, "sample {}: {}", i, sample);
+        }
+    }
+
+    #[test]
+    fn delay_is_per_channel() {
+        let frames = 480;
+        let mut samples = vec![0.0; frames * 2 * 2];
+        samples[1] = 1.0;
+        chain(&[delay(10.0, 0.0, 1.0)], 2).process(&mut samples, 2);
+        assert_eq!(samples[frames * 2], 0.0);
+        assert_eq!(samples[frames * 2 + 1], 1.0);
+        assert_eq!(samples.iter().filter(|&&s| s != 0.0).count(), 1);
+    }
+
+    #[test]
+    fn delay_tail_outlasts_echoes() {
+        let mut chain = chain(&[delay(10.0, 0.5, 0.5)], 1);
+        let tail_frames = chain.tail_frames;
+        let mut samples = impulse(tail_frames + 4_800);
+        chain.process(&mut samples, 1);
+        assert!(samples[tail_frames..].iter().all(|s| s.abs() < TAIL_THRESHOLD));
+        assert!(samples[..tail_frames].iter().any(|s| s.abs() >= TAIL_THRESHOLD));
+    }
+
+    #[test]
+    fn reverb_tail_outlasts_decay() {
+        let reverb = ReverbSend { send: fixed(1.0), room_size: fixed(0.5), damping: fixed(0.5) };
+        let mut chain = chain(&[Effect::ReverbSend(reverb)], 1);
+        let tail_frames = chain.tail_frames;
+        assert!(tail_frames > 0);
+        let mut samples = impulse(tail_frames + 4_800);
+        chain.process(&mut samples, 1);
+        assert!(samples[tail_frames..].iter().all(|s| s.abs() < TAIL_THRESHOLD));
+    }
+
+    #[test]
+    fn ring_out_counts_down_tail() {
+        let mut chain = chain(&[delay(10.0, 0.0, 0.5)], 1);
+        assert_eq!(chain.tail_frames, 480);
+        assert!(!chain.is_ringing_out());
+        assert!(chain.ring_out(200));
+        assert!(chain.is_ringing_out());
+        assert!(chain.ring_out(279));
+        assert!(!chain.ring_out(1));
+    }
+
+    #[test]
+    fn chain_without_tail_ends_immediately() {
+        let tremolo = Tremolo { rate_hz: fixed(4.0), depth: fixed(0.5) };
+        let mut chain = chain(&[Effect::Tremolo(tremolo)], 2);
+        assert_eq!(chain.tail_frames, 0);
+        assert!(!chain.ring_out(0));
+    }
+
+    #[test]
+    fn endless_feedback_is_limited_to_max_tail() {
+        let chain = chain(&[delay(10.0, 1.0, 0.5)], 1);
+        assert_eq!(chain.tail_frames, MAX_TAIL.samples(SAMPLE_RATE) as usize);
+    }
+}
@@ -2,12 +2,14 @@
 //!
 //! The input stream has a number of `Source`s that read from one or more of the stream's channels.
 
-use audio::source;
+use audio::{conditioning, latency, source};
+use audio::{FRAMES_PER_BUFFER, MAX_CHANNELS};
 use fxhash::FxHashMap;
+use gui;
 use nannou_audio::Buffer;
 use std::cmp;
 use std::sync::Arc;
-use std::sync::atomic::{self, AtomicBool};
+use std::sync::atomic::{self, AtomicBool, AtomicUsize};
 
 /// Simplified type alias for the nannou audio input stream used by the audio server.
 pub type Stream = nannou_audio::Stream<Model>;
@@ -18,6 +20,14 @@ pub struct Model {
     pub sources: FxHashMap<source::Id, source::Realtime>,
     // The currently active sounds using the realtime source with the given source ID.
     pub active_sounds: FxHashMap<source::Id, Vec<ActiveSound>>,
+    // Detects clicks on the input while a latency measurement is in progress.
+    pub latency_detector: Option<latency::Detector>,
+    // The trim, gate and compressor applied to each input channel.
+    conditioning: Vec<conditioning::Processor>,
+    // The conditioned samples of the current buffer, from which realtime sounds are fed.
+    conditioned: Vec<f32>,
+    // For sending the level of each input channel to the GUI.
+    gui_audio_monitor_msg_tx: gui::monitor::Sender,
 }
 
 /// The duration of an active sound's playback.
@@ -44,6 +54,10 @@ pub struct ActiveSound {
     pub buffer_tx: source::realtime::BufferTx,
     /// Receives used buffers read for re-use.
     pub buffer_rx: source::realtime::BufferRx,
+    /// The number of samples sent to the `Sound` that have not yet been received.
+    ///
+    /// Used by the `Sound`'s signal to compensate for drift between the input and output clocks.
+    pub queued_samples: Arc<AtomicUsize>,
 }
 
 impl Model {
@@ -51,12 +65,26 @@ impl Model {
     ///
     /// This pre-allocates all possibly required memory for all of the model's buffers in order to
     /// avoid unexpected dynamic allocation within on the audio thread.
-    pub fn new() -> Self {
+    pub fn new(gui_audio_monitor_msg_tx: gui::monitor::Sender) -> Self {
         let sources = Default::default();
         let active_sounds = Default::default();
+        let latency_detector = None;
+        let conditioning = vec![Default::default(); MAX_CHANNELS];
+        let conditioned = Vec::with_capacity(FRAMES_PER_BUFFER * MAX_CHANNELS);
         Model {
             sources,
             active_sounds,
+            latency_detector,
+            conditioning,
+            conditioned,
+            gui_audio_monitor_msg_tx,
+        }
+    }
+
+    /// Update the conditioning applied to the given input channel.
+    pub fn set_channel_conditioning(&mut self, channel: usize, params: &conditioning::Channel) {
+        if let Some(processor) = self.conditioning.get_mut(channel) {
+            processor.set_params(params);
         }
     }
 
@@ -66,6 +94,9 @@ impl Model {
     pub fn clear_project_specific_data(&mut self) {
         self.sources.clear();
         self.active_sounds.clear();
+        for processor in &mut self.conditioning {
+            processor.set_params(&Default::default());
+        }
     }
 }
 
@@ -74,8 +105,33 @@ pub fn capture(model: &mut Model, buffer: &Buffer) {
     let Model {
         ref sources,
         ref mut active_sounds,
+        ref mut latency_detector,
+        ref mut conditioning,
+        ref mut conditioned,
+        ref gui_audio_monitor_msg_tx,
     } = *model;
 
+    // Search for latency measurement clicks within the unconditioned input.
+    if let Some(detector) = latency_detector.as_mut() {
+        detector.capture(buffer);
+    }
+
+    // Apply the trim, gate and compressor of each channel.
+    let n_channels = buffer.channels();
+    conditioned.clear();
+    for frame in buffer.frames() {
+        for (&sample, processor) in frame.iter().zip(conditioning.iter_mut()) {
+            conditioned.push(processor.process(sample));
+        }
+    }
+
+    // Send the level of each channel to the GUI.
+    for (channel, processor) in conditioning.iter_mut().take(n_channels).enumerate() {
+        let conditioning::Levels { peak, rms } = processor.take_levels();
+        let msg = gui::AudioMonitorMessage::Input { channel, peak, rms };
+        gui_audio_monitor_msg_tx.push(msg);
+    }
+
     // Remove any sounds that have been closed.
     for sounds in active_sounds.values_mut() {
         sounds.retain(|s| !s.is_closed.load(atomic::Ordering::Relaxed));
@@ -124,11 +180,15 @@ pub fn capture(model: &mut Model, buffer: &Buffer) {
             let end = cmp::min(realtime.channels.end, buffer.channels());
 
             // Read the necessary samples from the buffer.
-            for frame in buffer.frames().take(frames_to_take) {
+            for frame in conditioned.chunks(n_channels).take(frames_to_take) {
                 samples.extend(frame[start..end].iter().cloned());
             }
 
             // Send the buffer to the realtime signal.
+            //
+            // The queued samples are counted first so that the signal never receives samples
+            // that have not yet been counted.
+            sound.queued_samples.fetch_add(samples.len(), atomic::Ordering::Relaxed);
             sound.buffer_tx.push(samples);
         }
     }
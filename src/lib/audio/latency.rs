@@ -0,0 +1,250 @@
+//! Measurement of the round-trip latency between the audio output and input streams.
+//!
+//! A series of clicks is emitted on a chosen output channel which is routed back to a chosen input
+//! channel, e.g. via a loopback cable or a microphone placed in front of a speaker. The time
+//! between emitting and detecting each click describes the round-trip latency, from which a safe
+//! `realtime_source_latency` may be derived.
+
+use audio::{FRAMES_PER_BUFFER, SAMPLE_RATE};
+use crossbeam::sync::SegQueue;
+use nannou_audio::Buffer;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use time_calc::Ms;
+
+/// The number of clicks emitted during a single measurement.
+pub const NUM_CLICKS: usize = 8;
+
+/// The amplitude above which the input signal is considered to contain a click.
+pub const DETECTION_THRESHOLD: f32 = 0.1;
+
+/// The number of frames between the onset of each click.
+const CLICK_INTERVAL_FRAMES: usize = (SAMPLE_RATE * 0.5) as usize;
+
+/// The duration of each click in frames.
+///
+/// Each click is a single cycle square pulse, giving a sharp onset that is easy to detect.
+const CLICK_FRAMES: usize = 48;
+
+/// The amplitude of each click.
+const CLICK_AMPLITUDE: f32 = 0.5;
+
+/// The number of frames ignored following a detected click so that its tail and any
+/// reflections are not mistaken for the next click.
+const HOLDOFF_FRAMES: usize = (SAMPLE_RATE * 0.25) as usize;
+
+/// Clicks not detected within this many seconds of being emitted are considered lost.
+const TIMEOUT_SECS: f64 = 2.0;
+
+/// Emits clicks onto the output stream.
+///
+/// Stored within the `audio::output::Model` while a measurement is in progress.
+pub struct Emitter {
+    channel: usize,
+    clicks_remaining: usize,
+    frames_until_click: usize,
+    // The position within the current click if one is being emitted.
+    click_frame: Option<usize>,
+    emitted_tx: Arc<SegQueue<Instant>>,
+}
+
+/// Detects clicks within the input stream.
+///
+/// Stored within the `audio::input::Model` while a measurement is in progress.
+pub struct Detector {
+    channel: usize,
+    holdoff_frames: usize,
+    detected_tx: Arc<SegQueue<Instant>>,
+}
+
+/// The GUI's handle to a measurement in progress.
+pub struct Measurement {
+    emitted_rx: Arc<SegQueue<Instant>>,
+    detected_rx: Arc<SegQueue<Instant>>,
+    emitted: Vec<Instant>,
+    detected: Vec<Instant>,
+}
+
+/// The result of a successful measurement.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Latency {
+    /// The median time between emitting a click and detecting it.
+    pub round_trip: Ms,
+    /// The longest measured round trip plus a buffer's worth of margin for the jitter between the
+    /// input and output callbacks.
+    pub safe: Ms,
+    /// The number of emitted clicks that were detected.
+    pub clicks_detected: usize,
+}
+
+/// The state of a measurement.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Status {
+    /// Clicks are still being emitted or awaited.
+    Measuring { clicks_emitted: usize, clicks_detected: usize },
+    /// The measurement completed successfully.
+    Complete(Latency),
+    /// None of the emitted clicks were detected.
+    Failed,
+}
+
+impl Emitter {
+    /// Write any clicks that fall within the given buffer onto the emitter's channel.
+    ///
+    /// Should be called at the end of the output stream's render function.
+    pub fn render(&mut self, buffer: &mut Buffer) {
+        if self.channel >= buffer.channels() {
+            return;
+        }
+        let now = Instant::now();
+        let channel = self.channel;
+        for (frame_i, frame) in buffer.frames_mut().enumerate() {
+            if self.click_frame.is_none() {
+                if self.clicks_remaining == 0 {
+                    break;
+                }
+                if self.frames_until_click > 0 {
+                    self.frames_until_click -= 1;
+                    continue;
+                }
+                self.click_frame = Some(0);
+                self.clicks_remaining -= 1;
+                self.frames_until_click = CLICK_INTERVAL_FRAMES - CLICK_FRAMES;
+                self.emitted_tx.push(now + frames_to_duration(frame_i));
+            }
+            if let Some(click_frame) = self.click_frame {
+                frame[channel] = match click_frame < CLICK_FRAMES / 2 {
+                    true => CLICK_AMPLITUDE,
+                    false => -CLICK_AMPLITUDE,
+                };
+                self.click_frame = match click_frame + 1 {
+                    next if next < CLICK_FRAMES => Some(next),
+                    _ => None,
+                };
+            }
+        }
+    }
+}
+
+impl Detector {
+    /// Search the given input buffer for the onset of a click on the detector's channel.
+    pub fn capture(&mut self, buffer: &Buffer) {
+        if self.channel >= buffer.channels() {
+            return;
+        }
+        let now = Instant::now();
+        let len_frames = buffer.len_frames();
+        for (frame_i, frame) in buffer.frames().enumerate() {
+            if self.holdoff_frames > 0 {
+                self.holdoff_frames -= 1;
+                continue;
+            }
+            if frame[self.channel].abs() > DETECTION_THRESHOLD {
+                // The callback is invoked once the whole buffer has been captured.
+                let frames_ago = len_frames - frame_i;
+                self.detected_tx.push(now - frames_to_duration(frames_ago));
+                self.holdoff_frames = HOLDOFF_FRAMES;
+            }
+        }
+    }
+}
+
+impl Measurement {
+    /// Begin a new measurement between the given output and input channels.
+    ///
+    /// The returned `Emitter` and `Detector` should be sent to the audio output and input streams
+    /// respectively.
+    pub fn new(output_channel: usize, input_channel: usize) -> (Self, Emitter, Detector) {
+        let emitted_queue = Arc::new(SegQueue::new());
+        let detected_queue = Arc::new(SegQueue::new());
+        let emitter = Emitter {
+            channel: output_channel,
+            clicks_remaining: NUM_CLICKS,
+            frames_until_click: CLICK_INTERVAL_FRAMES / 2,
+            click_frame: None,
+            emitted_tx: emitted_queue.clone(),
+        };
+        let detector = Detector {
+            channel: input_channel,
+            holdoff_frames: 0,
+            detected_tx: detected_queue.clone(),
+        };
+        let measurement = Measurement {
+            emitted_rx: emitted_queue,
+            detected_rx: detected_queue,
+            emitted: Vec::with_capacity(NUM_CLICKS),
+            detected: Vec::new(),
+        };
+        (measurement, emitter, detector)
+    }
+
+    /// Collect any emitted or detected clicks and produce the current status of the measurement.
+    pub fn update(&mut self) -> Status {
+        while let Some(instant) = self.emitted_rx.try_pop() {
+            self.emitted.push(instant);
+        }
+        while let Some(instant) = self.detected_rx.try_pop() {
+            self.detected.push(instant);
+        }
+
+        let round_trips = self.round_trips();
+        let is_complete = self.emitted.len() == NUM_CLICKS
+            && (round_trips.len() == NUM_CLICKS
+                || self.emitted[NUM_CLICKS - 1].elapsed() > secs_to_duration(TIMEOUT_SECS));
+        if !is_complete {
+            let clicks_emitted = self.emitted.len();
+            let clicks_detected = round_trips.len();
+            return Status::Measuring { clicks_emitted, clicks_detected };
+        }
+
+        let mut round_trips = round_trips;
+        if round_trips.is_empty() {
+            return Status::Failed;
+        }
+        round_trips.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let median = round_trips[round_trips.len() / 2];
+        let max = round_trips[round_trips.len() - 1];
+        let margin = FRAMES_PER_BUFFER as f64 * 1_000.0 / SAMPLE_RATE;
+        let latency = Latency {
+            round_trip: Ms(median),
+            safe: Ms(max + margin),
+            clicks_detected: round_trips.len(),
+        };
+        Status::Complete(latency)
+    }
+
+    // Pair each emitted click with the first detection that follows it within the timeout,
+    // producing the round trip of each in milliseconds.
+    fn round_trips(&self) -> Vec<f64> {
+        let timeout = secs_to_duration(TIMEOUT_SECS);
+        let mut detected = self.detected.iter().peekable();
+        let mut round_trips = Vec::with_capacity(self.emitted.len());
+        for &emitted in &self.emitted {
+            // Skip detections that preceded this click, e.g. noise or a lost click.
+            while detected.peek().map(|&&d| d < emitted).unwrap_or(false) {
+                detected.next();
+            }
+            if let Some(&&d) = detected.peek() {
+                let round_trip = d.duration_since(emitted);
+                if round_trip < timeout {
+                    round_trips.push(duration_to_ms(round_trip));
+                    detected.next();
+                }
+            }
+        }
+        round_trips
+    }
+}
+
+fn frames_to_duration(frames: usize) -> Duration {
+    secs_to_duration(frames as f64 / SAMPLE_RATE)
+}
+
+fn secs_to_duration(secs: f64) -> Duration {
+    let whole = secs.trunc();
+    Duration::new(whole as u64, ((secs - whole) * 1e9) as u32)
+}
+
+fn duration_to_ms(duration: Duration) -> f64 {
+    duration.as_secs() as f64 * 1_000.0 + duration.subsec_nanos() as f64 / 1e6
+}
@@ -7,12 +7,17 @@ pub use self::sound::Sound;
 pub use self::source::Source;
 pub use self::speaker::Speaker;
 
+pub mod conditioning;
 pub mod dbap;
 pub mod detection;
 pub mod detector;
+pub mod ducking;
+pub mod effect;
 pub mod fft;
 pub mod input;
+pub mod latency;
 pub mod output;
+pub mod scene;
 pub mod sound;
 pub mod source;
 pub mod speaker;
@@ -42,6 +47,12 @@ pub const DEFAULT_MASTER_VOLUME: f32 = 0.5;
 /// audio output thread.
 pub const DEFAULT_REALTIME_SOURCE_LATENCY: Ms = Ms(512.0);
 
+/// The initial, default duration after which a scribble stream that stops updating is ended.
+pub const DEFAULT_SCRIBBLE_TIMEOUT: Ms = Ms(1_000.0);
+
+/// The initial, default duration over which the sound of an ended scribble stream fades out.
+pub const DEFAULT_SCRIBBLE_RELEASE: Ms = Ms(50.0);
+
 /// The default rolloff decibel amount, used to attenuate speaker gains over distances.
 pub const DEFAULT_DBAP_ROLLOFF_DB: f64 = 4.0;
 
@@ -5,7 +5,7 @@
 
 use audio::{DISTANCE_BLUR, FRAMES_PER_BUFFER, MAX_CHANNELS, MAX_SOUNDS};
 use audio::{Sound, Speaker};
-use audio::{dbap, detection, source, sound, speaker};
+use audio::{dbap, detection, ducking, latency, scene, source, sound, speaker};
 use fxhash::{FxHashMap, FxHashSet};
 use gui;
 use installation;
@@ -52,6 +52,10 @@ struct SoundOrdered {
     unmixed_samples: Vec<f32>,
     /// The number of channels in the sound.
     channels: usize,
+    /// The peak amplitude of the sound's samples for the current buffer.
+    level: f32,
+    /// The gain applied to the sound by any ducking rules for the current buffer.
+    ducking_gain: ducking::Gain,
 }
 
 /// Information about a single channel within a single sound.
@@ -194,7 +198,12 @@ pub struct Model {
     /// The current value of proximity limit. The limit in meters
     /// for a speaker to be considered in the dbap calculations
     pub proximity_limit_2: Metres,
-
+    /// Emits clicks onto the output while a latency measurement is in progress.
+    pub latency_emitter: Option<latency::Emitter>,
+    /// Attenuates soundscape sounds in response to the level of other sounds.
+    pub ducker: ducking::Ducker,
+    /// The crossfade between scenes currently in progress, if any.
+    scene_fade: Option<scene::Fade>,
 }
 
 struct Channels {
@@ -250,6 +259,8 @@ impl Model {
                 id: sound::Id::INITIAL,
                 unmixed_samples: vec![0.0; FRAMES_PER_BUFFER * 2],
                 channels: 0,
+                level: 0.0,
+                ducking_gain: ducking::Gain::UNITY,
             })
             .collect();
 
@@ -282,6 +293,15 @@ impl Model {
         // Initialise the proximity limit to the default value.
         let proximity_limit_2 = super::DEFAULT_PROXIMITY_LIMIT_2;
 
+        // No latency measurement is in progress to begin.
+        let latency_emitter = None;
+
+        // Ducking rules are provided by the project.
+        let ducker = ducking::Ducker::default();
+
+        // No scene is being recalled to begin.
+        let scene_fade = None;
+
         let channels = Channels {
             detection,
             gui_audio_monitor_msg_tx,
@@ -305,6 +325,9 @@ impl Model {
             dbap_speaker_gains,
             dbap_speakers,
             proximity_limit_2,
+            latency_emitter,
+            ducker,
+            scene_fade,
         }
     }
 
@@ -471,6 +494,49 @@ impl Model {
         }
     }
 
+    /// Begin crossfading the mix towards that of a recalled scene.
+    ///
+    /// If a fade is already in progress, the new fade begins from its current position.
+    pub fn begin_scene_fade(&mut self, mut fade: scene::Fade) {
+        if let Some(previous) = self.scene_fade.take() {
+            fade.continue_from(&previous);
+        }
+        self.scene_fade = Some(fade);
+    }
+
+    /// Step the scene fade in progress forward by the given number of frames.
+    ///
+    /// During the fade, the mute and solo state of each source is expressed via the volume of its
+    /// sounds. Once complete, the target volume, mute and solo state are applied.
+    fn update_scene_fade(&mut self, frames: usize) {
+        let mut fade = match self.scene_fade.take() {
+            None => return,
+            Some(fade) => fade,
+        };
+        fade.advance(frames);
+        self.master_volume = fade.master_volume();
+
+        if fade.is_complete() {
+            for (_, sound) in self.sounds_mut() {
+                if let Some(mix) = fade.target_source_mix(&sound.source_id()) {
+                    sound.volume = mix.volume;
+                    sound.muted = mix.muted;
+                }
+            }
+            self.soloed = fade.target_soloed().clone();
+            return;
+        }
+
+        for (_, sound) in self.sounds_mut() {
+            if let Some(gain) = fade.source_gain(&sound.source_id()) {
+                sound.volume = gain;
+                sound.muted = false;
+            }
+        }
+        self.soloed.clear();
+        self.scene_fade = Some(fade);
+    }
+
     /// An iterator yielding mutable access to all sounds currently playing.
     pub fn sounds_mut(&mut self) -> SoundsMut {
         let iter = self.sounds.iter_mut();
@@ -485,6 +551,8 @@ impl Model {
         self.frame_count.store(0, atomic::Ordering::Relaxed);
         self.soloed.clear();
         self.speakers.clear();
+        self.ducker.set_rules(Vec::new());
+        self.scene_fade = None;
 
         let Model { ref mut sounds, ref channels, .. } = *self;
         for (sound_id, sound) in sounds.drain() {
@@ -525,6 +593,9 @@ fn lerp(a: f32, b: f32, lerp: f32) -> f32 {
 
 /// The function given to nannou to use for rendering.
 pub fn render(model: &mut Model, buffer: &mut Buffer) {
+    // Step any scene fade in progress before reading the master volume and solo state.
+    model.update_scene_fade(buffer.len_frames());
+
     let Model {
         master_volume,
         cpu_saving_enabled,
@@ -541,6 +612,9 @@ pub fn render(model: &mut Model, buffer: &mut Buffer) {
         ref mut dbap_speakers,
         ref channels,
         proximity_limit_2,
+        ref mut latency_emitter,
+        ref mut ducker,
+        scene_fade: _,
     } = *model;
 
     // Always silence the buffer to begin.
@@ -576,6 +650,7 @@ pub fn render(model: &mut Model, buffer: &mut Buffer) {
     for (ordered_sound, &sound_id) in sounds_ordered.iter_mut().zip(sounds.keys()) {
         ordered_sound.id = sound_id;
         ordered_sound.unmixed_samples.clear();
+        ordered_sound.level = 0.0;
     }
 
     // Clear the channel sounds buffer.
@@ -638,25 +713,40 @@ pub fn render(model: &mut Model, buffer: &mut Buffer) {
         // Collect the samples from the `Sound`'s `Signal`.
         {
             let mut samples_written = 0;
-            for sample in sound.signal.samples().take(num_samples) {
-                let sample = sample * sound.volume;
-                ordered_sound.unmixed_samples.push(sample);
-                samples_written += 1;
+            if !sound.effects.is_ringing_out() {
+                for sample in sound.signal.samples().take(num_samples) {
+                    let sample = sample * sound.volume * sound.normalisation_gain;
+                    ordered_sound.unmixed_samples.push(sample);
+                    samples_written += 1;
+                }
             }
 
+            // If we didn't write the expected number of samples, the signal has been exhausted.
+            // Keep the sound alive until the tail of its effects (e.g. delay, reverb) decays.
+            if samples_written < num_samples {
+                let silent_frames = (num_samples - samples_written) / n_channels;
+                if !sound.effects.ring_out(silent_frames) {
+                    exhausted_sounds.push(sound_id);
+                }
+                let remaining_silence = (samples_written..num_samples).map(|_| 0.0);
+                ordered_sound.unmixed_samples.extend(remaining_silence);
+            }
+
+            // Apply the pre-spatial effects chain.
+            sound.effects.process(&mut ordered_sound.unmixed_samples, n_channels);
+
+            // Measure the peak level for evaluating ducking rules.
+            ordered_sound.level = ordered_sound
+                .unmixed_samples
+                .iter()
+                .fold(0.0, |peak, &s| s.abs().max(peak));
+
             // If CPU saving is not enabled, send the samples to the detector for analysis.
             if !cpu_saving_enabled {
                 let mut detection_buffer = channels.detection.pop_sound_buffer();
                 detection_buffer.extend(ordered_sound.unmixed_samples.iter().cloned());
                 channels.detection.update_sound(sound_id, detection_buffer, n_channels);
             }
-
-            // If we didn't write the expected number of samples, the sound has been exhausted.
-            if samples_written < num_samples {
-                exhausted_sounds.push(sound_id);
-                let remaining_silence = (samples_written..num_samples).map(|_| 0.0);
-                ordered_sound.unmixed_samples.extend(remaining_silence);
-            }
         }
 
         // Mix the audio from the signal onto each of the output channels.
@@ -786,6 +876,15 @@ pub fn render(model: &mut Model, buffer: &mut Buffer) {
         }
     }
 
+    // Step the ducking rules using the level of every sound and determine each sound's gain.
+    {
+        let levels = sounds_ordered.iter().map(|s| (&sounds[&s.id].sound, s.level));
+        ducker.update(levels, buffer.len_frames());
+    }
+    for ordered_sound in sounds_ordered.iter_mut() {
+        ordered_sound.ducking_gain = ducker.sound_gain(&sounds[&ordered_sound.id].sound);
+    }
+
     // Sum the samples for all sound channels onto the output buffer at once.
     //
     // Iterate over each frame and track its index for gain interpolation.
@@ -807,7 +906,8 @@ pub fn render(model: &mut Model, buffer: &mut Buffer) {
             // Retrieve the unmixed sample for this channel at this frame.
             let sound = &sounds_ordered[sound_index];
             let channel_sample_index = frame_i * sound.channels + sound_channel_index;
-            let channel_sample = sound.unmixed_samples[channel_sample_index];
+            let ducking_gain = sound.ducking_gain.at(lerp_amt);
+            let channel_sample = sound.unmixed_samples[channel_sample_index] * ducking_gain;
 
             // Sum this sound channel onto each of the output channels for the nearby speakers.
             for speaker_info in &dbap_speaker_infos[speaker_infos_range.clone()] {
@@ -854,6 +954,11 @@ pub fn render(model: &mut Model, buffer: &mut Buffer) {
         *sample *= master_volume;
     }
 
+    // Emit the latency measurement clicks after the master volume so that they are always audible.
+    if let Some(emitter) = latency_emitter.as_mut() {
+        emitter.render(buffer);
+    }
+
     // Find the peak amplitude and send it via the monitor channel.
     let peak = buffer.iter().fold(0.0, |peak, &s| s.max(peak));
     channels.gui_audio_monitor_msg_tx.push(gui::AudioMonitorMessage::Master { peak });
@@ -0,0 +1,143 @@
+//! Crossfading the mix of the audio output stream between scenes.
+//!
+//! While a `Fade` is in progress, the mute and solo state of every source is expressed as a gain
+//! so that sources may fade in and out of the mix rather than switching abruptly. Once the fade
+//! completes, the target volume, mute and solo state are applied directly.
+
+use audio::{source, SAMPLE_RATE};
+use fxhash::{FxHashMap, FxHashSet};
+use time_calc::Ms;
+
+/// A crossfade of the output mix from one scene to another.
+#[derive(Clone, Debug)]
+pub struct Fade {
+    duration_frames: f64,
+    elapsed_frames: f64,
+    master_volume: Target<f32>,
+    sources: FxHashMap<source::Id, SourceFade>,
+    soloed: FxHashSet<source::Id>,
+}
+
+/// The state of the output mix at either end of a fade.
+#[derive(Clone, Debug, Default)]
+pub struct Mix {
+    pub master_volume: f32,
+    pub sources: FxHashMap<source::Id, SourceMix>,
+    pub soloed: FxHashSet<source::Id>,
+}
+
+/// The mix state of a single source at either end of a fade.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct SourceMix {
+    pub volume: f32,
+    pub muted: bool,
+}
+
+// The fade of a single source.
+#[derive(Copy, Clone, Debug)]
+struct SourceFade {
+    gain: Target<f32>,
+    target: SourceMix,
+}
+
+// A value being interpolated from `start` to `end`.
+#[derive(Copy, Clone, Debug)]
+struct Target<T> {
+    start: T,
+    end: T,
+}
+
+impl Fade {
+    /// Create a fade between the given mix states over the given duration.
+    ///
+    /// Sources missing from `target` retain their starting mix.
+    pub fn new(duration: Ms, start: &Mix, target: &Mix) -> Self {
+        let sources = start
+            .sources
+            .iter()
+            .map(|(&id, &start_mix)| {
+                let target_mix = target.sources.get(&id).cloned().unwrap_or(start_mix);
+                let gain = Target {
+                    start: audible_gain(id, &start_mix, &start.soloed),
+                    end: audible_gain(id, &target_mix, &target.soloed),
+                };
+                (id, SourceFade { gain, target: target_mix })
+            })
+            .collect();
+        let master_volume = Target { start: start.master_volume, end: target.master_volume };
+        Fade {
+            duration_frames: duration.ms() * 0.001 * SAMPLE_RATE,
+            elapsed_frames: 0.0,
+            master_volume,
+            sources,
+            soloed: target.soloed.clone(),
+        }
+    }
+
+    /// Begin the fade from the current position of the given fade rather than its own start.
+    ///
+    /// This avoids jumps in gain when a scene is recalled while another is still fading in.
+    pub fn continue_from(&mut self, previous: &Fade) {
+        self.master_volume.start = previous.master_volume();
+        for (id, source) in self.sources.iter_mut() {
+            if let Some(gain) = previous.source_gain(id) {
+                source.gain.start = gain;
+            }
+        }
+    }
+
+    /// Step the fade forward by the given number of frames.
+    pub fn advance(&mut self, frames: usize) {
+        self.elapsed_frames += frames as f64;
+    }
+
+    /// Whether or not the fade has reached its target.
+    pub fn is_complete(&self) -> bool {
+        self.elapsed_frames >= self.duration_frames
+    }
+
+    /// The master volume at the current position through the fade.
+    pub fn master_volume(&self) -> f32 {
+        self.master_volume.at(self.progress())
+    }
+
+    /// The gain applied to the sounds of the given source at the current position through the
+    /// fade, taking into account mute and solo state.
+    pub fn source_gain(&self, id: &source::Id) -> Option<f32> {
+        let progress = self.progress();
+        self.sources.get(id).map(|source| source.gain.at(progress))
+    }
+
+    /// The target mix of the given source.
+    pub fn target_source_mix(&self, id: &source::Id) -> Option<SourceMix> {
+        self.sources.get(id).map(|source| source.target)
+    }
+
+    /// The set of soloed sources once the fade is complete.
+    pub fn target_soloed(&self) -> &FxHashSet<source::Id> {
+        &self.soloed
+    }
+
+    // The normalised progress through the fade.
+    fn progress(&self) -> f32 {
+        if self.duration_frames <= 0.0 {
+            return 1.0;
+        }
+        (self.elapsed_frames / self.duration_frames).min(1.0) as f32
+    }
+}
+
+impl Target<f32> {
+    fn at(&self, progress: f32) -> f32 {
+        self.start + (self.end - self.start) * progress
+    }
+}
+
+// The gain at which a source is heard given its mix and the set of soloed sources.
+fn audible_gain(id: source::Id, mix: &SourceMix, soloed: &FxHashSet<source::Id>) -> f32 {
+    if mix.muted || (!soloed.is_empty() && !soloed.contains(&id)) {
+        0.0
+    } else {
+        mix.volume
+    }
+}
@@ -1,18 +1,19 @@
-use audio::{input, output, source, Source, SAMPLE_RATE};
+use audio::{ducking, effect, input, output, source, Source, SAMPLE_RATE};
 use crossbeam::sync::SegQueue;
 use fxhash::FxHashSet;
 use installation;
 use metres::Metres;
 use nannou::geom::Point2;
+use nannou::rand::Rng;
 use std::ops;
 use std::sync::{Arc, Mutex};
-use std::sync::atomic::{self, AtomicBool};
+use std::sync::atomic::{self, AtomicBool, AtomicUsize};
 use time_calc::{Ms, Samples};
 
 /// `Sound`s can be thought of as a stack of three primary components:
 ///
 /// 1. **Source**: for generating audio data (via oscillator, wave, audio input, etc).
-/// 2. **Pre-spatial effects processing**: E.g. fades.
+/// 2. **Pre-spatial effects processing**: E.g. fades, filters, delay, reverb.
 /// 3. **Spatial Output**: maps the sound from a position in space to the output channels.
 pub struct Sound {
     // State shared with the handles.
@@ -21,6 +22,10 @@ pub struct Sound {
     pub channels: usize,
     // An amplitude multiplier specified by the user for mixing the sound.
     pub volume: f32,
+    // An amplitude multiplier that brings the sound's source to its target loudness.
+    //
+    // This is applied on top of `volume`.
+    pub normalisation_gain: f32,
     // Whether or not the sound's source has been muted.
     pub muted: bool,
     // Includes the source and pre-spatial effects.
@@ -34,6 +39,8 @@ pub struct Sound {
     // TODO: This could potentially just be an actual type? `sound::Signal` that matched on
     // the source kind, stored its own stack of effects, etc?
     pub signal: source::Signal,
+    // The chain of effects applied to the signal before it is spatialised.
+    pub effects: effect::Chain,
     // The location and orientation of the sound within the space.
     pub position: Position,
     // A constant radians offset for the channels, provided by the sound's `Source`.
@@ -45,6 +52,8 @@ pub struct Sound {
     pub spread: Metres,
     // Installations in which this sound can be played.
     pub installations: Installations,
+    // The properties by which the sound may trigger ducking rules.
+    pub ducking: ducking::Tags,
 }
 
 /// The location and orientation or a **Sound** within an exhibition.
@@ -102,6 +111,16 @@ impl Shared {
 }
 
 impl Handle {
+    /// A handle to a sound that is not rendered by any audio stream.
+    ///
+    /// Used for sounds spawned within an accelerated soundscape simulation.
+    pub fn detached(id: Id, source_id: source::Id) -> Self {
+        let is_playing = AtomicBool::new(true);
+        let source = SourceHandle::Wav;
+        let shared = Arc::new(Shared { is_playing, source_id, id, source });
+        Handle { shared }
+    }
+
     /// Whether or not the soundscape is currently playing.
     pub fn is_playing(&self) -> bool {
         self.shared.is_playing()
@@ -140,13 +159,18 @@ impl Handle {
 /// Creates a sound from the given `Source` and send it to the output stream.
 ///
 /// If the sound is a realtime source, send the source end to the input stream.
-pub fn spawn_from_source(
+///
+/// The `playback_rate` only applies to `Retrigger` and `Granular` WAV sources.
+///
+/// The given `rng` is used to generate the effect chain parameters, the start frame of
+/// `Retrigger` WAV sources and the seed of granular WAV sources.
+pub fn spawn_from_source<R>(
     id: Id,
     source_id: source::Id,
     source: &Source,
     position: Position,
-    attack_duration_frames: Samples,
-    release_duration_frames: Samples,
+    envelope: source::Envelope,
+    playback_rate: f64,
     continuous_preview: bool,
     max_duration_frames: Option<Samples>,
     frame_count: u64,
@@ -154,9 +178,18 @@ pub fn spawn_from_source(
     input_stream: &input::Stream,
     output_stream: &output::Stream,
     latency: Ms,
+    rng: &mut R,
 ) -> Handle
+where
+    R: Rng,
 {
     let installations = source.role.clone().into();
+    let ducking = ducking::Tags::from(&source.role);
+    let effects = effect::Chain::from_effects(
+        &source.effects,
+        source.channel_count(),
+        rng,
+    );
     match source.kind {
         source::Kind::Wav(ref wav) => {
             spawn_from_wav(
@@ -165,17 +198,21 @@ pub fn spawn_from_source(
                 wav,
                 source.spread,
                 source.volume,
+                source.normalisation_gain(),
                 source.muted,
                 position,
                 source.channel_radians,
                 installations,
-                attack_duration_frames,
-                release_duration_frames,
+                ducking,
+                effects,
+                envelope,
+                playback_rate,
                 continuous_preview,
                 max_duration_frames,
                 frame_count,
                 wav_reader,
                 output_stream,
+                rng,
             )
         },
 
@@ -190,8 +227,9 @@ pub fn spawn_from_source(
                 position,
                 source.channel_radians,
                 installations,
-                attack_duration_frames,
-                release_duration_frames,
+                ducking,
+                effects,
+                envelope,
                 continuous_preview,
                 max_duration_frames,
                 input_stream,
@@ -203,36 +241,67 @@ pub fn spawn_from_source(
 }
 
 /// Creates a sound from the given `source::Wav` and send it to the output audio stream.
-pub fn spawn_from_wav(
+pub fn spawn_from_wav<R>(
     id: Id,
     source_id: source::Id,
     wav: &source::Wav,
     spread: Metres,
     volume: f32,
+    normalisation_gain: f32,
     muted: bool,
     initial_position: Position,
     channel_radians: f32,
     installations: Installations,
-    attack_duration_frames: Samples,
-    release_duration_frames: Samples,
+    ducking: ducking::Tags,
+    effects: effect::Chain,
+    envelope: source::Envelope,
+    playback_rate: f64,
     continuous_preview: bool,
     max_duration_frames: Option<Samples>,
     frame_count: u64,
     wav_reader: &source::wav::reader::Handle,
     audio_output: &output::Stream,
+    rng: &mut R,
 ) -> Handle
+where
+    R: Rng,
 {
     // The wave samples iterator.
-    let samples = wav_reader.play(id, &wav.path, frame_count, wav.should_loop || continuous_preview)
+    //
+    // Granular sounds read grains from a continuous stream, so the WAV is always looped.
+    let looped = wav.is_endless() || continuous_preview;
+    // `Retrigger` sounds start from the WAV's start offset, while all others start from their
+    // position within the global continuous timeline.
+    let start_frame = match wav.playback {
+        source::wav::Playback::Retrigger => wav.retrigger_start_frame(rng),
+        _ => frame_count,
+    };
+    let loop_region = match looped {
+        true => Some(wav.loop_region()),
+        false => None,
+    };
+    let mut samples = wav_reader.play(id, &wav.path, start_frame, loop_region)
         .unwrap_or_else(|err| {
             panic!("failed to send new wav \"{}\"to wav_reader thread: {:?}: {}",
                    wav.path.display(), err, err);
         });
+    // `Continuous` sounds must stay aligned with the global timeline, so only play back at their
+    // natural rate.
+    match wav.playback {
+        source::wav::Playback::Continuous => (),
+        _ => samples.set_playback_rate(playback_rate),
+    }
 
     // The source signal.
-    let playback = wav.playback.clone();
-    let kind = source::SignalKind::Wav { samples, playback };
-    let mut signal = source::Signal::new(kind, attack_duration_frames, release_duration_frames);
+    let kind = match wav.playback {
+        source::wav::Playback::Granular(granular) => {
+            let seed = rng.gen();
+            let grains = source::wav::granular::Grains::new(samples, granular, seed);
+            source::SignalKind::Granular { grains }
+        },
+        playback => source::SignalKind::Wav { samples, playback },
+    };
+    let mut signal = source::Signal::new(kind, envelope);
     if let Some(duration) = max_duration_frames {
         signal = signal.with_duration_frames(duration);
     }
@@ -253,12 +322,15 @@ pub fn spawn_from_wav(
         shared: shared.clone(),
         channels: wav.channels,
         volume,
+        normalisation_gain,
         muted,
         signal,
+        effects,
         position: initial_position,
         channel_radians,
         spread,
         installations,
+        ducking,
     };
 
     // Create the handle to the sound.
@@ -292,8 +364,9 @@ pub fn spawn_from_realtime(
     initial_position: Position,
     channel_radians: f32,
     installations: Installations,
-    attack_duration_frames: Samples,
-    release_duration_frames: Samples,
+    ducking: ducking::Tags,
+    effects: effect::Chain,
+    envelope: source::Envelope,
     continuous_preview: bool,
     max_duration_frames: Option<Samples>,
     audio_input: &input::Stream,
@@ -324,7 +397,11 @@ pub fn spawn_from_realtime(
     let input_buffer_rx = input_buffer_queue;
 
     // Insert the silence for the delay.
+    //
+    // The signal's resampler aims to keep this many samples queued to compensate for any drift
+    // between the input and output clocks.
     signal_buffer_tx.push(vec![0.0; delay_samples]);
+    let queued_samples = Arc::new(AtomicUsize::new(delay_samples));
 
     // Insert a buffer into the input buffer tx ready for use.
     input_buffer_tx.push(Vec::with_capacity(super::FRAMES_PER_BUFFER * n_channels));
@@ -347,10 +424,12 @@ pub fn spawn_from_realtime(
         current_buffer: Vec::with_capacity(super::FRAMES_PER_BUFFER * n_channels),
         remaining_samples,
         is_closed: is_closed.clone(),
+        queued_samples: queued_samples.clone(),
+        resampler: source::realtime::Resampler::new(n_channels, delay_frames as usize),
     };
 
     let kind = source::SignalKind::Realtime { samples };
-    let mut signal = source::Signal::new(kind, attack_duration_frames, release_duration_frames);
+    let mut signal = source::Signal::new(kind, envelope);
     if let Some(duration) = max_duration_frames {
         signal = signal.with_duration_frames(duration);
     }
@@ -366,6 +445,7 @@ pub fn spawn_from_realtime(
         buffer_rx: input_buffer_rx,
         is_capturing: is_capturing.clone(),
         is_closed,
+        queued_samples,
     };
 
     // State shared between the handles to a realtime sound.
@@ -386,12 +466,16 @@ pub fn spawn_from_realtime(
         shared: shared.clone(),
         channels: n_channels,
         volume,
+        // Realtime sources are not analysed for loudness.
+        normalisation_gain: 1.0,
         muted,
         signal,
+        effects,
         position: initial_position,
         channel_radians,
         spread,
         installations,
+        ducking,
     };
 
     // Create the handle to the sound.
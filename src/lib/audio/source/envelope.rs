@@ -0,0 +1,113 @@
+//! The shape of the amplitude envelope applied to a sound's signal.
+
+use time_calc::Samples;
+
+/// The dynamic range in decibels covered by the `Exponential` curve.
+const EXPONENTIAL_RANGE_DB: f32 = 60.0;
+
+/// The curve along which the gain of an attack or release segment travels.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Deserialize, Serialize)]
+pub enum Curve {
+    /// Gain changes at a constant rate.
+    Linear,
+    /// Gain changes at a constant rate in decibels, heard as an even fade.
+    Exponential,
+    /// A quarter sine, maintaining constant power when crossfading with its opposite.
+    EqualPower,
+    /// Eases in and out of the segment with a raised cosine.
+    SCurve,
+}
+
+/// Describes the full envelope of a single sound.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Envelope {
+    /// The duration of the fade-in.
+    pub attack_frames: Samples,
+    /// The curve of the fade-in.
+    pub attack_curve: Curve,
+    /// The minimum duration for which the sound is held at full gain between the attack and
+    /// release.
+    pub hold_frames: Samples,
+    /// The duration of the fade-out.
+    pub release_frames: Samples,
+    /// The curve of the fade-out.
+    pub release_curve: Curve,
+}
+
+impl Curve {
+    pub const VARIANT_COUNT: usize = 4;
+
+    /// Map the given normalised position within a segment to a gain.
+    ///
+    /// `0.0` is silence and `1.0` is full gain. Release segments pass a position that descends from
+    /// `1.0` towards `0.0`.
+    pub fn gain(&self, t: f32) -> f32 {
+        let t = t.max(0.0).min(1.0);
+        match *self {
+            Curve::Linear => t,
+            Curve::Exponential => {
+                let floor = 10.0f32.powf(-EXPONENTIAL_RANGE_DB / 20.0);
+                let gain = 10.0f32.powf((t - 1.0) * EXPONENTIAL_RANGE_DB / 20.0);
+                (gain - floor) / (1.0 - floor)
+            },
+            Curve::EqualPower => (t * ::std::f32::consts::FRAC_PI_2).sin(),
+            Curve::SCurve => 0.5 - 0.5 * (t * ::std::f32::consts::PI).cos(),
+        }
+    }
+
+    /// Produce the index of the Curve value variant.
+    pub fn to_index(&self) -> usize {
+        match *self {
+            Curve::Linear => 0,
+            Curve::Exponential => 1,
+            Curve::EqualPower => 2,
+            Curve::SCurve => 3,
+        }
+    }
+
+    /// Produce the Curve variant at the given index.
+    pub fn from_index(i: usize) -> Option<Self> {
+        match i {
+            0 => Some(Curve::Linear),
+            1 => Some(Curve::Exponential),
+            2 => Some(Curve::EqualPower),
+            3 => Some(Curve::SCurve),
+            _ => None,
+        }
+    }
+
+    /// Produce a label for the variant at the given index.
+    pub fn label_from_index(i: usize) -> &'static str {
+        match i {
+            0 => "Linear",
+            1 => "Exponential",
+            2 => "Equal Power",
+            3 => "S-Curve",
+            _ => "",
+        }
+    }
+}
+
+impl Envelope {
+    /// The minimum number of frames required to play through the attack, hold and release.
+    pub fn min_duration_frames(&self) -> Samples {
+        self.attack_frames + self.hold_frames + self.release_frames
+    }
+}
+
+impl Default for Envelope {
+    fn default() -> Self {
+        let attack_frames = Samples(0);
+        let attack_curve = Curve::Linear;
+        let hold_frames = Samples(0);
+        let release_frames = Samples(0);
+        let release_curve = Curve::Linear;
+        Envelope {
+            attack_frames,
+            attack_curve,
+            hold_frames,
+            release_frames,
+            release_curve,
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use audio::effect::Effect;
+use audio::speaker;
 use fxhash::FxHashSet;
 use installation;
 use metres::Metres;
@@ -8,10 +10,12 @@ use std::ops;
 use time_calc::{Ms, Samples};
 use utils::{self, Range};
 
+pub use self::envelope::Envelope;
 pub use self::movement::Movement;
 pub use self::realtime::Realtime;
 pub use self::wav::Wav;
 
+pub mod envelope;
 pub mod realtime;
 pub mod wav;
 
@@ -21,6 +25,22 @@ pub const MAX_ATTACK_DURATION: Ms = Ms(utils::MIN_MS);
 
 pub const MAX_RELEASE_DURATION: Ms = Ms(utils::MIN_MS);
 
+pub const MAX_HOLD_DURATION: Ms = Ms(utils::MIN_MS);
+
+/// The slowest rate at which a WAV may be played back (two octaves down).
+pub const MIN_PLAYBACK_RATE: f64 = 0.25;
+
+/// The fastest rate at which a WAV may be played back (one octave up).
+///
+/// This is limited by the rate at which the `wav::reader` thread can provide buffers.
+pub const MAX_PLAYBACK_RATE: f64 = 2.0;
+
+/// The lowest weight that may be assigned to a soundscape source.
+pub const MIN_WEIGHT: f64 = 0.01;
+
+/// The highest weight that may be assigned to a soundscape source.
+pub const MAX_WEIGHT: f64 = 100.0;
+
 /// Items related to audio sources.
 ///
 /// Audio sources come in two kinds:
@@ -53,6 +73,14 @@ pub struct Source {
     /// Whether or not the source has been muted.
     #[serde(default)]
     pub muted: bool,
+    /// The chain of effects applied to the source's sounds before they are spatialised.
+    #[serde(default)]
+    pub effects: Vec<Effect>,
+    /// The integrated loudness in LUFS to which the source is normalised, if any.
+    ///
+    /// Normalisation is applied on top of `volume` and only applies to analysed WAV sources.
+    #[serde(default)]
+    pub loudness_target: Option<f32>,
 }
 
 /// A **Signal** yielding interleaved samples.
@@ -62,6 +90,8 @@ pub struct Signal {
     pub kind: SignalKind,
     attack: Attack,
     release: Release,
+    // The minimum duration required to play through the attack, hold and release in full.
+    min_duration_frames: Samples,
     // The duration of the signal if one was specified.
     //
     // If `None`, the signal will just play out until the `SignalKind` samples return `None`.
@@ -70,7 +100,8 @@ pub struct Signal {
 
 /// The kind of the **Signal**.
 ///
-/// Indicates whether the signal is sourced from a `Wav` or `Realtime` source.
+/// Indicates whether the signal is sourced from a `Wav` or `Realtime` source, or from grains
+/// scattered from a `Wav` source.
 pub enum SignalKind {
     Wav {
         samples: wav::reader::SamplesStream,
@@ -79,6 +110,9 @@ pub enum SignalKind {
     Realtime {
         samples: realtime::Signal,
     },
+    Granular {
+        grains: wav::granular::Grains,
+    },
 }
 
 /// An iterator yielding `Some` until the `current_frame` reaches `duration_frames`.
@@ -93,6 +127,7 @@ pub struct Duration {
 pub struct Attack {
     duration_frames: Samples,
     current_frame: Samples,
+    curve: envelope::Curve,
 }
 
 /// An iterator producing the volume modifier for a release envelope.
@@ -100,6 +135,7 @@ pub struct Attack {
 pub struct Release {
     duration_frames: Samples,
     frame_countdown: Samples,
+    curve: envelope::Curve,
 }
 
 /// The samples produced by a source signal with attack and release applied.
@@ -148,8 +184,97 @@ pub struct Soundscape {
     pub attack_duration: Range<Ms>,
     #[serde(default = "default::release_duration")]
     pub release_duration: Range<Ms>,
+    /// The curve along which sounds fade in.
+    #[serde(default = "default::attack_curve")]
+    pub attack_curve: envelope::Curve,
+    /// The curve along which sounds fade out.
+    #[serde(default = "default::release_curve")]
+    pub release_curve: envelope::Curve,
+    /// The range of durations for which sounds are held at full gain between fading in and out.
+    #[serde(default = "default::hold_duration")]
+    pub hold_duration: Range<Ms>,
+    /// The range of rates at which WAV sources are played back, where `1.0` is the natural rate.
+    ///
+    /// `Continuous` WAVs always play at their natural rate so that they remain aligned with the
+    /// global continuous timeline.
+    #[serde(default = "default::playback_rate")]
+    pub playback_rate: Range<f64>,
+    /// Biases the choice of this source over other eligible sources.
+    ///
+    /// Sources are sorted by the duration until a sound from them is needed, and this duration is
+    /// divided by the weight (or multiplied by it once overdue). A source with a weight of `2.0`
+    /// therefore appears to be needed in half the time of one with `1.0`. The weights are only
+    /// used as probabilities when picking between sources that remain equally suitable.
+    #[serde(default = "default::weight")]
+    pub weight: f64,
     #[serde(default = "default::movement")]
     pub movement: Movement,
+    /// Where the sounds of the source are initially placed within their installation.
+    #[serde(default = "default::placement")]
+    pub placement: Placement,
+    /// Describes when the source is enabled and how its simultaneous sounds are scaled.
+    #[serde(default)]
+    pub schedule: soundscape::Schedule,
+}
+
+/// Strategies for the initial placement of a soundscape source's sounds within an installation.
+///
+/// Placement only applies to sources with agent movement, as agents are free to begin anywhere.
+/// Fixed sources always begin at their fixed position and ngon sources at the first vertex of
+/// their path.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum Placement {
+    /// The centroid of the installation's speakers.
+    Centroid,
+    /// A random point within the bounding area of the installation.
+    Random,
+    /// The location of the given speaker, if it is assigned to the installation.
+    NearestSpeaker(speaker::Id),
+    /// The point within the installation area that is farthest from all other active sounds.
+    FarthestFromActive,
+    /// A random point along the edge of the installation area, facing inwards.
+    AreaEdge,
+}
+
+impl Placement {
+    pub const VARIANT_COUNT: usize = 5;
+
+    /// Produce the index of the Placement value variant.
+    pub fn to_index(&self) -> usize {
+        match *self {
+            Placement::Centroid => 0,
+            Placement::Random => 1,
+            Placement::NearestSpeaker(_) => 2,
+            Placement::FarthestFromActive => 3,
+            Placement::AreaEdge => 4,
+        }
+    }
+
+    /// Produce a label for the variant at the given index.
+    pub fn label_from_index(i: usize) -> &'static str {
+        match i {
+            0 => "Centroid",
+            1 => "Random",
+            2 => "Nearest Speaker",
+            3 => "Farthest From Active",
+            4 => "Area Edge",
+            _ => "",
+        }
+    }
+
+    /// Produce a Placement variant for the given variant index.
+    ///
+    /// The given `speaker` is used for the `NearestSpeaker` variant.
+    pub fn from_index(i: usize, speaker: speaker::Id) -> Option<Self> {
+        match i {
+            0 => Some(Placement::Centroid),
+            1 => Some(Placement::Random),
+            2 => Some(Placement::NearestSpeaker(speaker)),
+            3 => Some(Placement::FarthestFromActive),
+            4 => Some(Placement::AreaEdge),
+            _ => None,
+        }
+    }
 }
 
 /// Items related to the movement of a source's associated sounds within a soundscape.
@@ -316,7 +441,7 @@ impl Kind {
     pub fn playback_duration_skew(&self) -> f32 {
         match *self {
             Kind::Realtime(_) => skew::PLAYBACK_DURATION_MAX,
-            Kind::Wav(ref wav) => match wav.should_loop {
+            Kind::Wav(ref wav) => match wav.is_endless() {
                 true => skew::PLAYBACK_DURATION_MAX,
                 false => playback_duration_skew(wav.duration.to_ms(super::SAMPLE_RATE)),
             },
@@ -355,6 +480,22 @@ where
     Ms(utils::unskew_and_unnormalise(skewed_normalised_value, range.min.0, range.max.0, skew))
 }
 
+/// Generate a random playback rate within the given range.
+///
+/// The rate is chosen uniformly in octaves so that pitch deviations are perceptually even.
+pub fn random_playback_rate<R>(mut rng: R, range: Range<f64>) -> f64
+where
+    R: Rng,
+{
+    let min = range.min.max(MIN_PLAYBACK_RATE).min(MAX_PLAYBACK_RATE).log2();
+    let max = range.max.max(MIN_PLAYBACK_RATE).min(MAX_PLAYBACK_RATE).log2();
+    if min < max {
+        2.0f64.powf(rng.gen_range(min, max))
+    } else {
+        2.0f64.powf(min)
+    }
+}
+
 impl Source {
     pub fn channel_count(&self) -> usize {
         match self.kind {
@@ -362,21 +503,34 @@ impl Source {
             Kind::Realtime(ref rt) => rt.channels.len(),
         }
     }
+
+    /// The gain that brings the source to its `loudness_target`.
+    ///
+    /// Returns `1.0` if the source has no target or if its loudness has not yet been measured.
+    pub fn normalisation_gain(&self) -> f32 {
+        match (&self.kind, self.loudness_target) {
+            (&Kind::Wav(ref wav), Some(target)) => match wav.loudness {
+                Some(ref loudness) => loudness.normalisation_gain(target),
+                None => 1.0,
+            },
+            _ => 1.0,
+        }
+    }
 }
 
 impl Attack {
-    /// Construct an `Attack` from its duration in frames.
-    pub fn from_duration_frames(duration_frames: Samples) -> Self {
+    /// Construct an `Attack` from its duration in frames and the curve along which it travels.
+    pub fn from_duration_frames(duration_frames: Samples, curve: envelope::Curve) -> Self {
         let current_frame = Samples(0);
-        Attack { duration_frames, current_frame }
+        Attack { duration_frames, current_frame, curve }
     }
 }
 
 impl Release {
-    /// Construct a `Release` from its duration in frames.
-    pub fn from_duration_frames(duration_frames: Samples) -> Self {
+    /// Construct a `Release` from its duration in frames and the curve along which it travels.
+    pub fn from_duration_frames(duration_frames: Samples, curve: envelope::Curve) -> Self {
         let frame_countdown = duration_frames;
-        Release { duration_frames, frame_countdown }
+        Release { duration_frames, frame_countdown, curve }
     }
 }
 
@@ -394,6 +548,7 @@ impl SignalKind {
         match *self {
             SignalKind::Wav { ref samples, .. } => samples.remaining_frames(),
             SignalKind::Realtime { ref samples } => samples.remaining_frames(),
+            SignalKind::Granular { .. } => None,
         }
     }
 
@@ -402,6 +557,20 @@ impl SignalKind {
         match *self {
             SignalKind::Wav { ref samples, .. } => samples.channels(),
             SignalKind::Realtime { ref samples } => samples.channels(),
+            SignalKind::Granular { ref grains } => grains.channels(),
+        }
+    }
+
+    /// Set the rate at which the signal's WAV is played back.
+    ///
+    /// This has no effect on `Realtime` signals or `Continuous` WAVs, as the latter must remain
+    /// aligned with the global continuous timeline.
+    pub fn set_playback_rate(&mut self, rate: f64) {
+        match *self {
+            SignalKind::Wav { playback: wav::Playback::Continuous, .. } => (),
+            SignalKind::Wav { ref mut samples, .. } => samples.set_playback_rate(rate),
+            SignalKind::Granular { ref mut grains } => grains.set_playback_rate(rate),
+            SignalKind::Realtime { .. } => (),
         }
     }
 
@@ -410,25 +579,46 @@ impl SignalKind {
         match *self {
             SignalKind::Wav { ref mut samples, .. } => samples as _,
             SignalKind::Realtime { ref mut samples } => samples as _,
+            SignalKind::Granular { ref mut grains } => grains as _,
         }
     }
 }
 
 impl Signal {
-    /// Construct a new `Signal` from the given source kind, attack and release frames.
-    pub fn new(kind: SignalKind, attack_frames: Samples, release_frames: Samples) -> Self {
-        let attack = Attack::from_duration_frames(attack_frames);
-        let release = Release::from_duration_frames(release_frames);
+    /// Construct a new `Signal` from the given source kind and envelope.
+    pub fn new(kind: SignalKind, envelope: Envelope) -> Self {
+        let attack = Attack::from_duration_frames(envelope.attack_frames, envelope.attack_curve);
+        let release = Release::from_duration_frames(envelope.release_frames, envelope.release_curve);
+        let min_duration_frames = envelope.min_duration_frames();
         let duration = None;
-        Signal { kind, attack, release, duration }
+        Signal { kind, attack, release, min_duration_frames, duration }
     }
 
     /// Specify the duration of the signal in frames in the `Signal`.
+    ///
+    /// The duration is extended if necessary so that the attack, hold and release may play out in
+    /// full.
     pub fn with_duration_frames(mut self, frames: Samples) -> Self {
+        let frames = ::std::cmp::max(frames, self.min_duration_frames);
         self.duration = Some(Duration::from_frames(frames));
         self
     }
 
+    /// Fade the signal out over the given number of frames, after which it ends.
+    ///
+    /// Useful for stopping a signal that has no known end without an audible click. The signal
+    /// ends sooner if it was already due to end within the given number of frames.
+    pub fn release(&mut self, frames: Samples) {
+        if let Some(remaining) = self.remaining_frames() {
+            if remaining <= frames {
+                return;
+            }
+        }
+        let curve = self.release.curve;
+        self.release = Release::from_duration_frames(frames, curve);
+        self.duration = Some(Duration::from_frames(frames));
+    }
+
     /// The minimum number of frames between `self.remaining_frames` and
     /// `self.kind.remaining_frames()` if any.
     ///
@@ -453,6 +643,7 @@ impl Signal {
             ref mut attack,
             ref mut release,
             ref mut duration,
+            ..
         } = *self;
 
         // If the signal has no duration, this will be some max `i64` value that should never get
@@ -513,7 +704,7 @@ impl Attack {
             let current = self.current_frame.samples() as f32;
             let duration = self.duration_frames.samples() as f32;
             self.current_frame += Samples(1);
-            current / duration
+            self.curve.gain(current / duration)
         } else {
             1.0
         }
@@ -526,7 +717,7 @@ impl Release {
             let current = self.frame_countdown.samples() as f32;
             let duration = self.duration_frames.samples() as f32;
             self.frame_countdown -= Samples(1);
-            current / duration
+            self.curve.gain(current / duration)
         } else {
             1.0
         }
@@ -602,13 +793,14 @@ impl<'a> Iterator for SignalSamples<'a> {
 pub mod skew {
     pub const ATTACK: f32 = 0.5;
     pub const RELEASE: f32 = 0.5;
+    pub const HOLD: f32 = 0.5;
     pub const PLAYBACK_DURATION_MAX: f32 = 0.1;
 }
 
 pub mod default {
     use metres::Metres;
     use nannou::geom::{Point2, Vector2};
-    use super::{movement, Movement};
+    use super::{envelope, movement, Movement, Placement};
     use time_calc::Ms;
     use utils::{HR_MS, Range};
 
@@ -616,6 +808,8 @@ pub mod default {
     // Rotate the channel radians 90deg so that stereo channels are to the side by default.
     pub const CHANNEL_RADIANS: f32 = ::std::f32::consts::PI * 0.5;
     pub const VOLUME: f32 = 0.6;
+    // The integrated loudness in LUFS used when normalisation is first enabled.
+    pub const LOUDNESS_TARGET: f32 = -23.0;
     pub const OCCURRENCE_RATE: Range<Ms> = Range { min: Ms(500.0), max: Ms(HR_MS as _) };
     pub const SIMULTANEOUS_SOUNDS: Range<usize> = Range { min: 0, max: 1 };
     // Assume that the user wants to play back the sound endlessly at first.
@@ -625,6 +819,11 @@ pub mod default {
     };
     pub const ATTACK_DURATION: Range<Ms> = Range { min: Ms(0.0), max: Ms(0.0) };
     pub const RELEASE_DURATION: Range<Ms> = Range { min: Ms(0.0), max: Ms(0.0) };
+    pub const ATTACK_CURVE: envelope::Curve = envelope::Curve::Linear;
+    pub const RELEASE_CURVE: envelope::Curve = envelope::Curve::Linear;
+    pub const HOLD_DURATION: Range<Ms> = Range { min: Ms(0.0), max: Ms(0.0) };
+    pub const PLAYBACK_RATE: Range<f64> = Range { min: 1.0, max: 1.0 };
+    pub const WEIGHT: f64 = 1.0;
     pub const FIXED: Point2<f64> = Point2 { x: 0.5, y: 0.5 };
     pub const MAX_SPEED: Range<f64> = Range { min: 1.0, max: 5.0 };
     pub const MAX_FORCE: Range<f64> = Range { min: 0.04, max: 0.06 };
@@ -661,6 +860,7 @@ pub mod default {
     };
     pub const GENERATIVE: movement::Generative = movement::Generative::Agent(AGENT);
     pub const MOVEMENT: Movement = Movement::Fixed(FIXED);
+    pub const PLACEMENT: Placement = Placement::Random;
 
     pub fn spread() -> Metres {
         SPREAD
@@ -694,10 +894,34 @@ pub mod default {
         RELEASE_DURATION
     }
 
+    pub fn attack_curve() -> envelope::Curve {
+        ATTACK_CURVE
+    }
+
+    pub fn release_curve() -> envelope::Curve {
+        RELEASE_CURVE
+    }
+
+    pub fn hold_duration() -> Range<Ms> {
+        HOLD_DURATION
+    }
+
+    pub fn playback_rate() -> Range<f64> {
+        PLAYBACK_RATE
+    }
+
+    pub fn weight() -> f64 {
+        WEIGHT
+    }
+
     pub fn movement() -> Movement {
         MOVEMENT
     }
 
+    pub fn placement() -> Placement {
+        PLACEMENT
+    }
+
     pub fn radians_offset() -> Range<f64> {
         RADIANS_OFFSET
     }
@@ -728,7 +952,14 @@ impl Default for Soundscape {
         let playback_duration = default::PLAYBACK_DURATION;
         let attack_duration = default::ATTACK_DURATION;
         let release_duration = default::RELEASE_DURATION;
+        let attack_curve = default::ATTACK_CURVE;
+        let release_curve = default::RELEASE_CURVE;
+        let hold_duration = default::HOLD_DURATION;
+        let playback_rate = default::PLAYBACK_RATE;
+        let weight = default::WEIGHT;
         let movement = default::MOVEMENT;
+        let placement = default::PLACEMENT;
+        let schedule = Default::default();
         Soundscape {
             installations,
             groups,
@@ -737,7 +968,14 @@ impl Default for Soundscape {
             playback_duration,
             attack_duration,
             release_duration,
+            attack_curve,
+            release_curve,
+            hold_duration,
+            playback_rate,
+            weight,
             movement,
+            placement,
+            schedule,
         }
     }
 }
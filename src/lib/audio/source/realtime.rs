@@ -1,15 +1,35 @@
 //! Items related to the realtime audio input sound source kind.
 
+use audio::{FRAMES_PER_BUFFER, SAMPLE_RATE};
 use crossbeam::sync::SegQueue;
 use std::mem;
 use std::ops;
 use std::sync::{atomic, Arc};
-use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicBool, AtomicUsize};
 use time_calc::{Ms, Samples};
 
 pub type BufferTx = Arc<SegQueue<Vec<f32>>>;
 pub type BufferRx = Arc<SegQueue<Vec<f32>>>;
 
+/// The greatest deviation of the resampling ratio from `1.0` used to correct clock drift.
+///
+/// At 0.5% the resulting change in pitch is under 9 cents.
+pub const MAX_DRIFT_CORRECTION: f64 = 0.005;
+
+/// The duration over which the depth of the queue is averaged in seconds.
+const DEPTH_SMOOTHING_SECS: f64 = 0.5;
+
+/// The maximum correction is applied once the averaged depth of the queue deviates from the
+/// target by this many target depths.
+const CORRECTION_RANGE: f64 = 1.0;
+
+/// Queued frames beyond the target by more than this many seconds are discarded rather than
+/// corrected gradually, e.g. after the output stream stalls.
+const MAX_EXCESS_SECS: f64 = 0.5;
+
+/// The signal ends if no input arrives for this many seconds.
+const MAX_UNDERRUN_SECS: f64 = 1.0;
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Realtime {
     // Duration for which the realtime input is played.
@@ -20,12 +40,15 @@ pub struct Realtime {
 
 /// The signal end of a `Realtime` audio source.
 ///
-/// Implemented as a simple wrapper around an spsc receiver.
+/// Implemented as a wrapper around an spsc receiver.
 ///
-/// Returns all samples as it receives them.
+/// The input and output devices are driven by independent clocks that slowly drift apart. To
+/// compensate, the signal monitors the number of samples queued between the two streams and
+/// resamples its input to keep the queue near its initial depth.
 ///
-/// Returns `None` as soon as the inner receiver either runs out of samples due to falling behind
-/// or if the channel is disconneceted as the sound has played out its duration.
+/// If the queue runs dry, silence is yielded until it has refilled to its target depth. Returns
+/// `None` once the sound has played out its duration, or if no input arrives for
+/// `MAX_UNDERRUN_SECS`.
 pub struct Signal {
     pub buffer_rx: BufferRx,
     pub buffer_tx: BufferTx,
@@ -34,6 +57,33 @@ pub struct Signal {
     pub channels: usize,
     pub remaining_samples: Option<usize>,
     pub is_closed: Arc<AtomicBool>,
+    /// The number of samples within buffers that have been sent by the input stream but not yet
+    /// received by the signal.
+    pub queued_samples: Arc<AtomicUsize>,
+    pub resampler: Resampler,
+}
+
+/// Resamples the input to correct for drift between the input and output clocks.
+pub struct Resampler {
+    // The queue depth in frames that the resampler aims to maintain.
+    target_frames: f64,
+    // The averaged depth of the queue in frames.
+    average_frames: f64,
+    // The rate at which input frames are read for each output frame.
+    ratio: f64,
+    // The two input frames between which the output is currently being interpolated.
+    frame_a: Vec<f32>,
+    frame_b: Vec<f32>,
+    // The fractional position between `frame_a` and `frame_b`.
+    frame_phase: f64,
+    // The index of the next channel to yield within the current output frame.
+    output_channel: usize,
+    // Whether or not the first two frames have been read.
+    primed: bool,
+    // Whether or not the queue ran dry and is being refilled to the target depth.
+    refilling: bool,
+    // The number of consecutive output frames for which no input was available.
+    underrun_frames: usize,
 }
 
 impl Signal {
@@ -48,36 +98,198 @@ impl Signal {
     pub fn remaining_frames(&self) -> Option<Samples> {
         self.remaining_samples.map(|s| Samples((s / self.channels) as _))
     }
+
+    // The number of samples available to the signal, including those within the current buffer.
+    fn available_samples(&self) -> usize {
+        let queued = self.queued_samples.load(atomic::Ordering::Relaxed);
+        queued + self.current_buffer.len().saturating_sub(self.sample_index)
+    }
+
+    // Whether or not all input for the sound's duration has arrived, in which case no more input
+    // will be sent by the input stream.
+    fn is_complete(&self) -> bool {
+        match self.remaining_samples {
+            Some(remaining) => self.available_samples() >= remaining,
+            None => false,
+        }
+    }
+
+    // Update the averaged queue depth and the resampling ratio.
+    //
+    // Called once per output frame.
+    fn update_ratio(&mut self) {
+        let channels = self.channels.max(1);
+        let depth_frames = (self.available_samples() / channels) as f64;
+        let smoothing = 1.0 / (DEPTH_SMOOTHING_SECS * SAMPLE_RATE);
+        let resampler = &mut self.resampler;
+        resampler.average_frames += (depth_frames - resampler.average_frames) * smoothing;
+        let range = resampler.target_frames.max(FRAMES_PER_BUFFER as f64) * CORRECTION_RANGE;
+        let error = (resampler.average_frames - resampler.target_frames) / range;
+        resampler.ratio = 1.0 + error.max(-1.0).min(1.0) * MAX_DRIFT_CORRECTION;
+    }
+
+    // Discard whole buffers while the queue exceeds the target by more than `MAX_EXCESS_SECS`.
+    fn discard_excess(&mut self) {
+        let channels = self.channels.max(1);
+        let max_excess_samples = (MAX_EXCESS_SECS * SAMPLE_RATE) as usize * channels;
+        let target_samples = self.resampler.target_frames as usize * channels;
+        while self.available_samples() > target_samples + max_excess_samples {
+            if !self.next_buffer() {
+                break;
+            }
+            self.resampler.average_frames = self.resampler.target_frames;
+        }
+    }
+
+    // Replace the current buffer with the next queued buffer, returning the current buffer to the
+    // input stream for re-use.
+    //
+    // Returns `false` if there are no queued buffers.
+    fn next_buffer(&mut self) -> bool {
+        match self.buffer_rx.try_pop() {
+            None => false,
+            Some(buffer) => {
+                self.queued_samples.fetch_sub(buffer.len(), atomic::Ordering::Relaxed);
+                let used_buffer = mem::replace(&mut self.current_buffer, buffer);
+                self.buffer_tx.push(used_buffer);
+                self.sample_index = 0;
+                true
+            },
+        }
+    }
+
+    // The next input sample, or `None` if the queue has run dry.
+    fn next_input_sample(&mut self) -> Option<f32> {
+        loop {
+            if self.sample_index < self.current_buffer.len() {
+                let sample = self.current_buffer[self.sample_index];
+                self.remaining_samples = self.remaining_samples.map(|n| n.saturating_sub(1));
+                self.sample_index += 1;
+                return Some(sample);
+            }
+            if !self.next_buffer() {
+                return None;
+            }
+        }
+    }
+
+    // Read the next input frame into `frame_b`, returning whether or not a full frame was read.
+    //
+    // A full frame is only read if one is available so that frames are never split by an
+    // underrun.
+    fn read_frame_b(&mut self) -> bool {
+        if self.available_samples() < self.channels {
+            return false;
+        }
+        let mut frame = mem::replace(&mut self.resampler.frame_b, Vec::new());
+        let mut complete = true;
+        for sample in frame.iter_mut() {
+            match self.next_input_sample() {
+                Some(s) => *sample = s,
+                None => {
+                    complete = false;
+                    break;
+                },
+            }
+        }
+        self.resampler.frame_b = frame;
+        complete
+    }
+
+    // Advance the resampler to the next output frame.
+    //
+    // Returns `false` if there was not enough input to do so.
+    fn advance_frame(&mut self) -> bool {
+        if !self.resampler.primed {
+            if self.available_samples() < self.channels * 2 {
+                return false;
+            }
+            self.read_frame_b();
+            mem::swap(&mut self.resampler.frame_a, &mut self.resampler.frame_b);
+            self.read_frame_b();
+            self.resampler.frame_phase = 0.0;
+            self.resampler.primed = true;
+            return true;
+        }
+        self.update_ratio();
+        let next_phase = self.resampler.frame_phase + self.resampler.ratio;
+        let frames_needed = next_phase.floor() as usize;
+        if self.available_samples() < frames_needed * self.channels {
+            return false;
+        }
+        for _ in 0..frames_needed {
+            mem::swap(&mut self.resampler.frame_a, &mut self.resampler.frame_b);
+            self.read_frame_b();
+        }
+        self.resampler.frame_phase = next_phase - frames_needed as f64;
+        true
+    }
+}
+
+impl Resampler {
+    /// Create a resampler that aims to keep the given number of frames queued between the input
+    /// and output streams.
+    pub fn new(channels: usize, target_frames: usize) -> Self {
+        Resampler {
+            target_frames: target_frames as f64,
+            average_frames: target_frames as f64,
+            ratio: 1.0,
+            frame_a: vec![0.0; channels],
+            frame_b: vec![0.0; channels],
+            frame_phase: 0.0,
+            output_channel: 0,
+            primed: false,
+            refilling: false,
+            underrun_frames: 0,
+        }
+    }
 }
 
 impl Iterator for Signal {
     type Item = f32;
     fn next(&mut self) -> Option<Self::Item> {
-        let Signal {
-            ref buffer_rx,
-            ref buffer_tx,
-            ref mut sample_index,
-            ref mut current_buffer,
-            ref mut remaining_samples,
-            ..
-        } = *self;
+        if self.channels == 0 {
+            return None;
+        }
 
-        loop {
-            if *sample_index < current_buffer.len() {
-                let sample = current_buffer[*sample_index];
-                *remaining_samples = remaining_samples.map(|n| n.saturating_sub(1));
-                *sample_index += 1;
-                return Some(sample);
+        // Advance to the next output frame if necessary.
+        if self.resampler.output_channel == 0 {
+            self.discard_excess();
+
+            // Wait for the queue to refill to its target depth after running dry.
+            if self.resampler.refilling {
+                let target_samples = self.resampler.target_frames as usize * self.channels;
+                if self.available_samples() >= target_samples.max(self.channels * 2) {
+                    self.resampler.refilling = false;
+                    self.resampler.primed = false;
+                    self.resampler.average_frames = self.resampler.target_frames;
+                }
             }
-            match buffer_rx.try_pop() {
-                None => return None,
-                Some(buffer) => {
-                    let used_buffer = mem::replace(current_buffer, buffer);
-                    buffer_tx.push(used_buffer);
-                    *sample_index = 0;
-                },
+
+            let advanced = !self.resampler.refilling && self.advance_frame();
+            if advanced {
+                self.resampler.underrun_frames = 0;
+            } else {
+                if self.is_complete() {
+                    return None;
+                }
+                self.resampler.refilling = true;
+                self.resampler.underrun_frames += 1;
+                if self.resampler.underrun_frames as f64 > MAX_UNDERRUN_SECS * SAMPLE_RATE {
+                    return None;
+                }
+                // Yield a frame of silence while the queue refills.
+                self.resampler.frame_a.iter_mut().for_each(|s| *s = 0.0);
+                self.resampler.frame_b.iter_mut().for_each(|s| *s = 0.0);
+                self.resampler.frame_phase = 0.0;
             }
         }
+
+        let ch = self.resampler.output_channel;
+        let a = self.resampler.frame_a[ch];
+        let b = self.resampler.frame_b[ch];
+        self.resampler.output_channel = (ch + 1) % self.channels;
+        Some(a + (b - a) * self.resampler.frame_phase as f32)
     }
 }
 
@@ -86,3 +298,120 @@ impl Drop for Signal {
         self.is_closed.store(true, atomic::Ordering::Relaxed);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use audio::SAMPLE_RATE;
+    use crossbeam::sync::SegQueue;
+    use std::sync::atomic::{self, AtomicBool, AtomicUsize};
+    use std::sync::Arc;
+    use super::{Resampler, Signal, MAX_DRIFT_CORRECTION, MAX_EXCESS_SECS, MAX_UNDERRUN_SECS};
+
+    // A continuous signal aiming for the given queue depth with the given buffers already queued.
+    fn signal(channels: usize, target_frames: usize, buffers: Vec<Vec<f32>>) -> Signal {
+        let buffer_rx = Arc::new(SegQueue::new());
+        let queued: usize = buffers.iter().map(|b| b.len()).sum();
+        for buffer in buffers {
+            buffer_rx.push(buffer);
+        }
+        Signal {
+            buffer_rx,
+            buffer_tx: Arc::new(SegQueue::new()),
+            sample_index: 0,
+            current_buffer: Vec::new(),
+            channels,
+            remaining_samples: None,
+            is_closed: Arc::new(AtomicBool::new(false)),
+            queued_samples: Arc::new(AtomicUsize::new(queued)),
+            resampler: Resampler::new(channels, target_frames),
+        }
+    }
+
+    // Send a buffer to the signal in the same manner as the input stream.
+    fn send(signal: &Signal, buffer: Vec<f32>) {
+        signal.queued_samples.fetch_add(buffer.len(), atomic::Ordering::Relaxed);
+        signal.buffer_rx.push(buffer);
+    }
+
+    // The resampling ratio once the averaged depth has settled at the given queue depth.
+    fn settled_ratio(target_frames: usize, depth_frames: usize) -> f64 {
+        let mut signal = signal(2, target_frames, vec![vec![0.0; depth_frames * 2]]);
+        let settle_frames = (SAMPLE_RATE * 5.0) as usize;
+        for _ in 0..settle_frames {
+            signal.update_ratio();
+        }
+        signal.resampler.ratio
+    }
+
+    #[test]
+    fn ratio_is_unity_at_target_depth() {
+        assert!((settled_ratio(1024, 1024) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn ratio_corrects_in_proportion_to_depth() {
+        let half = MAX_DRIFT_CORRECTION * 0.5;
+        assert!((settled_ratio(1024, 1536) - (1.0 + half)).abs() < 1e-5);
+        assert!((settled_ratio(1024, 512) - (1.0 - half)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn ratio_is_clamped() {
+        let max = 1.0 + MAX_DRIFT_CORRECTION;
+        let min = 1.0 - MAX_DRIFT_CORRECTION;
+        assert!((settled_ratio(1024, 2048) - max).abs() < 1e-5);
+        assert_eq!(settled_ratio(1024, 8192), max);
+        assert!((settled_ratio(1024, 0) - min).abs() < 1e-5);
+    }
+
+    #[test]
+    fn excess_beyond_half_a_second_is_dropped() {
+        let target_frames = 480;
+        let max_excess_frames = (MAX_EXCESS_SECS * SAMPLE_RATE) as usize;
+        let buffers = (0..101).map(|_| vec![0.0; 480]).collect();
+        let mut signal = signal(1, target_frames, buffers);
+        assert_eq!(signal.next(), Some(0.0));
+        let available = signal.available_samples();
+        assert!(available <= target_frames + max_excess_frames);
+        assert!(available >= max_excess_frames);
+        // Dropped buffers are returned to the input stream for re-use.
+        let mut returned = 0;
+        while signal.buffer_tx.try_pop().is_some() {
+            returned += 1;
+        }
+        assert!(returned >= 48);
+    }
+
+    #[test]
+    fn underrun_yields_silence_until_refilled() {
+        let target_frames = 480;
+        let mut signal = signal(1, target_frames, vec![vec![1.0; target_frames]]);
+
+        // Drain the queue until it runs dry.
+        let drained = signal.by_ref().take(target_frames * 2).take_while(|&s| s == 1.0).count();
+        assert!(drained > target_frames / 2 && drained < target_frames * 2);
+        assert!(signal.resampler.refilling);
+
+        // Partially refilled, the signal continues to yield silence.
+        send(&signal, vec![1.0; target_frames / 2]);
+        assert!(signal.by_ref().take(64).all(|s| s == 0.0));
+
+        // Once the queue reaches its target depth, the input resumes.
+        send(&signal, vec![1.0; target_frames / 2]);
+        assert_eq!(signal.next(), Some(1.0));
+        assert!(!signal.resampler.refilling);
+    }
+
+    #[test]
+    fn signal_ends_after_a_second_of_underrun() {
+        let mut signal = signal(2, 480, vec![]);
+        let underrun_frames = (MAX_UNDERRUN_SECS * SAMPLE_RATE) as usize;
+        let mut yielded = 0;
+        for sample in signal.by_ref() {
+            assert_eq!(sample, 0.0);
+            yielded += 1;
+        }
+        assert_eq!(yielded, underrun_frames * 2);
+        assert_eq!(signal.next(), None);
+    }
+}
@@ -0,0 +1,123 @@
+//! A thread dedicated to analysing WAV files in the background.
+//!
+//! Analysis may require reading entire WAV files, so it is performed away from the GUI thread
+//! and the results are collected by the GUI as they become available.
+
+use crossbeam::sync::{MsQueue, SegQueue};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{self, AtomicBool};
+use std::thread;
+use super::loudness::{self, Loudness};
+use super::overview::{self, Overview};
+
+/// Sends messages to the `wav::analysis` thread.
+pub type Tx = Arc<MsQueue<Message>>;
+
+/// Receives `Message`s on the `wav::analysis` thread.
+pub type Rx = Arc<MsQueue<Message>>;
+
+/// Sends the results of each analysis from the `wav::analysis` thread.
+pub type OutputTx = Arc<SegQueue<Output>>;
+
+/// Receives the results of each analysis.
+pub type OutputRx = Arc<SegQueue<Output>>;
+
+/// A handle to the WAV analysis thread.
+#[derive(Clone)]
+pub struct Handle {
+    tx: Tx,
+    output_rx: OutputRx,
+    is_exiting: Arc<AtomicBool>,
+    thread: Arc<Mutex<Option<thread::JoinHandle<()>>>>,
+}
+
+/// Messages received by the analysis thread.
+pub enum Message {
+    /// Measure the loudness of the WAV at the given path.
+    Loudness(PathBuf),
+    /// Load or generate the overview of the WAV at the given path.
+    Overview(PathBuf),
+    /// Break from the loop as the application is closing.
+    Exit,
+}
+
+/// The result of an analysis.
+#[derive(Debug)]
+pub enum Output {
+    /// The loudness of the WAV at the given path.
+    Loudness(PathBuf, Loudness),
+    /// The overview of the WAV at the given path.
+    Overview(PathBuf, Overview),
+}
+
+impl Handle {
+    /// Request that the loudness of the WAV at the given path be measured.
+    pub fn analyse_loudness(&self, path: PathBuf) {
+        self.tx.push(Message::Loudness(path));
+    }
+
+    /// Request the overview of the WAV at the given path.
+    ///
+    /// The overview is loaded from its cache if it is up to date, otherwise it is generated.
+    pub fn overview(&self, path: PathBuf) {
+        self.tx.push(Message::Overview(path));
+    }
+
+    /// Produce the next completed analysis if there is one.
+    pub fn try_recv(&self) -> Option<Output> {
+        self.output_rx.try_pop()
+    }
+
+    /// Stops the analysis thread and returns the raw handle to its thread.
+    ///
+    /// Any pending analysis requests are discarded.
+    pub fn exit(self) -> Option<thread::JoinHandle<()>> {
+        self.is_exiting.store(true, atomic::Ordering::Relaxed);
+        self.tx.push(Message::Exit);
+        self.thread.lock().unwrap().take()
+    }
+}
+
+/// Runs the WAV analysis thread and returns a handle to it.
+pub fn spawn() -> Handle {
+    let queue = Arc::new(MsQueue::new());
+    let tx = queue.clone();
+    let rx = queue;
+    let output_queue = Arc::new(SegQueue::new());
+    let output_tx = output_queue.clone();
+    let output_rx = output_queue;
+    let is_exiting = Arc::new(AtomicBool::new(false));
+    let is_exiting_2 = is_exiting.clone();
+    let thread = thread::Builder::new()
+        .name("wav_analysis".into())
+        .spawn(move || run(rx, output_tx, is_exiting_2))
+        .unwrap();
+    let thread = Arc::new(Mutex::new(Some(thread)));
+    Handle { tx, output_rx, is_exiting, thread }
+}
+
+/// Run the analysis loop, processing each request in turn.
+fn run(rx: Rx, output_tx: OutputTx, is_exiting: Arc<AtomicBool>) {
+    loop {
+        let msg = rx.pop();
+        if is_exiting.load(atomic::Ordering::Relaxed) {
+            break;
+        }
+        match msg {
+            Message::Loudness(path) => match loudness::analyse(&path) {
+                Ok(loudness) => output_tx.push(Output::Loudness(path, loudness)),
+                Err(err) => {
+                    eprintln!("failed to measure loudness of \"{}\": {}", path.display(), err);
+                },
+            },
+            Message::Overview(path) => match overview::load_or_generate(&path) {
+                Ok(overview) => output_tx.push(Output::Overview(path, overview)),
+                Err(err) => {
+                    eprintln!("failed to generate overview of \"{}\": {}", path.display(), err);
+                },
+            },
+            Message::Exit => break,
+        }
+    }
+}
@@ -0,0 +1,102 @@
+//! Reading of the optional RIFF chunks within a WAV file that `hound` does not expose.
+
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use time_calc::Samples;
+use utils::Range;
+
+/// The size of a single cue point entry within a `cue ` chunk in bytes.
+const CUE_POINT_SIZE: usize = 24;
+
+/// The size of the `smpl` chunk header that precedes the list of sample loops in bytes.
+const SAMPLER_HEADER_SIZE: usize = 36;
+
+/// The size of a single sample loop entry within a `smpl` chunk in bytes.
+const SAMPLE_LOOP_SIZE: usize = 24;
+
+/// Read the frame positions of all cue markers within the WAV file at the given path.
+///
+/// The returned positions are sorted and de-duplicated. An empty list is returned if the WAV has
+/// no `cue ` chunk.
+pub fn read_cue_points(path: &Path) -> io::Result<Vec<Samples>> {
+    let data = match read_chunk(path, b"cue ")? {
+        None => return Ok(vec![]),
+        Some(data) => data,
+    };
+    if data.len() < 4 {
+        return Ok(vec![]);
+    }
+    let num_cues = u32_le(&data[0..4]) as usize;
+    let mut frames: Vec<u32> = data[4..]
+        .chunks(CUE_POINT_SIZE)
+        .take(num_cues)
+        .filter(|cue| cue.len() == CUE_POINT_SIZE)
+        // The `sample_offset` field is the last in each cue point entry.
+        .map(|cue| u32_le(&cue[20..24]))
+        .collect();
+    frames.sort();
+    frames.dedup();
+    Ok(frames.into_iter().map(|frame| Samples(frame as _)).collect())
+}
+
+/// Read the sample loops described by the `smpl` chunk of the WAV file at the given path.
+///
+/// Each loop is returned as a range of frames with an exclusive `max`. Loops whose end does not
+/// follow their start are ignored. An empty list is returned if the WAV has no `smpl` chunk.
+pub fn read_sample_loops(path: &Path) -> io::Result<Vec<Range<Samples>>> {
+    let data = match read_chunk(path, b"smpl")? {
+        None => return Ok(vec![]),
+        Some(data) => data,
+    };
+    if data.len() < SAMPLER_HEADER_SIZE {
+        return Ok(vec![]);
+    }
+    let num_loops = u32_le(&data[28..32]) as usize;
+    let loops = data[SAMPLER_HEADER_SIZE..]
+        .chunks(SAMPLE_LOOP_SIZE)
+        .take(num_loops)
+        .filter(|sample_loop| sample_loop.len() == SAMPLE_LOOP_SIZE)
+        // The `end` of each sample loop is the last frame to be played.
+        .map(|sample_loop| (u32_le(&sample_loop[8..12]), u32_le(&sample_loop[12..16])))
+        .filter(|&(start, end)| start <= end)
+        .map(|(start, end)| Range { min: Samples(start as _), max: Samples(end as i64 + 1) })
+        .collect();
+    Ok(loops)
+}
+
+/// Search the top-level chunks of the RIFF file at the given path for the chunk with the given
+/// id and return its data.
+fn read_chunk(path: &Path, id: &[u8; 4]) -> io::Result<Option<Vec<u8>>> {
+    let mut file = BufReader::new(File::open(path)?);
+    let mut header = [0u8; 12];
+    file.read_exact(&mut header)?;
+    if &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not a RIFF WAVE file"));
+    }
+    let mut chunk_header = [0u8; 8];
+    loop {
+        match file.read_exact(&mut chunk_header) {
+            Ok(()) => (),
+            Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err),
+        }
+        let len = u32_le(&chunk_header[4..8]) as u64;
+        if &chunk_header[0..4] == id {
+            let mut data = vec![0u8; len as usize];
+            file.read_exact(&mut data)?;
+            return Ok(Some(data));
+        }
+        // Chunks are padded to an even number of bytes.
+        let skip = len + (len & 1);
+        file.seek(SeekFrom::Current(skip as i64))?;
+    }
+}
+
+/// Interpret the given 4 bytes as a little-endian `u32`.
+fn u32_le(bytes: &[u8]) -> u32 {
+    bytes[0] as u32
+        | (bytes[1] as u32) << 8
+        | (bytes[2] as u32) << 16
+        | (bytes[3] as u32) << 24
+}
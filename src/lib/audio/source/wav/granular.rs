@@ -0,0 +1,413 @@
+//! Items related to the granular playback of WAV sources.
+//!
+//! A granular sound continuously streams its WAV (looped) from the `wav::reader` thread into a
+//! short history window. Grains of a random size are then read from random positions within this
+//! window at a random pitch and scattered over time, summing into a single interleaved signal.
+
+use audio;
+use nannou::rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
+use std::f64::consts::PI;
+use super::reader::SamplesStream;
+use time_calc::Ms;
+use utils::{Range, Seed};
+
+/// The duration of source audio retained for reading grains.
+pub const WINDOW_DURATION: Ms = Ms(4_000.0);
+
+/// The absolute minimum size of a single grain.
+pub const MIN_GRAIN_SIZE: Ms = Ms(5.0);
+
+/// The absolute maximum size of a single grain.
+pub const MAX_GRAIN_SIZE: Ms = Ms(1_000.0);
+
+/// The absolute minimum grain density in grains per second.
+///
+/// Densities below this would postpone the next grain almost indefinitely.
+pub const MIN_DENSITY: f64 = 0.1;
+
+/// The absolute maximum grain density in grains per second.
+pub const MAX_DENSITY: f64 = 200.0;
+
+/// The absolute maximum pitch jitter in semitones.
+pub const MAX_PITCH_JITTER: f64 = 24.0;
+
+/// The maximum number of grains that may sound simultaneously for a single sound.
+///
+/// Grains are dropped if the density and grain size would require more than this.
+pub const MAX_GRAINS: usize = 128;
+
+/// Parameters describing the granular playback of a WAV.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Granular {
+    /// The range from which the duration of each grain is chosen.
+    #[serde(default = "default::grain_size")]
+    pub grain_size: Range<Ms>,
+    /// The average number of grains triggered per second.
+    #[serde(default = "default::density")]
+    pub density: f64,
+    /// How far back into the history window a grain may start reading.
+    ///
+    /// `0.0` reads grains from the most recently streamed audio, `1.0` may read from anywhere
+    /// within the window.
+    #[serde(default = "default::position_jitter")]
+    pub position_jitter: f64,
+    /// The maximum deviation in pitch of each grain in semitones.
+    #[serde(default = "default::pitch_jitter")]
+    pub pitch_jitter: f64,
+}
+
+/// A single grain currently being played.
+#[derive(Copy, Clone, Debug)]
+struct Grain {
+    // The absolute frame position within the stream at which the grain starts reading.
+    start: f64,
+    // The rate at which the grain reads through the stream.
+    rate: f64,
+    // The total duration of the grain in frames.
+    duration_frames: usize,
+    // The current frame within the grain.
+    current_frame: usize,
+}
+
+/// A signal yielding interleaved samples produced by scattering grains read from a WAV stream.
+pub struct Grains {
+    /// The granular parameters, updated via the GUI.
+    pub params: Granular,
+    samples: SamplesStream,
+    channels: usize,
+    // A ring buffer of the most recently streamed interleaved samples.
+    window: Vec<f32>,
+    window_frames: usize,
+    // The total number of frames written to the window.
+    written_frames: u64,
+    grains: Vec<Grain>,
+    frames_until_next_grain: f64,
+    rng: XorShiftRng,
+    // The current output frame and the index of the next sample to yield from it.
+    frame: Vec<f32>,
+    frame_index: usize,
+}
+
+impl Grains {
+    /// Construct a new granular signal from the given looped WAV samples stream.
+    ///
+    /// All memory required by the signal is allocated here so that no allocation occurs on the
+    /// audio thread.
+    pub fn new(samples: SamplesStream, params: Granular, seed: Seed) -> Self {
+        let channels = samples.channels();
+        let window_frames = WINDOW_DURATION.samples(audio::SAMPLE_RATE) as usize;
+        let window = vec![0.0; window_frames * channels];
+        let grains = Vec::with_capacity(MAX_GRAINS);
+        let frame = vec![0.0; channels];
+        let frame_index = channels;
+        let rng = XorShiftRng::from_seed(seed);
+        Grains {
+            params,
+            samples,
+            channels,
+            window,
+            window_frames,
+            written_frames: 0,
+            grains,
+            frames_until_next_grain: 0.0,
+            rng,
+            frame,
+            frame_index,
+        }
+    }
+
+    /// The number of channels yielded by the signal.
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Set the rate at which the WAV is streamed into the history window.
+    pub fn set_playback_rate(&mut self, rate: f64) {
+        self.samples.set_playback_rate(rate);
+    }
+
+    // Read the next frame from the WAV stream into the history window.
+    //
+    // If the reader has not yet delivered the next buffer, the write head is not advanced so that
+    // the frame is written to the same position once it arrives.
+    fn write_next_frame(&mut self) {
+        let start = (self.written_frames % self.window_frames as u64) as usize * self.channels;
+        for sample in &mut self.window[start..start + self.channels] {
+            *sample = self.samples.next_sample().unwrap_or(0.0);
+        }
+        if !self.samples.is_stalled() {
+            self.written_frames += 1;
+        }
+    }
+
+    // Attempt to trigger a new grain using the current parameters.
+    fn trigger_grain(&mut self) {
+        let Granular { grain_size, position_jitter, pitch_jitter, .. } = self.params;
+
+        let min_ms = grain_size.min.ms().max(MIN_GRAIN_SIZE.ms());
+        let max_ms = grain_size.max.ms().max(min_ms);
+        let size_ms = if min_ms < max_ms { self.rng.gen_range(min_ms, max_ms) } else { min_ms };
+        let duration_frames = Ms(size_ms).samples(audio::SAMPLE_RATE) as usize;
+        if duration_frames == 0 {
+            return;
+        }
+
+        let semitones = if pitch_jitter > 0.0 {
+            self.rng.gen_range(-pitch_jitter, pitch_jitter)
+        } else {
+            0.0
+        };
+        let rate = 2.0f64.powf(semitones / 12.0);
+
+        let size = duration_frames as f64;
+        let (min_lookback, max_lookback) =
+            match lookback_range(self.written_frames, self.window_frames, size, rate) {
+                None => return,
+                Some(range) => range,
+            };
+        let jitter = position_jitter.max(0.0).min(1.0) * self.rng.gen::<f64>();
+        let lookback = min_lookback + jitter * (max_lookback - min_lookback);
+        let start = self.written_frames as f64 - lookback;
+
+        let grain = Grain { start, rate, duration_frames, current_frame: 0 };
+        self.grains.push(grain);
+    }
+
+    // Produce the next interleaved output frame.
+    fn next_frame(&mut self) {
+        self.write_next_frame();
+
+        // Trigger a new grain if it is time.
+        self.frames_until_next_grain -= 1.0;
+        if self.frames_until_next_grain <= 0.0 {
+            if self.grains.len() < MAX_GRAINS {
+                self.trigger_grain();
+            }
+            self.frames_until_next_grain += next_grain_interval(&mut self.rng, self.params.density);
+        }
+
+        // Scale the output by the expected number of overlapping grains.
+        let mean_size_ms = (self.params.grain_size.min.ms() + self.params.grain_size.max.ms()) * 0.5;
+        let overlap = self.params.density * mean_size_ms / 1_000.0;
+        let gain = 1.0 / overlap.max(1.0).sqrt() as f32;
+
+        let Grains {
+            ref mut grains,
+            ref mut frame,
+            ref window,
+            window_frames,
+            channels,
+            ..
+        } = *self;
+
+        for sample in frame.iter_mut() {
+            *sample = 0.0;
+        }
+
+        let mut i = 0;
+        while i < grains.len() {
+            let finished = {
+                let grain = &mut grains[i];
+                let amp = grain_amp(grain.current_frame, grain.duration_frames) as f32 * gain;
+                let position = grain.start + grain.current_frame as f64 * grain.rate;
+                let (frame_a, frame_b, fract) = window_frames_at(position, window_frames, channels);
+                for (ch, sample) in frame.iter_mut().enumerate() {
+                    let a = window[frame_a + ch];
+                    let b = window[frame_b + ch];
+                    *sample += (a + (b - a) * fract) * amp;
+                }
+                grain.current_frame += 1;
+                grain.current_frame >= grain.duration_frames
+            };
+            if finished {
+                grains.swap_remove(i);
+            } else {
+                i += 1;
+            }
+        }
+
+        self.frame_index = 0;
+    }
+}
+
+// The number of frames until the grain following the one just triggered at the given density.
+//
+// Intervals vary randomly between half and one and a half times the mean interval.
+fn next_grain_interval<R>(rng: &mut R, density: f64) -> f64
+where
+    R: Rng,
+{
+    let interval_frames = audio::SAMPLE_RATE / density.max(MIN_DENSITY);
+    interval_frames * rng.gen_range(0.5, 1.5)
+}
+
+// The range of distances back from the write head at which a grain of the given size and rate
+// may start so that it neither overtakes the write head nor reads audio that has been
+// overwritten.
+//
+// Returns `None` if not enough audio has been written to the window for the grain.
+fn lookback_range(
+    written_frames: u64,
+    window_frames: usize,
+    size: f64,
+    rate: f64,
+) -> Option<(f64, f64)> {
+    let available = ::std::cmp::min(written_frames, window_frames as u64) as f64;
+    let min_lookback = 1.0 + size * (rate - 1.0).max(0.0);
+    let max_lookback = available - 1.0 - size * (1.0 - rate).max(0.0);
+    if max_lookback < min_lookback {
+        return None;
+    }
+    Some((min_lookback, max_lookback))
+}
+
+// The amplitude of the Hann window at the given frame of a grain.
+fn grain_amp(current_frame: usize, duration_frames: usize) -> f64 {
+    let phase = current_frame as f64 / duration_frames as f64;
+    0.5 - 0.5 * (phase * 2.0 * PI).cos()
+}
+
+// The sample indices of the two window frames surrounding the given absolute frame position,
+// along with the fractional position between them.
+fn window_frames_at(position: f64, window_frames: usize, channels: usize) -> (usize, usize, f32) {
+    let index_a = position.floor() as u64;
+    let fract = (position - index_a as f64) as f32;
+    let frame_a = (index_a % window_frames as u64) as usize * channels;
+    let frame_b = ((index_a + 1) % window_frames as u64) as usize * channels;
+    (frame_a, frame_b, fract)
+}
+
+impl Iterator for Grains {
+    type Item = f32;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.frame_index >= self.channels {
+            self.next_frame();
+        }
+        let sample = self.frame.get(self.frame_index).cloned();
+        self.frame_index += 1;
+        sample
+    }
+}
+
+pub mod default {
+    use super::Granular;
+    use time_calc::Ms;
+    use utils::Range;
+
+    pub const GRAIN_SIZE: Range<Ms> = Range { min: Ms(50.0), max: Ms(150.0) };
+    pub const DENSITY: f64 = 20.0;
+    pub const POSITION_JITTER: f64 = 0.5;
+    pub const PITCH_JITTER: f64 = 0.0;
+    pub const GRANULAR: Granular = Granular {
+        grain_size: GRAIN_SIZE,
+        density: DENSITY,
+        position_jitter: POSITION_JITTER,
+        pitch_jitter: PITCH_JITTER,
+    };
+
+    pub fn grain_size() -> Range<Ms> {
+        GRAIN_SIZE
+    }
+
+    pub fn density() -> f64 {
+        DENSITY
+    }
+
+    pub fn position_jitter() -> f64 {
+        POSITION_JITTER
+    }
+
+    pub fn pitch_jitter() -> f64 {
+        PITCH_JITTER
+    }
+}
+
+impl Default for Granular {
+    fn default() -> Self {
+        default::GRANULAR
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use audio;
+    use nannou::rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+    use super::{grain_amp, lookback_range, next_grain_interval, window_frames_at, MIN_DENSITY};
+
+    #[test]
+    fn grain_interval_averages_density() {
+        let mut rng = XorShiftRng::from_seed([1; 16]);
+        let density = 20.0;
+        let mean = audio::SAMPLE_RATE / density;
+        let n = 10_000;
+        let mut total = 0.0;
+        for _ in 0..n {
+            let interval = next_grain_interval(&mut rng, density);
+            assert!(interval >= mean * 0.5 && interval < mean * 1.5);
+            total += interval;
+        }
+        let average = total / n as f64;
+        assert!((average - mean).abs() < mean * 0.02);
+    }
+
+    #[test]
+    fn grain_interval_is_bounded_at_zero_density() {
+        let mut rng = XorShiftRng::from_seed([1; 16]);
+        let max = audio::SAMPLE_RATE / MIN_DENSITY * 1.5;
+        for _ in 0..100 {
+            let interval = next_grain_interval(&mut rng, 0.0);
+            assert!(interval.is_finite() && interval <= max);
+        }
+    }
+
+    #[test]
+    fn lookback_requires_written_audio() {
+        assert_eq!(lookback_range(0, 1_000, 100.0, 1.0), None);
+        assert_eq!(lookback_range(50, 1_000, 100.0, 0.5), None);
+        assert_eq!(lookback_range(1_000, 1_000, 100.0, 1.0), Some((1.0, 999.0)));
+        // Only the window is available once more than it has been written.
+        assert_eq!(lookback_range(5_000, 1_000, 100.0, 1.0), Some((1.0, 999.0)));
+    }
+
+    #[test]
+    fn grains_stay_within_written_window() {
+        let window_frames = 1_000;
+        for &written in &[200u64, 1_000, 12_345] {
+            for &rate in &[0.5, 1.0, 2.0] {
+                let size = 100.0;
+                let (min, max) = match lookback_range(written, window_frames, size, rate) {
+                    None => continue,
+                    Some(range) => range,
+                };
+                for &lookback in &[min, max] {
+                    let start = written as f64 - lookback;
+                    for k in 0..size as u64 {
+                        // The write head advances by one frame for every frame of the grain.
+                        let head = written + k;
+                        let position = start + k as f64 * rate;
+                        let oldest = head.saturating_sub(window_frames as u64) as f64;
+                        assert!(position >= oldest, "read overwritten audio");
+                        assert!(position <= (head - 1) as f64, "overtook write head");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn window_frames_wrap() {
+        assert_eq!(window_frames_at(0.0, 4, 2), (0, 2, 0.0));
+        assert_eq!(window_frames_at(2.5, 4, 2), (4, 6, 0.5));
+        assert_eq!(window_frames_at(3.25, 4, 2), (6, 0, 0.25));
+        assert_eq!(window_frames_at(9.0, 4, 2), (2, 4, 0.0));
+    }
+
+    #[test]
+    fn grain_amp_is_hann_window() {
+        assert_eq!(grain_amp(0, 100), 0.0);
+        assert!((grain_amp(50, 100) - 1.0).abs() < 1e-9);
+        assert!((grain_amp(25, 100) - 0.5).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,433 @@
+//! Measurement of the integrated loudness and true peak of WAV files as described by EBU R128 and
+//! ITU-R BS.1770.
+//!
+//! All channels are weighted equally as the channels of our WAVs are not associated with any
+//! standard surround layout.
+
+use audio;
+use fxhash::FxHashMap;
+use hound::{self, SampleFormat};
+use nannou_audio::sample::Sample;
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// The lowest loudness that may be measured in LUFS.
+///
+/// This is the absolute gate of the measurement. Silent WAVs are reported at this loudness.
+pub const MIN_LOUDNESS_LUFS: f64 = -70.0;
+
+/// The lowest true peak that may be measured in dBTP. Silent WAVs are reported at this peak.
+pub const MIN_TRUE_PEAK_DBTP: f64 = -120.0;
+
+/// Normalisation gain is limited so that the true peak of a source never exceeds this level.
+pub const MAX_TRUE_PEAK_DBTP: f64 = -1.0;
+
+/// The range of target loudness values that may be selected for a source in LUFS.
+pub const MIN_TARGET_LUFS: f32 = -40.0;
+pub const MAX_TARGET_LUFS: f32 = -6.0;
+
+/// The duration of each gating block in 100ms segments.
+const SEGMENTS_PER_BLOCK: usize = 4;
+
+/// The number of frames within each 100ms segment at the audio server's sample rate.
+const SEGMENT_FRAMES: usize = (audio::SAMPLE_RATE / 10.0) as usize;
+
+/// The loudness of each block is compared against the ungated loudness less this many LU.
+const RELATIVE_GATE_LU: f64 = -10.0;
+
+/// The factor by which the signal is oversampled when searching for the true peak.
+const OVERSAMPLING: usize = 4;
+
+/// The number of taps in each phase of the oversampling interpolation filter.
+const TAPS_PER_PHASE: usize = 12;
+
+/// The K-weighting pre-filter (high shelf) coefficients at 48kHz as `[b0, b1, b2, a1, a2]`.
+const PRE_FILTER: [f64; 5] = [
+    1.53512485958697,
+    -2.69169618940638,
+    1.19839281085285,
+    -1.69065929318241,
+    0.73248077421585,
+];
+
+/// The K-weighting RLB filter (high pass) coefficients at 48kHz as `[b0, b1, b2, a1, a2]`.
+const RLB_FILTER: [f64; 5] = [1.0, -2.0, 1.0, -1.99004745483398, 0.99007225036621];
+
+/// The result of measuring the loudness of a WAV.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Loudness {
+    /// The gated, integrated loudness in LUFS.
+    pub integrated_lufs: f64,
+    /// The highest peak of the 4x oversampled signal in dBTP.
+    pub true_peak_dbtp: f64,
+}
+
+/// Loudness measurements for WAV files, saved alongside a project so that each WAV need only be
+/// measured once.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Cache {
+    #[serde(default)]
+    entries: FxHashMap<PathBuf, CacheEntry>,
+}
+
+/// A single measurement within the `Cache`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct CacheEntry {
+    loudness: Loudness,
+    // The size of the file in bytes at the time it was measured.
+    len: u64,
+    // The modification time of the file in seconds since the unix epoch at the time it was
+    // measured.
+    modified_secs: u64,
+}
+
+/// A direct form 1 biquad filter.
+#[derive(Clone, Debug, Default)]
+struct Biquad {
+    x1: f64,
+    x2: f64,
+    y1: f64,
+    y2: f64,
+}
+
+/// Per-channel measurement state.
+#[derive(Clone, Debug)]
+struct Channel {
+    pre_filter: Biquad,
+    rlb_filter: Biquad,
+    // The sum of the squared, K-weighted samples within the current segment.
+    segment_sum: f64,
+    // The most recent samples, newest first, used for interpolating the true peak.
+    history: VecDeque<f32>,
+}
+
+/// The state of an ongoing loudness measurement.
+struct Analysis {
+    channels: Vec<Channel>,
+    // The number of frames written to the current segment.
+    segment_frames: usize,
+    // The summed mean square of all channels for each of the most recent segments.
+    segments: VecDeque<f64>,
+    // The summed mean square of all channels for each gating block.
+    blocks: Vec<f64>,
+    // The polyphase oversampling filter.
+    interpolation_filter: Vec<f32>,
+    // The highest absolute sample value found.
+    peak: f32,
+}
+
+impl Loudness {
+    /// The gain necessary to bring a WAV of this loudness to the given target loudness.
+    ///
+    /// The gain is limited so that the true peak does not exceed `MAX_TRUE_PEAK_DBTP`.
+    pub fn normalisation_gain(&self, target_lufs: f32) -> f32 {
+        let gain_db = (target_lufs as f64 - self.integrated_lufs)
+            .min(MAX_TRUE_PEAK_DBTP - self.true_peak_dbtp);
+        db_to_amp(gain_db) as f32
+    }
+}
+
+impl Cache {
+    /// The cached loudness of the WAV at the given path.
+    ///
+    /// Returns `None` if the WAV has not been measured or if the file has changed since it was.
+    pub fn get(&self, path: &Path) -> Option<Loudness> {
+        let entry = self.entries.get(path)?;
+        match file_stamp(path) {
+            Some((len, modified_secs)) if len == entry.len && modified_secs == entry.modified_secs => {
+                Some(entry.loudness)
+            },
+            _ => None,
+        }
+    }
+
+    /// Cache the loudness of the WAV at the given path.
+    pub fn insert(&mut self, path: PathBuf, loudness: Loudness) {
+        if let Some((len, modified_secs)) = file_stamp(&path) {
+            let entry = CacheEntry { loudness, len, modified_secs };
+            self.entries.insert(path, entry);
+        }
+    }
+}
+
+impl Biquad {
+    fn process(&mut self, coeffs: &[f64; 5], x: f64) -> f64 {
+        let y = coeffs[0] * x + coeffs[1] * self.x1 + coeffs[2] * self.x2
+            - coeffs[3] * self.y1
+            - coeffs[4] * self.y2;
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+}
+
+impl Analysis {
+    fn new(channels: usize) -> Self {
+        let channel = Channel {
+            pre_filter: Default::default(),
+            rlb_filter: Default::default(),
+            segment_sum: 0.0,
+            history: (0..TAPS_PER_PHASE).map(|_| 0.0).collect(),
+        };
+        Analysis {
+            channels: vec![channel; channels],
+            segment_frames: 0,
+            segments: VecDeque::with_capacity(SEGMENTS_PER_BLOCK),
+            blocks: vec![],
+            interpolation_filter: interpolation_filter(),
+            peak: 0.0,
+        }
+    }
+
+    /// Measure the given frame of samples.
+    fn push_frame(&mut self, frame: &[f32]) {
+        let Analysis { ref mut channels, ref interpolation_filter, ref mut peak, .. } = *self;
+        for (channel, &sample) in channels.iter_mut().zip(frame.iter()) {
+            // K-weighted mean square.
+            let x = sample as f64;
+            let y = channel.pre_filter.process(&PRE_FILTER, x);
+            let y = channel.rlb_filter.process(&RLB_FILTER, y);
+            channel.segment_sum += y * y;
+
+            // True peak.
+            channel.history.pop_back();
+            channel.history.push_front(sample);
+            *peak = peak.max(sample.abs());
+            for phase in 1..OVERSAMPLING {
+                let interpolated: f32 = channel
+                    .history
+                    .iter()
+                    .enumerate()
+                    .map(|(k, &s)| s * interpolation_filter[k * OVERSAMPLING + phase])
+                    .sum();
+                *peak = peak.max(interpolated.abs());
+            }
+        }
+
+        self.segment_frames += 1;
+        if self.segment_frames == SEGMENT_FRAMES {
+            self.end_segment();
+        }
+    }
+
+    // Complete the current 100ms segment, producing a new gating block if enough segments have
+    // been measured.
+    fn end_segment(&mut self) {
+        let mean_square: f64 = self.channels
+            .iter_mut()
+            .map(|channel| {
+                let mean_square = channel.segment_sum / SEGMENT_FRAMES as f64;
+                channel.segment_sum = 0.0;
+                mean_square
+            })
+            .sum();
+        self.segment_frames = 0;
+        if self.segments.len() == SEGMENTS_PER_BLOCK {
+            self.segments.pop_front();
+        }
+        self.segments.push_back(mean_square);
+        if self.segments.len() == SEGMENTS_PER_BLOCK {
+            let block = self.segments.iter().sum::<f64>() / SEGMENTS_PER_BLOCK as f64;
+            self.blocks.push(block);
+        }
+    }
+
+    /// Apply the absolute and relative gates and produce the resulting `Loudness`.
+    fn finish(self) -> Loudness {
+        let above_absolute_gate = self.blocks
+            .iter()
+            .cloned()
+            .filter(|&block| power_to_lufs(block) > MIN_LOUDNESS_LUFS)
+            .collect::<Vec<_>>();
+        let integrated_lufs = match mean(&above_absolute_gate) {
+            None => MIN_LOUDNESS_LUFS,
+            Some(ungated) => {
+                let relative_gate = power_to_lufs(ungated) + RELATIVE_GATE_LU;
+                let above_relative_gate = above_absolute_gate
+                    .into_iter()
+                    .filter(|&block| power_to_lufs(block) > relative_gate)
+                    .collect::<Vec<_>>();
+                mean(&above_relative_gate)
+                    .map(power_to_lufs)
+                    .unwrap_or(MIN_LOUDNESS_LUFS)
+                    .max(MIN_LOUDNESS_LUFS)
+            },
+        };
+        let true_peak_dbtp = amp_to_db(self.peak as f64).max(MIN_TRUE_PEAK_DBTP);
+        Loudness { integrated_lufs, true_peak_dbtp }
+    }
+}
+
+/// Measure the loudness of the WAV file at the given path.
+///
+/// This reads the entire file and may take some time for long WAVs.
+pub fn analyse(path: &Path) -> Result<Loudness, hound::Error> {
+    let mut reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+    let channels = spec.channels as usize;
+    let mut analysis = Analysis::new(channels);
+    let mut frame = Vec::with_capacity(channels);
+
+    // A macro to simplify reading samples of each supported format.
+    macro_rules! analyse_samples {
+        ($T:ty) => {{
+            for sample in reader.samples::<$T>() {
+                frame.push(sample?.to_sample::<f32>());
+                if frame.len() == channels {
+                    analysis.push_frame(&frame);
+                    frame.clear();
+                }
+            }
+        }};
+    }
+
+    match (spec.sample_format, spec.bits_per_sample) {
+        (SampleFormat::Float, 32) => analyse_samples!(f32),
+        (SampleFormat::Int, 8) => analyse_samples!(i8),
+        (SampleFormat::Int, 16) => analyse_samples!(i16),
+        (SampleFormat::Int, 32) => analyse_samples!(i32),
+        _ => return Err(hound::Error::Unsupported),
+    }
+
+    Ok(analysis.finish())
+}
+
+// The windowed-sinc low pass filter used to interpolate between samples when oversampling.
+//
+// Coefficients are laid out such that the coefficient for history sample `k` and phase `p` is
+// found at `k * OVERSAMPLING + p`. Phase `0` reproduces the original samples.
+fn interpolation_filter() -> Vec<f32> {
+    let len = TAPS_PER_PHASE * OVERSAMPLING;
+    let centre = (len / 2) as f64;
+    (0..len)
+        .map(|i| {
+            let x = (i as f64 - centre) / OVERSAMPLING as f64;
+            let sinc = match x == 0.0 {
+                true => 1.0,
+                false => (::std::f64::consts::PI * x).sin() / (::std::f64::consts::PI * x),
+            };
+            let window = 0.5 + 0.5 * (::std::f64::consts::PI * (i as f64 - centre) / centre).cos();
+            (sinc * window) as f32
+        })
+        .collect()
+}
+
+/// The size in bytes and modification time in seconds since the unix epoch of the file at the
+/// given path.
+///
+/// Used to determine whether or not a WAV has changed since it was last analysed.
+pub fn file_stamp(path: &Path) -> Option<(u64, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok()?;
+    let modified_secs = modified.duration_since(UNIX_EPOCH).ok()?.as_secs();
+    Some((metadata.len(), modified_secs))
+}
+
+fn mean(values: &[f64]) -> Option<f64> {
+    match values.len() {
+        0 => None,
+        n => Some(values.iter().sum::<f64>() / n as f64),
+    }
+}
+
+fn power_to_lufs(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+fn amp_to_db(amp: f64) -> f64 {
+    20.0 * amp.log10()
+}
+
+fn db_to_amp(db: f64) -> f64 {
+    10.0f64.powf(db / 20.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use audio;
+    use std::f64::consts::PI;
+    use super::{Analysis, Loudness, MIN_LOUDNESS_LUFS};
+
+    // Measure `secs` seconds of a sine wave of the given frequency, amplitude and phase.
+    fn sine(channels: usize, hz: f64, amp: f64, phase: f64, secs: f64) -> Vec<f32> {
+        let frames = (audio::SAMPLE_RATE * secs) as usize;
+        let mut samples = Vec::with_capacity(frames * channels);
+        for i in 0..frames {
+            let t = i as f64 / audio::SAMPLE_RATE;
+            let sample = (amp * (2.0 * PI * hz * t + phase).sin()) as f32;
+            for _ in 0..channels {
+                samples.push(sample);
+            }
+        }
+        samples
+    }
+
+    fn measure(channels: usize, samples: &[f32]) -> Loudness {
+        let mut analysis = Analysis::new(channels);
+        for frame in samples.chunks(channels) {
+            analysis.push_frame(frame);
+        }
+        analysis.finish()
+    }
+
+    #[test]
+    fn full_scale_sine() {
+        let loudness = measure(1, &sine(1, 997.0, 1.0, 0.0, 2.0));
+        assert!((loudness.integrated_lufs + 3.01).abs() < 0.05, "{:?}", loudness);
+        assert!(loudness.true_peak_dbtp.abs() < 0.1, "{:?}", loudness);
+    }
+
+    #[test]
+    fn channels_are_summed() {
+        let loudness = measure(2, &sine(2, 997.0, 0.5, 0.0, 2.0));
+        assert!((loudness.integrated_lufs + 6.02).abs() < 0.05, "{:?}", loudness);
+    }
+
+    #[test]
+    fn silence_is_gated() {
+        assert_eq!(measure(1, &vec![0.0; 48_000 * 2]).integrated_lufs, MIN_LOUDNESS_LUFS);
+        let quiet = measure(1, &sine(1, 997.0, 1e-4, 0.0, 2.0));
+        assert_eq!(quiet.integrated_lufs, MIN_LOUDNESS_LUFS);
+    }
+
+    #[test]
+    fn too_short_for_a_block() {
+        // Less than 400ms produces no gating blocks.
+        let loudness = measure(1, &sine(1, 997.0, 1.0, 0.0, 0.35));
+        assert_eq!(loudness.integrated_lufs, MIN_LOUDNESS_LUFS);
+    }
+
+    #[test]
+    fn quiet_passages_are_relative_gated() {
+        // -9.03 LUFS followed by twice as long at -49.03 LUFS. Without the relative gate this
+        // would measure around -13.8 LUFS.
+        let mut samples = sine(1, 997.0, 0.5, 0.0, 2.0);
+        samples.extend(sine(1, 997.0, 0.005, 0.0, 4.0));
+        let loudness = measure(1, &samples);
+        assert!((loudness.integrated_lufs + 9.03).abs() < 0.5, "{:?}", loudness);
+    }
+
+    #[test]
+    fn inter_sample_peaks() {
+        // A quarter sample rate sine offset by 45 degrees only ever samples at -3 dBFS.
+        let samples = sine(1, audio::SAMPLE_RATE / 4.0, 1.0, PI / 4.0, 0.1);
+        let sample_peak = samples.iter().fold(0.0f32, |max, s| max.max(s.abs()));
+        assert!((20.0 * (sample_peak as f64).log10() + 3.01).abs() < 0.05);
+        let loudness = measure(1, &samples);
+        assert!(loudness.true_peak_dbtp.abs() < 0.5, "{:?}", loudness);
+    }
+
+    #[test]
+    fn normalisation_gain_limited_by_true_peak() {
+        let loudness = Loudness { integrated_lufs: -30.0, true_peak_dbtp: -6.0 };
+        // +7 dB would reach the target, but only +5 dB is allowed before -1 dBTP.
+        let gain = loudness.normalisation_gain(-23.0);
+        assert!((20.0 * (gain as f64).log10() - 5.0).abs() < 1e-3);
+        let gain = loudness.normalisation_gain(-33.0);
+        assert!((20.0 * (gain as f64).log10() + 3.0).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,262 @@
+//! An in-memory cache of decoded WAV files.
+//!
+//! WAVs small enough to fit within the cache are decoded once and played straight from RAM,
+//! avoiding the disk I/O and first-buffer latency of streaming them through the `wav::reader`.
+
+use fxhash::{FxHashMap, FxHashSet};
+use hound::{self, SampleFormat};
+use nannou_audio::sample::Sample;
+use std::collections::BTreeMap;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// The number of bytes within a megabyte, for specifying the memory budget.
+pub const BYTES_PER_MB: u64 = 1024 * 1024;
+
+/// The number of bytes occupied by each decoded sample.
+const BYTES_PER_SAMPLE: u64 = mem::size_of::<f32>() as u64;
+
+/// A WAV file decoded into memory.
+#[derive(Clone, Debug)]
+pub struct Wav {
+    /// The interleaved samples of the WAV.
+    pub samples: Arc<[f32]>,
+    /// The specification of the WAV file from which the samples were decoded.
+    pub spec: hound::WavSpec,
+}
+
+/// A cache of decoded WAVs limited to a memory budget.
+///
+/// Once the budget is reached, the least recently played WAVs are evicted to make room.
+#[derive(Debug)]
+pub struct Cache {
+    /// The maximum number of bytes occupied by all decoded WAVs.
+    budget_bytes: u64,
+    /// WAVs that would occupy more than this number of bytes are never cached.
+    max_wav_bytes: u64,
+    /// The number of bytes currently occupied by all decoded WAVs.
+    total_bytes: u64,
+    /// Incremented each time a WAV is played, used for determining the least recently used WAV.
+    clock: u64,
+    entries: FxHashMap<PathBuf, Entry>,
+    /// The path of each cached WAV keyed by the time at which it was last used, oldest first.
+    ///
+    /// Allows for finding the least recently used WAV without scanning all entries.
+    recency: BTreeMap<u64, PathBuf>,
+    /// WAVs that are currently being decoded.
+    pending: FxHashSet<PathBuf>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    wav: Wav,
+    last_used: u64,
+}
+
+impl Wav {
+    /// The number of bytes occupied by the decoded samples.
+    pub fn bytes(&self) -> u64 {
+        self.samples.len() as u64 * BYTES_PER_SAMPLE
+    }
+}
+
+impl Cache {
+    /// Create an empty cache with the given memory budget.
+    pub fn new(budget_bytes: u64, max_wav_bytes: u64) -> Self {
+        Cache {
+            budget_bytes,
+            max_wav_bytes,
+            total_bytes: 0,
+            clock: 0,
+            entries: Default::default(),
+            recency: BTreeMap::new(),
+            pending: Default::default(),
+        }
+    }
+
+    /// The decoded WAV at the given path, marking it as the most recently used.
+    pub fn get(&mut self, path: &Path) -> Option<Wav> {
+        let entry = self.entries.get_mut(path)?;
+        self.clock += 1;
+        let path = self.recency
+            .remove(&entry.last_used)
+            .expect("no recency for the cached WAV");
+        self.recency.insert(self.clock, path);
+        entry.last_used = self.clock;
+        Some(entry.wav.clone())
+    }
+
+    /// Whether or not a WAV with the given number of samples should be decoded into the cache.
+    ///
+    /// Returns `false` if the WAV is too large or is already being decoded. Otherwise, the WAV is
+    /// marked as pending until it is `insert`ed or `cancel`led.
+    pub fn request(&mut self, path: &Path, len_samples: u64) -> bool {
+        let bytes = len_samples * BYTES_PER_SAMPLE;
+        if bytes > self.max_wav_bytes || bytes > self.budget_bytes {
+            return false;
+        }
+        if self.entries.contains_key(path) || self.pending.contains(path) {
+            return false;
+        }
+        self.pending.insert(path.to_path_buf());
+        true
+    }
+
+    /// Stop waiting on the requested WAV, e.g. if it failed to decode.
+    pub fn cancel(&mut self, path: &Path) {
+        self.pending.remove(path);
+    }
+
+    /// Insert a decoded WAV, evicting the least recently used WAVs as necessary to remain within
+    /// the budget.
+    pub fn insert(&mut self, path: PathBuf, wav: Wav) {
+        self.pending.remove(&path);
+        let bytes = wav.bytes();
+        if bytes > self.budget_bytes {
+            return;
+        }
+        if let Some(old) = self.entries.remove(&path) {
+            self.recency.remove(&old.last_used);
+            self.total_bytes -= old.wav.bytes();
+        }
+        while self.total_bytes + bytes > self.budget_bytes {
+            let lru = match self.recency.keys().next() {
+                Some(&last_used) => last_used,
+                None => break,
+            };
+            let lru_path = self.recency.remove(&lru).expect("no path for the recency");
+            let entry = self.entries.remove(&lru_path).expect("no entry for the cached WAV");
+            self.total_bytes -= entry.wav.bytes();
+        }
+        self.clock += 1;
+        self.recency.insert(self.clock, path.clone());
+        self.entries.insert(path, Entry { wav, last_used: self.clock });
+        self.total_bytes += bytes;
+    }
+}
+
+/// Decode the entire WAV file at the given path into memory.
+pub fn decode(path: &Path) -> Result<Wav, hound::Error> {
+    let mut reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+    let mut samples = Vec::with_capacity(reader.len() as usize);
+
+    // A macro to simplify reading samples of each supported format.
+    macro_rules! read_samples {
+        ($T:ty) => {{
+            for sample in reader.samples::<$T>() {
+                samples.push(sample?.to_sample::<f32>());
+            }
+        }};
+    }
+
+    match (spec.sample_format, spec.bits_per_sample) {
+        (SampleFormat::Float, 32) => read_samples!(f32),
+        (SampleFormat::Int, 8) => read_samples!(i8),
+        (SampleFormat::Int, 16) => read_samples!(i16),
+        (SampleFormat::Int, 32) => read_samples!(i32),
+        _ => return Err(hound::Error::Unsupported),
+    }
+
+    let samples = samples.into();
+    Ok(Wav { samples, spec })
+}
+
+#[cfg(test)]
+mod tests {
+    use hound::{self, SampleFormat};
+    use std::path::Path;
+    use super::{Cache, Wav, BYTES_PER_SAMPLE};
+
+    fn wav(len_samples: usize) -> Wav {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 48_000,
+            bits_per_sample: 32,
+            sample_format: SampleFormat::Float,
+        };
+        Wav { samples: vec![0.0; len_samples].into(), spec }
+    }
+
+    fn insert(cache: &mut Cache, path: &str, len_samples: usize) {
+        let path = Path::new(path);
+        assert!(cache.request(path, len_samples as u64));
+        cache.insert(path.to_path_buf(), wav(len_samples));
+    }
+
+    fn is_cached(cache: &mut Cache, path: &str) -> bool {
+        cache.get(Path::new(path)).is_some()
+    }
+
+    // A cache with room for four WAVs of 100 samples.
+    fn cache() -> Cache {
+        Cache::new(400 * BYTES_PER_SAMPLE, 200 * BYTES_PER_SAMPLE)
+    }
+
+    #[test]
+    fn oversize_wavs_bypass_the_cache() {
+        let mut cache = cache();
+        assert!(!cache.request(Path::new("large.wav"), 201));
+        assert!(cache.request(Path::new("small.wav"), 200));
+        assert!(!Cache::new(100 * BYTES_PER_SAMPLE, 200 * BYTES_PER_SAMPLE)
+            .request(Path::new("small.wav"), 101));
+    }
+
+    #[test]
+    fn pending_wavs_are_requested_once() {
+        let mut cache = cache();
+        let path = Path::new("a.wav");
+        assert!(cache.request(path, 100));
+        assert!(!cache.request(path, 100));
+        cache.cancel(path);
+        assert!(cache.request(path, 100));
+        cache.insert(path.to_path_buf(), wav(100));
+        assert!(!cache.request(path, 100));
+    }
+
+    #[test]
+    fn total_bytes_remain_within_budget() {
+        let mut cache = cache();
+        for i in 0..10 {
+            insert(&mut cache, &format!("{}.wav", i), 60 + i * 10);
+            assert!(cache.total_bytes <= cache.budget_bytes);
+            let sum: u64 = cache.entries.values().map(|e| e.wav.bytes()).sum();
+            assert_eq!(cache.total_bytes, sum);
+            assert_eq!(cache.entries.len(), cache.recency.len());
+        }
+    }
+
+    #[test]
+    fn least_recently_used_wavs_are_evicted_first() {
+        let mut cache = cache();
+        for path in &["a.wav", "b.wav", "c.wav", "d.wav"] {
+            insert(&mut cache, path, 100);
+        }
+        assert!(is_cached(&mut cache, "a.wav"));
+        assert!(is_cached(&mut cache, "c.wav"));
+
+        // "b" and then "d" are now the least recently used.
+        insert(&mut cache, "e.wav", 100);
+        assert!(!is_cached(&mut cache, "b.wav"));
+        insert(&mut cache, "f.wav", 100);
+        assert!(!is_cached(&mut cache, "d.wav"));
+        insert(&mut cache, "g.wav", 200);
+        assert!(!is_cached(&mut cache, "a.wav"));
+        assert!(!is_cached(&mut cache, "c.wav"));
+        for path in &["e.wav", "f.wav", "g.wav"] {
+            assert!(is_cached(&mut cache, path));
+        }
+        assert_eq!(cache.total_bytes, 400 * BYTES_PER_SAMPLE);
+    }
+
+    #[test]
+    fn reinserting_a_wav_replaces_it() {
+        let mut cache = cache();
+        insert(&mut cache, "a.wav", 100);
+        cache.insert(Path::new("a.wav").to_path_buf(), wav(150));
+        assert_eq!(cache.total_bytes, 150 * BYTES_PER_SAMPLE);
+        assert_eq!(cache.entries.len(), 1);
+        assert_eq!(cache.recency.len(), 1);
+    }
+}
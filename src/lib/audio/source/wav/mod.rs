@@ -1,8 +1,20 @@
 use audio;
 use hound;
+use nannou::rand::Rng;
 use std::path::PathBuf;
 use time_calc::{Ms, SampleHz, Samples};
+use utils::Range;
 
+pub use self::granular::Granular;
+pub use self::loudness::Loudness;
+pub use self::overview::Overview;
+
+pub mod analysis;
+pub mod chunk;
+pub mod granular;
+pub mod loudness;
+pub mod memory;
+pub mod overview;
 pub mod reader;
 pub mod samples;
 
@@ -15,8 +27,27 @@ pub struct Wav {
     pub sample_hz: SampleHz,
     #[serde(default = "default_should_loop")]
     pub should_loop: bool,
+    /// The range of frames that is repeated when the WAV is looped.
+    ///
+    /// If `None`, the whole WAV is looped.
+    #[serde(default)]
+    pub loop_points: Option<Range<Samples>>,
+    /// The duration of the crossfade applied across the seam of the loop.
+    #[serde(default = "default_loop_crossfade")]
+    pub loop_crossfade: Ms,
     #[serde(default = "default_playback")]
     pub playback: Playback,
+    /// The offset into the WAV from which `Retrigger` playback begins.
+    #[serde(default = "default_start_offset")]
+    pub start_offset: StartOffset,
+    /// The frame positions of the cue markers within the WAV file, in ascending order.
+    #[serde(default)]
+    pub cue_points: Vec<Samples>,
+    /// The measured loudness of the WAV, if it has been analysed.
+    ///
+    /// This is cached separately from the project state. See `loudness::Cache`.
+    #[serde(skip)]
+    pub loudness: Option<Loudness>,
 }
 
 /// The playback mode of the WAV file.
@@ -30,10 +61,30 @@ pub enum Playback {
     /// This acts as though the WAV is constantly being played but is just "muted" or "unmuted" as
     /// we remove it and re-introduce it respectively.
     Continuous,
+    /// Continuously stream the WAV and scatter short grains read from it over time.
+    ///
+    /// This is useful for generating long evolving textures from short recordings.
+    Granular(Granular),
 }
 
 /// The number of variants within the `Playback` enum.
-pub const NUM_PLAYBACK_OPTIONS: usize = 2;
+pub const NUM_PLAYBACK_OPTIONS: usize = 3;
+
+/// Describes where within the WAV a `Retrigger` sound starts playing from.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum StartOffset {
+    /// Always start from the given offset into the WAV.
+    Fixed(Ms),
+    /// Start from an offset chosen at random within the given range.
+    Random(Range<Ms>),
+    /// Start from one of the WAV's cue markers, chosen at random.
+    ///
+    /// Falls back to the beginning of the WAV if it contains no cue markers.
+    CueMarker,
+}
+
+/// The number of variants within the `StartOffset` enum.
+pub const NUM_START_OFFSET_OPTIONS: usize = 3;
 
 /// Default to `Retrigger` mode.
 fn default_playback() -> Playback {
@@ -45,6 +96,16 @@ fn default_should_loop() -> bool {
     false
 }
 
+/// Default to a hard seam between the end and start of the loop.
+fn default_loop_crossfade() -> Ms {
+    Ms(0.0)
+}
+
+/// Default to starting from the beginning of the WAV.
+fn default_start_offset() -> StartOffset {
+    StartOffset::Fixed(Ms(0.0))
+}
+
 impl Wav {
     /// Attempts to load the WAV header and read the number of channels.
     pub fn from_path(path: PathBuf) -> Result<Self, hound::Error> {
@@ -57,6 +118,20 @@ impl Wav {
         let duration = Samples(reader.duration() as _);
         let playback = default_playback();
         let should_loop = default_should_loop();
+        let start_offset = default_start_offset();
+        let cue_points = chunk::read_cue_points(&path).unwrap_or_else(|err| {
+            eprintln!("failed to read cue markers from \"{}\": {}", path.display(), err);
+            vec![]
+        });
+        // Use the first of the WAV's sample loops, if any, as the loop points.
+        let loop_points = chunk::read_sample_loops(&path)
+            .unwrap_or_else(|err| {
+                eprintln!("failed to read sample loops from \"{}\": {}", path.display(), err);
+                vec![]
+            })
+            .into_iter()
+            .next();
+        let loop_crossfade = default_loop_crossfade();
         Ok(Wav {
             path,
             channels,
@@ -64,6 +139,11 @@ impl Wav {
             sample_hz,
             playback,
             should_loop,
+            loop_points,
+            loop_crossfade,
+            start_offset,
+            cue_points,
+            loudness: None,
         })
     }
 
@@ -71,4 +151,69 @@ impl Wav {
     pub fn duration_ms(&self) -> Ms {
         self.duration.to_ms(self.sample_hz)
     }
+
+    /// Whether or not sounds using this WAV will play endlessly unless given some duration.
+    ///
+    /// This is the case if the WAV is looped or played back granularly.
+    pub fn is_endless(&self) -> bool {
+        match self.playback {
+            Playback::Granular(_) => true,
+            _ => self.should_loop,
+        }
+    }
+
+    /// Determine the frame from which a newly triggered `Retrigger` sound should start.
+    ///
+    /// The resulting frame is always within the duration of the WAV.
+    pub fn retrigger_start_frame<R>(&self, rng: &mut R) -> u64
+    where
+        R: Rng,
+    {
+        let frame = match self.start_offset {
+            StartOffset::Fixed(ms) => ms.samples(self.sample_hz) as u64,
+            StartOffset::Random(range) => {
+                let min = range.min.samples(self.sample_hz) as u64;
+                let max = range.max.samples(self.sample_hz) as u64;
+                if min < max { rng.gen_range(min, max) } else { min }
+            },
+            StartOffset::CueMarker => match self.cue_points.len() {
+                0 => 0,
+                n => self.cue_points[rng.gen_range(0, n)].samples() as u64,
+            },
+        };
+        let duration_frames = self.duration.samples() as u64;
+        if duration_frames == 0 { 0 } else { frame.min(duration_frames - 1) }
+    }
+
+    /// The region of frames repeated when the WAV is looped, clamped to the duration of the WAV.
+    ///
+    /// The crossfade is limited to half of the length of the region.
+    pub fn loop_region(&self) -> reader::LoopRegion {
+        let duration_frames = self.duration.samples().max(0) as u64;
+        let (start, end) = match self.loop_points {
+            None => (0, duration_frames),
+            Some(range) => {
+                let start = (range.min.samples().max(0) as u64).min(duration_frames);
+                let end = (range.max.samples().max(0) as u64).min(duration_frames);
+                match start < end {
+                    true => (start, end),
+                    false => (0, duration_frames),
+                }
+            },
+        };
+        let crossfade = (self.loop_crossfade.samples(self.sample_hz).max(0) as u64)
+            .min((end - start) / 2);
+        reader::LoopRegion { start, end, crossfade }
+    }
+}
+
+impl StartOffset {
+    /// A short, human-readable name for the start offset mode.
+    pub fn label(&self) -> &'static str {
+        match *self {
+            StartOffset::Fixed(_) => "Fixed",
+            StartOffset::Random(_) => "Random",
+            StartOffset::CueMarker => "Cue Marker",
+        }
+    }
 }
@@ -0,0 +1,207 @@
+//! Overviews of the content of WAV files for display within the GUI.
+//!
+//! Each overview describes the minimum, maximum and RMS sample value of each of a fixed number of
+//! bins spread evenly over the duration of the WAV. Overviews are cached within a binary file
+//! alongside each WAV so that they need only be generated once.
+
+use hound::{self, SampleFormat};
+use nannou_audio::sample::Sample;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use super::loudness::file_stamp;
+
+/// The number of bins into which each WAV is divided.
+pub const NUM_BINS: usize = 512;
+
+/// The name of the directory alongside each WAV in which its overview is cached.
+pub const CACHE_DIRECTORY_NAME: &'static str = ".overview";
+
+/// The extension of each cached overview file.
+const CACHE_EXTENSION: &'static str = "overview";
+
+/// Identifies the format of the cached overview files.
+const CACHE_MAGIC: &'static [u8; 4] = b"OVW1";
+
+/// A summary of the samples within a single bin of an overview.
+///
+/// All channels contribute to each bin.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Bin {
+    pub min: f32,
+    pub max: f32,
+    pub rms: f32,
+}
+
+/// A bin to which no samples have yet been added.
+const EMPTY_BIN: Bin = Bin {
+    min: ::std::f32::MAX,
+    max: ::std::f32::MIN,
+    rms: 0.0,
+};
+
+/// An overview of the content of a WAV.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Overview {
+    /// The bins of the overview from the start of the WAV to its end.
+    ///
+    /// WAVs shorter than `NUM_BINS` frames have a single bin per frame.
+    pub bins: Vec<Bin>,
+}
+
+/// The path of the cached overview for the WAV at the given path.
+pub fn cache_path(wav_path: &Path) -> Option<PathBuf> {
+    let directory = wav_path.parent()?.join(CACHE_DIRECTORY_NAME);
+    let file_name = wav_path.file_name()?;
+    Some(directory.join(file_name).with_extension(CACHE_EXTENSION))
+}
+
+/// Load the cached overview of the WAV at the given path, or generate and cache it if the cache
+/// is missing or out of date.
+pub fn load_or_generate(path: &Path) -> Result<Overview, hound::Error> {
+    let stamp = file_stamp(path);
+    let cache_path = cache_path(path);
+    if let (Some(stamp), Some(cache_path)) = (stamp, cache_path.as_ref()) {
+        if let Ok(Some(overview)) = read_cache(cache_path, stamp) {
+            return Ok(overview);
+        }
+    }
+    let overview = generate(path)?;
+    if let (Some(stamp), Some(cache_path)) = (stamp, cache_path.as_ref()) {
+        if let Err(err) = write_cache(cache_path, stamp, &overview) {
+            eprintln!("failed to cache overview at \"{}\": {}", cache_path.display(), err);
+        }
+    }
+    Ok(overview)
+}
+
+/// Generate the overview of the WAV file at the given path.
+///
+/// This reads the entire file and may take some time for long WAVs.
+pub fn generate(path: &Path) -> Result<Overview, hound::Error> {
+    let mut reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+    let channels = spec.channels as u64;
+    let frames = reader.duration() as u64;
+    let num_bins = ::std::cmp::min(NUM_BINS as u64, frames);
+    let mut bins = Vec::with_capacity(num_bins as usize);
+    let mut bin = EMPTY_BIN;
+    let mut sum_sq = 0.0f64;
+    let mut bin_samples = 0u64;
+    let mut sample_index = 0u64;
+
+    // Complete the current bin once all of its samples have been read.
+    macro_rules! push_sample {
+        ($sample:expr) => {{
+            let sample: f32 = $sample;
+            bin.min = bin.min.min(sample);
+            bin.max = bin.max.max(sample);
+            sum_sq += (sample * sample) as f64;
+            bin_samples += 1;
+            sample_index += 1;
+            let frame = sample_index / channels;
+            let bin_end = (bins.len() as u64 + 1) * frames / num_bins;
+            if sample_index % channels == 0 && frame >= bin_end {
+                bin.rms = (sum_sq / bin_samples as f64).sqrt() as f32;
+                bins.push(bin);
+                bin = EMPTY_BIN;
+                sum_sq = 0.0;
+                bin_samples = 0;
+            }
+        }};
+    }
+
+    // A macro to simplify reading samples of each supported format.
+    macro_rules! read_samples {
+        ($T:ty) => {{
+            for sample in reader.samples::<$T>() {
+                push_sample!(sample?.to_sample::<f32>());
+            }
+        }};
+    }
+
+    match (spec.sample_format, spec.bits_per_sample) {
+        (SampleFormat::Float, 32) => read_samples!(f32),
+        (SampleFormat::Int, 8) => read_samples!(i8),
+        (SampleFormat::Int, 16) => read_samples!(i16),
+        (SampleFormat::Int, 32) => read_samples!(i32),
+        _ => return Err(hound::Error::Unsupported),
+    }
+
+    Ok(Overview { bins })
+}
+
+// Read the cached overview at the given path.
+//
+// Returns `None` if the cache was generated from a different version of the WAV.
+fn read_cache(cache_path: &Path, stamp: (u64, u64)) -> io::Result<Option<Overview>> {
+    let mut file = BufReader::new(File::open(cache_path)?);
+    let mut magic = [0u8; 4];
+    file.read_exact(&mut magic)?;
+    if &magic != CACHE_MAGIC {
+        return Ok(None);
+    }
+    let len = read_u64(&mut file)?;
+    let modified_secs = read_u64(&mut file)?;
+    if (len, modified_secs) != stamp {
+        return Ok(None);
+    }
+    let num_bins = read_u32(&mut file)? as usize;
+    if num_bins > NUM_BINS {
+        return Ok(None);
+    }
+    let mut bins = Vec::with_capacity(num_bins);
+    for _ in 0..num_bins {
+        let min = f32::from_bits(read_u32(&mut file)?);
+        let max = f32::from_bits(read_u32(&mut file)?);
+        let rms = f32::from_bits(read_u32(&mut file)?);
+        bins.push(Bin { min, max, rms });
+    }
+    Ok(Some(Overview { bins }))
+}
+
+// Write the overview to the cache at the given path, creating the cache directory if necessary.
+fn write_cache(cache_path: &Path, stamp: (u64, u64), overview: &Overview) -> io::Result<()> {
+    if let Some(directory) = cache_path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    let mut file = BufWriter::new(File::create(cache_path)?);
+    file.write_all(CACHE_MAGIC)?;
+    file.write_all(&u64_le(stamp.0))?;
+    file.write_all(&u64_le(stamp.1))?;
+    file.write_all(&u32_le(overview.bins.len() as u32))?;
+    for bin in &overview.bins {
+        file.write_all(&u32_le(bin.min.to_bits()))?;
+        file.write_all(&u32_le(bin.max.to_bits()))?;
+        file.write_all(&u32_le(bin.rms.to_bits()))?;
+    }
+    file.flush()
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes.iter().rev().fold(0, |acc, &b| acc << 8 | b as u32))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes.iter().rev().fold(0, |acc, &b| acc << 8 | b as u64))
+}
+
+fn u32_le(n: u32) -> [u8; 4] {
+    let mut bytes = [0u8; 4];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = (n >> (i * 8)) as u8;
+    }
+    bytes
+}
+
+fn u64_le(n: u64) -> [u8; 8] {
+    let mut bytes = [0u8; 8];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = (n >> (i * 8)) as u8;
+    }
+    bytes
+}
@@ -1,7 +1,11 @@
 //! A thread dedicated to reading sounds from WAV files and feeding their samples to sounds on the
 //! audio thread.
+//!
+//! WAVs small enough to fit within the `memory::Cache` are decoded once by the reader's child
+//! threads, after which they are played straight from memory.
 
 use audio::{self, sound};
+use audio::source::envelope::Curve;
 use crossbeam::sync::{MsQueue, SegQueue};
 use fxhash::FxHashMap;
 use hound::{self, SampleFormat};
@@ -12,11 +16,12 @@ use std::io::BufReader;
 use std::fs::File;
 use std::mem;
 use std::ops;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use time_calc::Samples;
 use threadpool::ThreadPool;
+use super::memory;
 
 /// The number of sample buffers that the `reader` thread prepares ahead of time for a single
 /// sound.
@@ -32,10 +37,10 @@ pub type Tx = Arc<MsQueue<Message>>;
 pub type Rx = Arc<MsQueue<Message>>;
 
 /// For sending buffers to a sound's associated `ThreadedSamplesStream`.
-pub type BufferTx = Arc<SegQueue<Buffer>>;
+pub type BufferTx = Arc<SegQueue<StreamMessage>>;
 
 /// Receives buffers sent from the wav reader thread. Used by the `ThreadedSamplesStream` type.
-pub type BufferRx = Arc<SegQueue<Buffer>>;
+pub type BufferRx = Arc<SegQueue<StreamMessage>>;
 
 /// The mpmc queue used for distributing `Play` messages across the child threads.
 type ChildMessageQueue = MsQueue<ChildMessage>;
@@ -49,6 +54,7 @@ struct ChildId(usize);
 pub struct Handle {
     tx: Tx,
     thread: Arc<Mutex<Option<thread::JoinHandle<()>>>>,
+    memory: Arc<Mutex<memory::Cache>>,
 }
 
 /// All state stored on the `wav::reader` thread.
@@ -79,9 +85,33 @@ pub struct Sound {
     /// The reader thread will ensure that the length of this `prepared_buffers` vec is always
     /// `NUM_BUFFERS`.
     prepared_buffers: VecDeque<PreparedBuffer>,
-    /// Whether or not the wav reader should loop back to the beginning of the file when it reaches
-    /// the end.
-    looped: bool,
+    /// The state required to loop a region of the WAV, or `None` if the WAV is not looped.
+    looping: Option<Looping>,
+    /// Whether or not `StreamMessage::End` has been sent to the `SamplesStream`.
+    ended: bool,
+}
+
+/// The region of a WAV that is repeated when the WAV is looped, described in frames.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LoopRegion {
+    /// The first frame of the loop.
+    pub start: u64,
+    /// The frame following the last frame of the loop.
+    pub end: u64,
+    /// The number of frames at the end of the loop that are crossfaded with those at its start.
+    ///
+    /// Must be no greater than half the length of the loop.
+    pub crossfade: u64,
+}
+
+/// The state required to loop a region of a WAV.
+struct Looping {
+    region: LoopRegion,
+    /// The first `region.crossfade` frames of the loop.
+    ///
+    /// These are faded in over the end of the loop, after which playback continues from the
+    /// frame that follows them. This hides the seam between the end and start of the loop.
+    head: Vec<f32>,
 }
 
 /// The state of the sound as tracked by the `Model`.
@@ -107,6 +137,8 @@ enum ChildMessage {
     Play(sound::Id, Play),
     /// Process the next buffer and send the result back to the parent thread.
     NextBuffer(sound::Id, Sound, Vec<f32>),
+    /// Decode the WAV at the given path into the memory cache.
+    Decode(PathBuf),
 }
 
 /// Messages received by the wav reader thread.
@@ -123,10 +155,21 @@ pub enum Message {
     NextBufferComplete(sound::Id, Sound),
     /// Indicates that the sound associated with the given Id has ended.
     End(sound::Id),
+    /// Decode the WAV at the given path into the memory cache.
+    Decode(PathBuf),
     /// Break from the loop as the application is closing.
     Exit,
 }
 
+/// Messages sent from the reader thread to a sound's `SamplesStream`.
+pub enum StreamMessage {
+    /// The next buffer of samples read from the WAV.
+    Buffer(Buffer),
+    /// The reader has stopped reading the WAV, either because the WAV is depleted or because it
+    /// could not be read. No more buffers follow.
+    End,
+}
+
 /// The buffer sent to the `ThreadedSamplesStream`
 ///
 /// When this buffer is depleted, the allocated `Vec` gets sent back to the reader thread for
@@ -154,44 +197,134 @@ pub struct Play {
     pub buffer_tx: BufferTx,
     /// The frame from which the sound should start.
     pub start_frame: u64,
-    /// Whether or not the WAV should be looped.
-    pub looped: bool,
+    /// The region to loop, or `None` if the WAV should not be looped.
+    pub loop_region: Option<LoopRegion>,
 }
 
 /// A handle to a WAV that receives the buffered samples for use on the audio thread.
+///
+/// Frames are read at the stream's `playback_rate`, linearly interpolating between the two
+/// nearest frames of the WAV.
 pub struct SamplesStream {
-    buffer_rx: BufferRx,
-    buffer: RefCell<Option<Buffer>>,
-    buffer_index: usize,
+    source: StreamSource,
     wav_spec: hound::WavSpec,
     wav_len_samples: usize,
     // Whether or not the WAV is looped.
     wav_looped: bool,
+    // The rate at which frames are read from the WAV, where `1.0` is the natural rate.
+    playback_rate: f64,
+    // The two WAV frames between which the output is currently being interpolated.
+    frame_a: Vec<f32>,
+    frame_b: Vec<f32>,
+    // Whether or not each of the frames were successfully read from the WAV.
+    frame_a_valid: bool,
+    frame_b_valid: bool,
+    // The WAV frame currently being read, retained across calls while waiting for buffers.
+    pending_frame: Vec<f32>,
+    // The number of samples of `pending_frame` that have been read so far.
+    pending_len: usize,
+    // Whether or not the end of the WAV has been reached.
+    wav_ended: bool,
+    // The fractional position between `frame_a` and `frame_b`.
+    frame_phase: f64,
+    // The index of the next channel to yield within the current output frame.
+    output_channel: usize,
+    // Whether or not the phase should advance before the next output frame.
+    //
+    // This is only set once an output frame has been yielded from the WAV so that output frames
+    // stalled waiting on the reader thread are retried rather than skipped.
+    advance: bool,
+    // Whether or not the current output frame is silence as the reader thread has fallen behind.
+    stalled: bool,
+}
+
+// The result of reading the next sample from the WAV.
+enum WavSample {
+    Sample(f32),
+    // The next buffer has not yet arrived from the reader thread.
+    Pending,
+    // The WAV is depleted or the reader thread has sent `StreamMessage::End`.
+    Ended,
+}
+
+// The result of reading the next frame from the WAV.
+enum WavFrame {
+    Complete,
+    Pending,
+    Ended,
+}
+
+/// The origin of the samples read by a `SamplesStream`.
+enum StreamSource {
+    /// Buffers streamed from the WAV file by the reader thread.
+    Buffered {
+        buffer_rx: BufferRx,
+        buffer: RefCell<Option<Buffer>>,
+        buffer_index: usize,
+    },
+    /// The WAV decoded within the memory cache.
+    Memory(MemoryCursor),
+}
+
+/// Reads samples from a WAV decoded within the memory cache.
+struct MemoryCursor {
+    samples: Arc<[f32]>,
+    channels: usize,
+    // The index of the next sample to read.
+    position: usize,
+    loop_region: Option<LoopRegion>,
 }
 
 impl Handle {
     /// Play the given sound.
     ///
-    /// When called, the reader thread will add an entry for this sound into the map and prepare
-    /// the first `NUM_BUFFERS` buffers by reading samples from the given `WavReader`.
+    /// If the WAV is within the memory cache, the returned stream reads the WAV straight from
+    /// memory. Otherwise, the reader thread will add an entry for this sound into the map and
+    /// prepare the first `NUM_BUFFERS` buffers by reading samples from the WAV file. WAVs small
+    /// enough to fit within the memory cache are decoded in the background so that following
+    /// sounds may be played from memory.
+    ///
+    /// If some `loop_region` is given, the WAV is looped endlessly over that region once reached.
     pub fn play(
         &self,
         sound_id: sound::Id,
         wav_path: &Path,
         start_frame: u64,
-        looped: bool,
+        loop_region: Option<LoopRegion>,
     ) -> Result<SamplesStream, hound::Error>
     {
+        let looped = loop_region.is_some();
+
+        // Play straight from memory if we can.
+        if let Some(wav) = self.memory.lock().unwrap().get(wav_path) {
+            let wav_len_samples = wav.samples.len();
+            let cursor = MemoryCursor::new(wav.samples, wav.spec, start_frame, loop_region);
+            let source = StreamSource::Memory(cursor);
+            let samples_stream = SamplesStream::new(source, wav.spec, wav_len_samples, looped);
+            return Ok(samples_stream);
+        }
+
         let reader = WavReader::open(wav_path)?;
         let wav_len_samples = reader.len() as _;
         let buffer_queue = Arc::new(SegQueue::new());
         let buffer_tx = buffer_queue.clone();
         let buffer_rx = buffer_queue;
         let spec = reader.spec();
-        let play = Play { reader, buffer_tx, start_frame, looped };
-        let samples_stream = SamplesStream::new(buffer_rx, spec, wav_len_samples, looped);
+        let play = Play { reader, buffer_tx, start_frame, loop_region };
+        let source = StreamSource::Buffered {
+            buffer_rx,
+            buffer: RefCell::new(None),
+            buffer_index: 0,
+        };
+        let samples_stream = SamplesStream::new(source, spec, wav_len_samples, looped);
         let msg = Message::Play(sound_id, play);
         self.tx.push(msg);
+
+        // Decode the WAV into the memory cache if it is small enough.
+        if self.memory.lock().unwrap().request(wav_path, wav_len_samples as u64) {
+            self.tx.push(Message::Decode(wav_path.to_path_buf()));
+        }
+
         Ok(samples_stream)
     }
 
@@ -226,20 +359,80 @@ impl Drop for Buffer {
     }
 }
 
+impl MemoryCursor {
+    fn new(
+        samples: Arc<[f32]>,
+        spec: hound::WavSpec,
+        start_frame: u64,
+        loop_region: Option<LoopRegion>,
+    ) -> Self {
+        let channels = spec.channels as usize;
+        // Wrap frames beyond the end of the WAV back around to its beginning.
+        let duration_frames = (samples.len() / channels) as u64;
+        let frame = match duration_frames {
+            0 => 0,
+            n => start_frame % n,
+        };
+        let position = frame as usize * channels;
+        MemoryCursor { samples, channels, position, loop_region }
+    }
+
+    // The next sample in the WAV, wrapping back around to the start of the loop region each time
+    // its end is reached.
+    fn next_sample(&mut self) -> Option<f32> {
+        let region = match self.loop_region {
+            None => {
+                let sample = *self.samples.get(self.position)?;
+                self.position += 1;
+                return Some(sample);
+            },
+            Some(region) => region,
+        };
+        let mut frame = (self.position / self.channels) as u64;
+        if frame >= region.end {
+            frame = region.start + region.crossfade;
+            self.position = frame as usize * self.channels;
+        }
+        let ch = self.position % self.channels;
+        let sample = self.samples.get(self.position).cloned().unwrap_or(0.0);
+        self.position += 1;
+        let sample = match loop_fade(&region, frame) {
+            None => sample,
+            Some((i, gain_out, gain_in)) => {
+                let head_index = (region.start as usize + i) * self.channels + ch;
+                let head = self.samples.get(head_index).cloned().unwrap_or(0.0);
+                sample * gain_out + head * gain_in
+            },
+        };
+        Some(sample)
+    }
+}
+
 impl SamplesStream {
     fn new(
-        buffer_rx: BufferRx,
+        source: StreamSource,
         wav_spec: hound::WavSpec,
         wav_len_samples: usize,
         wav_looped: bool,
     ) -> Self {
+        let channels = wav_spec.channels as usize;
         SamplesStream {
-            buffer_rx,
-            buffer: RefCell::new(None),
-            buffer_index: 0,
+            source,
             wav_spec,
             wav_len_samples,
             wav_looped,
+            playback_rate: 1.0,
+            frame_a: vec![0.0; channels],
+            frame_b: vec![0.0; channels],
+            frame_a_valid: false,
+            frame_b_valid: false,
+            pending_frame: vec![0.0; channels],
+            pending_len: 0,
+            wav_ended: false,
+            frame_phase: 0.0,
+            output_channel: 0,
+            advance: false,
+            stalled: false,
         }
     }
 
@@ -248,42 +441,193 @@ impl SamplesStream {
         self.wav_spec.channels as _
     }
 
+    /// The rate at which frames are read from the WAV, where `1.0` is the natural rate.
+    pub fn playback_rate(&self) -> f64 {
+        self.playback_rate
+    }
+
+    /// Set the rate at which frames are read from the WAV.
+    ///
+    /// The rate is clamped to the range supported by `audio::source`.
+    pub fn set_playback_rate(&mut self, rate: f64) {
+        self.playback_rate = rate
+            .max(audio::source::MIN_PLAYBACK_RATE)
+            .min(audio::source::MAX_PLAYBACK_RATE);
+    }
+
+    /// Whether or not the most recent output frame was silence yielded while waiting on the
+    /// reader thread.
+    pub fn is_stalled(&self) -> bool {
+        self.stalled
+    }
+
     /// The number of frames remaining in the stream.
     pub fn remaining_frames(&self) -> Option<Samples> {
         if self.wav_looped {
             return None;
         }
+        let (buffer_rx, buffer, buffer_index) = match self.source {
+            StreamSource::Buffered { ref buffer_rx, ref buffer, buffer_index } => {
+                (buffer_rx, buffer, buffer_index)
+            },
+            StreamSource::Memory(ref cursor) => {
+                let remaining_samples = self.wav_len_samples.saturating_sub(cursor.position);
+                let remaining_frames = remaining_samples / self.wav_spec.channels as usize;
+                return Some(self.frames_at_playback_rate(remaining_frames));
+            },
+        };
         loop {
-            if let Some(ref buffer) = *self.buffer.borrow() {
+            if let Some(ref buffer) = *buffer.borrow() {
                 let remaining_samples =
-                    self.wav_len_samples - (buffer.info.samples_range.start + self.buffer_index);
-                let remaining_frames = (remaining_samples / self.wav_spec.channels as usize) as _;
-                return Some(Samples(remaining_frames));
+                    self.wav_len_samples - (buffer.info.samples_range.start + buffer_index);
+                let remaining_frames = remaining_samples / self.wav_spec.channels as usize;
+                return Some(self.frames_at_playback_rate(remaining_frames));
             }
 
-            let mut buffer_mut = self.buffer.borrow_mut();
-            *buffer_mut = match self.buffer_rx.try_pop() {
-                None => return Some(Samples(self.wav_len_samples as _)),
-                Some(buffer) => Some(buffer),
+            let mut buffer_mut = buffer.borrow_mut();
+            *buffer_mut = match buffer_rx.try_pop() {
+                Some(StreamMessage::Buffer(buffer)) => Some(buffer),
+                Some(StreamMessage::End) => return Some(Samples(0)),
+                None => {
+                    let frames = self.wav_len_samples / self.wav_spec.channels as usize;
+                    return Some(self.frames_at_playback_rate(frames));
+                },
             };
         }
     }
 
+    // The number of output frames required to play the given number of WAV frames.
+    fn frames_at_playback_rate(&self, wav_frames: usize) -> Samples {
+        Samples((wav_frames as f64 / self.playback_rate) as _)
+    }
+
     /// The next sample in the stream.
+    ///
+    /// Returns `None` once the WAV is depleted or the reader thread has stopped reading it.
+    ///
+    /// If the next buffer has not yet arrived from the reader thread, a frame of silence is
+    /// yielded and the same position within the WAV is retried for the following frame.
     pub fn next_sample(&mut self) -> Option<f32> {
-        let SamplesStream {
-            ref buffer,
-            ref buffer_rx,
-            ref mut buffer_index,
-            ..
-        } = *self;
+        // Advance to the next output frame if necessary.
+        if self.output_channel == 0 {
+            if self.advance {
+                self.frame_phase += self.playback_rate;
+                self.advance = false;
+            }
+            match self.read_frames() {
+                WavFrame::Complete => {
+                    self.advance = true;
+                    self.stalled = false;
+                },
+                WavFrame::Pending => self.stalled = true,
+                WavFrame::Ended => return None,
+            }
+        }
+
+        let ch = self.output_channel;
+        self.output_channel = (ch + 1) % self.frame_a.len();
+        if self.stalled {
+            return Some(0.0);
+        }
+        let a = self.frame_a[ch];
+        let b = match self.frame_b_valid {
+            true => self.frame_b[ch],
+            false => a,
+        };
+        Some(a + (b - a) * self.frame_phase as f32)
+    }
+
+    // Read WAV frames until `frame_a` and `frame_b` surround the current phase.
+    fn read_frames(&mut self) -> WavFrame {
+        loop {
+            if !self.frame_a_valid {
+                if self.wav_ended {
+                    return WavFrame::Ended;
+                }
+                match self.read_pending_frame() {
+                    WavFrame::Complete => {
+                        mem::swap(&mut self.frame_a, &mut self.pending_frame);
+                        self.frame_a_valid = true;
+                    },
+                    WavFrame::Pending => return WavFrame::Pending,
+                    WavFrame::Ended => {
+                        self.wav_ended = true;
+                        return WavFrame::Ended;
+                    },
+                }
+            }
+
+            if !self.frame_b_valid && !self.wav_ended {
+                match self.read_pending_frame() {
+                    WavFrame::Complete => {
+                        mem::swap(&mut self.frame_b, &mut self.pending_frame);
+                        self.frame_b_valid = true;
+                    },
+                    WavFrame::Pending => return WavFrame::Pending,
+                    WavFrame::Ended => self.wav_ended = true,
+                }
+            }
+
+            if self.frame_phase < 1.0 {
+                return WavFrame::Complete;
+            }
+
+            // Step past `frame_a`. If there is no following frame, the WAV is depleted.
+            if !self.frame_b_valid {
+                self.frame_a_valid = false;
+                return WavFrame::Ended;
+            }
+            mem::swap(&mut self.frame_a, &mut self.frame_b);
+            self.frame_b_valid = false;
+            self.frame_phase -= 1.0;
+        }
+    }
+
+    // Continue reading the next WAV frame into `pending_frame`.
+    //
+    // Samples already read into the frame are retained while waiting on the next buffer.
+    fn read_pending_frame(&mut self) -> WavFrame {
+        while self.pending_len < self.pending_frame.len() {
+            match self.next_wav_sample() {
+                WavSample::Sample(sample) => {
+                    self.pending_frame[self.pending_len] = sample;
+                    self.pending_len += 1;
+                },
+                WavSample::Pending => return WavFrame::Pending,
+                WavSample::Ended => return WavFrame::Ended,
+            }
+        }
+        self.pending_len = 0;
+        WavFrame::Complete
+    }
+
+    // The next sample in the WAV.
+    fn next_wav_sample(&mut self) -> WavSample {
+        let wav_len_samples = self.wav_len_samples;
+        let wav_looped = self.wav_looped;
+        let (buffer_rx, buffer, buffer_index) = match self.source {
+            StreamSource::Buffered { ref buffer_rx, ref buffer, ref mut buffer_index } => {
+                (buffer_rx, buffer, buffer_index)
+            },
+            StreamSource::Memory(ref mut cursor) => {
+                return match cursor.next_sample() {
+                    Some(sample) => WavSample::Sample(sample),
+                    None => WavSample::Ended,
+                };
+            },
+        };
 
         loop {
             // If there is a sample in the current buffer, return it.
             if let Some(ref buffer) = *buffer.borrow() {
                 if let Some(&sample) = buffer.get(*buffer_index) {
                     *buffer_index += 1;
-                    return Some(sample);
+                    return WavSample::Sample(sample);
+                }
+
+                // The final buffer of a WAV that is not looped.
+                if !wav_looped && buffer.info.samples_range.end >= wav_len_samples {
+                    return WavSample::Ended;
                 }
             }
 
@@ -296,10 +640,12 @@ impl SamplesStream {
 
             // Receive the next buffer.
             *buffer_mut = match buffer_rx.try_pop() {
-                // If there are no more buffers, there must be no more samples so we're done.
-                None => return None,
+                // The reader thread has stopped reading the WAV.
+                Some(StreamMessage::End) => return WavSample::Ended,
+                // The next buffer has not yet arrived, so try again next time.
+                None => return WavSample::Pending,
                 // Otherwise reset
-                Some(buffer) => {
+                Some(StreamMessage::Buffer(buffer)) => {
                     *buffer_index = 0;
                     Some(buffer)
                 },
@@ -328,7 +674,24 @@ impl Model {
 
 /// Process the given `Play` command and return the resulting `Sound`.
 fn play_sound(play: Play) -> Sound {
-    let Play { mut reader, buffer_tx, start_frame, looped } = play;
+    let Play { mut reader, buffer_tx, start_frame, loop_region } = play;
+
+    // Read the head of the loop region ahead of time for crossfading.
+    let looping = loop_region.map(|region| {
+        let spec = reader.spec();
+        let mut head = Vec::with_capacity(region.crossfade as usize * spec.channels as usize);
+        if region.crossfade > 0 {
+            reader.seek(region.start as u32)
+                .expect("failed to seek to loop start in wav source");
+            for _ in 0..head.capacity() {
+                let sample = read_next_sample(&mut reader, &spec)
+                    .expect("failed to read loop start in wav source")
+                    .unwrap_or(0.0);
+                head.push(sample);
+            }
+        }
+        Looping { region, head }
+    });
 
     // Seek to the given `start_frame` within the file.
     //
@@ -348,7 +711,7 @@ fn play_sound(play: Play) -> Sound {
         .map(|_| {
             let mut samples = vec![];
             let start_sample = wav_len_samples - super::samples::remaining(&mut reader);
-            fill_buffer(&mut reader, &mut samples, looped)
+            fill_buffer(&mut reader, &mut samples, looping.as_ref())
                 .expect("failed to fill buffer");
             let end_sample = wav_len_samples - super::samples::remaining(&mut reader);
             let samples_range = start_sample..end_sample;
@@ -360,7 +723,8 @@ fn play_sound(play: Play) -> Sound {
         reader,
         buffer_tx,
         prepared_buffers,
-        looped,
+        looping,
+        ended: false,
     }
 }
 
@@ -380,24 +744,37 @@ fn next_buffer(
         ref mut reader,
         ref mut prepared_buffers,
         ref buffer_tx,
-        looped,
+        ref looping,
+        ref mut ended,
     } = *sound;
 
+    // Once the end of the stream has been sent there is nothing more to read.
+    if *ended {
+        return Ok(());
+    }
+
     // The total number of samples in the WAV, tracked for `BufferInfo`.
     let wav_len_samples = reader.len() as usize;
 
     // First, send the next queued buffer over the channel.
+    //
+    // An empty buffer means the WAV is depleted, in which case the end of the stream is sent.
     if let Some(PreparedBuffer { samples, samples_range }) = prepared_buffers.pop_front() {
+        if samples.is_empty() {
+            buffer_tx.push(StreamMessage::End);
+            *ended = true;
+            return Ok(());
+        }
         let reader_tx = parent_tx.clone();
         let info = BufferInfo { samples_range };
         let buffer = Buffer { samples, sound_id, reader_tx, info };
         // The output thread may have exited before us so ignore closed channel error.
-        buffer_tx.push(buffer);
+        buffer_tx.push(StreamMessage::Buffer(buffer));
     }
 
     // Fill the given buffer using the reader and enqueue it.
     let start = wav_len_samples - super::samples::remaining(reader);
-    fill_buffer(reader, &mut samples, looped)?;
+    fill_buffer(reader, &mut samples, looping.as_ref())?;
     let end = wav_len_samples - super::samples::remaining(reader);
     let samples_range = start..end;
     let prepared_buffer = PreparedBuffer { samples, samples_range };
@@ -411,43 +788,72 @@ fn next_buffer(
 fn fill_buffer(
     reader: &mut WavReader,
     samples: &mut Vec<f32>,
-    looped: bool,
+    looping: Option<&Looping>,
 ) -> Result<(), hound::Error> {
     let spec = reader.spec();
     let channels = spec.channels as usize;
     let num_samples = audio::FRAMES_PER_BUFFER * channels;
     samples.clear();
-    if looped {
-        for _ in 0..num_samples {
-            let sample = read_next_sample_cycled(reader, &spec)?;
-            samples.push(sample);
-        }
-    } else {
-        for _ in 0..num_samples {
-            match read_next_sample(reader, &spec)? {
-                Some(sample) => samples.push(sample),
-                None => break,
+    match looping {
+        Some(looping) => fill_buffer_looped(reader, samples, looping)?,
+        None => {
+            for _ in 0..num_samples {
+                match read_next_sample(reader, &spec)? {
+                    Some(sample) => samples.push(sample),
+                    None => break,
+                }
             }
-        }
+        },
     }
     Ok(())
 }
 
-/// The same as `read_next_sample` but rather than returning `None` after the last sample in the
-/// `WAV` is read it seeks the reader back to the beginning of the file and
-fn read_next_sample_cycled(
+/// Fill the given `samples` buffer with `FRAMES_PER_BUFFER` frames, wrapping back around to the
+/// start of the loop region each time its end is reached.
+///
+/// The last `crossfade` frames of the region are mixed with the loop's `head` using an
+/// equal-power crossfade, after which playback resumes from the frame following the head.
+fn fill_buffer_looped(
     reader: &mut WavReader,
-    spec: &hound::WavSpec,
-) -> Result<f32, hound::Error>
-{
-    loop {
-        match read_next_sample(reader, spec)? {
-            Some(sample) => return Ok(sample),
-            None => {
-                reader.seek(0)?;
-            },
+    samples: &mut Vec<f32>,
+    looping: &Looping,
+) -> Result<(), hound::Error> {
+    let spec = reader.spec();
+    let channels = spec.channels as usize;
+    let Looping { region, ref head } = *looping;
+    let wav_len_samples = reader.len() as usize;
+    let mut frame = ((wav_len_samples - super::samples::remaining(reader)) / channels) as u64;
+    for _ in 0..audio::FRAMES_PER_BUFFER {
+        if frame >= region.end {
+            frame = region.start + region.crossfade;
+            reader.seek(frame as u32)?;
         }
+        let fade = loop_fade(&region, frame);
+        for ch in 0..channels {
+            let sample = read_next_sample(reader, &spec)?.unwrap_or(0.0);
+            let sample = match fade {
+                None => sample,
+                Some((i, gain_out, gain_in)) => {
+                    sample * gain_out + head[i * channels + ch] * gain_in
+                },
+            };
+            samples.push(sample);
+        }
+        frame += 1;
+    }
+    Ok(())
+}
+
+/// If the given frame lies within the crossfade at the end of the loop region, produce the index
+/// of the frame within the loop's head along with the gains of the outgoing and incoming frames.
+fn loop_fade(region: &LoopRegion, frame: u64) -> Option<(usize, f32, f32)> {
+    let fade_start = region.end - region.crossfade;
+    if frame < fade_start || frame >= region.end {
+        return None;
     }
+    let i = (frame - fade_start) as usize;
+    let t = (i as f32 + 0.5) / region.crossfade as f32;
+    Some((i, Curve::EqualPower.gain(1.0 - t), Curve::EqualPower.gain(t)))
 }
 
 /// Read a single sample from the reader.
@@ -486,23 +892,27 @@ fn read_next_sample(
 
 /// Runs the wav reader thread and returns a handle to it that may be used to play or seek sounds
 /// via their unique `Id`.
-pub fn spawn() -> Handle {
+///
+/// Short WAVs are decoded into the given `memory` cache.
+pub fn spawn(memory: memory::Cache) -> Handle {
     let queue = Arc::new(MsQueue::new());
     let tx = queue.clone();
     let rx = queue;
     let tx2 = tx.clone();
+    let memory = Arc::new(Mutex::new(memory));
+    let memory2 = memory.clone();
     let thread = thread::Builder::new()
         .name("wav_reader".into())
-        .spawn(move || run(tx2, rx))
+        .spawn(move || run(tx2, rx, memory2))
         .unwrap();
     let thread = Arc::new(Mutex::new(Some(thread)));
-    Handle { tx, thread }
+    Handle { tx, thread, memory }
 }
 
 /// Run the parent wav reader loop.
 ///
 /// The parent maintains all state while the children perform all significant processing.
-fn run(tx: Tx, rx: Rx) {
+fn run(tx: Tx, rx: Rx, memory: Arc<Mutex<memory::Cache>>) {
     // Create a threadpool for processing `Play` messages.
     let children = num_cpus::get();
     let threadpool = ThreadPool::with_name("wav_reader_children".into(), children);
@@ -512,7 +922,8 @@ fn run(tx: Tx, rx: Rx) {
     for _ in 0..children {
         let queue = child_message_queue.clone();
         let parent_tx = tx.clone();
-        threadpool.execute(move || run_child(queue, parent_tx));
+        let memory = memory.clone();
+        threadpool.execute(move || run_child(queue, parent_tx, memory));
     }
 
     // Block on receiving messages.
@@ -581,6 +992,11 @@ fn run(tx: Tx, rx: Rx) {
                 mem::drop(model.sounds.remove(&sound_id));
             },
 
+            // Enqueue the WAV for decoding by one of the child threads.
+            Message::Decode(path) => {
+                child_message_queue.push(ChildMessage::Decode(path));
+            },
+
             // Break from waiting on messages as the program has exited.
             Message::Exit => {
                 break;
@@ -591,7 +1007,11 @@ fn run(tx: Tx, rx: Rx) {
 
 /// Run the child thread, receiving child messages as quickly as possible and sending them back to
 /// the parent thread in their processed form.
-fn run_child(child_msg_queue: Arc<ChildMessageQueue>, parent_tx: Tx) {
+fn run_child(
+    child_msg_queue: Arc<ChildMessageQueue>,
+    parent_tx: Tx,
+    memory: Arc<Mutex<memory::Cache>>,
+) {
     loop {
         let msg = child_msg_queue.pop();
         match msg {
@@ -603,12 +1023,26 @@ fn run_child(child_msg_queue: Arc<ChildMessageQueue>, parent_tx: Tx) {
             },
 
             // Process the next buffer and return the resulting `Sound` to the parent thread.
+            //
+            // If the WAV can no longer be read, end the stream rather than leaving it waiting.
             ChildMessage::NextBuffer(sound_id, mut sound, buffer) => {
-                next_buffer(sound_id, &mut sound, buffer, &parent_tx)
-                    .expect("failed to process next buffer");
+                if let Err(err) = next_buffer(sound_id, &mut sound, buffer, &parent_tx) {
+                    eprintln!("failed to process next buffer: {}", err);
+                    sound.buffer_tx.push(StreamMessage::End);
+                    sound.ended = true;
+                }
                 let msg = Message::NextBufferComplete(sound_id, sound);
                 parent_tx.push(msg);
             },
+
+            // Decode the WAV and insert it into the memory cache.
+            ChildMessage::Decode(path) => match memory::decode(&path) {
+                Ok(wav) => memory.lock().unwrap().insert(path, wav),
+                Err(err) => {
+                    eprintln!("failed to decode \"{}\" into memory: {}", path.display(), err);
+                    memory.lock().unwrap().cancel(&path);
+                },
+            },
         }
     }
 }
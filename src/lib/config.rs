@@ -26,6 +26,14 @@ pub struct Config {
     /// selected.
     #[serde(default)]
     pub target_output_device_name: String,
+    /// The number of megabytes of memory that may be occupied by WAVs decoded for playback
+    /// straight from RAM.
+    #[serde(default = "default::wav_memory_cache_mb")]
+    pub wav_memory_cache_mb: u64,
+    /// WAVs that would occupy more than this number of megabytes once decoded are always streamed
+    /// from disk.
+    #[serde(default = "default::wav_memory_cache_max_wav_mb")]
+    pub wav_memory_cache_max_wav_mb: u64,
 }
 
 impl Default for Config {
@@ -35,12 +43,16 @@ impl Default for Config {
         let cpu_saving_mode = Default::default();
         let target_input_device_name = Default::default();
         let target_output_device_name = Default::default();
+        let wav_memory_cache_mb = default::wav_memory_cache_mb();
+        let wav_memory_cache_max_wav_mb = default::wav_memory_cache_max_wav_mb();
         Config {
             project_default,
             selected_project_slug,
             cpu_saving_mode,
             target_input_device_name,
             target_output_device_name,
+            wav_memory_cache_mb,
+            wav_memory_cache_max_wav_mb,
         }
     }
 }
@@ -62,4 +74,12 @@ mod default {
     pub fn cpu_saving_mode() -> bool {
         false
     }
+
+    pub fn wav_memory_cache_mb() -> u64 {
+        256
+    }
+
+    pub fn wav_memory_cache_max_wav_mb() -> u64 {
+        8
+    }
 }
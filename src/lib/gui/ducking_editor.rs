@@ -0,0 +1,497 @@
+//! A "Ducking Editor" side-bar widget for creating, editing and removing the rules by which the
+//! sounds of some sources duck the soundscape of an installation.
+
+use audio::ducking::{self, Role, Rule, Trigger};
+use gui::{collapsible_area, Channels, Gui, ProjectState, State, DARK_A};
+use gui::{ITEM_HEIGHT, SMALL_FONT_SIZE};
+use installation;
+use nannou::ui;
+use nannou::ui::prelude::*;
+use project::{self, Project};
+use time_calc::Ms;
+
+/// GUI state related to the ducking editor area.
+#[derive(Default)]
+pub struct DuckingEditor {
+    /// The index of the selected rule.
+    pub selected: Option<usize>,
+}
+
+/// The roles that may trigger a rule along with their labels.
+const ROLES: &'static [(Role, &'static str)] = &[
+    (Role::Soundscape, "Soundscape"),
+    (Role::Interactive, "Interactive"),
+    (Role::Scribbles, "Scribbles"),
+];
+
+/// Labels for each kind of trigger.
+const TRIGGER_KINDS: &'static [&'static str] = &["Role", "Source", "Group"];
+
+/// The maximum attenuation selectable for a rule.
+const MAX_DEPTH_DB: f32 = 48.0;
+
+/// The lowest trigger threshold selectable for a rule.
+const MIN_THRESHOLD_DB: f32 = -80.0;
+
+/// The longest attack or release selectable for a rule.
+const MAX_ENVELOPE_MS: f64 = 5_000.0;
+
+/// Sets all widgets in the ducking area and returns the `Id` of the last area.
+pub fn set(
+    last_area_id: widget::Id,
+    gui: &mut Gui,
+    project: &mut Project,
+    project_state: &mut ProjectState,
+) -> widget::Id {
+    let Gui {
+        ref mut ui,
+        ref ids,
+        channels,
+        state: &mut State {
+            ref mut is_open,
+            ..
+        },
+        ..
+    } = *gui;
+    let Project {
+        state: project::State {
+            ref mut ducking,
+            ref installations,
+            ref sources,
+            ref soundscape_groups,
+            ..
+        },
+        ..
+    } = *project;
+    let ProjectState {
+        ducking_editor: DuckingEditor {
+            ref mut selected,
+        },
+        ..
+    } = *project_state;
+
+    // Constants to use as widget heights.
+    const PAD: Scalar = 6.0;
+    const PLUS_RULE_H: Scalar = ITEM_HEIGHT;
+    const RULE_LIST_MAX_H: Scalar = ITEM_HEIGHT * 5.0;
+    const TITLE_H: Scalar = SMALL_FONT_SIZE as Scalar * 1.333;
+    const RULE_CANVAS_H: Scalar = PAD + TITLE_H + PAD + PLUS_RULE_H + RULE_LIST_MAX_H + PAD;
+    const ROW_H: Scalar = ITEM_HEIGHT;
+    const NUM_ROWS: Scalar = 5.0;
+    const SELECTED_CANVAS_H: Scalar = PAD + TITLE_H + PAD + (ROW_H + PAD) * NUM_ROWS;
+    const DUCKING_EDITOR_H: Scalar = PAD + RULE_CANVAS_H + PAD + SELECTED_CANVAS_H + PAD;
+
+    // The collapsible area.
+    let (area, event) = collapsible_area(is_open.ducking_editor, "Ducking Editor", ids.side_menu)
+        .align_middle_x_of(ids.side_menu)
+        .down_from(last_area_id, 0.0)
+        .set(ids.ducking_editor, ui);
+    if let Some(event) = event {
+        is_open.ducking_editor = event.is_open();
+    }
+
+    // If the area is open, get the area.
+    let area = match area {
+        Some(area) => area,
+        None => return ids.ducking_editor,
+    };
+
+    // The canvas on which the ducking editor will be placed.
+    let canvas = widget::Canvas::new()
+        .pad(PAD)
+        .h(DUCKING_EDITOR_H);
+    area.set(canvas, ui);
+
+    // Installations, sources and groups in a consistent order for display.
+    let mut installations_vec: Vec<_> = installations
+        .iter()
+        .map(|(&id, installation)| (id, installation.name.clone()))
+        .collect();
+    installations_vec.sort_by_key(|&(id, _)| id.0);
+    let mut sources_vec: Vec<_> = sources
+        .iter()
+        .map(|(&id, source)| (id, source.name.clone()))
+        .collect();
+    sources_vec.sort_by(|a, b| a.1.cmp(&b.1));
+    let mut groups_vec: Vec<_> = soundscape_groups
+        .iter()
+        .map(|(&id, group)| (id, group.name.clone()))
+        .collect();
+    groups_vec.sort_by(|a, b| a.1.cmp(&b.1));
+
+    // A label describing the sounds that trigger a rule.
+    let trigger_label = |trigger: &Trigger| -> String {
+        match *trigger {
+            Trigger::Role(role) => {
+                let &(_, label) = ROLES.iter().find(|&&(r, _)| r == role).unwrap();
+                format!("{} role", label)
+            },
+            Trigger::Source(id) => match sources.get(&id) {
+                Some(source) => source.name.clone(),
+                None => "<removed source>".to_string(),
+            },
+            Trigger::Group(id) => match soundscape_groups.get(&id) {
+                Some(group) => format!("Group {}", group.name),
+                None => "<removed group>".to_string(),
+            },
+        }
+    };
+
+    // A label for an installation.
+    let installation_label = |id: installation::Id| -> String {
+        match installations.get(&id) {
+            Some(installation) => installation.name.clone(),
+            None => "<removed installation>".to_string(),
+        }
+    };
+
+    ///////////////
+    // RULE LIST //
+    ///////////////
+
+    // A canvas on which rule selection takes place.
+    widget::Canvas::new()
+        .parent(area.id)
+        .kid_area_w_of(area.id)
+        .h(RULE_CANVAS_H)
+        .mid_top_of(area.id)
+        .pad(PAD)
+        .color(color::CHARCOAL)
+        .set(ids.ducking_editor_rule_canvas, ui);
+
+    // A title for the rules canvas.
+    widget::Text::new("Rules")
+        .top_left_of(ids.ducking_editor_rule_canvas)
+        .font_size(SMALL_FONT_SIZE)
+        .set(ids.ducking_editor_rule_text, ui);
+
+    // A button for adding new rules.
+    for _click in widget::Button::new()
+        .label("+")
+        .kid_area_w_of(ids.ducking_editor_rule_canvas)
+        .h(PLUS_RULE_H)
+        .align_middle_x_of(ids.ducking_editor_rule_canvas)
+        .down(PAD * 2.0)
+        .set(ids.ducking_editor_rule_add, ui)
+    {
+        // By default, interactive sounds duck the first installation.
+        let installation = installations_vec
+            .first()
+            .map(|&(id, _)| id)
+            .unwrap_or_default();
+        let rule = Rule {
+            trigger: Trigger::Role(Role::Interactive),
+            installation,
+            depth_db: ducking::default::DEPTH_DB,
+            threshold_db: ducking::default::THRESHOLD_DB,
+            attack: ducking::default::ATTACK,
+            release: ducking::default::RELEASE,
+        };
+        ducking.push(rule);
+        *selected = Some(ducking.len() - 1);
+        send_rules(channels, ducking);
+    }
+
+    // If there are no rules, display some text for adding a rule.
+    if ducking.is_empty() {
+        widget::Text::new("Add a ducking rule with the \"+\" button above!")
+            .font_size(SMALL_FONT_SIZE)
+            .align_middle_x_of(ids.ducking_editor_rule_canvas)
+            .down(PAD + ITEM_HEIGHT)
+            .set(ids.ducking_editor_rule_none, ui);
+        return area.id;
+    }
+
+    // A list of all rules in the order in which they were added.
+    let num_rules = ducking.len();
+    let (mut events, scrollbar) = widget::ListSelect::single(num_rules)
+        .down(0.0)
+        .flow_down()
+        .item_size(ITEM_HEIGHT)
+        .h(RULE_LIST_MAX_H)
+        .kid_area_w_of(ids.ducking_editor_rule_canvas)
+        .scrollbar_next_to()
+        .set(ids.ducking_editor_rule_list, ui);
+
+    // Track whether or not an item was removed.
+    let selected_index = *selected;
+    let mut maybe_remove_index = None;
+    while let Some(event) = events.next(ui, |i| Some(i) == selected_index) {
+        use self::ui::widget::list_select::Event;
+        match event {
+            // Instantiate the widget for this item.
+            Event::Item(item) => {
+                let is_selected = selected_index == Some(item.i);
+
+                // Blue if selected, gray otherwise.
+                let color = if is_selected {
+                    color::BLUE
+                } else {
+                    color::DARK_CHARCOAL
+                };
+
+                // Describe the rule.
+                let rule = &ducking[item.i];
+                let label = format!(
+                    "{} ducks {} by {:.1} dB",
+                    trigger_label(&rule.trigger),
+                    installation_label(rule.installation),
+                    rule.depth_db,
+                );
+
+                // Use a button widget for each item.
+                let label_x = position::Relative::Place(position::Place::Start(Some(10.0)));
+                let button = widget::Button::new()
+                    .label(&label)
+                    .label_font_size(SMALL_FONT_SIZE)
+                    .label_x(label_x)
+                    .color(color);
+                item.set(button, ui);
+
+                // If the button or any of its children are capturing the mouse, display
+                // the `remove` button.
+                let show_remove_button = ui.global_input()
+                    .current
+                    .widget_capturing_mouse
+                    .map(|id| {
+                        id == item.widget_id
+                            || ui.widget_graph()
+                                .does_recursive_depth_edge_exist(item.widget_id, id)
+                    })
+                    .unwrap_or(false);
+
+                if !show_remove_button {
+                    continue;
+                }
+
+                if widget::Button::new()
+                    .label("X")
+                    .label_font_size(SMALL_FONT_SIZE)
+                    .color(color::DARK_RED.alpha(0.5))
+                    .w_h(ITEM_HEIGHT, ITEM_HEIGHT)
+                    .align_right_of(item.widget_id)
+                    .align_middle_y_of(item.widget_id)
+                    .parent(item.widget_id)
+                    .set(ids.ducking_editor_rule_remove, ui)
+                    .was_clicked()
+                {
+                    maybe_remove_index = Some(item.i);
+                }
+            },
+
+            // Update the selected rule.
+            Event::Selection(idx) => *selected = Some(idx),
+
+            _ => (),
+        }
+    }
+
+    // The scrollbar for the list.
+    if let Some(s) = scrollbar {
+        s.set(ui);
+    }
+
+    // Remove a rule if necessary.
+    if let Some(i) = maybe_remove_index {
+        ducking.remove(i);
+        *selected = match *selected {
+            Some(s) if s == i => None,
+            Some(s) if s > i => Some(s - 1),
+            s => s,
+        };
+        send_rules(channels, ducking);
+    }
+
+    ///////////////////
+    // SELECTED RULE //
+    ///////////////////
+
+    // Only continue if there is some selected rule.
+    let i = match *selected {
+        Some(i) if i < ducking.len() => i,
+        _ => return area.id,
+    };
+
+    // A canvas for parameters specific to the selected rule.
+    widget::Canvas::new()
+        .parent(area.id)
+        .kid_area_w_of(area.id)
+        .h(SELECTED_CANVAS_H)
+        .align_middle_x_of(area.id)
+        .down_from(ids.ducking_editor_rule_canvas, PAD)
+        .pad(PAD)
+        .color(color::CHARCOAL)
+        .set(ids.ducking_editor_selected_canvas, ui);
+
+    widget::Text::new("Selected Rule")
+        .top_left_of(ids.ducking_editor_selected_canvas)
+        .font_size(SMALL_FONT_SIZE)
+        .set(ids.ducking_editor_selected_text, ui);
+
+    let kid_area = ui.kid_area_of(ids.ducking_editor_selected_canvas).unwrap();
+    let half_w = (kid_area.w() - PAD) / 2.0;
+    let rule = ducking[i].clone();
+
+    // The kind of trigger.
+    let kind_index = match rule.trigger {
+        Trigger::Role(_) => 0,
+        Trigger::Source(_) => 1,
+        Trigger::Group(_) => 2,
+    };
+    for new_index in widget::DropDownList::new(TRIGGER_KINDS, Some(kind_index))
+        .down(PAD)
+        .align_left_of(ids.ducking_editor_selected_text)
+        .w(half_w)
+        .h(ROW_H)
+        .color(DARK_A)
+        .border_color(color::LIGHT_CHARCOAL)
+        .label_font_size(SMALL_FONT_SIZE)
+        .set(ids.ducking_editor_selected_trigger_kind, ui)
+    {
+        // Switch to the first trigger of the new kind, if there is one.
+        let new_trigger = match new_index {
+            0 => Some(Trigger::Role(Role::Interactive)),
+            1 => sources_vec.first().map(|&(id, _)| Trigger::Source(id)),
+            _ => groups_vec.first().map(|&(id, _)| Trigger::Group(id)),
+        };
+        if let Some(trigger) = new_trigger {
+            ducking[i].trigger = trigger;
+            send_rules(channels, ducking);
+        }
+    }
+
+    // The specific role, source or group that triggers the rule.
+    let (target_labels, target_index): (Vec<String>, Option<usize>) = match rule.trigger {
+        Trigger::Role(role) => (
+            ROLES.iter().map(|&(_, label)| label.to_string()).collect(),
+            ROLES.iter().position(|&(r, _)| r == role),
+        ),
+        Trigger::Source(id) => (
+            sources_vec.iter().map(|&(_, ref name)| name.clone()).collect(),
+            sources_vec.iter().position(|&(s, _)| s == id),
+        ),
+        Trigger::Group(id) => (
+            groups_vec.iter().map(|&(_, ref name)| name.clone()).collect(),
+            groups_vec.iter().position(|&(g, _)| g == id),
+        ),
+    };
+    for new_index in widget::DropDownList::new(&target_labels, target_index)
+        .right(PAD)
+        .align_top_of(ids.ducking_editor_selected_trigger_kind)
+        .w(half_w)
+        .h(ROW_H)
+        .scrollbar_on_top()
+        .max_visible_items(5)
+        .color(DARK_A)
+        .border_color(color::LIGHT_CHARCOAL)
+        .label_font_size(SMALL_FONT_SIZE)
+        .set(ids.ducking_editor_selected_trigger, ui)
+    {
+        ducking[i].trigger = match rule.trigger {
+            Trigger::Role(_) => Trigger::Role(ROLES[new_index].0),
+            Trigger::Source(_) => Trigger::Source(sources_vec[new_index].0),
+            Trigger::Group(_) => Trigger::Group(groups_vec[new_index].0),
+        };
+        send_rules(channels, ducking);
+    }
+
+    // The installation whose soundscape is ducked.
+    let installation_labels: Vec<String> = installations_vec
+        .iter()
+        .map(|&(_, ref name)| format!("Ducks {}", name))
+        .collect();
+    let installation_index = installations_vec
+        .iter()
+        .position(|&(id, _)| id == rule.installation);
+    for new_index in widget::DropDownList::new(&installation_labels, installation_index)
+        .down_from(ids.ducking_editor_selected_trigger_kind, PAD)
+        .align_left_of(ids.ducking_editor_selected_trigger_kind)
+        .kid_area_w_of(ids.ducking_editor_selected_canvas)
+        .h(ROW_H)
+        .scrollbar_on_top()
+        .max_visible_items(5)
+        .color(DARK_A)
+        .border_color(color::LIGHT_CHARCOAL)
+        .label_font_size(SMALL_FONT_SIZE)
+        .set(ids.ducking_editor_selected_installation, ui)
+    {
+        ducking[i].installation = installations_vec[new_index].0;
+        send_rules(channels, ducking);
+    }
+
+    // The depth of the ducking.
+    let label = format!("Depth: {:.1} dB", rule.depth_db);
+    for new_depth in widget::Slider::new(rule.depth_db, 0.0, MAX_DEPTH_DB)
+        .label(&label)
+        .label_font_size(SMALL_FONT_SIZE)
+        .color(color::LIGHT_CHARCOAL)
+        .down(PAD)
+        .align_left_of(ids.ducking_editor_selected_installation)
+        .kid_area_w_of(ids.ducking_editor_selected_canvas)
+        .h(ROW_H)
+        .set(ids.ducking_editor_selected_depth, ui)
+    {
+        ducking[i].depth_db = (new_depth * 10.0).round() / 10.0;
+        send_rules(channels, ducking);
+    }
+
+    // The level of the trigger sounds above which ducking is applied.
+    let label = format!("Trigger Threshold: {:.1} dB", rule.threshold_db);
+    for new_threshold in widget::Slider::new(rule.threshold_db, MIN_THRESHOLD_DB, 0.0)
+        .label(&label)
+        .label_font_size(SMALL_FONT_SIZE)
+        .color(color::LIGHT_CHARCOAL)
+        .down(PAD)
+        .align_left_of(ids.ducking_editor_selected_depth)
+        .kid_area_w_of(ids.ducking_editor_selected_canvas)
+        .h(ROW_H)
+        .set(ids.ducking_editor_selected_threshold, ui)
+    {
+        ducking[i].threshold_db = (new_threshold * 10.0).round() / 10.0;
+        send_rules(channels, ducking);
+    }
+
+    // The attack and release of the ducking.
+    let label = format!("Attack: {:.0} ms", rule.attack.ms());
+    for new_attack in widget::Slider::new(rule.attack.ms(), 0.0, MAX_ENVELOPE_MS)
+        .skew(0.5)
+        .label(&label)
+        .label_font_size(SMALL_FONT_SIZE)
+        .color(color::LIGHT_CHARCOAL)
+        .down(PAD)
+        .align_left_of(ids.ducking_editor_selected_threshold)
+        .w(half_w)
+        .h(ROW_H)
+        .set(ids.ducking_editor_selected_attack, ui)
+    {
+        ducking[i].attack = Ms(new_attack.round());
+        send_rules(channels, ducking);
+    }
+    let label = format!("Release: {:.0} ms", rule.release.ms());
+    for new_release in widget::Slider::new(rule.release.ms(), 0.0, MAX_ENVELOPE_MS)
+        .skew(0.5)
+        .label(&label)
+        .label_font_size(SMALL_FONT_SIZE)
+        .color(color::LIGHT_CHARCOAL)
+        .right(PAD)
+        .w(half_w)
+        .h(ROW_H)
+        .set(ids.ducking_editor_selected_release, ui)
+    {
+        ducking[i].release = Ms(new_release.round());
+        send_rules(channels, ducking);
+    }
+
+    area.id
+}
+
+// Send the updated rules to the audio output thread.
+fn send_rules(channels: &Channels, ducking: &project::DuckingRules) {
+    let rules = ducking.clone();
+    channels
+        .audio_output
+        .send(move |audio| {
+            audio.ducker.set_rules(rules);
+        })
+        .expect("failed to send ducking rules to audio output thread");
+}
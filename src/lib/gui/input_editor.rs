@@ -0,0 +1,287 @@
+//! An "Input Editor" side-bar widget for metering and conditioning the audio input channels.
+
+use audio::conditioning;
+use gui::{collapsible_area, Channels, Gui, ProjectState, State, DARK_A};
+use gui::{ITEM_HEIGHT, SMALL_FONT_SIZE};
+use nannou::ui;
+use nannou::ui::prelude::*;
+use project::{self, Project};
+
+/// Runtime state relevant to the input editor GUI.
+#[derive(Default)]
+pub struct InputEditor {
+    /// The input channel whose conditioning is currently being edited.
+    pub selected_channel: usize,
+}
+
+/// The range over which the trim may be adjusted.
+const MAX_TRIM_DB: f32 = 24.0;
+
+/// The lowest threshold selectable for the gate and compressor.
+const MIN_THRESHOLD_DB: f32 = -80.0;
+
+/// The highest ratio selectable for the compressor.
+const MAX_RATIO: f32 = 20.0;
+
+/// The highest makeup gain selectable for the compressor.
+const MAX_MAKEUP_DB: f32 = 24.0;
+
+pub fn set(
+    last_area_id: widget::Id,
+    gui: &mut Gui,
+    project: &mut Project,
+    project_state: &mut ProjectState,
+) -> widget::Id {
+    let Gui {
+        ref mut ui,
+        ref ids,
+        channels,
+        ref audio_monitor,
+        state: &mut State {
+            ref mut is_open,
+            ref audio_channels,
+            ..
+        },
+        ..
+    } = *gui;
+    let Project {
+        state: project::State {
+            ref mut input,
+            ..
+        },
+        ..
+    } = *project;
+    let ProjectState {
+        input_editor: InputEditor {
+            ref mut selected_channel,
+        },
+        ..
+    } = *project_state;
+
+    const PAD: Scalar = 6.0;
+    const METER_H: Scalar = ITEM_HEIGHT * 2.0;
+    const ROW_H: Scalar = ITEM_HEIGHT;
+    const NUM_ROWS: Scalar = 6.0;
+    const INPUT_EDITOR_H: Scalar = PAD + METER_H + (PAD + ROW_H) * NUM_ROWS + PAD;
+
+    // The collapsible area widget.
+    let (area, event) = collapsible_area(is_open.input_editor, "Input Editor", ids.side_menu)
+        .align_middle_x_of(ids.side_menu)
+        .down_from(last_area_id, 0.0)
+        .set(ids.input_editor, ui);
+    if let Some(event) = event {
+        is_open.input_editor = event.is_open();
+    }
+
+    // Return early if the panel is not open.
+    let area = match area {
+        None => return ids.input_editor,
+        Some(area) => area,
+    };
+
+    // The canvas on which the controls will be placed.
+    let canvas = widget::Canvas::new().pad(PAD).h(INPUT_EDITOR_H);
+    area.set(canvas, ui);
+
+    // Display the level of every input channel as a row of bars, highlighting the selected channel.
+    let n_channels = audio_channels.input;
+    let kid_rect = ui.kid_area_of(area.id).unwrap();
+    let meter_rect = ui::Rect::from_xy_dim([0.0, 0.0], [kid_rect.w(), METER_H])
+        .align_top_of(kid_rect)
+        .align_left_of(kid_rect);
+    let bar_w = meter_rect.w() / n_channels.max(1) as Scalar;
+    let tris = (0..n_channels).flat_map(|channel| {
+        let levels = audio_monitor.inputs.get(channel);
+        let peak = levels.map(|l| l.peak).unwrap_or(0.0).min(1.0) as Scalar;
+        let rms = levels.map(|l| l.rms).unwrap_or(0.0).min(1.0) as Scalar;
+        let left = meter_rect.left() + channel as Scalar * bar_w;
+        let bar = |level: Scalar, color: ui::Color| {
+            let bottom_left = [left, meter_rect.bottom()];
+            let top_right = [left + bar_w * 0.8, meter_rect.bottom() + level * METER_H];
+            let rect = ui::Rect::from_corners(bottom_left, top_right);
+            let rgba: ui::color::Rgba = color.into();
+            let tl = (rect.top_left(), rgba);
+            let tr = (rect.top_right(), rgba);
+            let br = (rect.bottom_right(), rgba);
+            let bl = (rect.bottom_left(), rgba);
+            let tri_a = widget::triangles::Triangle([tl, tr, br]);
+            let tri_b = widget::triangles::Triangle([tl, br, bl]);
+            Some(tri_a).into_iter().chain(Some(tri_b))
+        };
+        let peak_color = match channel == *selected_channel {
+            true => ui::color::LIGHT_BLUE,
+            false => ui::color::LIGHT_CHARCOAL,
+        };
+        bar(peak, peak_color).chain(bar(rms, ui::color::DARK_GREEN))
+    });
+    widget::Triangles::multi_color(tris)
+        .with_bounding_rect(meter_rect)
+        .parent(area.id)
+        .set(ids.input_editor_meters, ui);
+
+    if n_channels == 0 {
+        widget::Text::new("No input channels available")
+            .font_size(SMALL_FONT_SIZE)
+            .middle_of(ids.input_editor_meters)
+            .set(ids.input_editor_none, ui);
+        return area.id;
+    }
+
+    // The drop down list for selecting the channel to edit.
+    let channel = ::std::cmp::min(*selected_channel, n_channels - 1);
+    let labels: Vec<String> = (0..n_channels)
+        .map(|ch| match audio_monitor.inputs.get(ch) {
+            Some(levels) => {
+                let peak_db = conditioning::amp_to_db(levels.peak);
+                format!("In CH {} ({:.1} dB peak)", ch + 1, peak_db)
+            },
+            None => format!("In CH {}", ch + 1),
+        })
+        .collect();
+    for new_index in widget::DropDownList::new(&labels, Some(channel))
+        .down_from(ids.input_editor_meters, PAD)
+        .align_middle_x_of(area.id)
+        .kid_area_w_of(area.id)
+        .h(ROW_H)
+        .parent(area.id)
+        .scrollbar_on_top()
+        .max_visible_items(5)
+        .color(DARK_A)
+        .border_color(color::LIGHT_CHARCOAL)
+        .label_font_size(SMALL_FONT_SIZE)
+        .set(ids.input_editor_channel, ui)
+    {
+        *selected_channel = new_index;
+    }
+
+    // Update the local copy of the channel's parameters along with the audio input copy.
+    fn update<F>(channels: &Channels, input: &mut conditioning::Input, channel: usize, update: F)
+    where
+        F: FnOnce(&mut conditioning::Channel),
+    {
+        let params = input.channel_mut(channel);
+        update(params);
+        let params = params.clone();
+        channels
+            .audio_input
+            .send(move |audio| {
+                audio.set_channel_conditioning(channel, &params);
+            })
+            .expect("failed to send input channel conditioning to audio input thread");
+    }
+
+    let params = input.channel(channel);
+    let half_w = (kid_rect.w() - PAD) / 2.0;
+    let enabled_color = |enabled: bool| match enabled {
+        true => ui::color::LIGHT_CHARCOAL,
+        false => ui::color::DARK_CHARCOAL,
+    };
+
+    // The trim slider.
+    let label = format!("Trim: {:.1} dB", params.trim_db);
+    for new_trim in widget::Slider::new(params.trim_db, -MAX_TRIM_DB, MAX_TRIM_DB)
+        .label(&label)
+        .label_font_size(SMALL_FONT_SIZE)
+        .color(ui::color::LIGHT_CHARCOAL)
+        .down_from(ids.input_editor_channel, PAD)
+        .align_middle_x_of(area.id)
+        .kid_area_w_of(area.id)
+        .h(ROW_H)
+        .set(ids.input_editor_trim, ui)
+    {
+        let new_trim = (new_trim * 10.0).round() / 10.0;
+        update(channels, input, channel, |params| params.trim_db = new_trim);
+    }
+
+    // The gate toggle and threshold.
+    let gate = &params.gate;
+    let label = if gate.enabled { "Gate: ON" } else { "Gate: OFF" };
+    for enabled in widget::Toggle::new(gate.enabled)
+        .color(ui::color::LIGHT_CHARCOAL)
+        .label(label)
+        .label_font_size(SMALL_FONT_SIZE)
+        .down_from(ids.input_editor_trim, PAD)
+        .align_left_of(ids.input_editor_trim)
+        .w(half_w)
+        .h(ROW_H)
+        .set(ids.input_editor_gate_toggle, ui)
+    {
+        update(channels, input, channel, |params| params.gate.enabled = enabled);
+    }
+    let label = format!("Threshold: {:.1} dB", gate.threshold_db);
+    for new_threshold in widget::Slider::new(gate.threshold_db, MIN_THRESHOLD_DB, 0.0)
+        .label(&label)
+        .label_font_size(SMALL_FONT_SIZE)
+        .color(enabled_color(gate.enabled))
+        .right(PAD)
+        .w(half_w)
+        .h(ROW_H)
+        .set(ids.input_editor_gate_threshold, ui)
+    {
+        let new_threshold = (new_threshold * 10.0).round() / 10.0;
+        update(channels, input, channel, |params| params.gate.threshold_db = new_threshold);
+    }
+
+    // The compressor toggle and threshold.
+    let compressor = &params.compressor;
+    let label = if compressor.enabled { "Compressor: ON" } else { "Compressor: OFF" };
+    for enabled in widget::Toggle::new(compressor.enabled)
+        .color(ui::color::LIGHT_CHARCOAL)
+        .label(label)
+        .label_font_size(SMALL_FONT_SIZE)
+        .down_from(ids.input_editor_gate_toggle, PAD)
+        .align_left_of(ids.input_editor_trim)
+        .w(half_w)
+        .h(ROW_H)
+        .set(ids.input_editor_compressor_toggle, ui)
+    {
+        update(channels, input, channel, |params| params.compressor.enabled = enabled);
+    }
+    let label = format!("Threshold: {:.1} dB", compressor.threshold_db);
+    for new_threshold in widget::Slider::new(compressor.threshold_db, MIN_THRESHOLD_DB, 0.0)
+        .label(&label)
+        .label_font_size(SMALL_FONT_SIZE)
+        .color(enabled_color(compressor.enabled))
+        .right(PAD)
+        .w(half_w)
+        .h(ROW_H)
+        .set(ids.input_editor_compressor_threshold, ui)
+    {
+        let new_threshold = (new_threshold * 10.0).round() / 10.0;
+        update(channels, input, channel, |params| params.compressor.threshold_db = new_threshold);
+    }
+
+    // The compressor ratio.
+    let label = format!("Ratio: {:.1}:1", compressor.ratio);
+    for new_ratio in widget::Slider::new(compressor.ratio, 1.0, MAX_RATIO)
+        .label(&label)
+        .label_font_size(SMALL_FONT_SIZE)
+        .color(enabled_color(compressor.enabled))
+        .down_from(ids.input_editor_compressor_toggle, PAD)
+        .align_middle_x_of(area.id)
+        .kid_area_w_of(area.id)
+        .h(ROW_H)
+        .set(ids.input_editor_compressor_ratio, ui)
+    {
+        let new_ratio = (new_ratio * 10.0).round() / 10.0;
+        update(channels, input, channel, |params| params.compressor.ratio = new_ratio);
+    }
+
+    // The compressor makeup gain.
+    let label = format!("Makeup: {:.1} dB", compressor.makeup_db);
+    for new_makeup in widget::Slider::new(compressor.makeup_db, 0.0, MAX_MAKEUP_DB)
+        .label(&label)
+        .label_font_size(SMALL_FONT_SIZE)
+        .color(enabled_color(compressor.enabled))
+        .down(PAD)
+        .align_middle_x_of(area.id)
+        .kid_area_w_of(area.id)
+        .h(ROW_H)
+        .set(ids.input_editor_compressor_makeup, ui)
+    {
+        let new_makeup = (new_makeup * 10.0).round() / 10.0;
+        update(channels, input, channel, |params| params.compressor.makeup_db = new_makeup);
+    }
+
+    area.id
+}
@@ -8,6 +8,17 @@ use osc;
 use project::{self, Project};
 use std::{io, net};
 use std::sync::Arc;
+use time_calc::Ms;
+use utils::{MIN_MS, SEC_MS};
+
+/// The maximum value at which an installation may be considered fully occupied.
+const MAX_OCCUPANCY: f64 = 500.0;
+
+/// The maximum duration over which occupancy values may be smoothed.
+const MAX_OCCUPANCY_SMOOTHING_MS: f64 = 10.0 * MIN_MS;
+
+/// The maximum duration without new occupancy values before they are forgotten.
+const MAX_OCCUPANCY_TIMEOUT_MS: f64 = 60.0 * MIN_MS;
 
 /// Runtime state relevant to the installation editor GUI.
 #[derive(Default)]
@@ -76,7 +87,8 @@ pub fn set(
     // - Music Data OSC Output (Text and TextBox)
     let osc_canvas_h = PAD + ITEM_HEIGHT * 3.0 + PAD;
     let computer_canvas_h = ITEM_HEIGHT + PAD + ITEM_HEIGHT + PAD + COMPUTER_LIST_HEIGHT;
-    let soundscape_canvas_h = PAD + PAD * 3.0 + PAD + SLIDER_H + PAD;
+    let soundscape_canvas_h = PAD + PAD * 3.0 + PAD + SLIDER_H + PAD
+        + PAD * 3.0 + PAD + (SLIDER_H + PAD) * 4.0;
     let selected_canvas_h = PAD
         + NAME_H + PAD
         + computer_canvas_h + PAD
@@ -438,6 +450,88 @@ pub fn set(
             .expect("failed to send installation update to soundscape thread");
     }
 
+    ///////////////
+    // OCCUPANCY //
+    ///////////////
+
+    widget::Text::new("Soundscape - Occupancy")
+        .font_size(SMALL_FONT_SIZE)
+        .align_left()
+        .down(PAD)
+        .set(ids.installation_editor_soundscape_occupancy_text, ui);
+
+    let slider = |value, min, max| {
+        widget::Slider::new(value, min, max)
+            .kid_area_w_of(ids.installation_editor_soundscape_canvas)
+            .h(SLIDER_H)
+            .label_font_size(SMALL_FONT_SIZE)
+            .color(ui::color::LIGHT_CHARCOAL)
+            .align_left()
+    };
+
+    let occupancy = installations[&id].soundscape.occupancy;
+    let mut new_occupancy = None;
+
+    // The occupancy value at which the installation is considered full.
+    let label = format!("Full At: {:.0}", occupancy.max);
+    for new_max in slider(occupancy.max, 1.0, MAX_OCCUPANCY)
+        .skew(0.5)
+        .label(&label)
+        .down(PAD * 2.0)
+        .set(ids.installation_editor_soundscape_occupancy_max_slider, ui)
+    {
+        new_occupancy = Some(installation::Occupancy { max: new_max.round(), ..occupancy });
+    }
+
+    // How strongly occupancy shifts the target number of sounds.
+    let label = format!("Influence: {:.0}%", occupancy.influence * 100.0);
+    for new_influence in slider(occupancy.influence, 0.0, 1.0)
+        .label(&label)
+        .down(PAD)
+        .set(ids.installation_editor_soundscape_occupancy_influence_slider, ui)
+    {
+        new_occupancy = Some(installation::Occupancy { influence: new_influence, ..occupancy });
+    }
+
+    // The duration over which occupancy values are smoothed.
+    let label = format!("Smoothing: {:.0} secs", occupancy.smoothing.ms() / SEC_MS);
+    for new_ms in slider(occupancy.smoothing.ms(), 0.0, MAX_OCCUPANCY_SMOOTHING_MS)
+        .skew(0.5)
+        .label(&label)
+        .down(PAD)
+        .set(ids.installation_editor_soundscape_occupancy_smoothing_slider, ui)
+    {
+        let smoothing = Ms((new_ms / SEC_MS).round() * SEC_MS);
+        new_occupancy = Some(installation::Occupancy { smoothing, ..occupancy });
+    }
+
+    // The duration without new values after which occupancy is forgotten.
+    let label = format!("Timeout: {:.1} mins", occupancy.timeout.ms() / MIN_MS);
+    for new_ms in slider(occupancy.timeout.ms(), SEC_MS, MAX_OCCUPANCY_TIMEOUT_MS)
+        .skew(0.5)
+        .label(&label)
+        .down(PAD)
+        .set(ids.installation_editor_soundscape_occupancy_timeout_slider, ui)
+    {
+        let timeout = Ms((new_ms / SEC_MS).round() * SEC_MS);
+        new_occupancy = Some(installation::Occupancy { timeout, ..occupancy });
+    }
+
+    if let Some(new_occupancy) = new_occupancy {
+        // Update the local copy.
+        installations.get_mut(&id).unwrap().soundscape.occupancy = new_occupancy;
+
+        // Update the soundscape copy.
+        channels
+            .soundscape
+            .send(move |soundscape| {
+                soundscape.update_installation(&id, |installation| {
+                    installation.occupancy = new_occupancy;
+                });
+            })
+            .expect("failed to send installation occupancy update to soundscape thread");
+    }
+
     ///////////////
     // COMPUTERS //
     ///////////////
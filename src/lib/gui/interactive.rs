@@ -0,0 +1,163 @@
+//! Handling of the OSC controls that spawn and manipulate interactive sounds.
+//!
+//! Each sound spawned via OSC is assigned a small integer handle that is sent back to the sender.
+//! The sender may then use the handle to move, re-volume and stop the sound.
+
+use audio;
+use fxhash::FxHashMap;
+use gui::Channels;
+use metres::Metres;
+use nannou;
+use nannou::prelude::*;
+use nannou::rand::thread_rng;
+use nannou_osc;
+use osc;
+use project::Project;
+use std::sync::atomic;
+
+/// Sounds spawned via the OSC interactive API, indexed by the handle returned to the sender.
+#[derive(Default)]
+pub struct Interactive {
+    next_handle: i32,
+    sounds: FxHashMap<i32, audio::sound::Id>,
+    // Lazily created socket for replying to senders with the handles of spawned sounds.
+    reply_tx: Option<nannou_osc::Sender>,
+}
+
+impl Interactive {
+    /// Spawn a sound from the named source and reply to the sender with its handle.
+    pub fn play(
+        &mut self,
+        play: &osc::input::InteractivePlay,
+        project: &Project,
+        channels: &Channels,
+        sound_id_gen: &audio::sound::IdGenerator,
+    ) {
+        let osc::input::InteractivePlay { ref name, ref location, reply_addr } = *play;
+
+        let (&source_id, source) = match project
+            .state
+            .sources
+            .iter()
+            .find(|&(_, ref s)| &s.name[..] == name)
+        {
+            None => return,
+            Some(source) => source,
+        };
+
+        let point = match *location {
+            osc::input::InteractiveLocation::Point(point) => point,
+            osc::input::InteractiveLocation::Installation(ref installation) => {
+                match installation_centroid(project, installation) {
+                    None => return,
+                    Some(point) => point,
+                }
+            },
+        };
+
+        // Interactive sounds play in full at their natural rate.
+        let sound_id = sound_id_gen.generate_next();
+        let position = audio::sound::Position { point, radians: 0.0 };
+        let envelope = audio::source::Envelope::default();
+        let playback_rate = 1.0;
+        let continuous_preview = false;
+        let max_duration = None;
+        let _handle = audio::sound::spawn_from_source(
+            sound_id,
+            source_id,
+            &source.audio,
+            position,
+            envelope,
+            playback_rate,
+            continuous_preview,
+            max_duration,
+            channels.frame_count.load(atomic::Ordering::Relaxed) as _,
+            &channels.wav_reader,
+            &channels.audio_input,
+            &channels.audio_output,
+            project.master.realtime_source_latency,
+            &mut thread_rng(),
+        );
+
+        let handle = self.next_handle;
+        self.next_handle = self.next_handle.wrapping_add(1);
+        self.sounds.insert(handle, sound_id);
+
+        // Reply to the sender with the handle.
+        if self.reply_tx.is_none() {
+            self.reply_tx = nannou_osc::sender().ok();
+        }
+        if let Some(ref reply_tx) = self.reply_tx {
+            let addr = osc::input::INTERACTIVE_HANDLE_ADDR.into();
+            let args = vec![nannou_osc::Type::Int(handle), nannou_osc::Type::String(name.clone())];
+            let msg = nannou_osc::Message { addr, args: Some(args) };
+            if let Err(err) = reply_tx.send(msg, reply_addr) {
+                eprintln!("failed to send interactive sound handle to {}: {}", reply_addr, err);
+            }
+        }
+    }
+
+    /// Move the sound associated with the given handle.
+    pub fn set_position(&self, handle: i32, point: Point2<Metres>, channels: &Channels) {
+        let sound_id = match self.sounds.get(&handle) {
+            None => return,
+            Some(&id) => id,
+        };
+        channels
+            .audio_output
+            .send(move |audio| {
+                audio.update_sound(&sound_id, |sound| sound.position.point = point);
+            })
+            .expect("failed to send interactive sound position to audio output thread");
+    }
+
+    /// Set the volume of the sound associated with the given handle.
+    pub fn set_volume(&self, handle: i32, volume: f32, channels: &Channels) {
+        let sound_id = match self.sounds.get(&handle) {
+            None => return,
+            Some(&id) => id,
+        };
+        channels
+            .audio_output
+            .send(move |audio| {
+                audio.update_sound(&sound_id, |sound| sound.volume = volume);
+            })
+            .expect("failed to send interactive sound volume to audio output thread");
+    }
+
+    /// Stop the sound associated with the given handle.
+    pub fn stop(&mut self, handle: i32, channels: &Channels) {
+        let sound_id = match self.sounds.remove(&handle) {
+            None => return,
+            Some(id) => id,
+        };
+        channels
+            .audio_output
+            .send(move |audio| {
+                audio.remove_sound(sound_id);
+            })
+            .expect("failed to remove interactive sound from audio output thread");
+    }
+
+    /// Forget the handle of the given sound once it has ended.
+    pub fn sound_ended(&mut self, sound_id: audio::sound::Id) {
+        self.sounds.retain(|_, &mut id| id != sound_id);
+    }
+}
+
+// The centroid of the speakers assigned to the installation with the given name.
+fn installation_centroid(project: &Project, installation: &str) -> Option<Point2<Metres>> {
+    let id = project
+        .state
+        .installations
+        .iter()
+        .find(|&(_, ref inst)| &inst.name[..] == installation)
+        .map(|(&id, _)| id)?;
+    let points = project
+        .state
+        .speakers
+        .values()
+        .filter(|speaker| speaker.audio.installations.contains(&id))
+        .map(|speaker| pt2(speaker.audio.point.x.0, speaker.audio.point.y.0));
+    nannou::geom::centroid(points).map(|p| pt2(Metres(p.x), Metres(p.y)))
+}
@@ -1,6 +1,8 @@
-//! A "Master" side-bar widget providing control over master volume and input latency.
+//! A "Master" side-bar widget providing control over master volume, input latency and the
+//! handling of scribble streams.
 
-use gui::{collapsible_area, Gui};
+use audio::latency;
+use gui::{collapsible_area, Gui, DARK_A};
 use gui::{ITEM_HEIGHT, SMALL_FONT_SIZE};
 use project::{self, Project};
 use nannou::ui;
@@ -8,6 +10,19 @@ use nannou::ui::prelude::*;
 use time_calc::Ms;
 use metres::Metres;
 
+/// State related to the measurement of the realtime source latency.
+#[derive(Default)]
+pub struct LatencyMeasurement {
+    /// The output channel on which clicks are emitted.
+    pub output_channel: usize,
+    /// The input channel on which clicks are detected.
+    pub input_channel: usize,
+    /// The most recent measurement, if any.
+    pub measurement: Option<latency::Measurement>,
+    /// The status of the most recent measurement.
+    pub status: Option<latency::Status>,
+}
+
 pub fn set(last_area_id: widget::Id, gui: &mut Gui, project: &mut Project) -> widget::Id {
     let Gui {
         ref mut ui,
@@ -31,7 +46,44 @@ pub fn set(last_area_id: widget::Id, gui: &mut Gui, project: &mut Project) -> wi
     const LATENCY_H: Scalar = ITEM_HEIGHT;
     const DECIBEL_H: Scalar = ITEM_HEIGHT;
     const PROXIMITY_H: Scalar = ITEM_HEIGHT;
-    const MASTER_H: Scalar = PAD + MASTER_VOLUME_H + PAD + LATENCY_H + PAD + DECIBEL_H + PAD + PROXIMITY_H + PAD;
+    const SCRIBBLE_TIMEOUT_H: Scalar = ITEM_HEIGHT;
+    const SCRIBBLE_RELEASE_H: Scalar = ITEM_HEIGHT;
+    const LATENCY_MEASUREMENT_H: Scalar = ITEM_HEIGHT * 3.0 + PAD * 2.0;
+    const MASTER_H: Scalar = PAD + MASTER_VOLUME_H + PAD + LATENCY_H + PAD + LATENCY_MEASUREMENT_H
+        + PAD + DECIBEL_H + PAD + PROXIMITY_H + PAD + SCRIBBLE_TIMEOUT_H + PAD
+        + SCRIBBLE_RELEASE_H + PAD;
+
+    // Progress any latency measurement, even while the area is closed.
+    let latency_state = &mut state.latency_measurement;
+    let is_measuring = match latency_state.status {
+        Some(latency::Status::Measuring { .. }) => true,
+        _ => false,
+    };
+    if is_measuring {
+        if let Some(ref mut measurement) = latency_state.measurement {
+            let status = measurement.update();
+            latency_state.status = Some(status);
+            let is_complete = match status {
+                latency::Status::Measuring { .. } => false,
+                _ => true,
+            };
+            if is_complete {
+                // Remove the emitter and detector from the audio threads.
+                channels
+                    .audio_output
+                    .send(move |audio| {
+                        audio.latency_emitter = None;
+                    })
+                    .expect("failed to remove latency emitter from audio output thread");
+                channels
+                    .audio_input
+                    .send(move |audio| {
+                        audio.latency_detector = None;
+                    })
+                    .expect("failed to remove latency detector from audio input thread");
+            }
+        }
+    }
 
     // The collapsible area widget.
     let is_open = state.is_open.master;
@@ -132,6 +184,155 @@ pub fn set(last_area_id: widget::Id, gui: &mut Gui, project: &mut Project) -> wi
             .expect("failed to send updated realtime source latency volume to soundscape thread");
     }
 
+    // Selection of the output and input channels used to measure the realtime source latency.
+    let latency_state = &mut state.latency_measurement;
+    let half_w = ui.kid_area_of(area.id).unwrap().w() / 2.0 - PAD / 2.0;
+    let output_channels = state.audio_channels.output;
+    let input_channels = state.audio_channels.input;
+    let output_labels: Vec<String> = (0..output_channels)
+        .map(|ch| format!("Out CH {}", ch + 1))
+        .collect();
+    let input_labels: Vec<String> = (0..input_channels)
+        .map(|ch| format!("In CH {}", ch + 1))
+        .collect();
+    let selected_output = match latency_state.output_channel < output_channels {
+        true => Some(latency_state.output_channel),
+        false => None,
+    };
+    let selected_input = match latency_state.input_channel < input_channels {
+        true => Some(latency_state.input_channel),
+        false => None,
+    };
+    for new_index in widget::DropDownList::new(&output_labels, selected_output)
+        .down(PAD)
+        .align_left_of(area.id)
+        .w(half_w)
+        .h(ITEM_HEIGHT)
+        .scrollbar_on_top()
+        .max_visible_items(5)
+        .color(DARK_A)
+        .border_color(ui::color::LIGHT_CHARCOAL)
+        .label_font_size(SMALL_FONT_SIZE)
+        .set(ids.master_latency_output_channel, ui)
+    {
+        latency_state.output_channel = new_index;
+    }
+    for new_index in widget::DropDownList::new(&input_labels, selected_input)
+        .right(PAD)
+        .align_top_of(ids.master_latency_output_channel)
+        .w(half_w)
+        .h(ITEM_HEIGHT)
+        .scrollbar_on_top()
+        .max_visible_items(5)
+        .color(DARK_A)
+        .border_color(ui::color::LIGHT_CHARCOAL)
+        .label_font_size(SMALL_FONT_SIZE)
+        .set(ids.master_latency_input_channel, ui)
+    {
+        latency_state.input_channel = new_index;
+    }
+
+    // The button for beginning a measurement.
+    let (measure_label, is_measuring) = match latency_state.status {
+        Some(latency::Status::Measuring { clicks_emitted, .. }) => {
+            (format!("Click {} of {}", clicks_emitted, latency::NUM_CLICKS), true)
+        },
+        _ => ("Measure Latency".to_string(), false),
+    };
+    for _click in widget::Button::new()
+        .label(&measure_label)
+        .label_font_size(SMALL_FONT_SIZE)
+        .color(DARK_A)
+        .down_from(ids.master_latency_output_channel, PAD)
+        .w(half_w)
+        .h(ITEM_HEIGHT)
+        .set(ids.master_latency_measure, ui)
+    {
+        if is_measuring || selected_output.is_none() || selected_input.is_none() {
+            continue;
+        }
+        let output_channel = latency_state.output_channel;
+        let input_channel = latency_state.input_channel;
+        let (measurement, emitter, detector) =
+            latency::Measurement::new(output_channel, input_channel);
+        latency_state.measurement = Some(measurement);
+        latency_state.status = Some(latency::Status::Measuring {
+            clicks_emitted: 0,
+            clicks_detected: 0,
+        });
+
+        // Send the detector first so that it is ready before the first click is emitted.
+        channels
+            .audio_input
+            .send(move |audio| {
+                audio.latency_detector = Some(detector);
+            })
+            .expect("failed to send latency detector to audio input thread");
+        channels
+            .audio_output
+            .send(move |audio| {
+                audio.latency_emitter = Some(emitter);
+            })
+            .expect("failed to send latency emitter to audio output thread");
+    }
+
+    // The button for applying the measured latency to the realtime source latency.
+    let measured = match latency_state.status {
+        Some(latency::Status::Complete(latency)) => Some(latency),
+        _ => None,
+    };
+    let apply_label = match measured {
+        Some(latency) => format!("Apply {:.2} ms", latency.safe.ms()),
+        None => "Apply".to_string(),
+    };
+    for _click in widget::Button::new()
+        .label(&apply_label)
+        .label_font_size(SMALL_FONT_SIZE)
+        .color(DARK_A)
+        .right(PAD)
+        .align_top_of(ids.master_latency_measure)
+        .w(half_w)
+        .h(ITEM_HEIGHT)
+        .set(ids.master_latency_apply, ui)
+    {
+        let new_latency = match measured {
+            Some(latency) => latency.safe,
+            None => continue,
+        };
+
+        // Update the local copy.
+        master.realtime_source_latency = new_latency;
+
+        // Update the soundscape copy.
+        channels
+            .soundscape
+            .send(move |soundscape| {
+                soundscape.realtime_source_latency = new_latency;
+            })
+            .expect("failed to send updated realtime source latency volume to soundscape thread");
+    }
+
+    // A description of the status of the measurement.
+    let status_text = match latency_state.status {
+        None => "Route an output channel to an input channel to measure.".to_string(),
+        Some(latency::Status::Measuring { clicks_emitted, clicks_detected }) => {
+            format!("Detected {} of {} clicks...", clicks_detected, clicks_emitted)
+        },
+        Some(latency::Status::Complete(latency)) => format!(
+            "Round trip: {:.2} ms ({} of {} clicks detected)",
+            latency.round_trip.ms(),
+            latency.clicks_detected,
+            latency::NUM_CLICKS,
+        ),
+        Some(latency::Status::Failed) => "No clicks detected - check the routing.".to_string(),
+    };
+    let status_text_y_offset = PAD + (ITEM_HEIGHT - SMALL_FONT_SIZE as Scalar) / 2.0;
+    widget::Text::new(&status_text)
+        .font_size(SMALL_FONT_SIZE)
+        .down_from(ids.master_latency_measure, status_text_y_offset)
+        .align_left_of(ids.master_latency_measure)
+        .set(ids.master_latency_status, ui);
+
     // The dbap slider.
     let label = format!("DBAP Rolloff: {:.2} db", master.dbap_rolloff_db);
     let max_rolloff = 6.0;
@@ -141,7 +342,7 @@ pub fn set(last_area_id: widget::Id, gui: &mut Gui, project: &mut Project) -> wi
         .h(DECIBEL_H)
         .kid_area_w_of(area.id)
         .align_middle_x_of(area.id)
-        .down(PAD)
+        .down_from(ids.master_latency_measure, PAD + ITEM_HEIGHT + PAD)
         .set(ids.master_dbap_rolloff, ui)
     {
         // Update the local rolloff.
@@ -181,6 +382,37 @@ pub fn set(last_area_id: widget::Id, gui: &mut Gui, project: &mut Project) -> wi
             .expect("failed to send updated proximity limit to audio output thread");
         }
 
+    // The duration after which a scribble stream that stops updating is ended.
+    let label = format!("Scribble Timeout: {:.2} secs", master.scribble_timeout.ms() / 1_000.0);
+    let max_timeout_ms = 10_000.0;
+    let ms = master.scribble_timeout.ms();
+    for new_timeout in widget::Slider::new(ms, 100.0, max_timeout_ms)
+        .label(&label)
+        .label_font_size(SMALL_FONT_SIZE)
+        .h(SCRIBBLE_TIMEOUT_H)
+        .kid_area_w_of(area.id)
+        .align_middle_x_of(area.id)
+        .down(PAD)
+        .set(ids.master_scribble_timeout, ui)
+    {
+        master.scribble_timeout = Ms(new_timeout);
+    }
+
+    // The duration over which the sound of an ended scribble stream fades out.
+    let label = format!("Scribble Release: {:.2} ms", master.scribble_release.ms());
+    let max_release_ms = 2_000.0;
+    let ms = master.scribble_release.ms();
+    for new_release in widget::Slider::new(ms, 0.0, max_release_ms)
+        .label(&label)
+        .label_font_size(SMALL_FONT_SIZE)
+        .h(SCRIBBLE_RELEASE_H)
+        .kid_area_w_of(area.id)
+        .align_middle_x_of(area.id)
+        .down(PAD)
+        .set(ids.master_scribble_release, ui)
+    {
+        master.scribble_release = Ms(new_release);
+    }
 
     area.id
 }
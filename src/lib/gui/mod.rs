@@ -11,7 +11,7 @@ use osc;
 use osc::input::Log as OscInputLog;
 use osc::output::Log as OscOutputLog;
 use project::{self, Project};
-use soundscape::Soundscape;
+use soundscape::{self, Soundscape};
 use slug::slugify;
 use std::collections::VecDeque;
 use std::path::{Path, PathBuf};
@@ -21,20 +21,35 @@ use std::sync::atomic::AtomicUsize;
 use time_calc::Ms;
 use utils::{self, HumanReadableTime, SEC_MS, MIN_MS, HR_MS};
 
+use self::ducking_editor::DuckingEditor;
+use self::input_editor::InputEditor;
 use self::installation_editor::InstallationEditor;
+use self::interactive::Interactive;
+use self::master::LatencyMeasurement;
+use self::play_history::PlayHistory;
 use self::project_editor::ProjectEditor;
+use self::rule_editor::RuleEditor;
+use self::scene_editor::SceneEditor;
+use self::scribbles::Scribbles;
 use self::soundscape_editor::SoundscapeEditor;
 use self::source_editor::{SourceEditor, SourcePreviewMode};
 use self::speaker_editor::SpeakerEditor;
 
 mod custom_widget;
+pub mod ducking_editor;
+pub mod input_editor;
 pub mod installation_editor;
+pub mod interactive;
 pub mod control_log;
 pub mod master;
 pub mod monitor;
 pub mod osc_in_log;
 pub mod osc_out_log;
+pub mod play_history;
 pub mod project_editor;
+pub mod rule_editor;
+pub mod scene_editor;
+pub mod scribbles;
 pub mod source_editor;
 pub mod soundscape_editor;
 pub mod speaker_editor;
@@ -84,8 +99,18 @@ pub struct Gui<'a> {
 /// GUI state related to a single project.
 #[derive(Default)]
 pub struct ProjectState {
+    /// Runtime state related to the ducking editor GUI panel.
+    ducking_editor: DuckingEditor,
+    /// Runtime state related to the input editor GUI panel.
+    input_editor: InputEditor,
     /// Runtime state related to the installation editor GUI panel.
     installation_editor: InstallationEditor,
+    /// Runtime state related to the play history GUI panel.
+    play_history: PlayHistory,
+    /// Runtime state related to the rule editor GUI panel.
+    rule_editor: RuleEditor,
+    /// Runtime state related to the scene editor GUI panel.
+    scene_editor: SceneEditor,
     /// Runtime state related to the source editor GUI panel.
     soundscape_editor: SoundscapeEditor,
     /// Runtime state related to the speaker editor GUI panel.
@@ -106,6 +131,12 @@ pub struct State {
     control_log: ControlLog,
     /// State related to the project editor.
     project_editor: ProjectEditor,
+    /// State related to the measurement of the realtime source latency.
+    latency_measurement: LatencyMeasurement,
+    /// Sounds spawned via the OSC interactive API.
+    interactive: Interactive,
+    /// The position streams driving the sounds of scribbles sources.
+    scribbles: Scribbles,
     /// Whether or not each of the collapsible areas are open within the sidebar.
     is_open: IsOpen,
 }
@@ -114,10 +145,15 @@ pub struct State {
 struct IsOpen {
     project_editor: bool,
     master: bool,
+    input_editor: bool,
     installation_editor: bool,
     soundscape_editor: bool,
     speaker_editor: bool,
     source_editor: bool,
+    ducking_editor: bool,
+    rule_editor: bool,
+    scene_editor: bool,
+    play_history: bool,
     side_menu: bool,
     osc_in_log: bool,
     osc_out_log: bool,
@@ -139,9 +175,11 @@ pub struct Channels {
     pub control_rx: mpsc::Receiver<osc::input::Control>,
     pub soundscape: Soundscape,
     pub wav_reader: audio::source::wav::reader::Handle,
+    pub wav_analysis: audio::source::wav::analysis::Handle,
     pub audio_input: audio::input::Stream,
     pub audio_output: audio::output::Stream,
     pub audio_monitor_msg_rx: monitor::Receiver,
+    pub history_rx: mpsc::Receiver<soundscape::history::Entry>,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -173,6 +211,8 @@ struct AudioMonitor {
     master_peak: f32,
     pub active_sounds: ActiveSoundMap,
     speakers: FxHashMap<audio::speaker::Id, ChannelLevels>,
+    // The levels of each audio input channel, indexed by channel.
+    inputs: Vec<ChannelLevels>,
 }
 
 impl AudioMonitor {
@@ -181,6 +221,7 @@ impl AudioMonitor {
         self.master_peak = 0.0;
         self.active_sounds.clear();
         self.speakers.clear();
+        self.inputs.clear();
     }
 
     /// Clears all invalid sounds and speakers from the monitor.
@@ -213,6 +254,7 @@ struct ChannelLevels {
 /// A message sent from the audio thread with some audio levels.
 pub enum AudioMonitorMessage {
     Master { peak: f32 },
+    Input { channel: usize, peak: f32, rms: f32 },
     ActiveSound(audio::sound::Id, ActiveSoundMessage),
     Speaker(audio::speaker::Id, SpeakerMessage),
 }
@@ -255,10 +297,15 @@ impl Default for IsOpen {
             side_menu: true,
             project_editor: false,
             master: false,
+            input_editor: false,
             installation_editor: false,
             soundscape_editor: false,
             speaker_editor: false,
             source_editor: false,
+            ducking_editor: false,
+            rule_editor: false,
+            scene_editor: false,
+            play_history: false,
             osc_in_log: false,
             osc_out_log: false,
             control_log: false,
@@ -394,6 +441,57 @@ impl Model {
             state.osc_out_log.push_msg(log);
         }
 
+        // Collect the results of any completed WAV analysis.
+        while let Some(output) = channels.wav_analysis.try_recv() {
+            match output {
+                audio::source::wav::analysis::Output::Loudness(path, loudness) => {
+                    let project = match *project {
+                        None => continue,
+                        Some((ref mut proj, _)) => proj,
+                    };
+
+                    for (&id, source) in project.state.sources.iter_mut() {
+                        // Update local copy.
+                        match source.audio.kind {
+                            audio::source::Kind::Wav(ref mut wav) if wav.path == path => {
+                                wav.loudness = Some(loudness);
+                            },
+                            _ => continue,
+                        }
+                        let gain = source.audio.normalisation_gain();
+
+                        // Update the soundscape copy.
+                        channels
+                            .soundscape
+                            .send(move |soundscape| {
+                                soundscape.update_source(&id, |source| {
+                                    if let audio::source::Kind::Wav(ref mut wav) = source.kind {
+                                        wav.loudness = Some(loudness);
+                                    }
+                                });
+                            })
+                            .expect("failed to send measured loudness to soundscape thread");
+
+                        // Update the audio output copies.
+                        channels
+                            .audio_output
+                            .send(move |audio| {
+                                audio.update_sounds_with_source(&id, move |_, sound| {
+                                    sound.normalisation_gain = gain;
+                                });
+                            })
+                            .expect("failed to send normalisation gain to audio output thread");
+                    }
+                },
+
+                audio::source::wav::analysis::Output::Overview(path, overview) => {
+                    if let Some((_, ref mut project_state)) = *project {
+                        project_state.source_editor.wav_overviews.insert(path, Some(overview));
+                    }
+                },
+            }
+        }
+
         // Handle control messages.
         for control in channels.control_rx.try_iter() {
             match &control {
@@ -451,6 +549,36 @@ impl Model {
                         .expect("failed to send updated source volume to audio output thread");
                 }
 
+                &osc::input::Control::SourcePlaybackRate(ref source_playback_rate) => {
+                    let osc::input::SourcePlaybackRate { ref name, rate } = *source_playback_rate;
+
+                    let project = match *project {
+                        None => continue,
+                        Some((ref proj, _)) => proj,
+                    };
+
+                    let id = match project
+                        .state
+                        .sources
+                        .iter()
+                        .find(|&(_, ref s)| &s.name[..] == name)
+                    {
+                        None => continue,
+                        Some((&id, _)) => id,
+                    };
+
+                    // Update the currently playing sounds on the audio output thread.
+                    let rate = rate as f64;
+                    channels
+                        .audio_output
+                        .send(move |audio| {
+                            audio.update_sounds_with_source(&id, move |_, sound| {
+                                sound.signal.kind.set_playback_rate(rate);
+                            });
+                        })
+                        .expect("failed to send updated source playback rate to audio output thread");
+                }
+
                 &osc::input::Control::PlaySoundscape => {
                     channels
                         .soundscape
@@ -464,12 +592,103 @@ impl Model {
                         .pause()
                         .expect("failed to send `Pause` message to soundscape thread");
                 }
+
+                &osc::input::Control::InteractivePlay(ref interactive_play) => {
+                    if let Some((ref project, _)) = *project {
+                        state.interactive.play(interactive_play, project, channels, sound_id_gen);
+                    }
+                }
+
+                &osc::input::Control::InteractivePosition(ref interactive_position) => {
+                    let osc::input::InteractivePosition { handle, point } = *interactive_position;
+                    state.interactive.set_position(handle, point, channels);
+                }
+
+                &osc::input::Control::InteractiveVolume(ref interactive_volume) => {
+                    let osc::input::InteractiveVolume { handle, volume } = *interactive_volume;
+                    state.interactive.set_volume(handle, volume, channels);
+                }
+
+                &osc::input::Control::InteractiveStop(osc::input::InteractiveStop { handle }) => {
+                    state.interactive.stop(handle, channels);
+                }
+
+                &osc::input::Control::ScribblePosition(ref scribble_position) => {
+                    if let Some((ref project, _)) = *project {
+                        state.scribbles.update_position(
+                            scribble_position,
+                            project,
+                            channels,
+                            sound_id_gen,
+                        );
+                    }
+                }
+
+                &osc::input::Control::ScribbleEnd(ref scribble_end) => {
+                    if let Some((ref project, _)) = *project {
+                        state.scribbles.end(scribble_end, project, channels);
+                    }
+                }
+
+                &osc::input::Control::RecallScene(ref recall_scene) => {
+                    let osc::input::RecallScene { ref name, crossfade } = *recall_scene;
+
+                    let project = match *project {
+                        None => continue,
+                        Some((ref mut proj, _)) => proj,
+                    };
+
+                    let scene = match project.scenes.iter().find(|s| &s.name == name) {
+                        None => continue,
+                        Some(scene) => scene.clone(),
+                    };
+                    let crossfade = crossfade.unwrap_or(scene.crossfade);
+                    scene.recall(crossfade, &mut project.state, channels);
+                }
+
+                &osc::input::Control::Occupancy(ref occupancy) => {
+                    let osc::input::Occupancy { ref name, value } = *occupancy;
+
+                    let project = match *project {
+                        None => continue,
+                        Some((ref proj, _)) => proj,
+                    };
+
+                    let id = match project.installations.iter().find(|&(_, i)| &i.name == name) {
+                        None => continue,
+                        Some((&id, _)) => id,
+                    };
+                    channels
+                        .soundscape
+                        .send(move |soundscape| {
+                            soundscape.set_occupancy(&id, value as f64);
+                        })
+                        .expect("failed to send installation occupancy to soundscape thread");
+                }
             }
 
             // Log the message.
             state.control_log.push_msg(control);
         }
 
+        // Record the sounds played and groups starved by the soundscape in the project's history.
+        for entry in channels.history_rx.try_iter() {
+            let (project, project_state) = match *project {
+                None => continue,
+                Some((ref project, ref mut project_state)) => (project, project_state),
+            };
+            let directory = project::project_directory_path(assets, &project.name);
+            let play_history = &mut project_state.play_history;
+            if let Err(err) = play_history.history.record(&directory, &entry) {
+                play_history.error = Some(format!("Failed to record the play history: {}", err));
+            }
+        }
+
+        // Fade out the sounds of any scribbles whose positions have stopped arriving.
+        if let Some((ref project, _)) = *project {
+            state.scribbles.remove_stale(&project.master, channels);
+        }
+
         // Update the map of active sounds.
         loop {
             let msg = match channels.audio_monitor_msg_rx.try_pop() {
@@ -481,6 +700,12 @@ impl Model {
                 AudioMonitorMessage::Master { peak } => {
                     audio_monitor.master_peak = peak;
                 },
+                AudioMonitorMessage::Input { channel, peak, rms } => {
+                    while audio_monitor.inputs.len() <= channel {
+                        audio_monitor.inputs.push(ChannelLevels::default());
+                    }
+                    audio_monitor.inputs[channel] = ChannelLevels { rms, peak };
+                },
                 AudioMonitorMessage::ActiveSound(id, msg) => match msg {
                     ActiveSoundMessage::Start {
                         source_id,
@@ -520,6 +745,8 @@ impl Model {
                     }
                     ActiveSoundMessage::End { sound: _sound } => {
                         audio_monitor.active_sounds.remove(&id);
+                        state.interactive.sound_ended(id);
+                        state.scribbles.sound_ended(id);
 
                         // If the Id of the sound being removed matches the current preview, remove
                         // it.
@@ -635,12 +862,18 @@ impl State {
         let control_log = Log::with_limit(config.control_log_limit);
         let is_open = Default::default();
         let project_editor = ProjectEditor::default();
+        let latency_measurement = LatencyMeasurement::default();
+        let interactive = Interactive::default();
+        let scribbles = Scribbles::default();
         State {
             osc_in_log,
             osc_out_log,
             control_log,
             audio_channels,
             project_editor,
+            latency_measurement,
+            interactive,
+            scribbles,
             is_open,
         }
     }
@@ -656,9 +889,11 @@ impl Channels {
         control_rx: mpsc::Receiver<osc::input::Control>,
         soundscape: Soundscape,
         wav_reader: audio::source::wav::reader::Handle,
+        wav_analysis: audio::source::wav::analysis::Handle,
         audio_input: audio::input::Stream,
         audio_output: audio::output::Stream,
         audio_monitor_msg_rx: monitor::Receiver,
+        history_rx: mpsc::Receiver<soundscape::history::Entry>,
     ) -> Self {
         Channels {
             frame_count,
@@ -668,9 +903,11 @@ impl Channels {
             control_rx,
             soundscape,
             wav_reader,
+            wav_analysis,
             audio_input,
             audio_output,
             audio_monitor_msg_rx,
+            history_rx,
         }
     }
 }
@@ -860,6 +1097,75 @@ widget_ids! {
         master_realtime_source_latency,
         master_dbap_rolloff,
         master_proximity_limit,
+        master_scribble_timeout,
+        master_scribble_release,
+        master_latency_output_channel,
+        master_latency_input_channel,
+        master_latency_measure,
+        master_latency_apply,
+        master_latency_status,
+        // Ducking editor.
+        ducking_editor,
+        ducking_editor_rule_canvas,
+        ducking_editor_rule_text,
+        ducking_editor_rule_add,
+        ducking_editor_rule_none,
+        ducking_editor_rule_list,
+        ducking_editor_rule_remove,
+        ducking_editor_selected_canvas,
+        ducking_editor_selected_text,
+        ducking_editor_selected_trigger_kind,
+        ducking_editor_selected_trigger,
+        ducking_editor_selected_installation,
+        ducking_editor_selected_depth,
+        ducking_editor_selected_threshold,
+        ducking_editor_selected_attack,
+        ducking_editor_selected_release,
+        // Rule editor.
+        rule_editor,
+        rule_editor_rule_canvas,
+        rule_editor_rule_text,
+        rule_editor_rule_add,
+        rule_editor_rule_none,
+        rule_editor_rule_list,
+        rule_editor_rule_remove,
+        rule_editor_selected_canvas,
+        rule_editor_selected_text,
+        rule_editor_selected_kind,
+        rule_editor_selected_first_source,
+        rule_editor_selected_second_source,
+        rule_editor_selected_duration,
+        // Scene editor.
+        scene_editor,
+        scene_editor_scene_canvas,
+        scene_editor_scene_text,
+        scene_editor_scene_add,
+        scene_editor_scene_none,
+        scene_editor_scene_list,
+        scene_editor_scene_remove,
+        scene_editor_selected_canvas,
+        scene_editor_selected_text,
+        scene_editor_selected_name,
+        scene_editor_selected_crossfade,
+        scene_editor_selected_recall,
+        scene_editor_selected_update,
+        // Play history.
+        play_history,
+        play_history_text,
+        play_history_scrollbar_y,
+        play_history_scrollbar_x,
+        // Input editor.
+        input_editor,
+        input_editor_meters,
+        input_editor_none,
+        input_editor_channel,
+        input_editor_trim,
+        input_editor_gate_toggle,
+        input_editor_gate_threshold,
+        input_editor_compressor_toggle,
+        input_editor_compressor_threshold,
+        input_editor_compressor_ratio,
+        input_editor_compressor_makeup,
         // OSC input log.
         osc_in_log,
         osc_in_log_text,
@@ -894,6 +1200,11 @@ widget_ids! {
         installation_editor_soundscape_canvas,
         installation_editor_soundscape_text,
         installation_editor_soundscape_simultaneous_sounds_slider,
+        installation_editor_soundscape_occupancy_text,
+        installation_editor_soundscape_occupancy_max_slider,
+        installation_editor_soundscape_occupancy_influence_slider,
+        installation_editor_soundscape_occupancy_smoothing_slider,
+        installation_editor_soundscape_occupancy_timeout_slider,
         // Speaker Editor.
         speaker_editor,
         speaker_editor_no_speakers,
@@ -913,6 +1224,7 @@ widget_ids! {
         // Audio Sources.
         soundscape_editor,
         soundscape_editor_is_playing,
+        soundscape_editor_simulate,
         soundscape_editor_group_canvas,
         soundscape_editor_group_text,
         soundscape_editor_group_add,
@@ -926,6 +1238,11 @@ widget_ids! {
         soundscape_editor_occurrence_rate_slider,
         soundscape_editor_simultaneous_sounds_text,
         soundscape_editor_simultaneous_sounds_slider,
+        soundscape_editor_markov_text,
+        soundscape_editor_markov_enabled,
+        soundscape_editor_markov_from,
+        soundscape_editor_markov_to,
+        soundscape_editor_markov_weight,
         // Audio Sources.
         source_editor,
         source_editor_no_sources,
@@ -952,8 +1269,17 @@ widget_ids! {
         source_editor_selected_soundscape_playback_duration_slider,
         source_editor_selected_soundscape_attack_duration_text,
         source_editor_selected_soundscape_attack_duration_slider,
+        source_editor_selected_soundscape_hold_duration_text,
+        source_editor_selected_soundscape_hold_duration_slider,
         source_editor_selected_soundscape_release_duration_text,
         source_editor_selected_soundscape_release_duration_slider,
+        source_editor_selected_soundscape_fade_curves_text,
+        source_editor_selected_soundscape_attack_curve,
+        source_editor_selected_soundscape_release_curve,
+        source_editor_selected_soundscape_playback_rate_text,
+        source_editor_selected_soundscape_playback_rate_slider,
+        source_editor_selected_soundscape_weight_text,
+        source_editor_selected_soundscape_weight_slider,
         source_editor_selected_soundscape_groups_text,
         source_editor_selected_soundscape_groups_list,
         source_editor_selected_soundscape_movement_text,
@@ -966,6 +1292,9 @@ widget_ids! {
         source_editor_selected_soundscape_movement_agent_max_force_slider,
         source_editor_selected_soundscape_movement_agent_max_rotation_text,
         source_editor_selected_soundscape_movement_agent_max_rotation_slider,
+        source_editor_selected_soundscape_movement_agent_placement_text,
+        source_editor_selected_soundscape_movement_agent_placement_list,
+        source_editor_selected_soundscape_movement_agent_placement_speaker,
         source_editor_selected_soundscape_movement_agent_directional,
         source_editor_selected_soundscape_movement_ngon_speed_text,
         source_editor_selected_soundscape_movement_ngon_speed_slider,
@@ -981,9 +1310,28 @@ widget_ids! {
         source_editor_selected_wav_canvas,
         source_editor_selected_wav_text,
         source_editor_selected_wav_data,
+        source_editor_selected_wav_overview,
+        source_editor_selected_wav_overview_text,
+        source_editor_selected_wav_overview_peaks,
+        source_editor_selected_wav_overview_rms,
+        source_editor_selected_wav_overview_playheads,
         source_editor_selected_wav_loop_toggle,
+        source_editor_selected_wav_normalise_toggle,
+        source_editor_selected_wav_normalise_target,
         source_editor_selected_wav_playback_text,
         source_editor_selected_wav_playback_list,
+        source_editor_selected_wav_granular_text,
+        source_editor_selected_wav_granular_grain_size,
+        source_editor_selected_wav_granular_density,
+        source_editor_selected_wav_granular_position_jitter,
+        source_editor_selected_wav_granular_pitch_jitter,
+        source_editor_selected_wav_start_offset_text,
+        source_editor_selected_wav_start_offset_list,
+        source_editor_selected_wav_start_offset_slider,
+        source_editor_selected_wav_start_offset_cue_text,
+        source_editor_selected_wav_loop_text,
+        source_editor_selected_wav_loop_points,
+        source_editor_selected_wav_loop_crossfade,
         source_editor_selected_realtime_canvas,
         source_editor_selected_realtime_text,
         source_editor_selected_realtime_duration,
@@ -1108,6 +1456,9 @@ fn set_side_menu_widgets(
         // Installation Editor - for editing installation-specific data.
         last_area_id = master::set(last_area_id, gui, project);
 
+        // Input Editor - for metering and conditioning the audio input channels.
+        last_area_id = input_editor::set(last_area_id, gui, project, project_state);
+
         // Installation Editor - for editing installation-specific data.
         last_area_id = installation_editor::set(last_area_id, gui, project, project_state);
 
@@ -1120,6 +1471,18 @@ fn set_side_menu_widgets(
         // For adding, changing and removing audio sources.
         last_area_id = source_editor::set(last_area_id, gui, project, project_state);
 
+        // Ducking Editor - for adding, editing and removing ducking rules.
+        last_area_id = ducking_editor::set(last_area_id, gui, project, project_state);
+
+        // Rule Editor - for adding, editing and removing rules between soundscape sources.
+        last_area_id = rule_editor::set(last_area_id, gui, project, project_state);
+
+        // Scene Editor - for capturing, recalling and removing scenes.
+        last_area_id = scene_editor::set(last_area_id, gui, project, project_state);
+
+        // Play History - statistics about the sounds played by the soundscape today.
+        last_area_id = play_history::set(last_area_id, gui, project, project_state);
+
         // The log of received controls.
         last_area_id = control_log::set(last_area_id, gui, project);
 
@@ -0,0 +1,134 @@
+//! A "Play History" side-bar widget displaying statistics about the sounds played today.
+
+use chrono;
+use gui::{collapsible_area, info_text, Gui, ProjectState};
+use nannou::ui::prelude::*;
+use project::{self, Project};
+use soundscape::history::{self, History};
+use std::time;
+
+/// GUI state related to the play history area.
+#[derive(Default)]
+pub struct PlayHistory {
+    /// The rolling on-disk history of the project.
+    pub history: History,
+    /// The most recent error encountered while reading or writing the history, if any.
+    pub error: Option<String>,
+}
+
+/// Sets all widgets in the play history area and returns the `Id` of the last area.
+pub fn set(
+    last_area_id: widget::Id,
+    gui: &mut Gui,
+    project: &Project,
+    project_state: &mut ProjectState,
+) -> widget::Id {
+    let is_open = gui.state.is_open.play_history;
+    let canvas_h = 300.0;
+    let (area, event) = collapsible_area(is_open, "Play History", gui.ids.side_menu)
+        .align_middle_x_of(gui.ids.side_menu)
+        .down_from(last_area_id, 0.0)
+        .set(gui.ids.play_history, gui);
+    if let Some(event) = event {
+        gui.state.is_open.play_history = event.is_open();
+    }
+
+    let area = match area {
+        Some(area) => area,
+        None => return gui.ids.play_history,
+    };
+
+    // The canvas on which the statistics will be placed.
+    let canvas = widget::Canvas::new()
+        .scroll_kids()
+        .pad(10.0)
+        .h(canvas_h);
+    area.set(canvas, gui);
+
+    // Ensure the history is open for the current day.
+    let play_history = &mut project_state.play_history;
+    let directory = project::project_directory_path(gui.assets, &project.name);
+    let today = chrono::Local::now().naive_local().date();
+    if let Err(err) = play_history.history.open(&directory, today) {
+        play_history.error = Some(format!("Failed to open the play history: {}", err));
+    }
+
+    // The text widget used to display the statistics.
+    let text = match play_history.history.today() {
+        Some((date, stats)) => format_stats(project, date, stats, &play_history.error),
+        None => play_history.error.clone().unwrap_or_default(),
+    };
+    info_text(&text)
+        .top_left_of(area.id)
+        .kid_area_w_of(area.id)
+        .set(gui.ids.play_history_text, gui);
+
+    // Scrollbars.
+    widget::Scrollbar::y_axis(area.id)
+        .color(color::LIGHT_CHARCOAL)
+        .auto_hide(false)
+        .set(gui.ids.play_history_scrollbar_y, gui);
+    widget::Scrollbar::x_axis(area.id)
+        .color(color::LIGHT_CHARCOAL)
+        .auto_hide(true)
+        .set(gui.ids.play_history_scrollbar_x, gui);
+
+    area.id
+}
+
+// Describe the statistics for the given day.
+//
+// Every source and group within the project is listed, including those that never played or
+// were never starved, so that their absence is easy to spot.
+fn format_stats(
+    project: &Project,
+    date: chrono::NaiveDate,
+    stats: &history::Stats,
+    error: &Option<String>,
+) -> String {
+    let total_plays: usize = stats.sources.values().map(|s| s.plays).sum();
+    let total_air_time = stats
+        .sources
+        .values()
+        .fold(time::Duration::from_secs(0), |total, s| total + s.air_time);
+    let mut text = format!(
+        "{}\n{} plays, {} air time\n",
+        date,
+        total_plays,
+        format_duration(&total_air_time),
+    );
+    if let Some(ref error) = *error {
+        text.push_str(&format!("{}\n", error));
+    }
+
+    // Plays and air time for each source, ordered by name.
+    text.push_str("\nSources:\n");
+    let mut sources: Vec<_> = project.sources.iter().collect();
+    sources.sort_by(|a, b| a.1.name.cmp(&b.1.name));
+    for (id, source) in sources {
+        let source_stats = stats.sources.get(id).cloned().unwrap_or_default();
+        text.push_str(&format!(
+            "  {}: {} plays, {}\n",
+            source.name,
+            source_stats.plays,
+            format_duration(&source_stats.air_time),
+        ));
+    }
+
+    // The number of times each group was starved, ordered by name.
+    text.push_str("\nStarved Groups:\n");
+    let mut groups: Vec<_> = project.soundscape_groups.iter().collect();
+    groups.sort_by(|a, b| a.1.name.cmp(&b.1.name));
+    for (id, group) in groups {
+        let count = stats.starved_groups.get(id).cloned().unwrap_or(0);
+        text.push_str(&format!("  {}: {} times\n", group.name, count));
+    }
+
+    text
+}
+
+// Format the duration as hours, minutes and seconds.
+fn format_duration(duration: &time::Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{}:{:02}:{:02}", secs / 3600, (secs / 60) % 60, secs % 60)
+}
@@ -0,0 +1,460 @@
+//! A "Rule Editor" side-bar widget for creating, editing and removing the rules that relate the
+//! playback of soundscape sources to one another.
+
+use audio;
+use gui::{collapsible_area, Channels, Gui, ProjectState, State, DARK_A};
+use gui::{ITEM_HEIGHT, SMALL_FONT_SIZE};
+use nannou::ui;
+use nannou::ui::prelude::*;
+use project::{self, Project};
+use soundscape::rule::{self, Rule};
+use time_calc::Ms;
+use utils::{HR_MS, MIN_MS, SEC_MS};
+
+/// GUI state related to the rule editor area.
+#[derive(Default)]
+pub struct RuleEditor {
+    /// The index of the selected rule.
+    pub selected: Option<usize>,
+}
+
+/// Labels for each kind of rule.
+const RULE_KINDS: &'static [&'static str] = &["Exclusive", "Follow-On", "No Repeat"];
+
+/// The longest duration selectable within which a follow-on sound must begin.
+const MAX_FOLLOW_ON_MS: f64 = 5.0 * MIN_MS;
+
+/// The longest interval selectable for a no-repeat rule.
+const MAX_NO_REPEAT_MS: f64 = 24.0 * HR_MS;
+
+/// Sets all widgets in the rule area and returns the `Id` of the last area.
+pub fn set(
+    last_area_id: widget::Id,
+    gui: &mut Gui,
+    project: &mut Project,
+    project_state: &mut ProjectState,
+) -> widget::Id {
+    let Gui {
+        ref mut ui,
+        ref ids,
+        channels,
+        state: &mut State {
+            ref mut is_open,
+            ..
+        },
+        ..
+    } = *gui;
+    let Project {
+        state: project::State {
+            ref mut soundscape_rules,
+            ref sources,
+            ..
+        },
+        ..
+    } = *project;
+    let ProjectState {
+        rule_editor: RuleEditor {
+            ref mut selected,
+        },
+        ..
+    } = *project_state;
+
+    // Constants to use as widget heights.
+    const PAD: Scalar = 6.0;
+    const PLUS_RULE_H: Scalar = ITEM_HEIGHT;
+    const RULE_LIST_MAX_H: Scalar = ITEM_HEIGHT * 5.0;
+    const TITLE_H: Scalar = SMALL_FONT_SIZE as Scalar * 1.333;
+    const RULE_CANVAS_H: Scalar = PAD + TITLE_H + PAD + PLUS_RULE_H + RULE_LIST_MAX_H + PAD;
+    const ROW_H: Scalar = ITEM_HEIGHT;
+    const NUM_ROWS: Scalar = 3.0;
+    const SELECTED_CANVAS_H: Scalar = PAD + TITLE_H + PAD + (ROW_H + PAD) * NUM_ROWS;
+    const RULE_EDITOR_H: Scalar = PAD + RULE_CANVAS_H + PAD + SELECTED_CANVAS_H + PAD;
+
+    // The collapsible area.
+    let (area, event) = collapsible_area(is_open.rule_editor, "Rule Editor", ids.side_menu)
+        .align_middle_x_of(ids.side_menu)
+        .down_from(last_area_id, 0.0)
+        .set(ids.rule_editor, ui);
+    if let Some(event) = event {
+        is_open.rule_editor = event.is_open();
+    }
+
+    // If the area is open, get the area.
+    let area = match area {
+        Some(area) => area,
+        None => return ids.rule_editor,
+    };
+
+    // The canvas on which the rule editor will be placed.
+    let canvas = widget::Canvas::new()
+        .pad(PAD)
+        .h(RULE_EDITOR_H);
+    area.set(canvas, ui);
+
+    // Soundscape sources in a consistent order for display.
+    let mut sources_vec: Vec<_> = sources
+        .iter()
+        .filter(|&(_, source)| match source.audio.role {
+            Some(audio::source::Role::Soundscape(_)) => true,
+            _ => false,
+        })
+        .map(|(&id, source)| (id, source.name.clone()))
+        .collect();
+    sources_vec.sort_by(|a, b| a.1.cmp(&b.1));
+
+    // A label for a source.
+    let source_label = |id: audio::source::Id| -> String {
+        match sources.get(&id) {
+            Some(source) => source.name.clone(),
+            None => "<removed source>".to_string(),
+        }
+    };
+
+    // A label describing a rule.
+    let rule_label = |rule: &Rule| -> String {
+        match *rule {
+            Rule::Exclusive { a, b } => {
+                format!("{} never plays with {}", source_label(a), source_label(b))
+            },
+            Rule::FollowOn { after, follow, within } => format!(
+                "{} follows {} within {:.1} secs",
+                source_label(follow),
+                source_label(after),
+                within.ms() / SEC_MS,
+            ),
+            Rule::NoRepeat { source, interval } => format!(
+                "{} never repeats within {:.1} mins",
+                source_label(source),
+                interval.ms() / MIN_MS,
+            ),
+        }
+    };
+
+    ///////////////
+    // RULE LIST //
+    ///////////////
+
+    // A canvas on which rule selection takes place.
+    widget::Canvas::new()
+        .parent(area.id)
+        .kid_area_w_of(area.id)
+        .h(RULE_CANVAS_H)
+        .mid_top_of(area.id)
+        .pad(PAD)
+        .color(color::CHARCOAL)
+        .set(ids.rule_editor_rule_canvas, ui);
+
+    // A title for the rules canvas.
+    widget::Text::new("Rules")
+        .top_left_of(ids.rule_editor_rule_canvas)
+        .font_size(SMALL_FONT_SIZE)
+        .set(ids.rule_editor_rule_text, ui);
+
+    // A button for adding new rules.
+    for _click in widget::Button::new()
+        .label("+")
+        .kid_area_w_of(ids.rule_editor_rule_canvas)
+        .h(PLUS_RULE_H)
+        .align_middle_x_of(ids.rule_editor_rule_canvas)
+        .down(PAD * 2.0)
+        .set(ids.rule_editor_rule_add, ui)
+    {
+        // Rules may only be added once there is a soundscape source to constrain.
+        let source = match sources_vec.first() {
+            Some(&(id, _)) => id,
+            None => continue,
+        };
+        let rule = Rule::NoRepeat {
+            source,
+            interval: rule::default::NO_REPEAT_INTERVAL,
+        };
+        soundscape_rules.push(rule);
+        *selected = Some(soundscape_rules.len() - 1);
+        send_rules(channels, soundscape_rules);
+    }
+
+    // If there are no rules, display some text for adding a rule.
+    if soundscape_rules.is_empty() {
+        widget::Text::new("Add a soundscape rule with the \"+\" button above!")
+            .font_size(SMALL_FONT_SIZE)
+            .align_middle_x_of(ids.rule_editor_rule_canvas)
+            .down(PAD + ITEM_HEIGHT)
+            .set(ids.rule_editor_rule_none, ui);
+        return area.id;
+    }
+
+    // A list of all rules in the order in which they were added.
+    let num_rules = soundscape_rules.len();
+    let (mut events, scrollbar) = widget::ListSelect::single(num_rules)
+        .down(0.0)
+        .flow_down()
+        .item_size(ITEM_HEIGHT)
+        .h(RULE_LIST_MAX_H)
+        .kid_area_w_of(ids.rule_editor_rule_canvas)
+        .scrollbar_next_to()
+        .set(ids.rule_editor_rule_list, ui);
+
+    // Track whether or not an item was removed.
+    let selected_index = *selected;
+    let mut maybe_remove_index = None;
+    while let Some(event) = events.next(ui, |i| Some(i) == selected_index) {
+        use self::ui::widget::list_select::Event;
+        match event {
+            // Instantiate the widget for this item.
+            Event::Item(item) => {
+                let is_selected = selected_index == Some(item.i);
+
+                // Blue if selected, gray otherwise.
+                let color = if is_selected {
+                    color::BLUE
+                } else {
+                    color::DARK_CHARCOAL
+                };
+
+                // Use a button widget for each item.
+                let label = rule_label(&soundscape_rules[item.i]);
+                let label_x = position::Relative::Place(position::Place::Start(Some(10.0)));
+                let button = widget::Button::new()
+                    .label(&label)
+                    .label_font_size(SMALL_FONT_SIZE)
+                    .label_x(label_x)
+                    .color(color);
+                item.set(button, ui);
+
+                // If the button or any of its children are capturing the mouse, display
+                // the `remove` button.
+                let show_remove_button = ui.global_input()
+                    .current
+                    .widget_capturing_mouse
+                    .map(|id| {
+                        id == item.widget_id
+                            || ui.widget_graph()
+                                .does_recursive_depth_edge_exist(item.widget_id, id)
+                    })
+                    .unwrap_or(false);
+
+                if !show_remove_button {
+                    continue;
+                }
+
+                if widget::Button::new()
+                    .label("X")
+                    .label_font_size(SMALL_FONT_SIZE)
+                    .color(color::DARK_RED.alpha(0.5))
+                    .w_h(ITEM_HEIGHT, ITEM_HEIGHT)
+                    .align_right_of(item.widget_id)
+                    .align_middle_y_of(item.widget_id)
+                    .parent(item.widget_id)
+                    .set(ids.rule_editor_rule_remove, ui)
+                    .was_clicked()
+                {
+                    maybe_remove_index = Some(item.i);
+                }
+            },
+
+            // Update the selected rule.
+            Event::Selection(idx) => *selected = Some(idx),
+
+            _ => (),
+        }
+    }
+
+    // The scrollbar for the list.
+    if let Some(s) = scrollbar {
+        s.set(ui);
+    }
+
+    // Remove a rule if necessary.
+    if let Some(i) = maybe_remove_index {
+        soundscape_rules.remove(i);
+        *selected = match *selected {
+            Some(s) if s == i => None,
+            Some(s) if s > i => Some(s - 1),
+            s => s,
+        };
+        send_rules(channels, soundscape_rules);
+    }
+
+    ///////////////////
+    // SELECTED RULE //
+    ///////////////////
+
+    // Only continue if there is some selected rule.
+    let i = match *selected {
+        Some(i) if i < soundscape_rules.len() => i,
+        _ => return area.id,
+    };
+
+    // A canvas for parameters specific to the selected rule.
+    widget::Canvas::new()
+        .parent(area.id)
+        .kid_area_w_of(area.id)
+        .h(SELECTED_CANVAS_H)
+        .align_middle_x_of(area.id)
+        .down_from(ids.rule_editor_rule_canvas, PAD)
+        .pad(PAD)
+        .color(color::CHARCOAL)
+        .set(ids.rule_editor_selected_canvas, ui);
+
+    widget::Text::new("Selected Rule")
+        .top_left_of(ids.rule_editor_selected_canvas)
+        .font_size(SMALL_FONT_SIZE)
+        .set(ids.rule_editor_selected_text, ui);
+
+    let kid_area = ui.kid_area_of(ids.rule_editor_selected_canvas).unwrap();
+    let half_w = (kid_area.w() - PAD) / 2.0;
+    let rule = soundscape_rules[i];
+    let (first, second) = rule_sources(&rule);
+
+    // The kind of rule.
+    let kind_index = match rule {
+        Rule::Exclusive { .. } => 0,
+        Rule::FollowOn { .. } => 1,
+        Rule::NoRepeat { .. } => 2,
+    };
+    for new_index in widget::DropDownList::new(RULE_KINDS, Some(kind_index))
+        .down(PAD)
+        .align_left_of(ids.rule_editor_selected_text)
+        .w(half_w)
+        .h(ROW_H)
+        .color(DARK_A)
+        .border_color(color::LIGHT_CHARCOAL)
+        .label_font_size(SMALL_FONT_SIZE)
+        .set(ids.rule_editor_selected_kind, ui)
+    {
+        // Retain the sources of the rule where possible.
+        let second = second.unwrap_or(first);
+        soundscape_rules[i] = match new_index {
+            0 => Rule::Exclusive { a: first, b: second },
+            1 => Rule::FollowOn {
+                after: first,
+                follow: second,
+                within: rule::default::FOLLOW_ON_WITHIN,
+            },
+            _ => Rule::NoRepeat {
+                source: first,
+                interval: rule::default::NO_REPEAT_INTERVAL,
+            },
+        };
+        send_rules(channels, soundscape_rules);
+    }
+
+    // The source to which the rule applies, or which the second source follows.
+    let source_labels: Vec<String> = sources_vec
+        .iter()
+        .map(|&(_, ref name)| name.clone())
+        .collect();
+    let first_index = sources_vec.iter().position(|&(id, _)| id == first);
+    for new_index in widget::DropDownList::new(&source_labels, first_index)
+        .right(PAD)
+        .align_top_of(ids.rule_editor_selected_kind)
+        .w(half_w)
+        .h(ROW_H)
+        .scrollbar_on_top()
+        .max_visible_items(5)
+        .color(DARK_A)
+        .border_color(color::LIGHT_CHARCOAL)
+        .label_font_size(SMALL_FONT_SIZE)
+        .set(ids.rule_editor_selected_first_source, ui)
+    {
+        let id = sources_vec[new_index].0;
+        match soundscape_rules[i] {
+            Rule::Exclusive { ref mut a, .. } => *a = id,
+            Rule::FollowOn { ref mut after, .. } => *after = id,
+            Rule::NoRepeat { ref mut source, .. } => *source = id,
+        }
+        send_rules(channels, soundscape_rules);
+    }
+
+    // The second source of exclusive and follow-on rules.
+    if let Some(second) = second {
+        let second_labels: Vec<String> = sources_vec
+            .iter()
+            .map(|&(_, ref name)| match rule {
+                Rule::FollowOn { .. } => format!("Followed by {}", name),
+                _ => format!("Never with {}", name),
+            })
+            .collect();
+        let second_index = sources_vec.iter().position(|&(id, _)| id == second);
+        for new_index in widget::DropDownList::new(&second_labels, second_index)
+            .down_from(ids.rule_editor_selected_kind, PAD)
+            .align_left_of(ids.rule_editor_selected_kind)
+            .kid_area_w_of(ids.rule_editor_selected_canvas)
+            .h(ROW_H)
+            .scrollbar_on_top()
+            .max_visible_items(5)
+            .color(DARK_A)
+            .border_color(color::LIGHT_CHARCOAL)
+            .label_font_size(SMALL_FONT_SIZE)
+            .set(ids.rule_editor_selected_second_source, ui)
+        {
+            let id = sources_vec[new_index].0;
+            match soundscape_rules[i] {
+                Rule::Exclusive { ref mut b, .. } => *b = id,
+                Rule::FollowOn { ref mut follow, .. } => *follow = id,
+                Rule::NoRepeat { .. } => (),
+            }
+            send_rules(channels, soundscape_rules);
+        }
+    }
+
+    // The duration associated with follow-on and no-repeat rules.
+    let duration = match rule {
+        Rule::Exclusive { .. } => None,
+        Rule::FollowOn { within, .. } => {
+            let label = format!("Within: {:.1} secs", within.ms() / SEC_MS);
+            Some((within, MAX_FOLLOW_ON_MS, label))
+        },
+        Rule::NoRepeat { interval, .. } => {
+            let label = format!("Interval: {:.1} mins", interval.ms() / MIN_MS);
+            Some((interval, MAX_NO_REPEAT_MS, label))
+        },
+    };
+    if let Some((duration, max, label)) = duration {
+        let above = match second {
+            Some(_) => ids.rule_editor_selected_second_source,
+            None => ids.rule_editor_selected_kind,
+        };
+        for new_ms in widget::Slider::new(duration.ms(), 0.0, max)
+            .skew(0.5)
+            .label(&label)
+            .label_font_size(SMALL_FONT_SIZE)
+            .color(color::LIGHT_CHARCOAL)
+            .down_from(above, PAD)
+            .align_left_of(ids.rule_editor_selected_kind)
+            .kid_area_w_of(ids.rule_editor_selected_canvas)
+            .h(ROW_H)
+            .set(ids.rule_editor_selected_duration, ui)
+        {
+            let new_duration = Ms((new_ms / 100.0).round() * 100.0);
+            match soundscape_rules[i] {
+                Rule::Exclusive { .. } => (),
+                Rule::FollowOn { ref mut within, .. } => *within = new_duration,
+                Rule::NoRepeat { ref mut interval, .. } => *interval = new_duration,
+            }
+            send_rules(channels, soundscape_rules);
+        }
+    }
+
+    area.id
+}
+
+// The first and second sources of a rule, if it has a second.
+fn rule_sources(rule: &Rule) -> (audio::source::Id, Option<audio::source::Id>) {
+    match *rule {
+        Rule::Exclusive { a, b } => (a, Some(b)),
+        Rule::FollowOn { after, follow, .. } => (after, Some(follow)),
+        Rule::NoRepeat { source, .. } => (source, None),
+    }
+}
+
+// Send the updated rules to the soundscape thread.
+fn send_rules(channels: &Channels, soundscape_rules: &project::SoundscapeRules) {
+    let rules = soundscape_rules.clone();
+    channels
+        .soundscape
+        .send(move |soundscape| {
+            soundscape.set_rules(rules);
+        })
+        .expect("failed to send rules to soundscape thread");
+}
@@ -0,0 +1,335 @@
+//! A "Scene Editor" side-bar widget for capturing, recalling, updating and removing scenes.
+
+use gui::{collapsible_area, Gui, ProjectState, State};
+use gui::{ITEM_HEIGHT, SMALL_FONT_SIZE};
+use nannou::ui;
+use nannou::ui::prelude::*;
+use project::{self, Project, Scene};
+use time_calc::Ms;
+
+/// GUI state related to the scene editor area.
+#[derive(Default)]
+pub struct SceneEditor {
+    /// The selected scene, if any.
+    pub selected: Option<Selected>,
+}
+
+/// The currently selected scene.
+pub struct Selected {
+    /// The index of the scene within the project's scenes.
+    pub index: usize,
+    /// The name of the scene as it is being edited.
+    pub name: String,
+}
+
+/// The longest crossfade selectable for a scene.
+const MAX_CROSSFADE_MS: f64 = 60_000.0;
+
+/// Sets all widgets in the scene area and returns the `Id` of the last area.
+pub fn set(
+    last_area_id: widget::Id,
+    gui: &mut Gui,
+    project: &mut Project,
+    project_state: &mut ProjectState,
+) -> widget::Id {
+    let Gui {
+        ref mut ui,
+        ref ids,
+        channels,
+        state: &mut State {
+            ref mut is_open,
+            ..
+        },
+        ..
+    } = *gui;
+    let Project { ref mut state, .. } = *project;
+    let ProjectState {
+        scene_editor: SceneEditor {
+            ref mut selected,
+        },
+        ..
+    } = *project_state;
+
+    // Constants to use as widget heights.
+    const PAD: Scalar = 6.0;
+    const PLUS_SCENE_H: Scalar = ITEM_HEIGHT;
+    const SCENE_LIST_MAX_H: Scalar = ITEM_HEIGHT * 5.0;
+    const TITLE_H: Scalar = SMALL_FONT_SIZE as Scalar * 1.333;
+    const SCENE_CANVAS_H: Scalar = PAD + TITLE_H + PAD + PLUS_SCENE_H + SCENE_LIST_MAX_H + PAD;
+    const ROW_H: Scalar = ITEM_HEIGHT;
+    const NUM_ROWS: Scalar = 3.0;
+    const SELECTED_CANVAS_H: Scalar = PAD + TITLE_H + PAD + (ROW_H + PAD) * NUM_ROWS;
+    const SCENE_EDITOR_H: Scalar = PAD + SCENE_CANVAS_H + PAD + SELECTED_CANVAS_H + PAD;
+
+    // The collapsible area.
+    let (area, event) = collapsible_area(is_open.scene_editor, "Scene Editor", ids.side_menu)
+        .align_middle_x_of(ids.side_menu)
+        .down_from(last_area_id, 0.0)
+        .set(ids.scene_editor, ui);
+    if let Some(event) = event {
+        is_open.scene_editor = event.is_open();
+    }
+
+    // If the area is open, get the area.
+    let area = match area {
+        Some(area) => area,
+        None => return ids.scene_editor,
+    };
+
+    // The canvas on which the scene editor will be placed.
+    let canvas = widget::Canvas::new()
+        .pad(PAD)
+        .h(SCENE_EDITOR_H);
+    area.set(canvas, ui);
+
+    ////////////////
+    // SCENE LIST //
+    ////////////////
+
+    // A canvas on which scene selection takes place.
+    widget::Canvas::new()
+        .parent(area.id)
+        .kid_area_w_of(area.id)
+        .h(SCENE_CANVAS_H)
+        .mid_top_of(area.id)
+        .pad(PAD)
+        .color(color::CHARCOAL)
+        .set(ids.scene_editor_scene_canvas, ui);
+
+    // A title for the scenes canvas.
+    widget::Text::new("Scenes")
+        .top_left_of(ids.scene_editor_scene_canvas)
+        .font_size(SMALL_FONT_SIZE)
+        .set(ids.scene_editor_scene_text, ui);
+
+    // A button for capturing the current state of the project as a new scene.
+    for _click in widget::Button::new()
+        .label("+")
+        .kid_area_w_of(ids.scene_editor_scene_canvas)
+        .h(PLUS_SCENE_H)
+        .align_middle_x_of(ids.scene_editor_scene_canvas)
+        .down(PAD * 2.0)
+        .set(ids.scene_editor_scene_add, ui)
+    {
+        let name = format!("Scene {}", state.scenes.len() + 1);
+        let scene = Scene::capture(name.clone(), project::scene::default::CROSSFADE, state);
+        state.scenes.push(scene);
+        let index = state.scenes.len() - 1;
+        *selected = Some(Selected { index, name });
+    }
+
+    // If there are no scenes, display some text for adding a scene.
+    if state.scenes.is_empty() {
+        widget::Text::new("Capture the current mix as a scene with the \"+\" button above!")
+            .font_size(SMALL_FONT_SIZE)
+            .align_middle_x_of(ids.scene_editor_scene_canvas)
+            .down(PAD + ITEM_HEIGHT)
+            .set(ids.scene_editor_scene_none, ui);
+        return area.id;
+    }
+
+    // A list of all scenes in the order in which they were captured.
+    let num_scenes = state.scenes.len();
+    let (mut events, scrollbar) = widget::ListSelect::single(num_scenes)
+        .down(0.0)
+        .flow_down()
+        .item_size(ITEM_HEIGHT)
+        .h(SCENE_LIST_MAX_H)
+        .kid_area_w_of(ids.scene_editor_scene_canvas)
+        .scrollbar_next_to()
+        .set(ids.scene_editor_scene_list, ui);
+
+    // Track whether or not an item was removed.
+    let selected_index = selected.as_ref().map(|s| s.index);
+    let mut maybe_remove_index = None;
+    while let Some(event) = events.next(ui, |i| Some(i) == selected_index) {
+        use self::ui::widget::list_select::Event;
+        match event {
+            // Instantiate the widget for this item.
+            Event::Item(item) => {
+                let is_selected = selected_index == Some(item.i);
+
+                // Blue if selected, gray otherwise.
+                let color = if is_selected {
+                    color::BLUE
+                } else {
+                    color::DARK_CHARCOAL
+                };
+
+                // Use a button widget for each item.
+                let label = &state.scenes[item.i].name;
+                let label_x = position::Relative::Place(position::Place::Start(Some(10.0)));
+                let button = widget::Button::new()
+                    .label(label)
+                    .label_font_size(SMALL_FONT_SIZE)
+                    .label_x(label_x)
+                    .color(color);
+                item.set(button, ui);
+
+                // If the button or any of its children are capturing the mouse, display
+                // the `remove` button.
+                let show_remove_button = ui.global_input()
+                    .current
+                    .widget_capturing_mouse
+                    .map(|id| {
+                        id == item.widget_id
+                            || ui.widget_graph()
+                                .does_recursive_depth_edge_exist(item.widget_id, id)
+                    })
+                    .unwrap_or(false);
+
+                if !show_remove_button {
+                    continue;
+                }
+
+                if widget::Button::new()
+                    .label("X")
+                    .label_font_size(SMALL_FONT_SIZE)
+                    .color(color::DARK_RED.alpha(0.5))
+                    .w_h(ITEM_HEIGHT, ITEM_HEIGHT)
+                    .align_right_of(item.widget_id)
+                    .align_middle_y_of(item.widget_id)
+                    .parent(item.widget_id)
+                    .set(ids.scene_editor_scene_remove, ui)
+                    .was_clicked()
+                {
+                    maybe_remove_index = Some(item.i);
+                }
+            },
+
+            // Update the selected scene.
+            Event::Selection(index) => {
+                let name = state.scenes[index].name.clone();
+                *selected = Some(Selected { index, name });
+            },
+
+            _ => (),
+        }
+    }
+
+    // The scrollbar for the list.
+    if let Some(s) = scrollbar {
+        s.set(ui);
+    }
+
+    // Remove a scene if necessary.
+    if let Some(i) = maybe_remove_index {
+        state.scenes.remove(i);
+        *selected = match selected.take() {
+            Some(ref s) if s.index == i => None,
+            Some(mut s) => {
+                if s.index > i {
+                    s.index -= 1;
+                }
+                Some(s)
+            },
+            None => None,
+        };
+    }
+
+    ////////////////////
+    // SELECTED SCENE //
+    ////////////////////
+
+    // Only continue if there is some selected scene.
+    let selected = match *selected {
+        Some(ref mut selected) => selected,
+        None => return area.id,
+    };
+    let i = selected.index;
+    if i >= state.scenes.len() {
+        return area.id;
+    }
+
+    // A canvas for parameters specific to the selected scene.
+    widget::Canvas::new()
+        .parent(area.id)
+        .kid_area_w_of(area.id)
+        .h(SELECTED_CANVAS_H)
+        .align_middle_x_of(area.id)
+        .down_from(ids.scene_editor_scene_canvas, PAD)
+        .pad(PAD)
+        .color(color::CHARCOAL)
+        .set(ids.scene_editor_selected_canvas, ui);
+
+    widget::Text::new("Selected Scene")
+        .top_left_of(ids.scene_editor_selected_canvas)
+        .font_size(SMALL_FONT_SIZE)
+        .set(ids.scene_editor_selected_text, ui);
+
+    let kid_area = ui.kid_area_of(ids.scene_editor_selected_canvas).unwrap();
+    let half_w = (kid_area.w() - PAD) / 2.0;
+
+    // The name of the scene, used to recall it via OSC.
+    for event in widget::TextBox::new(&selected.name)
+        .down(PAD)
+        .align_left_of(ids.scene_editor_selected_text)
+        .kid_area_w_of(ids.scene_editor_selected_canvas)
+        .h(ROW_H)
+        .font_size(SMALL_FONT_SIZE)
+        .color(color::BLACK)
+        .set(ids.scene_editor_selected_name, ui)
+    {
+        use self::ui::widget::text_box::Event;
+        match event {
+            // When typing generally, only update the temp selected name.
+            Event::Update(new_name) => {
+                selected.name = new_name;
+            },
+            // Only when enter is pressed do we update the actual name.
+            Event::Enter => {
+                state.scenes[i].name = selected.name.clone();
+            },
+        }
+    }
+
+    // The duration over which the scene is faded in when recalled.
+    let crossfade = state.scenes[i].crossfade;
+    let label = format!("Crossfade: {:.1} secs", crossfade.ms() / 1_000.0);
+    for new_crossfade in widget::Slider::new(crossfade.ms(), 0.0, MAX_CROSSFADE_MS)
+        .skew(0.5)
+        .label(&label)
+        .label_font_size(SMALL_FONT_SIZE)
+        .color(color::LIGHT_CHARCOAL)
+        .down(PAD)
+        .align_left_of(ids.scene_editor_selected_name)
+        .kid_area_w_of(ids.scene_editor_selected_canvas)
+        .h(ROW_H)
+        .set(ids.scene_editor_selected_crossfade, ui)
+    {
+        state.scenes[i].crossfade = Ms((new_crossfade / 100.0).round() * 100.0);
+    }
+
+    // Recall the scene, crossfading towards it.
+    for _click in widget::Button::new()
+        .label("Recall")
+        .label_font_size(SMALL_FONT_SIZE)
+        .color(color::DARK_GREEN)
+        .down(PAD)
+        .align_left_of(ids.scene_editor_selected_crossfade)
+        .w(half_w)
+        .h(ROW_H)
+        .set(ids.scene_editor_selected_recall, ui)
+    {
+        let scene = state.scenes[i].clone();
+        scene.recall(scene.crossfade, state, channels);
+    }
+
+    // Replace the scene with the current state of the project.
+    for _click in widget::Button::new()
+        .label("Update")
+        .label_font_size(SMALL_FONT_SIZE)
+        .color(color::DARK_CHARCOAL)
+        .right(PAD)
+        .w(half_w)
+        .h(ROW_H)
+        .set(ids.scene_editor_selected_update, ui)
+    {
+        let name = state.scenes[i].name.clone();
+        let crossfade = state.scenes[i].crossfade;
+        let scene = Scene::capture(name, crossfade, state);
+        state.scenes[i] = scene;
+    }
+
+    area.id
+}
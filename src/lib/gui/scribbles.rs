@@ -0,0 +1,151 @@
+//! Handling of the OSC position streams that drive the sounds of scribbles sources.
+//!
+//! Each stream is identified by the scribbles source it plays and a scribble id chosen by the
+//! sender. The first position of a stream spawns a looping sound from the source, which then
+//! follows each new position until the stream ends or stops updating, at which point the sound
+//! fades out over the master `scribble_release` duration.
+
+use audio;
+use fxhash::FxHashMap;
+use gui::Channels;
+use master::Master;
+use nannou::rand::thread_rng;
+use osc;
+use project::Project;
+use std::sync::atomic;
+use std::time::{Duration, Instant};
+use time_calc::Ms;
+
+/// The currently active scribble streams.
+#[derive(Default)]
+pub struct Scribbles {
+    streams: FxHashMap<(audio::source::Id, i32), Stream>,
+}
+
+// A single active scribble stream.
+struct Stream {
+    sound_id: audio::sound::Id,
+    last_update: Instant,
+}
+
+impl Scribbles {
+    /// Move the sound of the given scribble, spawning it if this is the first position received.
+    pub fn update_position(
+        &mut self,
+        scribble_position: &osc::input::ScribblePosition,
+        project: &Project,
+        channels: &Channels,
+        sound_id_gen: &audio::sound::IdGenerator,
+    ) {
+        let osc::input::ScribblePosition { ref name, id, point } = *scribble_position;
+        let (source_id, source) = match find_scribbles_source(project, name) {
+            None => return,
+            Some(source) => source,
+        };
+
+        // If the stream is already active, move its sound.
+        if let Some(stream) = self.streams.get_mut(&(source_id, id)) {
+            stream.last_update = Instant::now();
+            let sound_id = stream.sound_id;
+            channels
+                .audio_output
+                .send(move |audio| {
+                    audio.update_sound(&sound_id, |sound| sound.position.point = point);
+                })
+                .expect("failed to send scribble position to audio output thread");
+            return;
+        }
+
+        // Otherwise spawn a sound that loops for the lifetime of the stream.
+        let sound_id = sound_id_gen.generate_next();
+        let position = audio::sound::Position { point, radians: 0.0 };
+        let envelope = audio::source::Envelope::default();
+        let playback_rate = 1.0;
+        let looped = true;
+        let max_duration = None;
+        let _handle = audio::sound::spawn_from_source(
+            sound_id,
+            source_id,
+            source,
+            position,
+            envelope,
+            playback_rate,
+            looped,
+            max_duration,
+            channels.frame_count.load(atomic::Ordering::Relaxed) as _,
+            &channels.wav_reader,
+            &channels.audio_input,
+            &channels.audio_output,
+            project.master.realtime_source_latency,
+            &mut thread_rng(),
+        );
+        let last_update = Instant::now();
+        self.streams.insert((source_id, id), Stream { sound_id, last_update });
+    }
+
+    /// Fade out the sound of the given scribble.
+    pub fn end(
+        &mut self,
+        scribble_end: &osc::input::ScribbleEnd,
+        project: &Project,
+        channels: &Channels,
+    ) {
+        let osc::input::ScribbleEnd { ref name, id } = *scribble_end;
+        let source_id = match find_scribbles_source(project, name) {
+            None => return,
+            Some((source_id, _)) => source_id,
+        };
+        if let Some(stream) = self.streams.remove(&(source_id, id)) {
+            release_sound(stream.sound_id, project.master.scribble_release, channels);
+        }
+    }
+
+    /// Fade out the sounds of all streams that have not been updated within the master
+    /// `scribble_timeout`.
+    pub fn remove_stale(&mut self, master: &Master, channels: &Channels) {
+        let now = Instant::now();
+        let timeout = Duration::from_millis(master.scribble_timeout.ms() as u64);
+        let mut stale = vec![];
+        for (&key, stream) in self.streams.iter() {
+            if now.duration_since(stream.last_update) > timeout {
+                stale.push(key);
+            }
+        }
+        for key in stale {
+            let stream = self.streams.remove(&key).unwrap();
+            release_sound(stream.sound_id, master.scribble_release, channels);
+        }
+    }
+
+    /// Forget the stream of the given sound once it has ended, e.g. due to a project switch.
+    pub fn sound_ended(&mut self, sound_id: audio::sound::Id) {
+        self.streams.retain(|_, stream| stream.sound_id != sound_id);
+    }
+}
+
+// Find the source with the given name, ignoring it if it does not have the scribbles role.
+fn find_scribbles_source<'a>(
+    project: &'a Project,
+    name: &str,
+) -> Option<(audio::source::Id, &'a audio::Source)> {
+    project
+        .state
+        .sources
+        .iter()
+        .find(|&(_, ref s)| &s.name[..] == name)
+        .and_then(|(&id, source)| match source.audio.role {
+            Some(audio::source::Role::Scribbles) => Some((id, &source.audio)),
+            _ => None,
+        })
+}
+
+// Fade the sound out over the given release duration, after which the output thread removes it.
+fn release_sound(sound_id: audio::sound::Id, release: Ms, channels: &Channels) {
+    let frames = release.to_samples(audio::SAMPLE_RATE);
+    channels
+        .audio_output
+        .send(move |audio| {
+            audio.update_sound(&sound_id, |sound| sound.signal.release(frames));
+        })
+        .expect("failed to release scribble sound on audio output thread");
+}
@@ -1,27 +1,67 @@
 //! A `Soundscape` panel displaying:
 //!
 //! - Play/Pause toggle for the soundscape.
+//! - A button for running an accelerated simulation of the soundscape.
 //! - Groups panel for creating/removing soundscape source groups.
+//! - Markov sequencing of the sources within the selected group.
 
-use gui::{collapsible_area, hz_label, Gui, ProjectState, State};
-use gui::{ITEM_HEIGHT, SMALL_FONT_SIZE};
+use audio;
+use gui::{collapsible_area, hz_label, Channels, Gui, ProjectState, State};
+use gui::{DARK_A, ITEM_HEIGHT, SMALL_FONT_SIZE};
 use project::{self, Project};
 use nannou::ui;
 use nannou::ui::prelude::*;
 use soundscape;
+use std::{fs, io, thread, time};
+use std::sync::mpsc;
 use time_calc::Ms;
 use utils;
 
+/// The duration of virtual time covered by a simulation run from the GUI.
+const SIMULATION_HOURS: u64 = 24;
+
 /// GUI state related to the soundscape editor area.
 #[derive(Default)]
 pub struct SoundscapeEditor {
     pub selected: Option<Selected>,
+    pub simulation: Simulation,
+}
+
+/// The state of the most recent soundscape simulation.
+pub enum Simulation {
+    /// No simulation has been run.
+    Idle,
+    /// A simulation is running on another thread.
+    Running(mpsc::Receiver<io::Result<soundscape::simulation::Summary>>),
+    /// The simulation has finished with the given status.
+    Finished(String),
 }
 
 /// The currently selected group.
 pub struct Selected {
     name: String,
     id: soundscape::group::Id,
+    /// The source from which the edited Markov transition begins.
+    transition_from: Option<audio::source::Id>,
+    /// The source at which the edited Markov transition ends.
+    transition_to: Option<audio::source::Id>,
+}
+
+impl Selected {
+    fn new(id: soundscape::group::Id, name: String) -> Self {
+        Selected {
+            name,
+            id,
+            transition_from: None,
+            transition_to: None,
+        }
+    }
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Simulation::Idle
+    }
 }
 
 /// Sets all widgets in the soundscape area and returns the `Id` of the last area.
@@ -35,6 +75,7 @@ pub fn set(
         ref mut ui,
         ref ids,
         channels,
+        assets,
         state: &mut State {
             ref mut is_open,
             ..
@@ -42,14 +83,6 @@ pub fn set(
         ..
     } = gui;
 
-    let Project {
-        state: project::State {
-            ref mut soundscape_groups,
-            ..
-        },
-        ..
-    } = *project;
-
     let ProjectState {
         ref mut soundscape_editor,
         ..
@@ -58,6 +91,7 @@ pub fn set(
     // Constants to use as widget heights.
     const PAD: Scalar = 6.0;
     const IS_PLAYING_H: Scalar = ITEM_HEIGHT;
+    const SIMULATE_H: Scalar = ITEM_HEIGHT;
     const PLUS_GROUP_H: Scalar = ITEM_HEIGHT;
     const GROUP_LIST_MAX_H: Scalar = ITEM_HEIGHT * 5.0;
     const TEXT_BOX_H: Scalar = ITEM_HEIGHT;
@@ -67,8 +101,10 @@ pub fn set(
     const SELECTED_CANVAS_H: Scalar = PAD
         + TITLE_H + PAD * 2.0 + TEXT_BOX_H + PAD
         + TITLE_H + PAD * 2.0 + SLIDER_H + PAD
-        + TITLE_H + PAD + SLIDER_H + PAD;
-    let soundscape_editor_canvas_h = PAD + IS_PLAYING_H + PAD + GROUP_CANVAS_H + PAD + SELECTED_CANVAS_H + PAD;
+        + TITLE_H + PAD + SLIDER_H + PAD
+        + TITLE_H + PAD * 2.0 + ITEM_HEIGHT + PAD + ITEM_HEIGHT + PAD + SLIDER_H + PAD;
+    let soundscape_editor_canvas_h = PAD + IS_PLAYING_H + PAD + SIMULATE_H + PAD + GROUP_CANVAS_H
+        + PAD + SELECTED_CANVAS_H + PAD;
 
     // The collapsible area.
     let (area, event) = collapsible_area(is_open.soundscape_editor, "Soundscape Editor", ids.side_menu)
@@ -119,6 +155,71 @@ pub fn set(
         }
     }
 
+    ////////////////
+    // SIMULATION //
+    ////////////////
+
+    // Check whether or not a running simulation has finished.
+    let finished = match soundscape_editor.simulation {
+        Simulation::Running(ref rx) => match rx.try_recv() {
+            Ok(Ok(summary)) => Some(format!(
+                "Simulated {} sounds, max {} at once",
+                summary.sounds, summary.max_simultaneous_sounds,
+            )),
+            Ok(Err(err)) => Some(format!("Simulation failed: {}", err)),
+            Err(mpsc::TryRecvError::Empty) => None,
+            Err(mpsc::TryRecvError::Disconnected) => Some("Simulation failed".to_string()),
+        },
+        _ => None,
+    };
+    if let Some(status) = finished {
+        soundscape_editor.simulation = Simulation::Finished(status);
+    }
+
+    // A button for simulating a day of the soundscape, writing the log to the project directory.
+    let label = match soundscape_editor.simulation {
+        Simulation::Idle => format!("Simulate {} Hours", SIMULATION_HOURS),
+        Simulation::Running(_) => "Simulating...".to_string(),
+        Simulation::Finished(ref status) => status.clone(),
+    };
+    for _click in widget::Button::new()
+        .color(color::DARK_CHARCOAL)
+        .h(SIMULATE_H)
+        .down(PAD)
+        .kid_area_w_of(area.id)
+        .label(&label)
+        .label_font_size(SMALL_FONT_SIZE)
+        .set(ids.soundscape_editor_simulate, ui)
+    {
+        if let Simulation::Running(_) = soundscape_editor.simulation {
+            continue;
+        }
+        let duration = time::Duration::from_secs(SIMULATION_HOURS * 60 * 60);
+        let config = soundscape::simulation::Config::from_now(duration);
+        let simulation = project.soundscape_simulation(config);
+        let directory = project::project_directory_path(assets, &project.name);
+        let (tx, rx) = mpsc::channel();
+        thread::Builder::new()
+            .name("soundscape_simulation".into())
+            .spawn(move || {
+                let result = fs::create_dir_all(&directory)
+                    .and_then(|_| fs::File::create(project::project_simulation_path(&directory)))
+                    .and_then(|file| simulation.run(file));
+                tx.send(result).ok();
+            })
+            .expect("failed to spawn soundscape simulation thread");
+        soundscape_editor.simulation = Simulation::Running(rx);
+    }
+
+    let Project {
+        state: project::State {
+            ref mut soundscape_groups,
+            ref sources,
+            ..
+        },
+        ..
+    } = *project;
+
     //////////////////
     // GROUP EDITOR //
     //////////////////
@@ -129,7 +230,7 @@ pub fn set(
         .kid_area_w_of(area.id)
         .h(GROUP_CANVAS_H)
         .align_middle_x_of(area.id)
-        .down(PAD)
+        .down_from(ids.soundscape_editor_simulate, PAD)
         .pad(PAD)
         .color(color::CHARCOAL)
         .set(ids.soundscape_editor_group_canvas, ui);
@@ -158,7 +259,7 @@ pub fn set(
         };
         let id = project::next_soundscape_group_id(soundscape_groups);
         soundscape_groups.insert(id, group);
-        soundscape_editor.selected = Some(Selected { id, name });
+        soundscape_editor.selected = Some(Selected::new(id, name));
     }
 
     // If there are no groups, display some text for adding a group.
@@ -260,7 +361,7 @@ pub fn set(
             Event::Selection(idx) => {
                 soundscape_editor.selected = {
                     let (id, ref name) = groups_vec[idx];
-                    Some(Selected { id, name: name.clone() })
+                    Some(Selected::new(id, name.clone()))
                 };
             }
 
@@ -470,5 +571,170 @@ pub fn set(
             .expect("failed to send updated simultaneous sounds constraint to soundscape thread");
     }
 
+    ///////////////////////
+    // MARKOV SEQUENCING //
+    ///////////////////////
+
+    widget::Text::new("Markov Sequencing")
+        .align_left()
+        .down(PAD)
+        .font_size(SMALL_FONT_SIZE)
+        .set(ids.soundscape_editor_markov_text, ui);
+
+    // A toggle for enabling the sequencing of sources via the transition matrix.
+    let enabled = soundscape_groups[&selected.id].markov.enabled;
+    let label = match enabled {
+        true => "Enabled",
+        false => "Disabled",
+    };
+    for new_enabled in widget::Toggle::new(enabled)
+        .align_left()
+        .down(PAD * 2.0)
+        .kid_area_w_of(ids.soundscape_editor_selected_canvas)
+        .h(ITEM_HEIGHT)
+        .label(label)
+        .label_font_size(SMALL_FONT_SIZE)
+        .color(color::LIGHT_CHARCOAL)
+        .set(ids.soundscape_editor_markov_enabled, ui)
+    {
+        let group = soundscape_groups.get_mut(&selected.id).unwrap();
+        group.markov.enabled = new_enabled;
+        send_markov(channels, selected.id, &group.markov);
+    }
+
+    // Collect the soundscape sources of the group into alphabetical order.
+    let mut group_sources: Vec<(audio::source::Id, String)> = sources
+        .iter()
+        .filter(|&(_, source)| match source.audio.role {
+            Some(audio::source::Role::Soundscape(ref s)) => s.groups.contains(&selected.id),
+            _ => false,
+        })
+        .map(|(&id, source)| (id, source.name.clone()))
+        .collect();
+    group_sources.sort_by(|a, b| a.1.cmp(&b.1));
+
+    // Drop the edited transition if either of its sources have left the group.
+    let in_group = |id: &audio::source::Id| group_sources.iter().any(|&(s, _)| s == *id);
+    if !selected.transition_from.as_ref().map(&in_group).unwrap_or(true) {
+        selected.transition_from = None;
+    }
+    if !selected.transition_to.as_ref().map(&in_group).unwrap_or(true) {
+        selected.transition_to = None;
+    }
+
+    // Drop down lists for selecting the transition to edit.
+    let kid_area = ui.kid_area_of(ids.soundscape_editor_selected_canvas).unwrap();
+    let half_w = (kid_area.w() - PAD) / 2.0;
+    let from_labels: Vec<String> = group_sources
+        .iter()
+        .map(|&(_, ref name)| format!("From: {}", name))
+        .collect();
+    let from_index = selected
+        .transition_from
+        .and_then(|from| group_sources.iter().position(|&(id, _)| id == from));
+    for new_index in widget::DropDownList::new(&from_labels, from_index)
+        .align_left()
+        .down(PAD)
+        .w(half_w)
+        .h(ITEM_HEIGHT)
+        .scrollbar_on_top()
+        .max_visible_items(5)
+        .color(DARK_A)
+        .border_color(color::LIGHT_CHARCOAL)
+        .label_font_size(SMALL_FONT_SIZE)
+        .set(ids.soundscape_editor_markov_from, ui)
+    {
+        selected.transition_from = Some(group_sources[new_index].0);
+    }
+
+    let to_labels: Vec<String> = group_sources
+        .iter()
+        .map(|&(_, ref name)| format!("To: {}", name))
+        .collect();
+    let to_index = selected
+        .transition_to
+        .and_then(|to| group_sources.iter().position(|&(id, _)| id == to));
+    for new_index in widget::DropDownList::new(&to_labels, to_index)
+        .right(PAD)
+        .align_top_of(ids.soundscape_editor_markov_from)
+        .w(half_w)
+        .h(ITEM_HEIGHT)
+        .scrollbar_on_top()
+        .max_visible_items(5)
+        .color(DARK_A)
+        .border_color(color::LIGHT_CHARCOAL)
+        .label_font_size(SMALL_FONT_SIZE)
+        .set(ids.soundscape_editor_markov_to, ui)
+    {
+        selected.transition_to = Some(group_sources[new_index].0);
+    }
+
+    // A slider for the weight of the selected transition.
+    let transition = match (selected.transition_from, selected.transition_to) {
+        (Some(from), Some(to)) => Some((from, to)),
+        _ => None,
+    };
+    let weight = transition
+        .map(|(from, to)| soundscape_groups[&selected.id].markov.weight(&from, &to))
+        .unwrap_or(0.0);
+    let label = match transition {
+        Some(_) => format!("Transition Weight: {:.2}", weight),
+        None => "Select a transition to weight".to_string(),
+    };
+    for new_weight in widget::Slider::new(weight, 0.0, 1.0)
+        .align_left_of(ids.soundscape_editor_markov_from)
+        .down_from(ids.soundscape_editor_markov_from, PAD)
+        .kid_area_w_of(ids.soundscape_editor_selected_canvas)
+        .h(SLIDER_H)
+        .label(&label)
+        .label_font_size(SMALL_FONT_SIZE)
+        .color(color::LIGHT_CHARCOAL)
+        .set(ids.soundscape_editor_markov_weight, ui)
+    {
+        let (from, to) = match transition {
+            Some(transition) => transition,
+            None => continue,
+        };
+        let group = soundscape_groups.get_mut(&selected.id).unwrap();
+        let new_weight = (new_weight * 100.0).round() / 100.0;
+        if new_weight > 0.0 {
+            group
+                .markov
+                .transitions
+                .entry(from)
+                .or_insert_with(Default::default)
+                .insert(to, new_weight);
+        } else {
+            let is_empty = match group.markov.transitions.get_mut(&from) {
+                Some(weights) => {
+                    weights.remove(&to);
+                    weights.is_empty()
+                },
+                None => false,
+            };
+            if is_empty {
+                group.markov.transitions.remove(&from);
+            }
+        }
+        send_markov(channels, selected.id, &group.markov);
+    }
+
     area.id
 }
+
+// Send the updated Markov sequencing of the group to the soundscape thread.
+fn send_markov(
+    channels: &Channels,
+    id: soundscape::group::Id,
+    markov: &soundscape::group::Markov,
+) {
+    let markov = markov.clone();
+    channels
+        .soundscape
+        .send(move |soundscape| {
+            soundscape.update_group(&id, |group| {
+                group.markov = markov;
+            });
+        })
+        .expect("failed to send updated markov sequencing to soundscape thread");
+}
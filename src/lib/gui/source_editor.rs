@@ -1,17 +1,20 @@
 use audio;
 use audio::source::Role;
-use audio::source::wav::Playback;
+use audio::source::wav::{Playback, StartOffset};
+use fxhash::FxHashMap;
 use gui::{collapsible_area, duration_label, hz_label, Gui, ProjectState, State};
 use gui::{DARK_A, ITEM_HEIGHT, SMALL_FONT_SIZE};
 use metres::Metres;
 use nannou::prelude::*;
+use nannou::rand::thread_rng;
 use nannou::ui;
 use nannou::ui::prelude::*;
 use project::{self, Project};
 use soundscape;
 use std::{self, cmp, mem, ops};
+use std::path::PathBuf;
 use std::sync::atomic;
-use time_calc::{Ms, Samples};
+use time_calc::Ms;
 use utils;
 
 /// Runtime state related to the source editor GUI panel.
@@ -21,6 +24,10 @@ pub struct SourceEditor {
     pub selected: Option<audio::source::Id>,
     /// The source currently being previewed via the source editor GUI.
     pub preview: SourcePreview,
+    /// The overview of each WAV requested from the WAV analysis thread.
+    ///
+    /// `None` while the overview is being loaded or generated.
+    pub wav_overviews: FxHashMap<PathBuf, Option<audio::source::wav::Overview>>,
 }
 
 /// A source currently being previewed.
@@ -79,6 +86,7 @@ pub fn set(
             ref master,
             ref soundscape_groups,
             ref installations,
+            ref speakers,
             ref mut sources,
             ..
         },
@@ -106,21 +114,50 @@ pub fn set(
         + TEXT_PAD + PAD + SLIDER_H + PAD
         + TEXT_PAD + PAD + SLIDER_H + PAD
         + TEXT_PAD + PAD + SLIDER_H + PAD
+        + TEXT_PAD + PAD + SLIDER_H + PAD
         + TEXT_PAD + PAD * 3.5 + SOUNDSCAPE_GROUP_LIST_H + PAD
         + TEXT_PAD + PAD * 2.0 + BUTTON_H + PAD + BUTTON_H + PAD
         + TEXT_PAD + PAD * 2.0 + SLIDER_H + PAD
         + TEXT_PAD + PAD * 2.0 + SLIDER_H + PAD
         + TEXT_PAD + PAD * 2.0 + SLIDER_H + PAD
+        + TEXT_PAD + PAD * 2.0 + SLIDER_H + PAD
+        + TEXT_PAD + PAD * 2.0 + SLIDER_H + PAD
         + TEXT_PAD + PAD * 2.0 + SLIDER_H * 2.0 + PAD
+        + TEXT_PAD + PAD * 2.0 + SLIDER_H + PAD
         + TEXT_PAD + PAD * 2.0 + SLIDER_H + PAD;
     const LOOP_TOGGLE_H: Scalar = ITEM_HEIGHT;
+    const NORMALISE_H: Scalar = ITEM_HEIGHT;
     const PLAYBACK_MODE_H: Scalar = ITEM_HEIGHT;
-    const WAV_CANVAS_H: Scalar =
-        100.0 + PAD + LOOP_TOGGLE_H + PAD * 4.0 + PLAYBACK_MODE_H + PAD;
+    const OVERVIEW_H: Scalar = 60.0;
+    const WAV_CANVAS_H: Scalar = 120.0 + PAD + OVERVIEW_H + PAD + LOOP_TOGGLE_H + PAD
+        + NORMALISE_H + PAD * 4.0 + PLAYBACK_MODE_H + PAD;
+    const GRANULAR_H: Scalar = TEXT_PAD + (PAD + SLIDER_H) * 4.0 + PAD;
+    const START_OFFSET_H: Scalar = TEXT_PAD + PAD * 2.0 + PLAYBACK_MODE_H + PAD + SLIDER_H + PAD;
+    const LOOP_REGION_H: Scalar = TEXT_PAD + (PAD + SLIDER_H) * 2.0 + PAD;
     const REALTIME_CANVAS_H: Scalar = 94.0;
     const CHANNEL_LAYOUT_H: Scalar = 200.0;
     const COMMON_CANVAS_H: Scalar = TEXT_PAD + PAD + SLIDER_H + PAD + CHANNEL_LAYOUT_H;
-    let kind_specific_h = WAV_CANVAS_H.max(REALTIME_CANVAS_H);
+
+    // The WAV canvas is extended to fit the parameters of the selected playback mode along with
+    // the loop region if the WAV is looped.
+    let playback_h = source_editor
+        .selected
+        .and_then(|id| sources.get(&id))
+        .map(|source| match source.kind {
+            audio::source::Kind::Wav(ref wav) => {
+                let mode_h = match wav.playback {
+                    Playback::Retrigger => START_OFFSET_H,
+                    Playback::Granular(_) => GRANULAR_H,
+                    Playback::Continuous => 0.0,
+                };
+                let loop_h = if wav.is_endless() { LOOP_REGION_H } else { 0.0 };
+                mode_h + loop_h
+            },
+            _ => 0.0,
+        })
+        .unwrap_or(0.0);
+    let wav_canvas_h = WAV_CANVAS_H + playback_h;
+    let kind_specific_h = wav_canvas_h.max(REALTIME_CANVAS_H);
     let selected_canvas_h = ITEM_HEIGHT * 2.0 + PAD * 7.0 + PREVIEW_CANVAS_H + kind_specific_h
         + COMMON_CANVAS_H + INSTALLATIONS_CANVAS_H + PAD + SOUNDSCAPE_CANVAS_H;
     let source_editor_canvas_h = LIST_HEIGHT + ITEM_HEIGHT + selected_canvas_h;
@@ -367,6 +404,8 @@ pub fn set(
         let channel_radians = audio::source::default::CHANNEL_RADIANS;
         let volume = audio::source::default::VOLUME;
         let muted = bool::default();
+        let effects = vec![];
+        let loudness_target = None;
         let audio = audio::Source {
             kind,
             role,
@@ -374,6 +413,8 @@ pub fn set(
             channel_radians,
             volume,
             muted,
+            effects,
+            loudness_target,
         };
         let source = project::Source { name, audio };
 
@@ -619,9 +660,9 @@ pub fn set(
                         SourcePreviewMode::Continuous => true,
                     };
 
-                    // No attack or release for previews.
-                    let attack_duration = Samples(0);
-                    let release_duration = Samples(0);
+                    // No attack or release for previews and always preview at the natural rate.
+                    let envelope = audio::source::Envelope::default();
+                    let playback_rate = 1.0;
                     let max_duration = None;
                     let position = audio::sound::Position {
                         point: preview.point.unwrap(),
@@ -638,8 +679,8 @@ pub fn set(
                         source_id,
                         &audio,
                         position,
-                        attack_duration,
-                        release_duration,
+                        envelope,
+                        playback_rate,
                         should_cycle,
                         max_duration,
                         channels.frame_count.load(atomic::Ordering::Relaxed) as _,
@@ -647,6 +688,7 @@ pub fn set(
                         &channels.audio_input,
                         &channels.audio_output,
                         *realtime_source_latency,
+                        &mut thread_rng(),
                     );
                 }
             }
@@ -703,6 +745,8 @@ pub fn set(
     }
 
     // Kind-specific data.
+    let loudness_target = sources[&id].loudness_target;
+    let mut new_loudness_target = None;
     let (kind_canvas_id, num_channels) = match sources.get_mut(&id).unwrap().audio.kind {
         audio::source::Kind::Wav(ref mut wav) => {
             // Instantiate a small canvas for displaying wav-specific stuff.
@@ -711,7 +755,7 @@ pub fn set(
                 .parent(ids.source_editor_selected_canvas)
                 .w(selected_canvas_kid_area.w())
                 .color(color::CHARCOAL)
-                .h(WAV_CANVAS_H)
+                .h(wav_canvas_h)
                 .pad(PAD)
                 .set(ids.source_editor_selected_wav_canvas, ui);
 
@@ -727,9 +771,16 @@ pub fn set(
                 format!("Duration: {:.4} milliseconds", duration_ms.ms())
             };
             let file_line = format!("File: {}", wav.path.file_name().unwrap().to_str().unwrap());
+            let loudness_line = match wav.loudness {
+                Some(ref loudness) => format!(
+                    "Loudness: {:.1} LUFS, Peak: {:.1} dBTP",
+                    loudness.integrated_lufs, loudness.true_peak_dbtp
+                ),
+                None => "Loudness: Analysing...".to_string(),
+            };
             let data = format!(
-                "{}\nChannels: {}\nSample Rate: {}\n{}",
-                file_line, wav.channels, wav.sample_hz, duration_line
+                "{}\nChannels: {}\nSample Rate: {}\n{}\n{}",
+                file_line, wav.channels, wav.sample_hz, duration_line, loudness_line
             );
             widget::Text::new(&data)
                 .font_size(SMALL_FONT_SIZE)
@@ -738,14 +789,108 @@ pub fn set(
                 .line_spacing(PAD)
                 .set(ids.source_editor_selected_wav_data, ui);
 
+            // The overview of the WAV's content along with the playhead of each active sound.
+            let canvas_kid_area = ui.kid_area_of(ids.source_editor_selected_wav_canvas).unwrap();
+            widget::Rectangle::fill([canvas_kid_area.w(), OVERVIEW_H])
+                .color(DARK_A)
+                .down(PAD * 2.0)
+                .align_middle_x_of(ids.source_editor_selected_wav_canvas)
+                .set(ids.source_editor_selected_wav_overview, ui);
+            let overview_rect = ui.rect_of(ids.source_editor_selected_wav_overview).unwrap();
+
+            // Request the overview from the analysis thread if we have not already done so.
+            if !source_editor.wav_overviews.contains_key(&wav.path) {
+                source_editor.wav_overviews.insert(wav.path.clone(), None);
+                channels.wav_analysis.overview(wav.path.clone());
+            }
+
+            // Produce the two triangles of a quad spanning the given bounds.
+            fn quad(l: Scalar, r: Scalar, b: Scalar, t: Scalar)
+                -> [widget::triangles::Triangle<[Scalar; 2]>; 2]
+            {
+                use nannou::ui::widget::triangles::Triangle;
+                let (bl, br, tl, tr) = ([l, b], [r, b], [l, t], [r, t]);
+                [Triangle([bl, tl, tr]), Triangle([bl, tr, br])]
+            }
+
+            match source_editor.wav_overviews[&wav.path] {
+                None => {
+                    widget::Text::new("Generating overview...")
+                        .font_size(SMALL_FONT_SIZE)
+                        .color(color::LIGHT_CHARCOAL)
+                        .middle_of(ids.source_editor_selected_wav_overview)
+                        .set(ids.source_editor_selected_wav_overview_text, ui);
+                },
+                Some(ref overview) => {
+                    let n_bins = overview.bins.len();
+                    let bin_w = overview_rect.w() / n_bins as Scalar;
+                    let y = overview_rect.y();
+                    let half_h = overview_rect.h() / 2.0;
+                    let bin_x = |i: usize| overview_rect.left() + i as Scalar * bin_w;
+                    let amp_y = |amp: f32| y + amp.max(-1.0).min(1.0) as Scalar * half_h;
+
+                    // The peak range of each bin.
+                    let peak_tris = overview.bins
+                        .iter()
+                        .enumerate()
+                        .flat_map(|(i, bin)| {
+                            quad(bin_x(i), bin_x(i + 1), amp_y(bin.min), amp_y(bin.max)).to_vec()
+                        });
+                    widget::Triangles::single_color(color::LIGHT_CHARCOAL, peak_tris)
+                        .with_bounding_rect(overview_rect)
+                        .set(ids.source_editor_selected_wav_overview_peaks, ui);
+
+                    // The RMS of each bin mirrored about the centre.
+                    let rms_tris = overview.bins
+                        .iter()
+                        .enumerate()
+                        .flat_map(|(i, bin)| {
+                            quad(bin_x(i), bin_x(i + 1), amp_y(-bin.rms), amp_y(bin.rms)).to_vec()
+                        });
+                    widget::Triangles::single_color(color::GREY, rms_tris)
+                        .with_bounding_rect(overview_rect)
+                        .set(ids.source_editor_selected_wav_overview_rms, ui);
+                },
+            }
+
+            // A line for the playhead of each active sound using this source. The playhead of the
+            // preview is highlighted.
+            let preview_id = source_editor.preview.current.map(|(_, sound_id)| sound_id);
+            let playhead_tris = audio_monitor.active_sounds
+                .iter()
+                .filter(|&(_, sound)| sound.source_id == id)
+                .filter_map(|(&sound_id, sound)| {
+                    let progress = sound.normalised_progress?.max(0.0).min(1.0);
+                    let x = overview_rect.left() + progress * overview_rect.w();
+                    let color = match Some(sound_id) == preview_id {
+                        true => color::BLUE,
+                        false => color::WHITE,
+                    };
+                    let rgba: ui::color::Rgba = color.into();
+                    let (b, t) = (overview_rect.bottom(), overview_rect.top());
+                    let tris = quad(x - 1.0, x + 1.0, b, t)
+                        .iter()
+                        .map(|tri| {
+                            let points = tri.0;
+                            let colored = [(points[0], rgba), (points[1], rgba), (points[2], rgba)];
+                            widget::triangles::Triangle(colored)
+                        })
+                        .collect::<Vec<_>>();
+                    Some(tris)
+                })
+                .flat_map(|tris| tris)
+                .collect::<Vec<_>>();
+            widget::Triangles::multi_color(playhead_tris)
+                .with_bounding_rect(overview_rect)
+                .set(ids.source_editor_selected_wav_overview_playheads, ui);
+
             // A `Toggle` for whether or not the WAV should loop.
             let label = if wav.should_loop { "Looping: ON" } else { "Looping: OFF" };
-            let canvas_kid_area = ui.kid_area_of(ids.source_editor_selected_wav_canvas).unwrap();
             for new_loop in widget::Toggle::new(wav.should_loop)
                 .color(color::LIGHT_CHARCOAL)
                 .label(label)
                 .label_font_size(SMALL_FONT_SIZE)
-                .down(PAD * 2.0)
+                .down_from(ids.source_editor_selected_wav_overview, PAD)
                 .h(LOOP_TOGGLE_H)
                 .w(canvas_kid_area.w())
                 .align_middle_x_of(ids.source_editor_selected_wav_canvas)
@@ -770,27 +915,72 @@ pub fn set(
                 // with a looping version.
             }
 
+            // A `Toggle` for whether or not the WAV should be normalised along with its target.
+            let half_w = (canvas_kid_area.w() - PAD) / 2.0;
+            let label = if loudness_target.is_some() { "Normalise: ON" } else { "Normalise: OFF" };
+            for normalise in widget::Toggle::new(loudness_target.is_some())
+                .color(color::LIGHT_CHARCOAL)
+                .label(label)
+                .label_font_size(SMALL_FONT_SIZE)
+                .down(PAD)
+                .h(NORMALISE_H)
+                .w(half_w)
+                .align_left_of(ids.source_editor_selected_wav_loop_toggle)
+                .set(ids.source_editor_selected_wav_normalise_toggle, ui)
+            {
+                new_loudness_target = Some(match normalise {
+                    true => Some(audio::source::default::LOUDNESS_TARGET),
+                    false => None,
+                });
+            }
+
+            let target = loudness_target.unwrap_or(audio::source::default::LOUDNESS_TARGET);
+            let label = format!("Target: {:.1} LUFS", target);
+            let min = audio::source::wav::loudness::MIN_TARGET_LUFS;
+            let max = audio::source::wav::loudness::MAX_TARGET_LUFS;
+            for new_target in widget::Slider::new(target, min, max)
+                .label(&label)
+                .label_font_size(SMALL_FONT_SIZE)
+                .color(match loudness_target {
+                    Some(_) => ui::color::LIGHT_CHARCOAL,
+                    None => ui::color::DARK_CHARCOAL,
+                })
+                .h(NORMALISE_H)
+                .w(half_w)
+                .right(PAD)
+                .set(ids.source_editor_selected_wav_normalise_target, ui)
+            {
+                // The target only applies while normalisation is enabled.
+                if loudness_target.is_some() {
+                    new_loudness_target = Some(Some((new_target * 10.0).round() / 10.0));
+                }
+            }
+
             // The playback mode selection.
             widget::Text::new("Playback Mode")
                 .font_size(SMALL_FONT_SIZE)
-                .down(PAD)
+                .down_from(ids.source_editor_selected_wav_normalise_toggle, PAD)
                 .align_left_of(ids.source_editor_selected_wav_text)
                 .set(ids.source_editor_selected_wav_playback_text, ui);
-            let item_w = canvas_kid_area.w() * 0.5;
             let n_items = audio::source::wav::NUM_PLAYBACK_OPTIONS;
+            let item_w = canvas_kid_area.w() / n_items as Scalar;
             let (mut events, _scrollbar) = widget::ListSelect::single(n_items)
                 .flow_right()
                 .item_size(item_w)
                 .down(PAD * 2.0)
                 .h(PLAYBACK_MODE_H)
                 .w(canvas_kid_area.w())
-                .mid_bottom_of(ids.source_editor_selected_wav_canvas)
+                .mid_top_with_margin_on(
+                    ids.source_editor_selected_wav_canvas,
+                    WAV_CANVAS_H - PLAYBACK_MODE_H - PAD * 2.0,
+                )
                 .set(ids.source_editor_selected_wav_playback_list, ui);
 
             fn playback_from_index(i: usize) -> Option<Playback> {
                 match i {
                     0 => Some(Playback::Retrigger),
                     1 => Some(Playback::Continuous),
+                    2 => Some(Playback::Granular(Default::default())),
                     _ => None,
                 }
             }
@@ -799,6 +989,7 @@ pub fn set(
                 match *playback {
                     Playback::Retrigger => 0,
                     Playback::Continuous => 1,
+                    Playback::Granular(_) => 2,
                 }
             }
 
@@ -806,6 +997,7 @@ pub fn set(
                 match *playback {
                     Playback::Retrigger => "Retrigger",
                     Playback::Continuous => "Continuous",
+                    Playback::Granular(_) => "Granular",
                 }
             }
 
@@ -835,6 +1027,11 @@ pub fn set(
                     },
                     // If a selection has occurred.
                     Event::Selection(new_index) => {
+                        // Re-selecting granular mode should not reset its parameters.
+                        if new_index == selected_index {
+                            continue;
+                        }
+
                         let new_playback = playback_from_index(new_index)
                             .expect("no playback mode for index");
 
@@ -858,8 +1055,16 @@ pub fn set(
                             .audio_output
                             .send(move |audio| {
                                 audio.update_sounds_with_source(&id, move |_, sound| {
-                                    if let audio::source::SignalKind::Wav { ref mut playback, .. } = sound.signal.kind {
-                                        *playback = new_playback;
+                                    match sound.signal.kind {
+                                        audio::source::SignalKind::Wav { ref mut playback, .. } => {
+                                            *playback = new_playback;
+                                        },
+                                        audio::source::SignalKind::Granular { ref mut grains } => {
+                                            if let Playback::Granular(granular) = new_playback {
+                                                grains.params = granular;
+                                            }
+                                        },
+                                        _ => (),
                                     }
                                 });
                             })
@@ -869,6 +1074,377 @@ pub fn set(
                 }
             }
 
+            // Granular playback parameters.
+            if let Playback::Granular(granular) = wav.playback {
+                // A small macro to simplify updating each of the local, soundscape and audio output
+                // copies of the granular parameters.
+                //
+                // We use a macro for the same reason as `update_realtime` below.
+                macro_rules! update_granular {
+                    ($update_fn:expr) => {
+                        if let Playback::Granular(ref mut granular) = wav.playback {
+                            $update_fn(granular);
+                        }
+
+                        // Update the soundscape thread copy.
+                        channels
+                            .soundscape
+                            .send(move |soundscape| {
+                                soundscape.update_source(&id, |source| {
+                                    if let audio::source::Kind::Wav(ref mut wav) = source.kind {
+                                        if let Playback::Granular(ref mut granular) = wav.playback {
+                                            $update_fn(granular);
+                                        }
+                                    }
+                                });
+                            })
+                            .expect("failed to send granular update to soundscape thread");
+
+                        // Update all audio thread copies.
+                        channels
+                            .audio_output
+                            .send(move |audio| {
+                                audio.update_sounds_with_source(&id, move |_, sound| {
+                                    if let audio::source::SignalKind::Granular { ref mut grains } = sound.signal.kind {
+                                        $update_fn(&mut grains.params);
+                                    }
+                                });
+                            })
+                            .expect("failed to send granular update to audio output thread");
+                    };
+                }
+
+                use audio::source::wav::granular::{self, Granular};
+
+                widget::Text::new("Granular")
+                    .font_size(SMALL_FONT_SIZE)
+                    .down_from(ids.source_editor_selected_wav_playback_list, PAD * 2.0)
+                    .align_left_of(ids.source_editor_selected_wav_text)
+                    .set(ids.source_editor_selected_wav_granular_text, ui);
+
+                // The range of sizes from which each grain is chosen.
+                let range = granular.grain_size;
+                let label = format!(
+                    "Grain Size: {} to {}",
+                    duration_label(&range.min),
+                    duration_label(&range.max),
+                );
+                let min = granular::MIN_GRAIN_SIZE.ms();
+                let max = granular::MAX_GRAIN_SIZE.ms();
+                for (edge, value) in widget::RangeSlider::new(range.min.ms(), range.max.ms(), min, max)
+                    .skew(0.5)
+                    .label(&label)
+                    .label_font_size(SMALL_FONT_SIZE)
+                    .color(ui::color::LIGHT_CHARCOAL)
+                    .w(canvas_kid_area.w())
+                    .h(SLIDER_H)
+                    .align_middle_x_of(ids.source_editor_selected_wav_canvas)
+                    .down(PAD)
+                    .set(ids.source_editor_selected_wav_granular_grain_size, ui)
+                {
+                    let ms = Ms(value.round());
+                    match edge {
+                        widget::range_slider::Edge::Start => {
+                            update_granular!(|g: &mut Granular| g.grain_size.min = ms);
+                        },
+                        widget::range_slider::Edge::End => {
+                            update_granular!(|g: &mut Granular| g.grain_size.max = ms);
+                        },
+                    }
+                }
+
+                // The average number of grains triggered per second.
+                let label = format!("Density: {:.1} grains per second", granular.density);
+                let (min, max) = (granular::MIN_DENSITY, granular::MAX_DENSITY);
+                for new_density in widget::Slider::new(granular.density, min, max)
+                    .skew(0.5)
+                    .label(&label)
+                    .label_font_size(SMALL_FONT_SIZE)
+                    .w(canvas_kid_area.w())
+                    .h(SLIDER_H)
+                    .align_middle_x_of(ids.source_editor_selected_wav_canvas)
+                    .down(PAD)
+                    .set(ids.source_editor_selected_wav_granular_density, ui)
+                {
+                    update_granular!(|g: &mut Granular| g.density = new_density);
+                }
+
+                // How far back into the streamed audio grains may be read from.
+                let label = format!("Position Jitter: {:.2}", granular.position_jitter);
+                for new_jitter in widget::Slider::new(granular.position_jitter, 0.0, 1.0)
+                    .label(&label)
+                    .label_font_size(SMALL_FONT_SIZE)
+                    .w(canvas_kid_area.w())
+                    .h(SLIDER_H)
+                    .align_middle_x_of(ids.source_editor_selected_wav_canvas)
+                    .down(PAD)
+                    .set(ids.source_editor_selected_wav_granular_position_jitter, ui)
+                {
+                    update_granular!(|g: &mut Granular| g.position_jitter = new_jitter);
+                }
+
+                // The maximum deviation in pitch of each grain.
+                let label = format!("Pitch Jitter: {:.2} semitones", granular.pitch_jitter);
+                let max = granular::MAX_PITCH_JITTER;
+                for new_jitter in widget::Slider::new(granular.pitch_jitter, 0.0, max)
+                    .skew(0.5)
+                    .label(&label)
+                    .label_font_size(SMALL_FONT_SIZE)
+                    .w(canvas_kid_area.w())
+                    .h(SLIDER_H)
+                    .align_middle_x_of(ids.source_editor_selected_wav_canvas)
+                    .down(PAD)
+                    .set(ids.source_editor_selected_wav_granular_pitch_jitter, ui)
+                {
+                    update_granular!(|g: &mut Granular| g.pitch_jitter = new_jitter);
+                }
+            }
+
+            // The start offset of retriggered playback.
+            if let Playback::Retrigger = wav.playback {
+                // A small macro to simplify updating both the local and soundscape copies of the
+                // start offset.
+                //
+                // Only newly spawned sounds are affected, so the audio thread need not be updated.
+                macro_rules! update_start_offset {
+                    ($new_offset:expr) => {
+                        let new_offset = $new_offset;
+
+                        // Update the local copy.
+                        wav.start_offset = new_offset;
+
+                        // Update the soundscape thread copy.
+                        channels
+                            .soundscape
+                            .send(move |soundscape| {
+                                soundscape.update_source(&id, |source| {
+                                    if let audio::source::Kind::Wav(ref mut wav) = source.kind {
+                                        wav.start_offset = new_offset;
+                                    }
+                                });
+                            })
+                            .expect("failed to send start offset update to soundscape thread");
+                    };
+                }
+
+                widget::Text::new("Start Offset")
+                    .font_size(SMALL_FONT_SIZE)
+                    .down_from(ids.source_editor_selected_wav_playback_list, PAD * 2.0)
+                    .align_left_of(ids.source_editor_selected_wav_text)
+                    .set(ids.source_editor_selected_wav_start_offset_text, ui);
+
+                fn start_offset_from_index(i: usize, wav: &audio::source::Wav) -> Option<StartOffset> {
+                    // Carry the current offset across to the new mode where possible.
+                    let range = match wav.start_offset {
+                        StartOffset::Fixed(ms) => utils::Range { min: ms, max: ms },
+                        StartOffset::Random(range) => range,
+                        StartOffset::CueMarker => utils::Range { min: Ms(0.0), max: wav.duration_ms() },
+                    };
+                    match i {
+                        0 => Some(StartOffset::Fixed(range.min)),
+                        1 => Some(StartOffset::Random(range)),
+                        2 => Some(StartOffset::CueMarker),
+                        _ => None,
+                    }
+                }
+
+                fn index_from_start_offset(start_offset: &StartOffset) -> usize {
+                    match *start_offset {
+                        StartOffset::Fixed(_) => 0,
+                        StartOffset::Random(_) => 1,
+                        StartOffset::CueMarker => 2,
+                    }
+                }
+
+                let n_items = audio::source::wav::NUM_START_OFFSET_OPTIONS;
+                let item_w = canvas_kid_area.w() / n_items as Scalar;
+                let (mut events, _scrollbar) = widget::ListSelect::single(n_items)
+                    .flow_right()
+                    .item_size(item_w)
+                    .down(PAD * 2.0)
+                    .h(PLAYBACK_MODE_H)
+                    .w(canvas_kid_area.w())
+                    .align_middle_x_of(ids.source_editor_selected_wav_canvas)
+                    .set(ids.source_editor_selected_wav_start_offset_list, ui);
+
+                let selected_index = index_from_start_offset(&wav.start_offset);
+                while let Some(event) = events.next(ui, |i| i == selected_index) {
+                    use self::ui::widget::list_select::Event;
+                    match event {
+                        Event::Item(item) => {
+                            let label = start_offset_from_index(item.i, wav)
+                                .expect("no start offset mode for index")
+                                .label();
+                            let color = if item.i == selected_index {
+                                color::LIGHT_CHARCOAL
+                            } else {
+                                DARK_A
+                            };
+                            let button = widget::Button::new()
+                                .label(label)
+                                .label_font_size(SMALL_FONT_SIZE)
+                                .color(color);
+                            item.set(button, ui);
+                        },
+                        Event::Selection(new_index) => {
+                            if new_index == selected_index {
+                                continue;
+                            }
+                            let new_offset = start_offset_from_index(new_index, wav)
+                                .expect("no start offset mode for index");
+                            update_start_offset!(new_offset);
+                        },
+                        _ => (),
+                    }
+                }
+
+                let min = 0.0;
+                let max = wav.duration_ms().ms();
+                match wav.start_offset {
+                    StartOffset::Fixed(ms) => {
+                        let label = format!("Offset: {}", duration_label(&ms));
+                        for new_ms in widget::Slider::new(ms.ms(), min, max)
+                            .label(&label)
+                            .label_font_size(SMALL_FONT_SIZE)
+                            .color(ui::color::LIGHT_CHARCOAL)
+                            .w(canvas_kid_area.w())
+                            .h(SLIDER_H)
+                            .align_middle_x_of(ids.source_editor_selected_wav_canvas)
+                            .down(PAD)
+                            .set(ids.source_editor_selected_wav_start_offset_slider, ui)
+                        {
+                            update_start_offset!(StartOffset::Fixed(Ms(new_ms.round())));
+                        }
+                    },
+                    StartOffset::Random(range) => {
+                        let label = format!(
+                            "Offset: {} to {}",
+                            duration_label(&range.min),
+                            duration_label(&range.max),
+                        );
+                        for (edge, value) in widget::RangeSlider::new(range.min.ms(), range.max.ms(), min, max)
+                            .label(&label)
+                            .label_font_size(SMALL_FONT_SIZE)
+                            .color(ui::color::LIGHT_CHARCOAL)
+                            .w(canvas_kid_area.w())
+                            .h(SLIDER_H)
+                            .align_middle_x_of(ids.source_editor_selected_wav_canvas)
+                            .down(PAD)
+                            .set(ids.source_editor_selected_wav_start_offset_slider, ui)
+                        {
+                            let ms = Ms(value.round());
+                            let mut new_range = range;
+                            match edge {
+                                widget::range_slider::Edge::Start => new_range.min = ms,
+                                widget::range_slider::Edge::End => new_range.max = ms,
+                            }
+                            update_start_offset!(StartOffset::Random(new_range));
+                        }
+                    },
+                    StartOffset::CueMarker => {
+                        let text = match wav.cue_points.len() {
+                            0 => "No cue markers found, starting from the beginning".to_string(),
+                            1 => "1 cue marker".to_string(),
+                            n => format!("{} cue markers", n),
+                        };
+                        widget::Text::new(&text)
+                            .font_size(SMALL_FONT_SIZE)
+                            .align_left_of(ids.source_editor_selected_wav_text)
+                            .down(PAD * 1.5)
+                            .set(ids.source_editor_selected_wav_start_offset_cue_text, ui);
+                    },
+                }
+            }
+
+            // The loop region and the crossfade across its seam.
+            if wav.is_endless() {
+                // A small macro to simplify updating both the local and soundscape copies of the
+                // loop.
+                //
+                // The loop is read when a sound is spawned, so the audio thread need not be updated.
+                macro_rules! update_loop {
+                    ($update_fn:expr) => {
+                        $update_fn(wav);
+
+                        // Update the soundscape thread copy.
+                        channels
+                            .soundscape
+                            .send(move |soundscape| {
+                                soundscape.update_source(&id, |source| {
+                                    if let audio::source::Kind::Wav(ref mut wav) = source.kind {
+                                        $update_fn(wav);
+                                    }
+                                });
+                            })
+                            .expect("failed to send loop update to soundscape thread");
+                    };
+                }
+
+                // The loop widgets follow the last of the playback mode widgets.
+                let last_playback_id = match wav.playback {
+                    Playback::Retrigger => match wav.start_offset {
+                        StartOffset::CueMarker => ids.source_editor_selected_wav_start_offset_cue_text,
+                        _ => ids.source_editor_selected_wav_start_offset_slider,
+                    },
+                    Playback::Continuous => ids.source_editor_selected_wav_playback_list,
+                    Playback::Granular(_) => ids.source_editor_selected_wav_granular_pitch_jitter,
+                };
+
+                widget::Text::new("Loop")
+                    .font_size(SMALL_FONT_SIZE)
+                    .down_from(last_playback_id, PAD * 2.0)
+                    .align_left_of(ids.source_editor_selected_wav_text)
+                    .set(ids.source_editor_selected_wav_loop_text, ui);
+
+                // The range of the WAV that is looped.
+                let duration_ms = wav.duration_ms();
+                let (start, end) = match wav.loop_points {
+                    None => (Ms(0.0), duration_ms),
+                    Some(range) => (range.min.to_ms(wav.sample_hz), range.max.to_ms(wav.sample_hz)),
+                };
+                let label = format!("Region: {} to {}", duration_label(&start), duration_label(&end));
+                for (edge, value) in widget::RangeSlider::new(start.ms(), end.ms(), 0.0, duration_ms.ms())
+                    .label(&label)
+                    .label_font_size(SMALL_FONT_SIZE)
+                    .color(ui::color::LIGHT_CHARCOAL)
+                    .w(canvas_kid_area.w())
+                    .h(SLIDER_H)
+                    .align_middle_x_of(ids.source_editor_selected_wav_canvas)
+                    .down(PAD)
+                    .set(ids.source_editor_selected_wav_loop_points, ui)
+                {
+                    let frames = Ms(value).to_samples(wav.sample_hz);
+                    let mut points = utils::Range {
+                        min: start.to_samples(wav.sample_hz),
+                        max: end.to_samples(wav.sample_hz),
+                    };
+                    match edge {
+                        widget::range_slider::Edge::Start => points.min = frames,
+                        widget::range_slider::Edge::End => points.max = frames,
+                    }
+                    update_loop!(|wav: &mut audio::source::Wav| wav.loop_points = Some(points));
+                }
+
+                // The duration of the crossfade across the seam, limited to half the region.
+                let crossfade = wav.loop_crossfade;
+                let max = ((end - start).ms() / 2.0).max(0.0);
+                let label = format!("Crossfade: {}", duration_label(&crossfade));
+                for new_ms in widget::Slider::new(crossfade.ms().min(max), 0.0, max)
+                    .skew(0.5)
+                    .label(&label)
+                    .label_font_size(SMALL_FONT_SIZE)
+                    .color(ui::color::LIGHT_CHARCOAL)
+                    .w(canvas_kid_area.w())
+                    .h(SLIDER_H)
+                    .align_middle_x_of(ids.source_editor_selected_wav_canvas)
+                    .down(PAD)
+                    .set(ids.source_editor_selected_wav_loop_crossfade, ui)
+                {
+                    let new_crossfade = Ms(new_ms.round());
+                    update_loop!(|wav: &mut audio::source::Wav| wav.loop_crossfade = new_crossfade);
+                }
+            }
+
             (ids.source_editor_selected_wav_canvas, wav.channels)
         }
         audio::source::Kind::Realtime(ref mut realtime) => {
@@ -1001,6 +1577,34 @@ pub fn set(
         }
     };
 
+    // Apply any change to the WAV's loudness normalisation.
+    if let Some(new_loudness_target) = new_loudness_target {
+        // Update the local copy.
+        let source = sources.get_mut(&id).unwrap();
+        source.loudness_target = new_loudness_target;
+        let gain = source.normalisation_gain();
+
+        // Update the soundscape copy.
+        channels
+            .soundscape
+            .send(move |soundscape| {
+                soundscape.update_source(&id, |source| {
+                    source.loudness_target = new_loudness_target;
+                });
+            })
+            .expect("failed to send source loudness target to soundscape thread");
+
+        // Update the audio output copies.
+        channels
+            .audio_output
+            .send(move |audio| {
+                audio.update_sounds_with_source(&id, move |_, sound| {
+                    sound.normalisation_gain = gain;
+                });
+            })
+            .expect("failed to send source normalisation gain to audio output thread");
+    }
+
     // Channel layout widgets.
     widget::Canvas::new()
         .down_from(kind_canvas_id, PAD)
@@ -1314,7 +1918,14 @@ pub fn set(
                 playback_duration,
                 attack_duration,
                 release_duration,
+                attack_curve,
+                release_curve,
+                hold_duration,
+                playback_rate,
+                weight,
                 movement,
+                placement,
+                schedule: _,
             } = soundscape;
 
             // A canvas on which installation selection widgets are instantiated.
@@ -1660,7 +2271,7 @@ pub fn set(
             let skew = sources[&id].kind.playback_duration_skew();
             let max_duration = match sources[&id].kind {
                 audio::source::Kind::Realtime(ref realtime) => realtime.duration,
-                audio::source::Kind::Wav(ref wav) => match wav.should_loop {
+                audio::source::Kind::Wav(ref wav) => match wav.is_endless() {
                     true => audio::source::MAX_PLAYBACK_DURATION,
                     false => wav.duration.to_ms(audio::SAMPLE_RATE),
                 }
@@ -1766,6 +2377,62 @@ pub fn set(
                     .expect("failed to send source attack duration to soundscape thread");
             }
 
+            ///////////////////
+            // Hold Duration //
+            ///////////////////
+
+            widget::Text::new("Hold Duration")
+                .align_left()
+                .down(PAD * 2.0)
+                .font_size(SMALL_FONT_SIZE)
+                .set(ids.source_editor_selected_soundscape_hold_duration_text, ui);
+
+            let min_duration = Ms(0.0);
+            let max_duration = audio::source::MAX_HOLD_DURATION;
+            let min_duration_ms = min_duration.ms();
+            let max_duration_ms = max_duration.ms();
+            let range = hold_duration;
+            let label = format!("{} to {}", duration_label(&range.min), duration_label(&range.max));
+            let start = range.min.ms();
+            let end = range.max.ms();
+            for (edge, value) in range_slider(start, end, min_duration_ms, max_duration_ms)
+                .skew(audio::source::skew::HOLD)
+                .align_left()
+                .label(&label)
+                .down(PAD * 2.0)
+                .set(ids.source_editor_selected_soundscape_hold_duration_slider, ui)
+            {
+                let duration = {
+                    let (unit, value) = utils::human_readable_ms(&Ms(value as _));
+                    let (unit, value) = unit.to_finer_unit(value);
+                    unit.to_ms(value.round())
+                };
+
+                // Update the local copy.
+                let new_duration = {
+                    let soundscape = expect_soundscape_mut(sources, &id);
+                    match edge {
+                        widget::range_slider::Edge::Start => {
+                            soundscape.hold_duration.min = duration;
+                        },
+                        widget::range_slider::Edge::End => {
+                            soundscape.hold_duration.max = duration;
+                        }
+                    }
+                    soundscape.hold_duration
+                };
+
+                // Update the soundscape copy.
+                channels
+                    .soundscape
+                    .send(move |soundscape| {
+                        soundscape.update_source(&id, |source| {
+                            source.hold_duration = new_duration;
+                        });
+                    })
+                    .expect("failed to send source hold duration to soundscape thread");
+            }
+
             //////////////////////
             // Release Duration //
             //////////////////////
@@ -1822,6 +2489,180 @@ pub fn set(
                     .expect("failed to send source release duration to soundscape thread");
             }
 
+            /////////////////
+            // Fade Curves //
+            /////////////////
+
+            widget::Text::new("Fade Curves")
+                .align_left()
+                .down(PAD * 2.0)
+                .font_size(SMALL_FONT_SIZE)
+                .set(ids.source_editor_selected_soundscape_fade_curves_text, ui);
+
+            use audio::source::envelope::Curve;
+            let curve_labels = |prefix: &str| {
+                (0..Curve::VARIANT_COUNT)
+                    .map(|i| format!("{}: {}", prefix, Curve::label_from_index(i)))
+                    .collect::<Vec<_>>()
+            };
+            let curve_w = ui.kid_area_of(ids.source_editor_selected_soundscape_canvas)
+                .unwrap()
+                .w() / 2.0 - PAD / 2.0;
+
+            // The curve along which sounds fade in (to the left).
+            let attack_curve_labels = curve_labels("In");
+            for new_index in widget::DropDownList::new(&attack_curve_labels, Some(attack_curve.to_index()))
+                .down(PAD * 2.0)
+                .align_left()
+                .label_font_size(SMALL_FONT_SIZE)
+                .scrollbar_on_top()
+                .max_visible_items(Curve::VARIANT_COUNT)
+                .w(curve_w)
+                .h(SLIDER_H)
+                .set(ids.source_editor_selected_soundscape_attack_curve, ui)
+            {
+                let new_curve = match Curve::from_index(new_index) {
+                    None => continue,
+                    Some(curve) => curve,
+                };
+
+                // Update the local copy.
+                expect_soundscape_mut(sources, &id).attack_curve = new_curve;
+
+                // Update the soundscape copy.
+                channels
+                    .soundscape
+                    .send(move |soundscape| {
+                        soundscape.update_source(&id, |source| {
+                            source.attack_curve = new_curve;
+                        });
+                    })
+                    .expect("failed to send source attack curve to soundscape thread");
+            }
+
+            // The curve along which sounds fade out (to the right).
+            let release_curve_labels = curve_labels("Out");
+            for new_index in widget::DropDownList::new(&release_curve_labels, Some(release_curve.to_index()))
+                .right(PAD)
+                .align_top()
+                .label_font_size(SMALL_FONT_SIZE)
+                .scrollbar_on_top()
+                .max_visible_items(Curve::VARIANT_COUNT)
+                .w(curve_w)
+                .h(SLIDER_H)
+                .set(ids.source_editor_selected_soundscape_release_curve, ui)
+            {
+                let new_curve = match Curve::from_index(new_index) {
+                    None => continue,
+                    Some(curve) => curve,
+                };
+
+                // Update the local copy.
+                expect_soundscape_mut(sources, &id).release_curve = new_curve;
+
+                // Update the soundscape copy.
+                channels
+                    .soundscape
+                    .send(move |soundscape| {
+                        soundscape.update_source(&id, |source| {
+                            source.release_curve = new_curve;
+                        });
+                    })
+                    .expect("failed to send source release curve to soundscape thread");
+            }
+
+            ///////////////////
+            // Playback Rate //
+            ///////////////////
+
+            widget::Text::new("Playback Rate")
+                .align_left_of(ids.source_editor_selected_soundscape_fade_curves_text)
+                .down(PAD * 2.0)
+                .font_size(SMALL_FONT_SIZE)
+                .set(ids.source_editor_selected_soundscape_playback_rate_text, ui);
+
+            // The rate is displayed and edited in octaves so that the slider is perceptually linear.
+            let range = playback_rate;
+            let label = format!("{:.2}x to {:.2}x", range.min, range.max);
+            let min_octaves = audio::source::MIN_PLAYBACK_RATE.log2();
+            let max_octaves = audio::source::MAX_PLAYBACK_RATE.log2();
+            let start = range.min.log2();
+            let end = range.max.log2();
+            for (edge, value) in range_slider(start, end, min_octaves, max_octaves)
+                .align_left()
+                .label(&label)
+                .down(PAD * 2.0)
+                .set(ids.source_editor_selected_soundscape_playback_rate_slider, ui)
+            {
+                let rate = 2.0f64.powf(value);
+
+                // Update the local copy.
+                let new_rate = {
+                    let soundscape = expect_soundscape_mut(sources, &id);
+                    match edge {
+                        widget::range_slider::Edge::Start => {
+                            soundscape.playback_rate.min = rate;
+                        },
+                        widget::range_slider::Edge::End => {
+                            soundscape.playback_rate.max = rate;
+                        }
+                    }
+                    soundscape.playback_rate
+                };
+
+                // Update the soundscape copy.
+                channels
+                    .soundscape
+                    .send(move |soundscape| {
+                        soundscape.update_source(&id, |source| {
+                            source.playback_rate = new_rate;
+                        });
+                    })
+                    .expect("failed to send source playback rate to soundscape thread");
+            }
+
+            ////////////
+            // Weight //
+            ////////////
+
+            widget::Text::new("Weight")
+                .align_left()
+                .down(PAD * 2.0)
+                .font_size(SMALL_FONT_SIZE)
+                .set(ids.source_editor_selected_soundscape_weight_text, ui);
+
+            // The weight is edited in powers of ten so that rare and common weights are both
+            // easy to reach.
+            let label = format!("{:.2}x as likely", weight);
+            let min = audio::source::MIN_WEIGHT.log10();
+            let max = audio::source::MAX_WEIGHT.log10();
+            let value = weight.max(audio::source::MIN_WEIGHT).log10();
+            for value in widget::Slider::new(value, min, max)
+                .label(&label)
+                .label_font_size(SMALL_FONT_SIZE)
+                .color(ui::color::LIGHT_CHARCOAL)
+                .kid_area_w_of(ids.source_editor_selected_soundscape_canvas)
+                .h(SLIDER_H)
+                .align_left()
+                .down(PAD * 2.0)
+                .set(ids.source_editor_selected_soundscape_weight_slider, ui)
+            {
+                let new_weight = 10.0f64.powf(value);
+
+                // Update the local copy.
+                expect_soundscape_mut(sources, &id).weight = new_weight;
+
+                // Update the soundscape copy.
+                channels
+                    .soundscape
+                    .send(move |soundscape| {
+                        soundscape.update_source(&id, |source| {
+                            source.weight = new_weight;
+                        });
+                    })
+                    .expect("failed to send source weight to soundscape thread");
+            }
+
             //////////////////////////////////
             // Soundscape Group Assignments //
             //////////////////////////////////
@@ -2311,6 +3152,92 @@ pub fn set(
                             })
                             .expect("failed to send movement update to soundscape thread");
                     }
+
+                    ///////////////
+                    // Placement //
+                    ///////////////
+
+                    widget::Text::new("Initial Placement")
+                        .mid_left_of(ids.source_editor_selected_soundscape_canvas)
+                        .down(PAD * 2.0)
+                        .font_size(SMALL_FONT_SIZE)
+                        .set(ids.source_editor_selected_soundscape_movement_agent_placement_text, ui);
+
+                    // Collect the speakers into alphabetical order for the nearest speaker.
+                    let mut speakers_vec: Vec<_> = speakers
+                        .iter()
+                        .map(|(&id, speaker)| (id, &speaker.name))
+                        .collect();
+                    speakers_vec.sort_by(|a, b| a.1.cmp(b.1));
+
+                    let placement_labels: Vec<_> = (0..audio::source::Placement::VARIANT_COUNT)
+                        .map(audio::source::Placement::label_from_index)
+                        .collect();
+                    let placement_speaker = match placement {
+                        audio::source::Placement::NearestSpeaker(speaker) => Some(speaker),
+                        _ => None,
+                    };
+                    let list_w = match placement_speaker {
+                        Some(_) => (canvas_kid_area.w() - PAD) / 2.0,
+                        None => canvas_kid_area.w(),
+                    };
+                    let selected_index = Some(placement.to_index());
+                    let mut new_placement = None;
+                    for index in widget::DropDownList::new(&placement_labels, selected_index)
+                        .align_left()
+                        .down(PAD * 2.0)
+                        .w(list_w)
+                        .h(ITEM_HEIGHT)
+                        .color(DARK_A)
+                        .border_color(color::LIGHT_CHARCOAL)
+                        .label_font_size(SMALL_FONT_SIZE)
+                        .set(ids.source_editor_selected_soundscape_movement_agent_placement_list, ui)
+                    {
+                        // Default to the first speaker when placing near a speaker.
+                        let first_speaker = speakers_vec.get(0).map(|s| s.0);
+                        let speaker = match placement_speaker.or(first_speaker) {
+                            Some(speaker) => speaker,
+                            None => continue,
+                        };
+                        new_placement = audio::source::Placement::from_index(index, speaker);
+                    }
+
+                    // A list for selecting the speaker near which sounds are placed.
+                    if let Some(speaker) = placement_speaker {
+                        let speaker_labels: Vec<_> = speakers_vec.iter().map(|s| s.1).collect();
+                        let speaker_index = speakers_vec.iter().position(|s| s.0 == speaker);
+                        for index in widget::DropDownList::new(&speaker_labels, speaker_index)
+                            .right(PAD)
+                            .align_top_of(ids.source_editor_selected_soundscape_movement_agent_placement_list)
+                            .w(list_w)
+                            .h(ITEM_HEIGHT)
+                            .scrollbar_on_top()
+                            .max_visible_items(5)
+                            .color(DARK_A)
+                            .border_color(color::LIGHT_CHARCOAL)
+                            .label_font_size(SMALL_FONT_SIZE)
+                            .set(ids.source_editor_selected_soundscape_movement_agent_placement_speaker, ui)
+                        {
+                            let speaker = speakers_vec[index].0;
+                            new_placement = Some(audio::source::Placement::NearestSpeaker(speaker));
+                        }
+                    }
+
+                    if let Some(placement) = new_placement {
+                        // Update local copy.
+                        let soundscape = expect_soundscape_mut(sources, &id);
+                        soundscape.placement = placement;
+
+                        // Update the soundscape thread copy.
+                        channels
+                            .soundscape
+                            .send(move |soundscape| {
+                                soundscape.update_source(&id, |source| {
+                                    source.placement = placement;
+                                });
+                            })
+                            .expect("failed to send placement update to soundscape thread");
+                    }
                 },
 
                 // Ngon-specific widgets.
@@ -6,6 +6,7 @@
 
 use serde::{Deserialize, Deserializer};
 use slug::slugify;
+use time_calc::Ms;
 use utils::Range;
 
 /// All known beyond perception installations (used by default).
@@ -90,12 +91,61 @@ impl Default for Installation {
 pub struct Soundscape {
     #[serde(default = "default::simultaneous_sounds")]
     pub simultaneous_sounds: Range<usize>,
+    /// How occupancy values received via OSC shift the density of the installation.
+    #[serde(default)]
+    pub occupancy: Occupancy,
+}
+
+/// Describes how the occupancy of an installation affects its target number of sounds.
+///
+/// Occupancy values (e.g. from a people counter) are received via OSC and normalised by `max`.
+/// The normalised occupancy shifts the target number of sounds within the installation's
+/// simultaneous sounds range, so that empty spaces quieten and busy spaces come alive.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Occupancy {
+    /// The occupancy value at or above which the installation is considered full.
+    #[serde(default = "default::occupancy_max")]
+    pub max: f64,
+    /// The degree to which occupancy overrides the slowly wandering target number of sounds.
+    ///
+    /// `0.0` ignores occupancy entirely while `1.0` determines the target by occupancy alone.
+    #[serde(default = "default::occupancy_influence")]
+    pub influence: f64,
+    /// The time taken to move roughly two thirds of the way towards a newly received value.
+    #[serde(default = "default::occupancy_smoothing")]
+    pub smoothing: Ms,
+    /// The duration without a new value after which occupancy is considered unknown.
+    ///
+    /// Once timed out, the influence of occupancy fades out over the smoothing duration.
+    #[serde(default = "default::occupancy_timeout")]
+    pub timeout: Ms,
 }
 
 impl Default for Soundscape {
     fn default() -> Self {
         let simultaneous_sounds = default::SIMULTANEOUS_SOUNDS;
-        Soundscape { simultaneous_sounds }
+        let occupancy = Default::default();
+        Soundscape { simultaneous_sounds, occupancy }
+    }
+}
+
+impl Default for Occupancy {
+    fn default() -> Self {
+        let max = default::OCCUPANCY_MAX;
+        let influence = default::OCCUPANCY_INFLUENCE;
+        let smoothing = default::OCCUPANCY_SMOOTHING;
+        let timeout = default::OCCUPANCY_TIMEOUT;
+        Occupancy { max, influence, smoothing, timeout }
+    }
+}
+
+impl Occupancy {
+    /// Normalise the given occupancy value to the range `0.0..=1.0`.
+    pub fn normalise(&self, value: f64) -> f64 {
+        if self.max <= 0.0 {
+            return 0.0;
+        }
+        (value / self.max).min(1.0).max(0.0)
     }
 }
 
@@ -123,9 +173,14 @@ pub fn beyond_perception_default_num_computers(name: &str) -> Option<usize> {
 
 /// Default soundscape constraints.
 pub mod default {
-    use utils::Range;
+    use time_calc::Ms;
+    use utils::{Range, MIN_MS, SEC_MS};
 
     pub const SIMULTANEOUS_SOUNDS: Range<usize> = Range { min: 1, max: 8 };
+    pub const OCCUPANCY_MAX: f64 = 20.0;
+    pub const OCCUPANCY_INFLUENCE: f64 = 0.75;
+    pub const OCCUPANCY_SMOOTHING: Ms = Ms(30.0 * SEC_MS);
+    pub const OCCUPANCY_TIMEOUT: Ms = Ms(5.0 * MIN_MS);
 
     pub fn name() -> &'static str {
         "<unnamed>"
@@ -138,6 +193,22 @@ pub mod default {
     pub fn simultaneous_sounds() -> Range<usize> {
         SIMULTANEOUS_SOUNDS
     }
+
+    pub fn occupancy_max() -> f64 {
+        OCCUPANCY_MAX
+    }
+
+    pub fn occupancy_influence() -> f64 {
+        OCCUPANCY_INFLUENCE
+    }
+
+    pub fn occupancy_smoothing() -> Ms {
+        OCCUPANCY_SMOOTHING
+    }
+
+    pub fn occupancy_timeout() -> Ms {
+        OCCUPANCY_TIMEOUT
+    }
 }
 
 /// State related to the computers available to an installation.
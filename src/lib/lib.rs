@@ -5,6 +5,7 @@
 extern crate conrod_core;
 #[macro_use]
 extern crate conrod_derive;
+extern crate chrono;
 extern crate crossbeam;
 #[macro_use]
 extern crate custom_derive;
@@ -65,6 +66,7 @@ struct Model {
     config: Config,
     audio_monitor: gui::monitor::Monitor,
     wav_reader: audio::source::wav::reader::Handle,
+    wav_analysis: audio::source::wav::analysis::Handle,
 }
 
 // The path to the server's config file.
@@ -111,8 +113,17 @@ fn model(app: &App) -> Model {
     let (audio_monitor, audio_monitor_tx, audio_monitor_rx) = gui::monitor::spawn(app_proxy)
         .expect("failed to spawn audio_monitor thread");
 
-    // Spawn the thread used for reading wavs.
-    let wav_reader = audio::source::wav::reader::spawn();
+    // Spawn the thread used for reading wavs, decoding short wavs into memory.
+    let wav_memory_cache = {
+        use audio::source::wav::memory::{self, BYTES_PER_MB};
+        let budget_bytes = config.wav_memory_cache_mb * BYTES_PER_MB;
+        let max_wav_bytes = config.wav_memory_cache_max_wav_mb * BYTES_PER_MB;
+        memory::Cache::new(budget_bytes, max_wav_bytes)
+    };
+    let wav_reader = audio::source::wav::reader::spawn(wav_memory_cache);
+
+    // Spawn the thread used for analysing wavs in the background.
+    let wav_analysis = audio::source::wav::analysis::spawn();
 
     // A channel for sending and receiving on the soundscape thread.
     let (soundscape_tx, soundscape_rx) = mpsc::channel();
@@ -130,7 +141,7 @@ fn model(app: &App) -> Model {
     let max_supported_input_channels = input_device.max_supported_input_channels();
     let audio_input_channels = std::cmp::min(max_supported_input_channels, audio::MAX_CHANNELS);
     println!("Selected Input Device: {:?}", input_device.name());
-    let audio_input_model = audio::input::Model::new();
+    let audio_input_model = audio::input::Model::new(audio_monitor_tx.clone());
     let audio_input_stream = audio_host
         .new_input_stream(audio_input_model)
         .capture(audio::input::capture)
@@ -168,6 +179,9 @@ fn model(app: &App) -> Model {
     // sounds and sending them to the audio thread.
     let sound_id_gen = audio::sound::IdGenerator::new();
 
+    // A channel for sending the soundscape's play history to the GUI.
+    let (history_tx, history_rx) = mpsc::channel();
+
     // Spawn the composer thread.
     let soundscape = soundscape::spawn(
         frame_count.clone(),
@@ -177,6 +191,7 @@ fn model(app: &App) -> Model {
         wav_reader.clone(),
         audio_input_stream.clone(),
         audio_output_stream.clone(),
+        history_tx,
         sound_id_gen.clone(),
     );
 
@@ -196,9 +211,11 @@ fn model(app: &App) -> Model {
         control_rx,
         soundscape.clone(),
         wav_reader.clone(),
+        wav_analysis.clone(),
         audio_input_stream.clone(),
         audio_output_stream.clone(),
         audio_monitor_rx,
+        history_rx,
     );
     let gui = gui::Model::new(
         &assets,
@@ -229,6 +246,7 @@ fn model(app: &App) -> Model {
         gui,
         audio_monitor,
         wav_reader,
+        wav_analysis,
     }
 }
 
@@ -251,6 +269,7 @@ fn exit(app: &App, model: Model) {
         soundscape,
         audio_monitor,
         wav_reader,
+        wav_analysis,
         ..
     } = model;
 
@@ -290,4 +309,8 @@ fn exit(app: &App, model: Model) {
     // Send exit signal to the wav reader thread.
     let wav_reader_thread = wav_reader.exit().expect("failed to exit wav_reader thread");
     wav_reader_thread.join().expect("failed to join the wav_reader thread when exiting");
+
+    // Send exit signal to the wav analysis thread.
+    let wav_analysis_thread = wav_analysis.exit().expect("failed to exit wav_analysis thread");
+    wav_analysis_thread.join().expect("failed to join the wav_analysis thread when exiting");
 }
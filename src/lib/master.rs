@@ -19,6 +19,12 @@ pub struct Master {
     /// It is stored as a square for faster calculations
     #[serde(default = "default_proximity_limit")]
     pub proximity_limit_2: Metres,
+    /// The duration after which a scribble stream that stops receiving positions is ended.
+    #[serde(default = "default_scribble_timeout")]
+    pub scribble_timeout: Ms,
+    /// The duration over which the sound of an ended scribble stream fades out.
+    #[serde(default = "default_scribble_release")]
+    pub scribble_release: Ms,
 }
 
 impl Default for Master {
@@ -27,8 +33,10 @@ impl Default for Master {
         let realtime_source_latency = default_realtime_source_latency();
         let dbap_rolloff_db = default_dbap_rolloff_db();
         let proximity_limit_2 = default_proximity_limit();
+        let scribble_timeout = default_scribble_timeout();
+        let scribble_release = default_scribble_release();
         Master { volume, realtime_source_latency, 
-            dbap_rolloff_db, proximity_limit_2 }
+            dbap_rolloff_db, proximity_limit_2, scribble_timeout, scribble_release }
     }
 }
 
@@ -47,3 +55,11 @@ fn default_dbap_rolloff_db() -> f64 {
 fn default_proximity_limit() -> Metres {
     audio::DEFAULT_PROXIMITY_LIMIT_2
 }
+
+fn default_scribble_timeout() -> Ms {
+    audio::DEFAULT_SCRIBBLE_TIMEOUT
+}
+
+fn default_scribble_release() -> Ms {
+    audio::DEFAULT_SCRIBBLE_RELEASE
+}
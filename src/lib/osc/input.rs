@@ -1,13 +1,28 @@
-use nannou_osc::{self as osc, Type::Float};
+use metres::Metres;
+use time_calc::Ms;
+use nannou::geom::{pt2, Point2};
+use nannou_osc::{self as osc, Type::{Float, Int}};
 use std;
 use std::net::SocketAddr;
 use std::sync::mpsc;
 
 const BEYOND_PERCEPTION_ADDR: &'static str = "/bp";
 const SOURCE_VOLUME_ADDR: &'static str = "/source_volume";
+const SOURCE_PLAYBACK_RATE_ADDR: &'static str = "/source_playback_rate";
 const MASTER_VOLUME_ADDR: &'static str = "/master_volume";
 const PLAY_SOUNDSCAPE: &'static str = "/play_soundscape";
 const PAUSE_SOUNDSCAPE: &'static str = "/pause_soundscape";
+const INTERACTIVE_PLAY_ADDR: &'static str = "/interactive_play";
+const INTERACTIVE_POSITION_ADDR: &'static str = "/interactive_position";
+const INTERACTIVE_VOLUME_ADDR: &'static str = "/interactive_volume";
+const INTERACTIVE_STOP_ADDR: &'static str = "/interactive_stop";
+const SCRIBBLE_ADDR: &'static str = "/scribble";
+const SCRIBBLE_END_ADDR: &'static str = "/scribble_end";
+const SCENE_ADDR: &'static str = "/scene";
+const OCCUPANCY_ADDR: &'static str = "/occupancy";
+
+/// The address of the reply sent with the handle of each newly spawned interactive sound.
+pub const INTERACTIVE_HANDLE_ADDR: &'static str = "/bp/interactive_handle";
 
 /// A record of a received message.
 #[derive(Debug)]
@@ -22,9 +37,18 @@ pub struct Log {
 #[derive(Clone, Debug)]
 pub enum Control {
     SourceVolume(SourceVolume),
+    SourcePlaybackRate(SourcePlaybackRate),
     MasterVolume(MasterVolume),
     PauseSoundscape,
     PlaySoundscape,
+    InteractivePlay(InteractivePlay),
+    InteractivePosition(InteractivePosition),
+    InteractiveVolume(InteractiveVolume),
+    InteractiveStop(InteractiveStop),
+    ScribblePosition(ScribblePosition),
+    ScribbleEnd(ScribbleEnd),
+    RecallScene(RecallScene),
+    Occupancy(Occupancy),
 }
 
 /// An OSC input message that was parsed as the master volume for the exhibition.
@@ -48,6 +72,173 @@ pub struct SourceVolume {
     pub volume: f32,
 }
 
+/// An OSC input message that was parsed as the playback rate for the currently playing sounds of a
+/// source.
+///
+/// Expects the following OSC message:
+///
+/// - Address: "/bp/source_playback_rate/<source_name>"
+/// - Arguments: `Float` where `Float` is the playback rate and `1.0` is the natural rate.
+///
+/// Only affects sounds that are currently playing. The rate of newly spawned sounds is still
+/// determined by the source's soundscape constraints.
+#[derive(Clone, Debug)]
+pub struct SourcePlaybackRate {
+    /// The name of the source whose sounds this will be applied to.
+    pub name: String,
+    /// The rate at which the sounds should be played back.
+    pub rate: f32,
+}
+
+/// An OSC input message requesting that a sound be spawned from the named source.
+///
+/// Expects one of the following OSC messages:
+///
+/// - Address: "/bp/interactive_play/<source_name>"
+/// - Arguments: `Float`, `Float` where the floats are the x and y position in metres.
+///
+/// or
+///
+/// - Address: "/bp/interactive_play/<source_name>"
+/// - Arguments: `String` where `String` is the name of the installation at whose centroid the
+///   sound should be played.
+///
+/// Either may be followed by an optional `Int` specifying the port to which the reply should be
+/// sent. By default the reply is sent to the port from which the message was received.
+///
+/// Once spawned, a "/bp/interactive_handle" message is sent back to the sender with the arguments
+/// `Int`, `String` where `Int` is the handle of the new sound and `String` is the source name.
+#[derive(Clone, Debug)]
+pub struct InteractivePlay {
+    /// The name of the source from which the sound will be spawned.
+    pub name: String,
+    /// Where the sound should be played.
+    pub location: InteractiveLocation,
+    /// The address to which the handle of the spawned sound will be sent.
+    pub reply_addr: SocketAddr,
+}
+
+/// Where an interactive sound should be played.
+#[derive(Clone, Debug)]
+pub enum InteractiveLocation {
+    /// An explicit position within the exhibition in metres.
+    Point(Point2<Metres>),
+    /// The centroid of the speakers assigned to the installation with the given name.
+    Installation(String),
+}
+
+/// An OSC input message that was parsed as a new position for an interactive sound.
+///
+/// Expects the following OSC message:
+///
+/// - Address: "/bp/interactive_position/<handle>"
+/// - Arguments: `Float`, `Float` where the floats are the x and y position in metres.
+#[derive(Clone, Debug)]
+pub struct InteractivePosition {
+    /// The handle returned when the sound was spawned.
+    pub handle: i32,
+    /// The new position of the sound.
+    pub point: Point2<Metres>,
+}
+
+/// An OSC input message that was parsed as the volume for an interactive sound.
+///
+/// Expects the following OSC message:
+///
+/// - Address: "/bp/interactive_volume/<handle>"
+/// - Arguments: `Float` where `Float` is the volume.
+#[derive(Clone, Debug)]
+pub struct InteractiveVolume {
+    /// The handle returned when the sound was spawned.
+    pub handle: i32,
+    /// The value that will be assigned to the sound's `volume` field.
+    pub volume: f32,
+}
+
+/// An OSC input message requesting that an interactive sound be stopped.
+///
+/// Expects the following OSC message:
+///
+/// - Address: "/bp/interactive_stop/<handle>"
+/// - Arguments: None.
+#[derive(Clone, Debug)]
+pub struct InteractiveStop {
+    /// The handle returned when the sound was spawned.
+    pub handle: i32,
+}
+
+/// An OSC input message that was parsed as the latest position of a scribble.
+///
+/// Expects the following OSC message:
+///
+/// - Address: "/bp/scribble/<source_name>"
+/// - Arguments: `Int`, `Float`, `Float` where `Int` is the scribble id and the floats are the x
+///   and y position in metres.
+///
+/// The first position received for a scribble spawns a sound from the named source, which must
+/// have the scribbles role. Following positions move the sound. The sound is stopped once its
+/// positions stop arriving.
+#[derive(Clone, Debug)]
+pub struct ScribblePosition {
+    /// The name of the scribbles source from which the sound is spawned.
+    pub name: String,
+    /// Identifies the stream of positions.
+    pub id: i32,
+    /// The latest position of the scribble.
+    pub point: Point2<Metres>,
+}
+
+/// An OSC input message indicating that a scribble has ended.
+///
+/// Expects the following OSC message:
+///
+/// - Address: "/bp/scribble_end/<source_name>"
+/// - Arguments: `Int` where `Int` is the scribble id.
+#[derive(Clone, Debug)]
+pub struct ScribbleEnd {
+    /// The name of the scribbles source from which the sound was spawned.
+    pub name: String,
+    /// Identifies the stream of positions.
+    pub id: i32,
+}
+
+/// An OSC input message requesting that a scene be recalled.
+///
+/// Expects the following OSC message:
+///
+/// - Address: "/bp/scene/<scene_name>"
+/// - Arguments: An optional `Float` specifying the crossfade duration in seconds. By default the
+///   crossfade stored with the scene is used.
+#[derive(Clone, Debug)]
+pub struct RecallScene {
+    /// The name of the scene to recall.
+    ///
+    /// Note that the first scene whose name matches this is recalled.
+    pub name: String,
+    /// Overrides the crossfade duration stored with the scene.
+    pub crossfade: Option<Ms>,
+}
+
+/// An OSC input message that was parsed as the occupancy of an installation.
+///
+/// Expects the following OSC message:
+///
+/// - Address: "/bp/occupancy/<installation_name>"
+/// - Arguments: `Float` or `Int` where the number is the occupancy or activity level of the
+///   installation, e.g. the number of visitors reported by a people counter.
+///
+/// The value is normalised by the maximum occupancy of the installation and shifts its target
+/// number of sounds within its simultaneous sounds range.
+#[derive(Clone, Debug)]
+pub struct Occupancy {
+    /// The name of the installation to which this will be applied.
+    ///
+    /// Note that the occupancy will be applied to the first installation whose name matches this.
+    pub name: String,
+    /// The occupancy or activity level of the installation.
+    pub value: f32,
+}
+
 impl From<MasterVolume> for Control {
     fn from(mv: MasterVolume) -> Self {
         Control::MasterVolume(mv)
@@ -83,6 +274,90 @@ fn parse_source_volume(s: &str) -> Option<&str> {
     }
 }
 
+impl From<SourcePlaybackRate> for Control {
+    fn from(spr: SourcePlaybackRate) -> Self {
+        Control::SourcePlaybackRate(spr)
+    }
+}
+
+// Finds the "/source_playback_rate" string and returns the name of the source.
+fn parse_source_playback_rate(s: &str) -> Option<&str> {
+    if s.starts_with(SOURCE_PLAYBACK_RATE_ADDR) {
+        let slice_start = SOURCE_PLAYBACK_RATE_ADDR.len() + "/".len();
+        if slice_start < s.len() {
+            Some(&s[slice_start..])
+        } else {
+            None
+        }
+    } else {
+        None
+    }
+}
+
+impl From<InteractivePlay> for Control {
+    fn from(ip: InteractivePlay) -> Self {
+        Control::InteractivePlay(ip)
+    }
+}
+
+impl From<InteractivePosition> for Control {
+    fn from(ip: InteractivePosition) -> Self {
+        Control::InteractivePosition(ip)
+    }
+}
+
+impl From<InteractiveVolume> for Control {
+    fn from(iv: InteractiveVolume) -> Self {
+        Control::InteractiveVolume(iv)
+    }
+}
+
+impl From<InteractiveStop> for Control {
+    fn from(is: InteractiveStop) -> Self {
+        Control::InteractiveStop(is)
+    }
+}
+
+impl From<ScribblePosition> for Control {
+    fn from(sp: ScribblePosition) -> Self {
+        Control::ScribblePosition(sp)
+    }
+}
+
+impl From<ScribbleEnd> for Control {
+    fn from(se: ScribbleEnd) -> Self {
+        Control::ScribbleEnd(se)
+    }
+}
+
+impl From<RecallScene> for Control {
+    fn from(rs: RecallScene) -> Self {
+        Control::RecallScene(rs)
+    }
+}
+
+impl From<Occupancy> for Control {
+    fn from(o: Occupancy) -> Self {
+        Control::Occupancy(o)
+    }
+}
+
+// Finds the given address followed by a "/" and returns the remainder if it is not empty.
+fn parse_addr_suffix<'a>(s: &'a str, addr: &str) -> Option<&'a str> {
+    if s.starts_with(addr) && s[addr.len()..].starts_with("/") {
+        let suffix = &s[addr.len() + "/".len()..];
+        if !suffix.is_empty() {
+            return Some(suffix);
+        }
+    }
+    None
+}
+
+// Finds the given address followed by the handle of an interactive sound.
+fn parse_interactive_handle(s: &str, addr: &str) -> Option<i32> {
+    parse_addr_suffix(s, addr).and_then(|handle| handle.parse().ok())
+}
+
 // Finds the "/master_volume" string. Returns `true` if found.
 fn parse_master_volume(s: &str) -> bool {
     s == MASTER_VOLUME_ADDR
@@ -99,7 +374,8 @@ fn parse_pause_soundscape(s: &str) -> bool {
 }
 
 impl Control {
-    fn from_osc_msg(msg: &osc::Message) -> Option<Self> {
+    fn from_osc_msg(msg: &osc::Message, addr: &SocketAddr) -> Option<Self> {
+        let args = msg.args.as_ref().map(|args| &args[..]).unwrap_or(&[]);
         parse_bp(&msg.addr)
             .and_then(|s| {
                 match (parse_master_volume(s), msg.args.as_ref().and_then(|args| args.get(0))) {
@@ -119,6 +395,15 @@ impl Control {
                     _ => (),
                 }
 
+                match (parse_source_playback_rate(s), msg.args.as_ref().and_then(|args| args.get(0))) {
+                    (Some(name), Some(&Float(rate))) => {
+                        let name = name.into();
+                        let source_playback_rate = SourcePlaybackRate { name, rate };
+                        return Some(source_playback_rate.into())
+                    }
+                    _ => (),
+                }
+
                 if parse_play_soundscape(s) {
                     return Some(Control::PlaySoundscape);
                 }
@@ -127,6 +412,90 @@ impl Control {
                     return Some(Control::PauseSoundscape);
                 }
 
+                if let Some(name) = parse_addr_suffix(s, INTERACTIVE_PLAY_ADDR) {
+                    let (location, rest) = match (args.get(0), args.get(1)) {
+                        (Some(&Float(x)), Some(&Float(y))) => {
+                            let point = pt2(Metres(x as _), Metres(y as _));
+                            (InteractiveLocation::Point(point), &args[2..])
+                        },
+                        (Some(&osc::Type::String(ref installation)), _) => {
+                            let installation = installation.clone();
+                            (InteractiveLocation::Installation(installation), &args[1..])
+                        },
+                        _ => return None,
+                    };
+                    let mut reply_addr = addr.clone();
+                    if let Some(&Int(port)) = rest.get(0) {
+                        reply_addr.set_port(port as u16);
+                    }
+                    let name = name.into();
+                    let interactive_play = InteractivePlay { name, location, reply_addr };
+                    return Some(interactive_play.into());
+                }
+
+                let position = (args.get(0), args.get(1));
+                match (parse_interactive_handle(s, INTERACTIVE_POSITION_ADDR), position) {
+                    (Some(handle), (Some(&Float(x)), Some(&Float(y)))) => {
+                        let point = pt2(Metres(x as _), Metres(y as _));
+                        let interactive_position = InteractivePosition { handle, point };
+                        return Some(interactive_position.into());
+                    }
+                    _ => (),
+                }
+
+                match (parse_interactive_handle(s, INTERACTIVE_VOLUME_ADDR), args.get(0)) {
+                    (Some(handle), Some(&Float(volume))) => {
+                        let interactive_volume = InteractiveVolume { handle, volume };
+                        return Some(interactive_volume.into());
+                    }
+                    _ => (),
+                }
+
+                if let Some(handle) = parse_interactive_handle(s, INTERACTIVE_STOP_ADDR) {
+                    return Some(InteractiveStop { handle }.into());
+                }
+
+                let position = (args.get(0), args.get(1), args.get(2));
+                match (parse_addr_suffix(s, SCRIBBLE_ADDR), position) {
+                    (Some(name), (Some(&Int(id)), Some(&Float(x)), Some(&Float(y)))) => {
+                        let name = name.into();
+                        let point = pt2(Metres(x as _), Metres(y as _));
+                        let scribble_position = ScribblePosition { name, id, point };
+                        return Some(scribble_position.into());
+                    }
+                    _ => (),
+                }
+
+                match (parse_addr_suffix(s, SCRIBBLE_END_ADDR), args.get(0)) {
+                    (Some(name), Some(&Int(id))) => {
+                        let name = name.into();
+                        let scribble_end = ScribbleEnd { name, id };
+                        return Some(scribble_end.into());
+                    }
+                    _ => (),
+                }
+
+                if let Some(name) = parse_addr_suffix(s, SCENE_ADDR) {
+                    let name = name.into();
+                    let crossfade = match args.get(0) {
+                        Some(&Float(secs)) => Some(Ms(secs.max(0.0) as f64 * 1_000.0)),
+                        _ => None,
+                    };
+                    let recall_scene = RecallScene { name, crossfade };
+                    return Some(recall_scene.into());
+                }
+
+                if let Some(name) = parse_addr_suffix(s, OCCUPANCY_ADDR) {
+                    let value = match args.get(0) {
+                        Some(&Float(value)) => value,
+                        Some(&Int(value)) => value as f32,
+                        _ => return None,
+                    };
+                    let name = name.into();
+                    let occupancy = Occupancy { name, value: value.max(0.0) };
+                    return Some(occupancy.into());
+                }
+
                 None
             })
     }
@@ -175,7 +544,7 @@ fn run(
             log_tx.send(log).ok();
 
             // OSC -> Control
-            if let Some(control) = Control::from_osc_msg(&message) {
+            if let Some(control) = Control::from_osc_msg(&message, &addr) {
                 control_tx.send(control).ok();
             }
         }
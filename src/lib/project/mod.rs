@@ -30,8 +30,10 @@ use utils;
 use walkdir::WalkDir;
 
 pub mod config;
+pub mod scene;
 
 pub use self::config::Config;
+pub use self::scene::{Scene, Scenes};
 
 /// The assets sub-directory in which all projects are stored.
 const PROJECTS_DIRECTORY_STEM: &'static str = "projects";
@@ -48,6 +50,18 @@ const CONFIG_FILE_STEM: &'static str = "config";
 /// The extension used for serializing and deserializing project config.
 const CONFIG_EXTENSION: &'static str = "json";
 
+/// The file stem of the cached loudness measurements of the project's WAVs.
+const LOUDNESS_CACHE_FILE_STEM: &'static str = "loudness";
+
+/// The extension used for serializing and deserializing the loudness cache.
+const LOUDNESS_CACHE_EXTENSION: &'static str = "json";
+
+/// The file stem of the event log written by a soundscape simulation.
+const SIMULATION_FILE_STEM: &'static str = "simulation";
+
+/// The extension used for the JSON lines event log written by a soundscape simulation.
+const SIMULATION_EXTENSION: &'static str = "jsonl";
+
 /// The name of the directory where the WAVs are stored.
 const AUDIO_DIRECTORY_STEM: &'static str = "audio";
 
@@ -84,6 +98,9 @@ pub struct State {
     /// Master state of the project. E.g. volume, DBAP rolloff, latency, etc.
     #[serde(default)]
     pub master: Master,
+    /// The trim, gate and compressor applied to each channel of the audio input stream.
+    #[serde(default)]
+    pub input: audio::conditioning::Input,
     /// All installations in the exhibition along with their soundscape constraints.
     #[serde(default = "default_beyond_perception_installations")]
     pub installations: Installations,
@@ -96,6 +113,16 @@ pub struct State {
     /// All sources within the exhibition along with the set of currently soloed sources.
     #[serde(default)]
     pub sources: Sources,
+    /// Rules describing how the sounds of some sources duck the soundscape of an installation.
+    #[serde(default)]
+    pub ducking: DuckingRules,
+    /// Rules constraining when the sounds of soundscape sources may play in relation to one
+    /// another.
+    #[serde(default)]
+    pub soundscape_rules: SoundscapeRules,
+    /// Named snapshots of the mix and soundscape constraints that may be recalled.
+    #[serde(default)]
+    pub scenes: Scenes,
     /// The state of the camera over the floorplan.
     #[serde(default)]
     pub camera: Camera,
@@ -116,6 +143,12 @@ pub type SourcesMap = FxHashMap<audio::source::Id, Source>;
 /// A set of soloed sources.
 pub type SoloedSources = FxHashSet<audio::source::Id>;
 
+/// The ducking rules of the exhibition, evaluated in order on the audio output thread.
+pub type DuckingRules = Vec<audio::ducking::Rule>;
+
+/// The rules relating the playback of soundscape sources to one another.
+pub type SoundscapeRules = Vec<soundscape::Rule>;
+
 /// All sources within the exhibition for the project along with the set of soloed sources.
 #[derive(Debug, Default, Deserialize, Serialize)]
 pub struct Sources {
@@ -157,18 +190,26 @@ pub struct Source {
 impl State {
     fn default_from_name(name: String) -> Self {
         let master = Default::default();
+        let input = Default::default();
         let installations = default_beyond_perception_installations();
         let soundscape_groups = Default::default();
         let speakers = Default::default();
         let sources = Default::default();
+        let ducking = Default::default();
+        let soundscape_rules = Default::default();
+        let scenes = Default::default();
         let camera = Default::default();
         State {
             name,
             master,
+            input,
             installations,
             soundscape_groups,
             speakers,
             sources,
+            ducking,
+            soundscape_rules,
+            scenes,
             camera,
         }
     }
@@ -257,6 +298,13 @@ impl Project {
             .osc_out_msg_tx
             .push(osc::output::Message::ClearProjectSpecificData);
 
+        // The seed from which all soundscape randomness is derived.
+        let seed = self.config.seed;
+        channels
+            .soundscape
+            .send(move |soundscape| soundscape.set_seed(seed))
+            .expect("failed to send seed to soundscape thread");
+
         // TODO: Consider updating config stuff here?
 
         // Master to audio input and output.
@@ -280,6 +328,33 @@ impl Project {
             })
             .expect("failed to send loaded realtime source latency");
 
+        // Input channel conditioning to audio input.
+        for (channel, params) in self.input.channels.iter().enumerate() {
+            let params = params.clone();
+            channels
+                .audio_input
+                .send(move |audio| {
+                    audio.set_channel_conditioning(channel, &params);
+                })
+                .expect("failed to send input channel conditioning to audio input thread");
+        }
+
+        // Ducking rules to audio output.
+        let ducking = self.ducking.clone();
+        channels
+            .audio_output
+            .send(move |audio| {
+                audio.ducker.set_rules(ducking);
+            })
+            .expect("failed to send ducking rules to audio output thread");
+
+        // Soundscape rules to the soundscape.
+        let soundscape_rules = self.soundscape_rules.clone();
+        channels
+            .soundscape
+            .send(move |soundscape| soundscape.set_rules(soundscape_rules))
+            .expect("failed to send rules to soundscape thread");
+
         // Installations to soundscape, osc output and audio output.
         for (&id, installation) in self.installations.iter() {
             // Soundscape.
@@ -362,9 +437,43 @@ impl Project {
                     })
                     .expect("failed to send source to soundscape thread");
             }
+            // Measure the loudness of any WAVs that were not found within the cache.
+            if let audio::source::Kind::Wav(ref wav) = source.kind {
+                if wav.loudness.is_none() {
+                    channels.wav_analysis.analyse_loudness(wav.path.clone());
+                }
+            }
         }
 
+    }
 
+    /// Create an accelerated simulation of the project's soundscape.
+    ///
+    /// The simulation is independent of the soundscape thread, so it may be run on another thread
+    /// while the exhibition continues to play.
+    pub fn soundscape_simulation(
+        &self,
+        config: soundscape::simulation::Config,
+    ) -> soundscape::Simulation {
+        let mut simulation = soundscape::Simulation::new(self.config.seed, config);
+        simulation.set_rules(self.soundscape_rules.clone());
+        for (&id, installation) in self.installations.iter() {
+            let soundscape = installation.soundscape.clone();
+            simulation.insert_installation(id, installation.name.clone(), soundscape);
+        }
+        for (&id, group) in self.soundscape_groups.iter() {
+            simulation.insert_group(id, group.name.clone(), group.soundscape.clone());
+        }
+        for (&id, speaker) in self.speakers.iter() {
+            let speaker = soundscape::Speaker::from_audio_speaker(&speaker.audio);
+            simulation.insert_speaker(id, speaker);
+        }
+        for (&id, source) in self.sources.iter() {
+            if let Some(soundscape_source) = soundscape::Source::from_audio_source(&source) {
+                simulation.insert_source(id, source.name.clone(), soundscape_source);
+            }
+        }
+        simulation
     }
 
     /// Create a new project with a unique, default name.
@@ -433,11 +542,18 @@ impl Project {
             .unwrap_or_else(|_| default_config.clone());
 
         // Load the state json.
-        let state_path = project_state_path(project_directory_path);
+        let state_path = project_state_path(&project_directory_path);
         let state: State = utils::load_from_json(&state_path)
             .expect("failed to load project state");
 
-        Self::from_config_and_state(assets_path, config, state)
+        let mut project = Self::from_config_and_state(assets_path, config, state);
+
+        // Load any previous loudness measurements of the project's WAVs.
+        let loudness_cache_path = project_loudness_cache_path(&project_directory_path);
+        let loudness_cache = utils::load_from_json_or_default(&loudness_cache_path);
+        project.state.sources.apply_loudness_cache(&loudness_cache);
+
+        project
     }
 
     /// Save the project in its current state.
@@ -460,11 +576,18 @@ impl Project {
         }
 
         // Save the state json file.
-        let state_path = project_state_path(project_directory);
+        let state_path = project_state_path(&project_directory);
         if let Err(err) = utils::save_to_json(&state_path, &self.state) {
             eprintln!("failed to save project state.json: {}", err);
         }
 
+        // Save the loudness measurements of the project's WAVs.
+        let loudness_cache_path = project_loudness_cache_path(&project_directory);
+        let loudness_cache = self.sources.loudness_cache();
+        if let Err(err) = utils::save_to_json(&loudness_cache_path, &loudness_cache) {
+            eprintln!("failed to save project loudness.json: {}", err);
+        }
+
         Ok(())
     }
 }
@@ -506,6 +629,29 @@ impl Sources {
     {
         load_missing_sources(audio_path, self);
     }
+
+    /// Assign the cached loudness to each WAV source whose file has not changed since it was
+    /// measured.
+    pub fn apply_loudness_cache(&mut self, cache: &audio::source::wav::loudness::Cache) {
+        for source in self.map.values_mut() {
+            if let audio::source::Kind::Wav(ref mut wav) = source.audio.kind {
+                wav.loudness = cache.get(&wav.path);
+            }
+        }
+    }
+
+    /// Collect the loudness of all measured WAV sources into a cache.
+    pub fn loudness_cache(&self) -> audio::source::wav::loudness::Cache {
+        let mut cache = audio::source::wav::loudness::Cache::default();
+        for source in self.map.values() {
+            if let audio::source::Kind::Wav(ref wav) = source.audio.kind {
+                if let Some(loudness) = wav.loudness {
+                    cache.insert(wav.path.clone(), loudness);
+                }
+            }
+        }
+        cache
+    }
 }
 
 /// Updates the path from the given new relative path.
@@ -583,6 +729,12 @@ where
                     };
                     new_wav.should_loop = wav.should_loop;
                     new_wav.playback = wav.playback;
+                    new_wav.start_offset = wav.start_offset;
+                    new_wav.loop_crossfade = wav.loop_crossfade;
+                    // Loop points set by the user take precedence over the WAV's `smpl` chunk.
+                    if wav.loop_points.is_some() {
+                        new_wav.loop_points = wav.loop_points;
+                    }
                     mem::swap(wav, &mut new_wav);
                     continue;
                 }
@@ -672,6 +824,8 @@ where
             let channel_radians = audio::source::default::CHANNEL_RADIANS;
             let volume = audio::source::default::VOLUME;
             let muted = bool::default();
+            let effects = vec![];
+            let loudness_target = None;
             let audio = audio::Source {
                 kind,
                 role,
@@ -679,6 +833,8 @@ where
                 channel_radians,
                 volume,
                 muted,
+                effects,
+                loudness_target,
             };
             let source = Source { name, audio };
             sources.map.insert(next_id, source);
@@ -859,6 +1015,28 @@ where
         .with_extension(STATE_EXTENSION)
 }
 
+/// The file path for the "loudness.json" file caching the loudness of the project's WAVs.
+pub fn project_loudness_cache_path<P>(project_directory: P) -> PathBuf
+where
+    P: AsRef<Path>,
+{
+    project_directory
+        .as_ref()
+        .join(LOUDNESS_CACHE_FILE_STEM)
+        .with_extension(LOUDNESS_CACHE_EXTENSION)
+}
+
+/// The file path for the "simulation.jsonl" event log written by a soundscape simulation.
+pub fn project_simulation_path<P>(project_directory: P) -> PathBuf
+where
+    P: AsRef<Path>,
+{
+    project_directory
+        .as_ref()
+        .join(SIMULATION_FILE_STEM)
+        .with_extension(SIMULATION_EXTENSION)
+}
+
 /// Loads the path of every project directory within the `projects/` directory.
 pub fn load_project_directories<P>(assets: P) -> io::Result<Vec<PathBuf>>
 where
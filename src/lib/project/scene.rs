@@ -0,0 +1,164 @@
+//! Named snapshots of the mix and soundscape constraints of a project.
+//!
+//! A `Scene` captures the master volume, the volume, mute and solo state of each source, the
+//! constraints of each soundscape group and the constraints of each installation. Recalling a
+//! scene crossfades the audio output and soundscape threads towards the captured values.
+
+use audio;
+use fxhash::FxHashMap;
+use gui;
+use installation;
+use project::{SoloedSources, State};
+use soundscape::{self, group};
+use time_calc::Ms;
+use utils::Range;
+
+/// All scenes within the project in the order in which they were created.
+pub type Scenes = Vec<Scene>;
+
+/// A named snapshot of the mix and soundscape constraints of a project.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Scene {
+    /// A human-friendly name for the scene, used to recall it over OSC.
+    pub name: String,
+    /// The duration over which the scene is faded in when recalled.
+    #[serde(default = "default::crossfade")]
+    pub crossfade: Ms,
+    /// The master volume of the exhibition.
+    pub master_volume: f32,
+    /// The volume and mute state of each source.
+    #[serde(default)]
+    pub sources: FxHashMap<audio::source::Id, audio::scene::SourceMix>,
+    /// The set of soloed sources.
+    #[serde(default)]
+    pub soloed: SoloedSources,
+    /// The constraints of each soundscape group.
+    #[serde(default)]
+    pub groups: FxHashMap<group::Id, soundscape::scene::GroupConstraints>,
+    /// The simultaneous sounds constraint of each installation.
+    #[serde(default)]
+    pub installations: FxHashMap<installation::Id, Range<usize>>,
+}
+
+impl Scene {
+    /// Capture the current state of the project as a new scene.
+    pub fn capture(name: String, crossfade: Ms, state: &State) -> Self {
+        let master_volume = state.master.volume;
+        let sources = mix_sources(state);
+        let soloed = state.sources.soloed.clone();
+        let groups = state
+            .soundscape_groups
+            .iter()
+            .map(|(&id, group)| (id, (&group.soundscape).into()))
+            .collect();
+        let installations = state
+            .installations
+            .iter()
+            .map(|(&id, installation)| (id, installation.soundscape.simultaneous_sounds))
+            .collect();
+        Scene {
+            name,
+            crossfade,
+            master_volume,
+            sources,
+            soloed,
+            groups,
+            installations,
+        }
+    }
+
+    /// Recall the scene, crossfading towards it over the given duration.
+    ///
+    /// The project state is updated to the scene's values immediately, while the audio output and
+    /// soundscape threads interpolate towards them.
+    pub fn recall(&self, crossfade: Ms, state: &mut State, channels: &gui::Channels) {
+        // The mix before and after the recall.
+        let start = audio::scene::Mix {
+            master_volume: state.master.volume,
+            sources: mix_sources(state),
+            soloed: state.sources.soloed.clone(),
+        };
+        let target = audio::scene::Mix {
+            master_volume: self.master_volume,
+            sources: self.sources.clone(),
+            soloed: self.soloed.clone(),
+        };
+
+        // Update the local copy, ignoring anything that no longer exists.
+        state.master.volume = self.master_volume;
+        for (id, source) in state.sources.iter_mut() {
+            if let Some(mix) = self.sources.get(id) {
+                source.audio.volume = mix.volume;
+                source.audio.muted = mix.muted;
+            }
+        }
+        state.sources.soloed = self.soloed.clone();
+        state.sources.remove_invalid_soloed();
+        for (id, group) in state.soundscape_groups.iter_mut() {
+            if let Some(constraints) = self.groups.get(id) {
+                group.soundscape.occurrence_rate = constraints.occurrence_rate;
+                group.soundscape.simultaneous_sounds = constraints.simultaneous_sounds;
+            }
+        }
+        for (id, installation) in state.installations.iter_mut() {
+            if let Some(&simultaneous_sounds) = self.installations.get(id) {
+                installation.soundscape.simultaneous_sounds = simultaneous_sounds;
+            }
+        }
+
+        // Crossfade the mix on the audio output thread.
+        let fade = audio::scene::Fade::new(crossfade, &start, &target);
+        channels
+            .audio_output
+            .send(move |audio| audio.begin_scene_fade(fade))
+            .expect("failed to send scene fade to audio output thread");
+
+        // Sounds spawned by the soundscape from here on should use the new source mix.
+        for (&id, &mix) in self.sources.iter() {
+            channels
+                .soundscape
+                .send(move |soundscape| {
+                    soundscape.update_source(&id, |source| {
+                        source.volume = mix.volume;
+                        source.muted = mix.muted;
+                    });
+                })
+                .expect("failed to send scene source mix to soundscape thread");
+        }
+
+        // Crossfade the group and installation constraints on the soundscape thread.
+        let groups = self.groups.clone();
+        let installations = self.installations.clone();
+        channels
+            .soundscape
+            .send(move |soundscape| {
+                soundscape.begin_scene_fade(crossfade, &groups, &installations);
+            })
+            .expect("failed to send scene fade to soundscape thread");
+    }
+}
+
+// The current volume and mute state of every source in the project.
+fn mix_sources(state: &State) -> FxHashMap<audio::source::Id, audio::scene::SourceMix> {
+    state
+        .sources
+        .iter()
+        .map(|(&id, source)| {
+            let mix = audio::scene::SourceMix {
+                volume: source.audio.volume,
+                muted: source.audio.muted,
+            };
+            (id, mix)
+        })
+        .collect()
+}
+
+pub mod default {
+    use time_calc::Ms;
+
+    pub const CROSSFADE: Ms = Ms(2_000.0);
+
+    pub fn crossfade() -> Ms {
+        CROSSFADE
+    }
+}
@@ -2,6 +2,9 @@
 //!
 //! Soundscape groups allow for describing rules/constraints for multiple sounds at once.
 
+use audio;
+use fxhash::FxHashMap;
+use soundscape::schedule::Schedule;
 use time_calc::Ms;
 use utils::Range;
 
@@ -18,6 +21,46 @@ pub struct Id(pub usize);
 pub struct Group {
     pub occurrence_rate: Range<Ms>,
     pub simultaneous_sounds: Range<usize>,
+    /// Describes when the group is enabled and how its simultaneous sounds are scaled.
+    #[serde(default)]
+    pub schedule: Schedule,
+    /// Optionally chooses the group's next source based on the source that it last spawned.
+    #[serde(default)]
+    pub markov: Markov,
+}
+
+/// Markov-chain sequencing of the sources within a group.
+///
+/// When enabled, each new sound for the group is chosen from the sources available to the group
+/// using the weights of the transitions from the source that the group last spawned. This allows
+/// groups to form musical or narrative phrases rather than independent random picks.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct Markov {
+    /// Whether or not the group uses Markov sequencing.
+    #[serde(default)]
+    pub enabled: bool,
+    /// The transition matrix.
+    ///
+    /// Maps each previously spawned source to the relative weights of the sources that may
+    /// follow it. Sources without any positive weight from the previous source are never chosen
+    /// by the chain. If the previous source has no transitions, or none of the sources that may
+    /// follow it are available, the source is chosen as though sequencing were disabled.
+    #[serde(default)]
+    pub transitions: Transitions,
+}
+
+/// The transition weights from each source to the sources that may follow it.
+pub type Transitions = FxHashMap<audio::source::Id, FxHashMap<audio::source::Id, f64>>;
+
+impl Markov {
+    /// The weight of the transition from the source `from` to the source `to`.
+    pub fn weight(&self, from: &audio::source::Id, to: &audio::source::Id) -> f64 {
+        self.transitions
+            .get(from)
+            .and_then(|weights| weights.get(to))
+            .cloned()
+            .unwrap_or(0.0)
+    }
 }
 
 pub mod default {
@@ -34,9 +77,13 @@ impl Default for Group {
     fn default() -> Self {
         let occurrence_rate = default::OCCURRENCE_RATE;
         let simultaneous_sounds = default::SIMULTANEOUS_SOUNDS;
+        let schedule = Default::default();
+        let markov = Default::default();
         Group {
             occurrence_rate,
             simultaneous_sounds,
+            schedule,
+            markov,
         }
     }
 }
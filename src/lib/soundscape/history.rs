@@ -0,0 +1,227 @@
+//! A rolling, on-disk history of the sounds played by the soundscape.
+//!
+//! The soundscape thread produces an `Entry` each time one of its sounds ends and each time a
+//! group becomes starved of sources. The GUI appends these entries as JSON lines to a file for the
+//! day on which they occurred within the project's `history/` directory, e.g.
+//! `history/2019-03-21.jsonl`. Files older than `RETENTION_DAYS` are removed as each new day
+//! begins.
+//!
+//! The entries of the current day are aggregated into `Stats` so that curators may check how
+//! often each source has played and how often each group was starved.
+
+use audio;
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use fxhash::FxHashMap;
+use installation;
+use serde_json;
+use soundscape::group;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time;
+
+/// The number of days for which history files are kept.
+pub const RETENTION_DAYS: i64 = 30;
+
+/// The name of the directory within the project directory in which history files are stored.
+const DIRECTORY_STEM: &'static str = "history";
+
+/// The extension used for each day's history file.
+const EXTENSION: &'static str = "jsonl";
+
+/// An event recorded within the history.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Entry {
+    /// A sound spawned by the soundscape finished playing.
+    Play(Play),
+    /// A group needed more sounds to reach its minimum but none of its sources were available.
+    Starved(Starved),
+}
+
+/// A sound that was played by the soundscape.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Play {
+    pub source: audio::source::Id,
+    pub group: group::Id,
+    /// The installation for which the sound was spawned.
+    pub installation: installation::Id,
+    /// The local date and time at which the sound was spawned.
+    pub start: NaiveDateTime,
+    /// The local date and time at which the sound ended.
+    pub end: NaiveDateTime,
+    /// The kind of movement assigned to the sound.
+    pub movement: String,
+}
+
+/// The beginning of a period during which a group was starved of available sources.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Starved {
+    pub group: group::Id,
+    /// The local date and time at which the group became starved.
+    pub time: NaiveDateTime,
+}
+
+/// Statistics aggregated from the entries of a single day.
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    /// Plays and air time for each source that played.
+    pub sources: FxHashMap<audio::source::Id, SourceStats>,
+    /// The number of times that each group became starved.
+    pub starved_groups: FxHashMap<group::Id, usize>,
+}
+
+/// Statistics for a single source.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SourceStats {
+    /// The number of sounds played from the source.
+    pub plays: usize,
+    /// The total duration over which sounds from the source were playing.
+    pub air_time: time::Duration,
+}
+
+/// The history of a project, open for a single day at a time.
+#[derive(Default)]
+pub struct History {
+    day: Option<Day>,
+}
+
+// The day to which entries are currently being appended.
+struct Day {
+    date: NaiveDate,
+    file: File,
+    stats: Stats,
+}
+
+impl Entry {
+    /// The local date and time at which the entry occurred.
+    ///
+    /// Plays are considered to have occurred when they started.
+    pub fn time(&self) -> NaiveDateTime {
+        match *self {
+            Entry::Play(ref play) => play.start,
+            Entry::Starved(ref starved) => starved.time,
+        }
+    }
+}
+
+impl Stats {
+    /// Include the given entry within the statistics.
+    pub fn insert(&mut self, entry: &Entry) {
+        match *entry {
+            Entry::Play(ref play) => {
+                let stats = self.sources.entry(play.source).or_insert_with(Default::default);
+                stats.plays += 1;
+                stats.air_time += (play.end - play.start)
+                    .to_std()
+                    .unwrap_or(time::Duration::from_secs(0));
+            },
+            Entry::Starved(ref starved) => {
+                *self.starved_groups.entry(starved.group).or_insert(0) += 1;
+            },
+        }
+    }
+}
+
+impl History {
+    /// The date of the day that is currently open along with its statistics.
+    pub fn today(&self) -> Option<(NaiveDate, &Stats)> {
+        self.day.as_ref().map(|day| (day.date, &day.stats))
+    }
+
+    /// Ensure the history is open for the given date within the given project directory.
+    ///
+    /// When a new day is opened, any existing entries for that day are loaded into its statistics
+    /// and files older than `RETENTION_DAYS` are removed.
+    pub fn open<P>(&mut self, project_directory: P, date: NaiveDate) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        if self.day.as_ref().map(|day| day.date) == Some(date) {
+            return Ok(());
+        }
+        self.day = None;
+        let directory = directory_path(project_directory);
+        fs::create_dir_all(&directory)?;
+        remove_expired(&directory, date)?;
+        let path = day_path(&directory, date);
+        let stats = load_stats(&path)?;
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        self.day = Some(Day { date, file, stats });
+        Ok(())
+    }
+
+    /// Append the entry to the file for the day on which it occurred.
+    ///
+    /// Entries that occurred before the open day (e.g. a play that started before midnight) are
+    /// appended to their own day's file without re-opening that day, so that the open day keeps
+    /// following the wall clock.
+    pub fn record<P>(&mut self, project_directory: P, entry: &Entry) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let date = entry.time().date();
+        match self.day.as_ref().map(|day| day.date) {
+            Some(open_date) if date < open_date => {
+                let path = day_path(&directory_path(project_directory), date);
+                let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+                serde_json::to_writer(&mut file, entry)?;
+                writeln!(file)?;
+                return Ok(());
+            },
+            _ => self.open(project_directory, date)?,
+        }
+        let day = self.day.as_mut().expect("no day open within history");
+        serde_json::to_writer(&mut day.file, entry)?;
+        writeln!(day.file)?;
+        day.stats.insert(entry);
+        Ok(())
+    }
+}
+
+/// The path of the directory in which the history of the project is stored.
+pub fn directory_path<P>(project_directory: P) -> PathBuf
+where
+    P: AsRef<Path>,
+{
+    project_directory.as_ref().join(DIRECTORY_STEM)
+}
+
+// The path of the file for the given date within the history directory.
+fn day_path(directory: &Path, date: NaiveDate) -> PathBuf {
+    directory.join(date.format("%Y-%m-%d").to_string()).with_extension(EXTENSION)
+}
+
+// Aggregate the statistics of the history file at the given path, if there is one.
+//
+// Lines that cannot be parsed (e.g. a partially written final line) are skipped.
+fn load_stats(path: &Path) -> io::Result<Stats> {
+    let mut stats = Stats::default();
+    if !path.exists() {
+        return Ok(stats);
+    }
+    let reader = BufReader::new(File::open(path)?);
+    for line in reader.lines() {
+        if let Ok(entry) = serde_json::from_str::<Entry>(&line?) {
+            stats.insert(&entry);
+        }
+    }
+    Ok(stats)
+}
+
+// Remove all history files that are older than `RETENTION_DAYS` before the given date.
+fn remove_expired(directory: &Path, date: NaiveDate) -> io::Result<()> {
+    let oldest = date - Duration::days(RETENTION_DAYS);
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        let file_date = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| NaiveDate::parse_from_str(stem, "%Y-%m-%d").ok());
+        match file_date {
+            Some(file_date) if file_date < oldest => fs::remove_file(&path)?,
+            _ => (),
+        }
+    }
+    Ok(())
+}
@@ -1,4 +1,5 @@
 use audio;
+use chrono::{self, NaiveDateTime};
 use fxhash::{FxHashMap, FxHashSet};
 use installation;
 use metres::Metres;
@@ -17,11 +18,21 @@ use time_calc::Ms;
 use utils::{self, duration_to_secs, Range, Seed};
 
 pub use self::group::Group;
+pub use self::history::History;
 pub use self::movement::Movement;
+pub use self::rule::Rule;
+pub use self::schedule::Schedule;
+pub use self::simulation::Simulation;
 use self::movement::BoundingRect;
 
 pub mod group;
+pub mod history;
 pub mod movement;
+pub mod occupancy;
+pub mod rule;
+pub mod scene;
+pub mod schedule;
+pub mod simulation;
 
 const TICK_RATE_MS: u64 = 16;
 
@@ -31,7 +42,9 @@ type Sources = FxHashMap<audio::source::Id, Source>;
 type Speakers = FxHashMap<audio::speaker::Id, Speaker>;
 type GroupsLastUsed = FxHashMap<group::Id, time::Instant>;
 type SourcesLastUsed = FxHashMap<audio::source::Id, time::Instant>;
+type GroupsLastSource = FxHashMap<group::Id, audio::source::Id>;
 type InstallationAreas = FxHashMap<installation::Id, movement::Area>;
+type InstallationOccupancy = FxHashMap<installation::Id, occupancy::Level>;
 type InstallationSpeakers = FxHashMap<installation::Id, Vec<audio::speaker::Id>>;
 type ActiveSounds = FxHashMap<audio::sound::Id, ActiveSound>;
 type ActiveSoundPositions = FxHashMap<audio::sound::Id, ActiveSoundPosition>;
@@ -39,6 +52,7 @@ type ActiveSoundsPerInstallation = FxHashMap<installation::Id, Vec<audio::sound:
 type TargetSoundsPerInstallation = FxHashMap<installation::Id, usize>;
 type AvailableGroups = Vec<AvailableGroup>;
 type AvailableSources = Vec<AvailableSource>;
+type GroupSet = FxHashSet<group::Id>;
 
 /// The kinds of messages received by the soundscape thread.
 pub enum Message {
@@ -63,6 +77,10 @@ pub struct Tick {
     ///
     /// This does not increase when the stream is paused.
     playback_duration: time::Duration,
+    /// The local wall-clock date and time at which the tick occurred.
+    ///
+    /// Used to evaluate the schedules of groups and sources.
+    local_time: NaiveDateTime,
 }
 
 /// The update function applied to a source.
@@ -99,6 +117,8 @@ pub struct Source {
     pub channel_radians: f32,
     pub volume: f32,
     pub muted: bool,
+    pub effects: Vec<audio::effect::Effect>,
+    pub loudness_target: Option<f32>,
     /// The time at which the source was last used to create a sound.
     pub last_sound_created: Option<time::Instant>,
 }
@@ -107,6 +127,10 @@ pub struct Source {
 pub struct ActiveSound {
     /// The installation for which this sound was initially spawned.
     pub initial_installation: installation::Id,
+    /// The group from which the sound's source was picked.
+    pub group: group::Id,
+    /// The local date and time at which the sound was spawned.
+    pub started: NaiveDateTime,
     /// State related to active sound's assigned movement.
     pub movement: Movement,
     /// The handle associated with this sound.
@@ -127,8 +151,13 @@ pub struct Model {
     frame_count: Arc<AtomicUsize>,
     /// The latency applied to realtime sounds when spawned.
     pub realtime_source_latency: Ms,
-    /// The soundscape's deterministic source of randomness.
+    /// The seed from which the soundscape's source of randomness is derived.
     seed: Seed,
+    /// The source of all randomness within the soundscape, derived from the `seed`.
+    ///
+    /// Routing all randomness through this generator ensures that a given seed and timeline
+    /// reproduce the same sequence of spawned sounds.
+    rng: XorShiftRng,
     /// For generating unique IDs for each new sound.
     sound_id_gen: audio::sound::IdGenerator,
     /// How long the soundscape has been actively playing (in an un-paused state).
@@ -147,8 +176,20 @@ pub struct Model {
     groups_last_used: GroupsLastUsed,
     /// The moment at which each `Source` was last used to spawn a sound.
     sources_last_used: SourcesLastUsed,
+    /// The source most recently used to spawn a sound within each `Group`.
+    groups_last_source: GroupsLastSource,
     /// All sounds currently being played that were spawned by the soundscape thread.
     active_sounds: ActiveSounds,
+    /// The crossfade of group and installation constraints towards a recalled scene, if any.
+    scene_fade: Option<scene::Fade>,
+    /// Rules constraining when the sounds of sources may play in relation to one another.
+    rules: Vec<Rule>,
+    /// Sounds that must follow those that have ended, as described by the follow-on rules.
+    follow_ons: Vec<rule::FollowOn>,
+    /// Groups that currently need sounds to reach their minimum but have no available sources.
+    starved_groups: GroupSet,
+    /// The smoothed occupancy of each installation for which values have been received.
+    installation_occupancy: InstallationOccupancy,
 
     // The following are intermediary buffers re-used between calculations.
     //
@@ -170,17 +211,36 @@ pub struct Model {
     available_groups: AvailableGroups,
     /// A buffer to re-use when calculating currently available sources.
     available_sources: AvailableSources,
+    /// Groups found to need sounds to reach their minimum during a tick.
+    groups_needing_sounds: GroupSet,
+    /// Groups needing sounds for which at least one source was available during a tick.
+    groups_with_available_sources: GroupSet,
 
     // Communication channels.
 
-    /// A handle to the wav reader thread.
-    wav_reader: audio::source::wav::reader::Handle,
-    /// A handle for submitting new sounds to the input stream.
-    audio_input_stream: audio::input::Stream,
-    /// A handle for submitting new sounds to the output stream.
-    audio_output_stream: audio::output::Stream,
+    /// Where the sounds spawned by the soundscape are sent.
+    output: Output,
     // A handle to the ticker thread.
-    _tick_thread: thread::JoinHandle<()>,
+    //
+    // This is `None` when the soundscape is driven by a simulated clock.
+    _tick_thread: Option<thread::JoinHandle<()>>,
+}
+
+// Where the sounds spawned by the soundscape are sent.
+enum Output {
+    // Sounds are sent to the audio streams for playback.
+    Audio {
+        // A handle to the wav reader thread.
+        wav_reader: audio::source::wav::reader::Handle,
+        // A handle for submitting new sounds to the input stream.
+        audio_input_stream: audio::input::Stream,
+        // A handle for submitting new sounds to the output stream.
+        audio_output_stream: audio::output::Stream,
+        // Where ended sounds and starved groups are sent for the GUI to record in the history.
+        history_tx: mpsc::Sender<history::Entry>,
+    },
+    // Sounds are only recorded by an accelerated simulation.
+    Simulation(simulation::Recorder),
 }
 
 // Data related to the suitability of a group or source for selection of use within the soundscape.
@@ -201,6 +261,12 @@ struct Suitability {
     timing: Option<Timing>,
     // Used to reset the  `duration` properties once a sound from this group is used.
     occurrence_rate_interval: Range<Ms>,
+    // The relative likelihood of selection over other candidates.
+    //
+    // The time until a sound is needed is scaled by the weight when sorting, and candidates that
+    // remain equally suitable are picked between randomly in proportion to their weights. This
+    // is always `1.0` for groups.
+    weight: f64,
 }
 
 // Timing info related to the `Suitability` struct.
@@ -232,6 +298,10 @@ struct AvailableSource {
     playback_duration: Range<Ms>,
     attack_duration: Range<Ms>,
     release_duration: Range<Ms>,
+    attack_curve: audio::source::envelope::Curve,
+    release_curve: audio::source::envelope::Curve,
+    hold_duration: Range<Ms>,
+    playback_rate: Range<f64>,
 }
 
 impl ActiveSound {
@@ -274,6 +344,8 @@ impl Source {
         let channel_radians = source.channel_radians;
         let volume = source.volume;
         let muted = source.muted;
+        let effects = source.effects.clone();
+        let loudness_target = source.loudness_target;
         let last_sound_created = None;
         Some(Source {
             constraints,
@@ -282,6 +354,8 @@ impl Source {
             channel_radians,
             volume,
             muted,
+            effects,
+            loudness_target,
             last_sound_created,
         })
     }
@@ -294,6 +368,8 @@ impl Source {
         let channel_radians = self.channel_radians;
         let volume = self.volume;
         let muted = self.muted;
+        let effects = self.effects.clone();
+        let loudness_target = self.loudness_target;
         audio::Source {
             kind,
             role,
@@ -301,6 +377,8 @@ impl Source {
             channel_radians,
             volume,
             muted,
+            effects,
+            loudness_target,
         }
     }
 }
@@ -388,6 +466,25 @@ impl Model {
         self.installations.remove(id)
     }
 
+    /// Set the occupancy of the given installation, e.g. as received from a people counter.
+    ///
+    /// The value is normalised by the installation's occupancy constraints and shifts the
+    /// installation's target number of sounds.
+    ///
+    /// Returns `false` if the installation was not there.
+    pub fn set_occupancy(&mut self, installation: &installation::Id, value: f64) -> bool {
+        let constraints = match self.installations.get(installation) {
+            None => return false,
+            Some(i) => &i.occupancy,
+        };
+        let target = constraints.normalise(value);
+        self.installation_occupancy
+            .entry(*installation)
+            .or_insert_with(|| occupancy::Level::new(target))
+            .receive(target);
+        true
+    }
+
     /// Update the given installation's state.
     ///
     /// Returns `false` if the installation was not there.
@@ -491,6 +588,7 @@ impl Model {
         // Now update all active sounds that use this source.
         let Model {
             seed,
+            ref mut rng,
             ref playback_duration,
             ref sources,
             ref speakers,
@@ -502,6 +600,7 @@ impl Model {
             ref mut installation_areas,
             ref mut target_sounds_per_installation,
             ref mut active_sound_positions,
+            ref installation_occupancy,
             ..
         } = *self;
 
@@ -513,6 +612,7 @@ impl Model {
             playback_duration,
             installations,
             &installation_areas,
+            installation_occupancy,
             target_sounds_per_installation,
         );
         update_active_sound_positions(active_sounds, active_sound_positions);
@@ -530,6 +630,7 @@ impl Model {
                 Some(installation) => {
                     // Generate the movement.
                     let movement = generate_movement(
+                        rng,
                         *source_id,
                         sources,
                         installation,
@@ -553,8 +654,38 @@ impl Model {
     }
 
     /// Remove an active sound from the hashmap.
+    ///
+    /// If the sound is being played back, it is sent to the GUI to be recorded in the history.
     pub fn remove_active_sound(&mut self, id: &audio::sound::Id) -> Option<ActiveSound> {
-        self.active_sounds.remove(id)
+        let sound = self.active_sounds.remove(id);
+
+        // Queue any sounds that must follow this one.
+        if let Some(ref sound) = sound {
+            let source_id = sound.handle.source_id();
+            let installation = sound.initial_installation;
+            let playback_duration = self.playback_duration;
+            let follow_ons = self
+                .rules
+                .iter()
+                .filter_map(|rule| rule.follow_on(source_id, installation, playback_duration));
+            self.follow_ons.extend(follow_ons);
+        }
+
+        if let Output::Audio { ref history_tx, .. } = self.output {
+            if let Some(ref sound) = sound {
+                let play = history::Play {
+                    source: sound.handle.source_id(),
+                    group: sound.group,
+                    installation: sound.initial_installation,
+                    start: sound.started,
+                    end: chrono::Local::now().naive_local(),
+                    movement: sound.movement.kind().to_string(),
+                };
+                // The GUI may have already exited.
+                history_tx.send(history::Entry::Play(play)).ok();
+            }
+        }
+        sound
     }
 
     /// Update the state of all active sounds spawned via the source with the given `Id`.
@@ -588,6 +719,7 @@ impl Model {
         self.speakers.clear();
         self.groups_last_used.clear();
         self.sources_last_used.clear();
+        self.groups_last_source.clear();
         self.active_sounds.clear();
         self.installation_speakers.clear();
         self.installation_areas.clear();
@@ -596,6 +728,57 @@ impl Model {
         self.active_sound_positions.clear();
         self.available_groups.clear();
         self.available_sources.clear();
+        self.groups_needing_sounds.clear();
+        self.groups_with_available_sources.clear();
+        self.starved_groups.clear();
+        self.installation_occupancy.clear();
+        self.follow_ons.clear();
+        self.rules.clear();
+        self.scene_fade = None;
+        self.rng = seeded_rng(&self.seed);
+    }
+
+    /// Replace the rules constraining when the sounds of sources may play.
+    pub fn set_rules(&mut self, rules: Vec<Rule>) {
+        self.rules = rules;
+    }
+
+    /// Set the seed from which the soundscape's randomness is derived and reset the generator.
+    pub fn set_seed(&mut self, seed: Seed) {
+        self.seed = seed;
+        self.rng = seeded_rng(&seed);
+    }
+
+    /// Begin crossfading the constraints of the given groups and installations from their
+    /// current values towards the given targets.
+    pub fn begin_scene_fade(
+        &mut self,
+        duration: Ms,
+        groups: &FxHashMap<group::Id, scene::GroupConstraints>,
+        installations: &FxHashMap<installation::Id, Range<usize>>,
+    ) {
+        let fade = scene::Fade::new(
+            duration,
+            &self.groups,
+            &self.installations,
+            groups,
+            installations,
+        );
+        fade.apply(&mut self.groups, &mut self.installations);
+        self.scene_fade = Some(fade);
+    }
+
+    // Step any scene fade in progress and apply the resulting constraints.
+    fn update_scene_fade(&mut self, since_last_tick: &time::Duration) {
+        let mut fade = match self.scene_fade.take() {
+            None => return,
+            Some(fade) => fade,
+        };
+        fade.advance(since_last_tick);
+        fade.apply(&mut self.groups, &mut self.installations);
+        if !fade.is_complete() {
+            self.scene_fade = Some(fade);
+        }
     }
 }
 
@@ -672,6 +855,7 @@ pub fn spawn(
     wav_reader: audio::source::wav::reader::Handle,
     audio_input_stream: audio::input::Stream,
     audio_output_stream: audio::output::Stream,
+    history_tx: mpsc::Sender<history::Entry>,
     sound_id_gen: audio::sound::IdGenerator,
 ) -> Soundscape {
     let is_playing = Arc::new(AtomicBool::new(true));
@@ -693,10 +877,12 @@ pub fn spawn(
                     continue;
                 }
                 playback_duration += since_last_tick;
+                let local_time = chrono::Local::now().naive_local();
                 let tick = Tick {
                     instant,
                     since_last_tick,
                     playback_duration,
+                    local_time,
                 };
                 if tick_tx.send(Message::Tick(tick)).is_err() {
                     break;
@@ -706,47 +892,13 @@ pub fn spawn(
         .unwrap();
 
     // The model maintaining state between messages.
-    let realtime_source_latency = audio::DEFAULT_REALTIME_SOURCE_LATENCY;
-    let playback_duration = time::Duration::from_secs(0);
-    let installations = Default::default();
-    let groups = Default::default();
-    let sources = Default::default();
-    let speakers = Default::default();
-    let active_sounds = Default::default();
-    let installation_speakers = Default::default();
-    let installation_areas = Default::default();
-    let groups_last_used = Default::default();
-    let sources_last_used = Default::default();
-    let target_sounds_per_installation = Default::default();
-    let active_sound_positions = Default::default();
-    let active_sounds_per_installation = Default::default();
-    let available_groups = Default::default();
-    let available_sources = Default::default();
-    let model = Model {
-        frame_count,
-        realtime_source_latency,
-        seed,
-        playback_duration,
-        installations,
-        groups,
-        sources,
-        speakers,
-        active_sounds,
-        groups_last_used,
-        sources_last_used,
-        installation_speakers,
-        installation_areas,
-        target_sounds_per_installation,
-        active_sounds_per_installation,
-        active_sound_positions,
-        available_groups,
-        available_sources,
+    let output = Output::Audio {
         wav_reader,
         audio_input_stream,
         audio_output_stream,
-        sound_id_gen,
-        _tick_thread,
+        history_tx,
     };
+    let model = Model::new(frame_count, seed, sound_id_gen, output, Some(_tick_thread));
 
     // Spawn the soundscape thread.
     let thread = thread::Builder::new()
@@ -761,6 +913,75 @@ pub fn spawn(
     }
 }
 
+impl Model {
+    // Create a model with no installations, groups, speakers or sources.
+    fn new(
+        frame_count: Arc<AtomicUsize>,
+        seed: Seed,
+        sound_id_gen: audio::sound::IdGenerator,
+        output: Output,
+        _tick_thread: Option<thread::JoinHandle<()>>,
+    ) -> Self {
+        let realtime_source_latency = audio::DEFAULT_REALTIME_SOURCE_LATENCY;
+        let playback_duration = time::Duration::from_secs(0);
+        let installations = Default::default();
+        let groups = Default::default();
+        let sources = Default::default();
+        let speakers = Default::default();
+        let active_sounds = Default::default();
+        let installation_speakers = Default::default();
+        let installation_areas = Default::default();
+        let groups_last_used = Default::default();
+        let sources_last_used = Default::default();
+        let groups_last_source = Default::default();
+        let target_sounds_per_installation = Default::default();
+        let active_sound_positions = Default::default();
+        let active_sounds_per_installation = Default::default();
+        let available_groups = Default::default();
+        let available_sources = Default::default();
+        let groups_needing_sounds = Default::default();
+        let groups_with_available_sources = Default::default();
+        let starved_groups = Default::default();
+        let installation_occupancy = Default::default();
+        let rules = Default::default();
+        let follow_ons = Default::default();
+        let scene_fade = None;
+        let rng = seeded_rng(&seed);
+        Model {
+            frame_count,
+            realtime_source_latency,
+            seed,
+            rng,
+            playback_duration,
+            installations,
+            groups,
+            sources,
+            speakers,
+            active_sounds,
+            scene_fade,
+            rules,
+            follow_ons,
+            starved_groups,
+            installation_occupancy,
+            groups_last_used,
+            sources_last_used,
+            groups_last_source,
+            installation_speakers,
+            installation_areas,
+            target_sounds_per_installation,
+            active_sounds_per_installation,
+            active_sound_positions,
+            available_groups,
+            available_sources,
+            groups_needing_sounds,
+            groups_with_available_sources,
+            output,
+            sound_id_gen,
+            _tick_thread,
+        }
+    }
+}
+
 // A blocking function that is run on the unique soundscape thread (called by spawn).
 fn run(mut model: Model, msg_rx: mpsc::Receiver<Message>) {
     // Wait for messages.
@@ -944,6 +1165,7 @@ fn agent_installation_data(
 
 // Generate a movement for some source within some given installation.
 fn generate_movement(
+    rng: &mut XorShiftRng,
     source_id: audio::source::Id,
     sources: &Sources,
     installation: installation::Id,
@@ -966,7 +1188,6 @@ fn generate_movement(
         },
         audio::source::Movement::Generative(ref gen) => match *gen {
             audio::source::movement::Generative::Agent(ref agent) => {
-                let mut rng = nannou::rand::thread_rng();
                 // TODO: Should these be skewed?
                 let r = &agent.max_speed;
                 let max_speed = map_range(rng.gen(), 0f64, 1.0, r.min, r.max);
@@ -985,7 +1206,7 @@ fn generate_movement(
                     &active_sound_positions,
                 );
                 let agent = movement::Agent::generate(
-                    rng,
+                    &mut *rng,
                     installation,
                     &installation_data,
                     max_speed,
@@ -999,7 +1220,6 @@ fn generate_movement(
             },
 
             audio::source::movement::Generative::Ngon(ref ngon) => {
-                let mut rng = nannou::rand::thread_rng();
                 // TODO: Should these be skewed?
                 let r = &ngon.vertices;
                 let vertices = map_range(rng.gen(), 0f64, 1.0, r.min, r.max);
@@ -1026,6 +1246,99 @@ fn generate_movement(
     }
 }
 
+// Determine the initial location of a sound within the given installation area.
+//
+// Falls back to a random location if the placement refers to a speaker that no longer exists or
+// that is not assigned to the installation. Speaker locations are clamped to the area.
+fn initial_location(
+    rng: &mut XorShiftRng,
+    placement: &audio::source::Placement,
+    area: &movement::Area,
+    installation_speakers: &[audio::speaker::Id],
+    speakers: &Speakers,
+    active_sounds: &ActiveSounds,
+) -> Point2<Metres> {
+    // The number of random candidates considered when searching for the farthest location.
+    const FARTHEST_CANDIDATES: usize = 16;
+
+    let rect = &area.bounding_rect;
+    let (w, h) = ((rect.right - rect.left).0, (rect.top - rect.bottom).0);
+    let random_location = |rng: &mut XorShiftRng| {
+        let x = rect.left + Metres(w * rng.gen::<f64>());
+        let y = rect.bottom + Metres(h * rng.gen::<f64>());
+        pt2(x, y)
+    };
+
+    match *placement {
+        audio::source::Placement::Centroid => area.centroid,
+
+        audio::source::Placement::Random => random_location(rng),
+
+        audio::source::Placement::NearestSpeaker(ref id) => {
+            match speakers.get(id) {
+                Some(speaker) if installation_speakers.contains(id) => {
+                    let x = speaker.point.x.0.max(rect.left.0).min(rect.right.0);
+                    let y = speaker.point.y.0.max(rect.bottom.0).min(rect.top.0);
+                    pt2(Metres(x), Metres(y))
+                },
+                _ => random_location(rng),
+            }
+        },
+
+        // Pick the candidate whose nearest active sound is the most distant.
+        audio::source::Placement::FarthestFromActive => {
+            let nearest_sound_distance = |p: Point2<Metres>| {
+                active_sounds
+                    .values()
+                    .map(|sound| {
+                        let q = sound.position().point;
+                        pt2(p.x.0, p.y.0).distance(pt2(q.x.0, q.y.0))
+                    })
+                    .fold(::std::f64::INFINITY, f64::min)
+            };
+            let mut farthest = random_location(rng);
+            let mut farthest_distance = nearest_sound_distance(farthest);
+            for _ in 1..FARTHEST_CANDIDATES {
+                let candidate = random_location(rng);
+                let distance = nearest_sound_distance(candidate);
+                if distance > farthest_distance {
+                    farthest = candidate;
+                    farthest_distance = distance;
+                }
+            }
+            farthest
+        },
+
+        // Pick a point along the perimeter of the area.
+        audio::source::Placement::AreaEdge => {
+            let mut d = rng.gen::<f64>() * (w + h) * 2.0;
+            if d < w {
+                return pt2(rect.left + Metres(d), rect.bottom);
+            }
+            d -= w;
+            if d < h {
+                return pt2(rect.right, rect.bottom + Metres(d));
+            }
+            d -= h;
+            if d < w {
+                return pt2(rect.right - Metres(d), rect.top);
+            }
+            d -= w;
+            pt2(rect.left, rect.top - Metres(d))
+        },
+    }
+}
+
+// Create the soundscape's source of randomness from the given seed.
+fn seeded_rng(seed: &Seed) -> XorShiftRng {
+    // `XorShiftRng` cannot be seeded with all zeroes.
+    let mut seed = *seed;
+    if seed == [0; 16] {
+        seed[0] = 1;
+    }
+    XorShiftRng::from_seed(seed)
+}
+
 // A unique, constant seed associated with the installation.
 fn installation_seed(installation: &installation::Id) -> Seed {
     // Convert the installation to its integer representation.
@@ -1084,6 +1397,8 @@ fn update_installation_areas(
 // We can determine this in a purely functional manner by using the playback duration as the phase
 // for a noise_walk signal.
 //
+// If the installation's occupancy is known, the target is shifted towards it.
+//
 // Note that an installation may not generate any sounds if it has no area (i.e. there are no
 // speakers assigned).
 fn installation_target_sounds(
@@ -1092,6 +1407,7 @@ fn installation_target_sounds(
     installation: &installation::Id,
     constraints: &installation::Soundscape,
     installation_areas: &InstallationAreas,
+    occupancy: Option<&occupancy::Level>,
 ) -> usize {
     if !installation_areas.contains_key(installation) {
         return 0;
@@ -1111,6 +1427,10 @@ fn installation_target_sounds(
     // Amplify the noise_walk slightly so that it occasionally reaches min and max.
     let amp = (noise_walk(phase) * 1.5).min(1.0).max(-1.0);
     let normalised_amp = amp * 0.5 + 0.5;
+    let normalised_amp = match occupancy {
+        Some(level) => level.apply(normalised_amp, &constraints.occupancy),
+        None => normalised_amp,
+    };
     let range = &constraints.simultaneous_sounds;
     let range_diff = range.max - range.min;
     (range.min as f64 + normalised_amp * range_diff as f64) as usize
@@ -1125,6 +1445,7 @@ fn update_target_sounds_per_installation(
     playback_duration: &time::Duration,
     installations: &Installations,
     installation_areas: &InstallationAreas,
+    installation_occupancy: &InstallationOccupancy,
     target_sounds_per_installation: &mut TargetSoundsPerInstallation,
 ) {
     target_sounds_per_installation.clear();
@@ -1135,6 +1456,7 @@ fn update_target_sounds_per_installation(
             installation,
             installation_constraints,
             installation_areas,
+            installation_occupancy.get(installation),
         );
         target_sounds_per_installation.insert(*installation, target_num_sounds);
     }
@@ -1148,12 +1470,20 @@ fn update_available_groups(
     groups: &Groups,
     active_sounds: &ActiveSounds,
     groups_last_used: &GroupsLastUsed,
+    follow_ons: &[rule::FollowOn],
     available_groups: &mut AvailableGroups,
 ) {
     available_groups.clear();
     let extension = groups
         .iter()
         .filter_map(|(group_id, group)| {
+            // Skip groups that are disabled by their schedule.
+            let range = &group.simultaneous_sounds;
+            let simultaneous_sounds = match group.schedule.evaluate(&tick.local_time) {
+                None => return None,
+                Some(scale) => schedule::scale_simultaneous_sounds(range, scale),
+            };
+
             // The total number of active sounds spawned via this group across all installations.
             let num_active_sounds = active_sounds
                 .values()
@@ -1168,18 +1498,26 @@ fn update_available_groups(
                 .count();
 
             // If there are no available sounds, skip this group.
-            let num_available_sounds = if group.simultaneous_sounds.max > num_active_sounds {
-                group.simultaneous_sounds.max - num_active_sounds
+            let num_available_sounds = if simultaneous_sounds.max > num_active_sounds {
+                simultaneous_sounds.max - num_active_sounds
             } else {
                 return None;
             };
 
-            let num_sounds_needed = if group.simultaneous_sounds.min > num_active_sounds {
-                group.simultaneous_sounds.min - num_active_sounds
+            let num_sounds_needed = if simultaneous_sounds.min > num_active_sounds {
+                simultaneous_sounds.min - num_active_sounds
             } else {
                 0
             };
 
+            // Groups containing the source of a pending follow-on ignore their occurrence rate.
+            let has_follow_on = follow_ons.iter().any(|follow_on| {
+                match sources.get(&follow_on.source) {
+                    Some(source) => source.groups.contains(group_id),
+                    None => false,
+                }
+            });
+
             // Find the duration since the last time a sound was spawned using a source from
             // this group.
             let timing = if let Some(&last_used) = groups_last_used.get(group_id) {
@@ -1190,6 +1528,8 @@ fn update_available_groups(
                 let duration_since_min_interval =
                     if duration_since_last_ms > group.occurrence_rate.min {
                         duration_since_last_ms - group.occurrence_rate.min
+                    } else if has_follow_on {
+                        Ms(0.0)
                     } else {
                         return None;
                     };
@@ -1209,6 +1549,7 @@ fn update_available_groups(
                 num_sounds_needed,
                 num_available_sounds,
                 timing,
+                weight: 1.0,
             };
 
             Some(AvailableGroup {
@@ -1226,6 +1567,8 @@ fn update_available_sources(
     sources: &Sources,
     active_sounds: &ActiveSounds,
     sources_last_used: &SourcesLastUsed,
+    rules: &[Rule],
+    follow_ons: &[rule::FollowOn],
     available_groups: &AvailableGroups,
     available_sources: &mut AvailableSources,
 ) {
@@ -1242,6 +1585,13 @@ fn update_available_sources(
             return None;
         }
 
+        // Skip sources that are disabled by their schedule.
+        let range = &source.simultaneous_sounds;
+        let simultaneous_sounds = match source.schedule.evaluate(&tick.local_time) {
+            None => return None,
+            Some(scale) => schedule::scale_simultaneous_sounds(range, scale),
+        };
+
         // How many instances of this sound are already playing.
         let num_sounds = active_sounds
             .values()
@@ -1249,28 +1599,51 @@ fn update_available_sources(
             .count();
 
         // If there are no available sounds, skip this group.
-        let num_available_sounds = if source.simultaneous_sounds.max > num_sounds {
-            source.simultaneous_sounds.max - num_sounds
+        let num_available_sounds = if simultaneous_sounds.max > num_sounds {
+            simultaneous_sounds.max - num_sounds
         } else {
             return None;
         };
 
+        // The duration since the last time a sound was spawned using this source.
+        let since_last_use = sources_last_used.get(source_id).map(|&last_use| {
+            let duration_since_last = tick.instant.duration_since(last_use);
+            Ms(duration_to_secs(&duration_since_last) * 1_000.0)
+        });
+
+        // Skip sources that are prevented from playing by a rule.
+        let is_active = |id| active_sounds.values().any(|s| s.source_id() == id);
+        if rules.iter().any(|rule| rule.prevents(*source_id, &is_active, since_last_use)) {
+            return None;
+        }
+
+        // Whether or not a sound from this source must follow one that has ended.
+        let is_follow_on = follow_ons.iter().any(|follow_on| {
+            follow_on.source == *source_id && follow_on.applies_to(source, installation)
+        });
+
         // Determine the number of this sound that is required to reach the minimum.
-        let num_sounds_needed = if source.simultaneous_sounds.min > num_sounds {
-            source.simultaneous_sounds.min - num_sounds
+        //
+        // A pending follow-on always requires a sound.
+        let num_sounds_needed = if simultaneous_sounds.min > num_sounds {
+            simultaneous_sounds.min - num_sounds
         } else {
             0
         };
+        let num_sounds_needed = if is_follow_on {
+            cmp::max(num_sounds_needed, 1)
+        } else {
+            num_sounds_needed
+        };
 
         // Find the duration since the last time a sound was spawned using a source
         // from this group.
-        let timing = if let Some(&last_use) = sources_last_used.get(source_id) {
-            let duration_since_last = tick.instant.duration_since(last_use);
-            let duration_since_last_ms =
-                Ms(duration_to_secs(&duration_since_last) * 1_000.0);
+        let timing = if let Some(duration_since_last_ms) = since_last_use {
             let duration_since_min_interval =
                 if duration_since_last_ms > source.occurrence_rate.min {
                     duration_since_last_ms - source.occurrence_rate.min
+                } else if is_follow_on {
+                    Ms(0.0)
                 } else {
                     return None;
                 };
@@ -1290,6 +1663,7 @@ fn update_available_sources(
             num_sounds_needed,
             num_available_sounds,
             timing,
+            weight: source.weight.max(audio::source::MIN_WEIGHT),
         };
 
         Some(AvailableSource {
@@ -1298,12 +1672,64 @@ fn update_available_sources(
             playback_duration: source.playback_duration,
             attack_duration: source.attack_duration,
             release_duration: source.release_duration,
+            attack_curve: source.attack_curve,
+            release_curve: source.release_curve,
+            hold_duration: source.hold_duration,
+            playback_rate: source.playback_rate,
         })
     });
     available_sources.extend(extension);
 }
 
 
+// Track the available groups that need sounds to reach their minimum and whether or not at least
+// one of their sources is available.
+fn track_group_sources(
+    sources: &Sources,
+    available_groups: &AvailableGroups,
+    available_sources: &AvailableSources,
+    groups_needing_sounds: &mut GroupSet,
+    groups_with_available_sources: &mut GroupSet,
+) {
+    for group in available_groups.iter() {
+        if group.suitability.num_sounds_needed == 0 {
+            continue;
+        }
+        groups_needing_sounds.insert(group.id);
+        let has_available_source = available_sources
+            .iter()
+            .any(|s| sources[&s.id].groups.contains(&group.id));
+        if has_available_source {
+            groups_with_available_sources.insert(group.id);
+        }
+    }
+}
+
+// Update the set of starved groups, i.e. those that need sounds but had no available sources.
+//
+// Each group that has newly become starved is sent to the GUI to be recorded in the history.
+fn update_starved_groups(
+    tick: &Tick,
+    groups_needing_sounds: &GroupSet,
+    groups_with_available_sources: &GroupSet,
+    starved_groups: &mut GroupSet,
+    output: &Output,
+) {
+    starved_groups.retain(|id| {
+        groups_needing_sounds.contains(id) && !groups_with_available_sources.contains(id)
+    });
+    for &group in groups_needing_sounds.difference(groups_with_available_sources) {
+        if !starved_groups.insert(group) {
+            continue;
+        }
+        if let Output::Audio { ref history_tx, .. } = *output {
+            let starved = history::Starved { group, time: tick.local_time };
+            // The GUI may have already exited.
+            history_tx.send(history::Entry::Starved(starved)).ok();
+        }
+    }
+}
+
 // Order the two sets or properties by their suitability for use as the next sound.
 fn suitability(a: &Suitability, b: &Suitability) -> cmp::Ordering {
     match b.num_sounds_needed.cmp(&a.num_sounds_needed) {
@@ -1311,20 +1737,110 @@ fn suitability(a: &Suitability, b: &Suitability) -> cmp::Ordering {
             (&None, &Some(_)) => cmp::Ordering::Less,
             (&Some(_), &None) => cmp::Ordering::Greater,
             (&None, &None) => cmp::Ordering::Equal,
-            (&Some(ref a), &Some(ref b)) => a.duration_until_sound_needed
-                .partial_cmp(&b.duration_until_sound_needed)
-                .expect("could not compare `duration_until_sound_needed`"),
+            (&Some(ref a_timing), &Some(ref b_timing)) => {
+                weighted_duration_until_sound_needed(a_timing, a.weight)
+                    .partial_cmp(&weighted_duration_until_sound_needed(b_timing, b.weight))
+                    .expect("could not compare `duration_until_sound_needed`")
+            },
         },
         ord => ord,
     }
 }
 
+// The duration until a sound is needed in milliseconds, scaled by the given weight.
+//
+// Heavier candidates appear to be needed sooner, or to be more overdue if the time has passed.
+fn weighted_duration_until_sound_needed(timing: &Timing, weight: f64) -> f64 {
+    let ms = timing.duration_until_sound_needed.ms();
+    if ms > 0.0 {
+        ms / weight
+    } else {
+        ms * weight
+    }
+}
+
+// The index of the pending follow-on that a sound from the given source would satisfy within the
+// given installation, if any.
+fn follow_on_index(
+    follow_ons: &[rule::FollowOn],
+    source_id: &audio::source::Id,
+    source: &audio::source::Soundscape,
+    installation: &installation::Id,
+) -> Option<usize> {
+    follow_ons
+        .iter()
+        .position(|f| f.source == *source_id && f.applies_to(source, installation))
+}
+
+// Pick the index of one of the given weights at random, in proportion to its weight.
+fn pick_weighted<R, I>(rng: &mut R, weights: I) -> usize
+where
+    R: Rng,
+    I: IntoIterator<Item = f64>,
+    I::IntoIter: Clone,
+{
+    let weights = weights.into_iter();
+    let total: f64 = weights.clone().sum();
+    let mut remaining = rng.gen::<f64>() * total;
+    let mut index = 0;
+    for (i, weight) in weights.enumerate() {
+        index = i;
+        if remaining < weight {
+            break;
+        }
+        remaining -= weight;
+    }
+    index
+}
+
+// Pick the index of the next source for the group from its Markov transitions, if it has any.
+//
+// Only the available sources belonging to the group with a positive weight from the group's last
+// spawned source are considered. Returns `None` if the group does not sequence its sources, has
+// not yet spawned a source or if none of the sources that may follow are available.
+fn markov_source_index<R>(
+    rng: &mut R,
+    group_id: &group::Id,
+    groups: &Groups,
+    groups_last_source: &GroupsLastSource,
+    sources: &Sources,
+    available_sources: &[AvailableSource],
+) -> Option<usize>
+where
+    R: Rng,
+{
+    let markov = match groups.get(group_id) {
+        Some(group) if group.markov.enabled => &group.markov,
+        _ => return None,
+    };
+    let last_source = match groups_last_source.get(group_id) {
+        Some(last_source) => last_source,
+        None => return None,
+    };
+    let weights: Vec<(usize, f64)> = available_sources
+        .iter()
+        .enumerate()
+        .filter(|&(_, s)| sources[&s.id].constraints.groups.contains(group_id))
+        .map(|(i, s)| (i, markov.weight(last_source, &s.id)))
+        .filter(|&(_, weight)| weight > 0.0)
+        .collect();
+    if weights.is_empty() {
+        return None;
+    }
+    let index = pick_weighted(rng, weights.iter().map(|&(_, weight)| weight));
+    Some(weights[index].0)
+}
+
 // Called each time the soundscape thread receives a tick.
 fn tick(model: &mut Model, tick: Tick) {
+    // Step any scene fade in progress before the constraints are used.
+    model.update_scene_fade(&tick.since_last_tick);
+
     let Model {
         ref frame_count,
         realtime_source_latency,
         seed,
+        ref mut rng,
         ref mut playback_duration,
         ref installations,
         ref groups,
@@ -1332,7 +1848,10 @@ fn tick(model: &mut Model, tick: Tick) {
         ref sources,
         ref mut groups_last_used,
         ref mut sources_last_used,
+        ref mut groups_last_source,
         ref mut active_sounds,
+        ref rules,
+        ref mut follow_ons,
         ref mut installation_speakers,
         ref mut installation_areas,
         ref mut target_sounds_per_installation,
@@ -1340,16 +1859,21 @@ fn tick(model: &mut Model, tick: Tick) {
         ref mut active_sound_positions,
         ref mut available_groups,
         ref mut available_sources,
+        ref mut groups_needing_sounds,
+        ref mut groups_with_available_sources,
+        ref mut starved_groups,
+        ref mut installation_occupancy,
         ref mut sound_id_gen,
-        ref wav_reader,
-        ref audio_input_stream,
-        ref audio_output_stream,
+        ref mut output,
         ..
     } = *model;
 
     // Update the playback duration so far.
     *playback_duration = tick.playback_duration;
 
+    // Forget any follow-on sounds that could not be spawned in time.
+    follow_ons.retain(|follow_on| follow_on.deadline >= tick.playback_duration);
+
     // Update the map from installations to speakers.
     update_installation_speakers(speakers, installation_speakers);
 
@@ -1358,6 +1882,12 @@ fn tick(model: &mut Model, tick: Tick) {
     // An installations `Area` is determined via the assigned speaker locations.
     update_installation_areas(speakers, installation_speakers, installation_areas);
 
+    // Smooth the occupancy of each installation, forgetting those that have timed out.
+    installation_occupancy.retain(|installation, level| match installations.get(installation) {
+        Some(constraints) => level.update(&tick.since_last_tick, &constraints.occupancy),
+        None => false,
+    });
+
     // Determine the target number of sounds per installation.
     //
     // We can determine this in a purely functional manner by using the playback duration as the
@@ -1367,12 +1897,12 @@ fn tick(model: &mut Model, tick: Tick) {
         &tick.playback_duration,
         installations,
         installation_areas,
+        installation_occupancy,
         target_sounds_per_installation,
     );
 
     // Update the movement of each active sound.
     {
-        let mut rng = nannou::rand::thread_rng();
         update_active_sound_positions(active_sounds, active_sound_positions);
         for (&sound_id, sound) in active_sounds.iter_mut() {
             let initial_installation_area = installation_areas.get(&sound.initial_installation);
@@ -1389,7 +1919,7 @@ fn tick(model: &mut Model, tick: Tick) {
                             &target_sounds_per_installation,
                             &active_sound_positions,
                         );
-                        agent.update(&mut rng, &tick.since_last_tick, &installation_data);
+                        agent.update(&mut *rng, &tick.since_last_tick, &installation_data);
                     },
                     movement::Generative::Ngon(ref mut ngon) => {
                         if let Some(area) = initial_installation_area {
@@ -1404,13 +1934,20 @@ fn tick(model: &mut Model, tick: Tick) {
             // The audio thread will then notify the GUI of the new position upon the next rendered
             // buffer.
             let position = sound.position();
-            audio_output_stream
-                .send(move |audio| {
-                    audio.update_sound(&sound_id, move |sound| {
-                        sound.position = position;
-                    });
-                })
-                .expect("failed to send audio output thread updated sound position");
+            match *output {
+                Output::Audio { ref audio_output_stream, .. } => {
+                    audio_output_stream
+                        .send(move |audio| {
+                            audio.update_sound(&sound_id, move |sound| {
+                                sound.position = position;
+                            });
+                        })
+                        .expect("failed to send audio output thread updated sound position");
+                },
+                Output::Simulation(ref mut recorder) => {
+                    recorder.update_position(sound_id, &tick, position);
+                },
+            }
         }
     }
 
@@ -1427,6 +1964,11 @@ fn tick(model: &mut Model, tick: Tick) {
         active_sounds_per_installation,
     );
 
+    // Track the groups that need sounds and whether any of their sources are available.
+    groups_needing_sounds.clear();
+    groups_with_available_sources.clear();
+    let mut groups_evaluated = false;
+
     // Determine how many sounds to add (if any) by finding the difference between the target
     // number and actual number.
     'installations: for (installation, &num_target_sounds) in target_sounds_per_installation.iter() {
@@ -1434,13 +1976,27 @@ fn tick(model: &mut Model, tick: Tick) {
             None => 0,
             Some(sounds) => sounds.len(),
         };
-        let sounds_to_add = if num_target_sounds > num_active_sounds {
+        let num_sounds_under_target = if num_target_sounds > num_active_sounds {
             num_target_sounds - num_active_sounds
         } else {
-            // If there are no sounds to add, move on to the next installation.
-            continue 'installations;
+            0
         };
 
+        // Follow-on sounds may exceed the target number of sounds for the installation.
+        let num_follow_ons = follow_ons
+            .iter()
+            .filter(|follow_on| match sources.get(&follow_on.source) {
+                Some(source) => follow_on.applies_to(source, installation),
+                None => false,
+            })
+            .count();
+
+        // If there are no sounds to add, move on to the next installation.
+        let sounds_to_add = cmp::max(num_sounds_under_target, num_follow_ons);
+        if sounds_to_add == 0 {
+            continue 'installations;
+        }
+
         // The movement area associated with this installation.
         //
         // If there is no area, there is nowhere we can safely place sounds so we continue.
@@ -1453,7 +2009,7 @@ fn tick(model: &mut Model, tick: Tick) {
         //
         // Each time a sound is added the available group from which it was sourced should be
         // updated and the vec should be re-sorted.
-        for _ in 0..sounds_to_add {
+        for i in 0..sounds_to_add {
             {
                 // Collect available groups of sounds (based on occurrence rate and simultaneous sounds).
                 update_available_groups(
@@ -1462,8 +2018,10 @@ fn tick(model: &mut Model, tick: Tick) {
                     groups,
                     active_sounds,
                     groups_last_used,
+                    follow_ons,
                     available_groups,
                 );
+                groups_evaluated = true;
 
                 // If there are no available groups, go to the next installation.
                 if available_groups.is_empty() {
@@ -1477,9 +2035,18 @@ fn tick(model: &mut Model, tick: Tick) {
                     sources,
                     active_sounds,
                     sources_last_used,
+                    rules,
+                    follow_ons,
                     available_groups,
                     available_sources,
                 );
+                track_group_sources(
+                    sources,
+                    available_groups,
+                    available_sources,
+                    groups_needing_sounds,
+                    groups_with_available_sources,
+                );
 
                 // If there are no available sources for this group, continue to the next
                 // installtion.
@@ -1504,15 +2071,58 @@ fn tick(model: &mut Model, tick: Tick) {
                     let num_equal = utils::count_equal(&*available_groups, |a, b| {
                         suitability(&a.suitability, &b.suitability)
                     });
-                    nannou::rand::thread_rng().gen_range(0, num_equal)
+                    rng.gen_range(0, num_equal)
                 };
 
-                // Retrieve one of the most suitable sources.
-                let source_index: usize = {
-                    let num_equal = utils::count_equal(&*available_sources, |a, b| {
-                        suitability(&a.suitability, &b.suitability)
-                    });
-                    nannou::rand::thread_rng().gen_range(0, num_equal)
+                // Retrieve one of the most suitable sources, favouring those with greater weight.
+                //
+                // If the group sequences its sources, the source follows from the group's last.
+                let markov_index = markov_source_index(
+                    &mut *rng,
+                    &available_groups[group_index].id,
+                    groups,
+                    groups_last_source,
+                    sources,
+                    available_sources,
+                );
+                let source_index: usize = match markov_index {
+                    Some(index) => index,
+                    None => {
+                        let num_equal = utils::count_equal(&*available_sources, |a, b| {
+                            suitability(&a.suitability, &b.suitability)
+                        });
+                        let weights = available_sources[..num_equal]
+                            .iter()
+                            .map(|s| s.suitability.weight);
+                        pick_weighted(&mut *rng, weights)
+                    },
+                };
+
+                // Sounds beyond the installation's target may only satisfy follow-ons, so search
+                // the remaining available sources for a pending follow-on before moving on.
+                let (group_index, source_index) = if i >= num_sounds_under_target {
+                    let follow_ons = &follow_ons[..];
+                    let is_follow_on = |s: &AvailableSource| {
+                        follow_on_index(follow_ons, &s.id, &sources[&s.id], installation).is_some()
+                    };
+                    let index = match is_follow_on(&available_sources[source_index]) {
+                        true => Some(source_index),
+                        false => available_sources.iter().position(|s| is_follow_on(s)),
+                    };
+                    let source_index = match index {
+                        None => continue 'installations,
+                        Some(index) => index,
+                    };
+                    // Spawn the follow-on from one of its own available groups.
+                    let source_id = available_sources[source_index].id;
+                    let source_groups = &sources[&source_id].constraints.groups;
+                    let group_index = available_groups
+                        .iter()
+                        .position(|g| source_groups.contains(&g.id))
+                        .unwrap_or(group_index);
+                    (group_index, source_index)
+                } else {
+                    (group_index, source_index)
                 };
 
                 // Pick one of the most suitable sources.
@@ -1520,66 +2130,35 @@ fn tick(model: &mut Model, tick: Tick) {
                     //let source = find_equally_suitable(available_sources.iter().map(|s| &s.suitability));
                     let source = &available_sources[source_index];
 
-                    // TODO: Determine the initial position of the sound based on:
-                    //
-                    // 1. Installation for which we're triggereing a sound.
-                    // 2. Movement properties and constraints of the source and group.
-                    let initial_position = {
-                        let mut rng = nannou::rand::thread_rng();
-                        let left: bool = rng.gen();
-                        let x_mag: f64 = rng.gen();
-                        let x = match left {
-                            true => {
-                                Metres(x_mag)
-                                    * (installation_area.centroid.x
-                                        - installation_area.bounding_rect.left)
-                                    + installation_area.centroid.x
-                            }
-                            false => {
-                                Metres(x_mag)
-                                    * (installation_area.centroid.x
-                                        - installation_area.bounding_rect.right)
-                                    + installation_area.centroid.x
-                            }
-                        };
-                        let down: bool = rng.gen();
-                        let y_mag: f64 = rng.gen();
-                        let y = match down {
-                            true => {
-                                Metres(y_mag)
-                                    * (installation_area.centroid.y
-                                        - installation_area.bounding_rect.bottom)
-                                    + installation_area.centroid.y
-                            }
-                            false => {
-                                Metres(y_mag)
-                                    * (installation_area.centroid.y
-                                        - installation_area.bounding_rect.top)
-                                    + installation_area.centroid.y
-                            }
-                        };
-                        let point = Point2 { x, y };
-                        let radians = rng.gen::<f32>() * 2.0 * ::std::f32::consts::PI;
-                        audio::sound::Position { point, radians }
-                    };
-
-                    // Generate the attack and release durations based on their source ranges.
-                    let mut rng = nannou::rand::thread_rng();
-                    let attack_duration_frames =
-                        audio::source::random_playback_duration(&mut rng, source.attack_duration)
+                    // Generate the attack, hold and release durations based on their source ranges.
+                    let attack_frames =
+                        audio::source::random_playback_duration(&mut *rng, source.attack_duration)
                             .to_samples(audio::SAMPLE_RATE);
-                    let release_duration_frames =
-                        audio::source::random_playback_duration(&mut rng, source.release_duration)
+                    let hold_frames =
+                        audio::source::random_playback_duration(&mut *rng, source.hold_duration)
                             .to_samples(audio::SAMPLE_RATE);
+                    let release_frames =
+                        audio::source::random_playback_duration(&mut *rng, source.release_duration)
+                            .to_samples(audio::SAMPLE_RATE);
+                    let envelope = audio::source::Envelope {
+                        attack_frames,
+                        attack_curve: source.attack_curve,
+                        hold_frames,
+                        release_frames,
+                        release_curve: source.release_curve,
+                    };
                     let duration_frames =
-                        audio::source::random_playback_duration(&mut rng, source.playback_duration)
+                        audio::source::random_playback_duration(&mut *rng, source.playback_duration)
                             .to_samples(audio::SAMPLE_RATE);
+                    let playback_rate =
+                        audio::source::random_playback_rate(&mut *rng, source.playback_rate);
 
                     // This is not a continuous preview (this is only used for GUI sounds).
                     let continuous_preview = false;
 
                     // Choose a movement type based on the source's assigned options.
-                    let movement = generate_movement(
+                    let mut movement = generate_movement(
+                        rng,
                         source.id,
                         &sources,
                         *installation,
@@ -1589,33 +2168,87 @@ fn tick(model: &mut Model, tick: Tick) {
                         &active_sounds,
                     );
 
+                    // Place agents using the source's placement strategy. Fixed and ngon
+                    // movements begin at the position determined by their constraints.
+                    if let Movement::Generative(movement::Generative::Agent(ref mut agent)) =
+                        movement
+                    {
+                        let location = initial_location(
+                            rng,
+                            &sources[&source.id].placement,
+                            installation_area,
+                            installation_speakers
+                                .get(installation)
+                                .map(|ids| &ids[..])
+                                .unwrap_or(&[]),
+                            speakers,
+                            &active_sounds,
+                        );
+                        agent.place(location);
+                    }
+                    let initial_position = movement.position();
+
                     // Spawn the sound from this source
                     let audio_source = sources[&source.id].to_audio_source();
                     let source_id = source.id;
+                    let group_id = available_groups[group_index].id;
                     let sound_id = sound_id_gen.generate_next();
-                    let sound = audio::sound::spawn_from_source(
-                        sound_id,
-                        source_id,
-                        &audio_source,
-                        initial_position,
-                        attack_duration_frames,
-                        release_duration_frames,
-                        continuous_preview,
-                        Some(duration_frames),
-                        frame_count.load(atomic::Ordering::Relaxed) as _,
-                        wav_reader,
-                        audio_input_stream,
-                        audio_output_stream,
-                        realtime_source_latency,
-                    );
+                    let sound = match *output {
+                        Output::Audio {
+                            ref wav_reader,
+                            ref audio_input_stream,
+                            ref audio_output_stream,
+                            ..
+                        } => audio::sound::spawn_from_source(
+                            sound_id,
+                            source_id,
+                            &audio_source,
+                            initial_position,
+                            envelope,
+                            playback_rate,
+                            continuous_preview,
+                            Some(duration_frames),
+                            frame_count.load(atomic::Ordering::Relaxed) as _,
+                            wav_reader,
+                            audio_input_stream,
+                            audio_output_stream,
+                            realtime_source_latency,
+                            rng,
+                        ),
+                        Output::Simulation(ref mut recorder) => recorder.spawn(
+                            sound_id,
+                            source_id,
+                            &audio_source,
+                            *installation,
+                            group_id,
+                            &tick,
+                            initial_position,
+                            playback_rate,
+                            duration_frames,
+                            &movement,
+                        ),
+                    };
 
                     // Track the time at which the group and source were last used.
-                    groups_last_used.insert(available_groups[group_index].id, tick.instant);
+                    groups_last_used.insert(group_id, tick.instant);
                     sources_last_used.insert(source_id, tick.instant);
 
+                    // Track the source as the last spawned within each of its groups.
+                    for group in &sources[&source_id].constraints.groups {
+                        groups_last_source.insert(*group, source_id);
+                    }
+
+                    // If this sound satisfies a follow-on, it is no longer pending.
+                    let source = &sources[&source_id];
+                    if let Some(i) = follow_on_index(follow_ons, &source_id, source, installation) {
+                        follow_ons.remove(i);
+                    }
+
                     // Create the active sound for out use.
                     let active_sound = ActiveSound {
                         initial_installation: *installation,
+                        group: group_id,
+                        started: tick.local_time,
                         handle: sound,
                         movement,
                     };
@@ -1626,4 +2259,17 @@ fn tick(model: &mut Model, tick: Tick) {
             }
         }
     }
+
+    // Record the groups that have become starved of sources.
+    //
+    // Starvation may only be determined on ticks where the soundscape looked for new sounds.
+    if groups_evaluated {
+        update_starved_groups(
+            &tick,
+            groups_needing_sounds,
+            groups_with_available_sources,
+            starved_groups,
+            output,
+        );
+    }
 }
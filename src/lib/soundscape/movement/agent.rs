@@ -97,6 +97,18 @@ impl Agent {
         agent
     }
 
+    /// Move the **Agent** to the given location, steering its velocity towards its target.
+    ///
+    /// This is used to apply the initial placement strategy of the agent's source.
+    pub fn place(&mut self, location: Point) {
+        let magnitude = vt2::to_f64(self.velocity).magnitude();
+        let desired_velocity = desired_velocity(location, self.target_location);
+        let radians = desired_velocity.y.0.atan2(desired_velocity.x.0);
+        let (vx, vy) = utils::rad_mag_to_x_y(radians, magnitude);
+        self.location = location;
+        self.velocity = [Metres(vx), Metres(vy)].into();
+    }
+
     /// The current location and orientation of the **Agent** for use within the audio engine's
     /// DBAP calculations.
     pub fn position(&self) -> audio::sound::Position {
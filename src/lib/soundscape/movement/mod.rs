@@ -60,6 +60,15 @@ impl Movement {
             Movement::Generative(ref generative) => generative.position(),
         }
     }
+
+    /// A short label describing the kind of movement, e.g. for logs and history.
+    pub fn kind(&self) -> &'static str {
+        match *self {
+            Movement::Fixed(_) => "fixed",
+            Movement::Generative(Generative::Agent(_)) => "agent",
+            Movement::Generative(Generative::Ngon(_)) => "ngon",
+        }
+    }
 }
 
 impl BoundingRect {
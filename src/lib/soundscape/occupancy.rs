@@ -0,0 +1,73 @@
+//! Smoothing the occupancy of installations received via OSC.
+//!
+//! Each installation's occupancy shifts its target number of sounds within its simultaneous sounds
+//! range. Levels are smoothed towards newly received values and fade out once values stop arriving
+//! so that a disconnected people counter does not leave an installation stuck at one density.
+
+use installation;
+use std::time;
+use utils::duration_to_secs;
+
+/// The smoothed occupancy of a single installation.
+#[derive(Copy, Clone, Debug)]
+pub struct Level {
+    /// The most recently received occupancy, normalised to `0.0..=1.0`.
+    target: f64,
+    /// The normalised occupancy smoothed towards the target.
+    smoothed: f64,
+    /// The degree to which occupancy is currently known, faded in as values arrive and out once
+    /// they time out.
+    presence: f64,
+    /// The soundscape playback duration since the last value was received.
+    since_received: time::Duration,
+}
+
+impl Level {
+    /// A new level beginning at the given normalised occupancy.
+    pub fn new(target: f64) -> Self {
+        Level {
+            target,
+            smoothed: target,
+            presence: 0.0,
+            since_received: time::Duration::from_secs(0),
+        }
+    }
+
+    /// Set the normalised occupancy towards which the level is smoothed.
+    pub fn receive(&mut self, target: f64) {
+        self.target = target;
+        self.since_received = time::Duration::from_secs(0);
+    }
+
+    /// Step the smoothing forward by the given duration.
+    ///
+    /// Returns `false` once the level has timed out and faded out entirely, at which point it may
+    /// be discarded.
+    pub fn update(
+        &mut self,
+        since_last_tick: &time::Duration,
+        constraints: &installation::Occupancy,
+    ) -> bool {
+        self.since_received += *since_last_tick;
+        let since_received_ms = duration_to_secs(&self.since_received) * 1_000.0;
+        let timed_out = since_received_ms >= constraints.timeout.ms();
+        let smoothing_ms = constraints.smoothing.ms();
+        let since_last_tick_ms = duration_to_secs(since_last_tick) * 1_000.0;
+        let amount = match smoothing_ms > 0.0 {
+            true => 1.0 - (-since_last_tick_ms / smoothing_ms).exp(),
+            false => 1.0,
+        };
+        let target_presence = if timed_out { 0.0 } else { 1.0 };
+        if !timed_out {
+            self.smoothed += (self.target - self.smoothed) * amount;
+        }
+        self.presence += (target_presence - self.presence) * amount;
+        !timed_out || self.presence > ::std::f64::EPSILON.sqrt()
+    }
+
+    /// Shift the given normalised target number of sounds towards the occupancy.
+    pub fn apply(&self, normalised_target: f64, constraints: &installation::Occupancy) -> f64 {
+        let influence = constraints.influence.min(1.0).max(0.0) * self.presence;
+        normalised_target * (1.0 - influence) + self.smoothed * influence
+    }
+}
@@ -0,0 +1,181 @@
+//! Rules relating the playback of soundscape sources to one another.
+//!
+//! Rules are evaluated as the soundscape determines which sources are available for new sounds,
+//! allowing narrative sequences (e.g. a thunder roll following lightning) to be composed from the
+//! soundscape itself.
+
+use audio;
+use installation;
+use std::time;
+use time_calc::Ms;
+
+/// A rule constraining when the sounds of one or more sources may play.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum Rule {
+    /// Sounds from the two sources never play at the same time.
+    Exclusive {
+        a: audio::source::Id,
+        b: audio::source::Id,
+    },
+    /// Once a sound from `after` ends, a sound from `follow` must begin `within` the duration.
+    ///
+    /// The following sound ignores the minimum occurrence rate of its source and group, and may
+    /// exceed the target number of sounds of its installation. It is preferably spawned within
+    /// the installation of the sound that ended.
+    FollowOn {
+        after: audio::source::Id,
+        follow: audio::source::Id,
+        within: Ms,
+    },
+    /// Sounds from the source may not begin within the `interval` of one another across all
+    /// installations.
+    NoRepeat {
+        source: audio::source::Id,
+        interval: Ms,
+    },
+}
+
+/// A sound that must follow one that has ended, as described by a `Rule::FollowOn`.
+#[derive(Copy, Clone, Debug)]
+pub struct FollowOn {
+    /// The source from which the following sound must be spawned.
+    pub source: audio::source::Id,
+    /// The installation in which the preceding sound was spawned.
+    pub installation: installation::Id,
+    /// The soundscape playback duration by which the following sound must have begun.
+    pub deadline: time::Duration,
+}
+
+pub mod default {
+    use time_calc::Ms;
+    use utils::{MIN_MS, SEC_MS};
+
+    pub const FOLLOW_ON_WITHIN: Ms = Ms(10.0 * SEC_MS);
+    pub const NO_REPEAT_INTERVAL: Ms = Ms(10.0 * MIN_MS);
+}
+
+impl Rule {
+    /// Whether or not the rule prevents a new sound from being spawned from the given source.
+    ///
+    /// `is_active` returns whether or not a sound from the given source is currently playing.
+    /// `since_last_use` is the duration since a sound was last spawned from `source_id`, if ever.
+    pub fn prevents<F>(
+        &self,
+        source_id: audio::source::Id,
+        is_active: F,
+        since_last_use: Option<Ms>,
+    ) -> bool
+    where
+        F: Fn(audio::source::Id) -> bool,
+    {
+        match *self {
+            Rule::Exclusive { a, b } if a == source_id => is_active(b),
+            Rule::Exclusive { a, b } if b == source_id => is_active(a),
+            Rule::NoRepeat { source, interval } if source == source_id => match since_last_use {
+                Some(since_last_use) => since_last_use < interval,
+                None => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// The follow-on that must occur now that a sound from the given source has ended, if any.
+    ///
+    /// `playback_duration` is the current soundscape playback duration.
+    pub fn follow_on(
+        &self,
+        ended: audio::source::Id,
+        installation: installation::Id,
+        playback_duration: time::Duration,
+    ) -> Option<FollowOn> {
+        match *self {
+            Rule::FollowOn { after, follow, within } if after == ended => {
+                let within = time::Duration::from_millis(within.ms() as u64);
+                Some(FollowOn {
+                    source: follow,
+                    installation,
+                    deadline: playback_duration + within,
+                })
+            },
+            _ => None,
+        }
+    }
+}
+
+impl FollowOn {
+    /// Whether or not the follow-on may be spawned within the given installation.
+    ///
+    /// If the following source is assigned to the installation of the preceding sound, the
+    /// follow-on only applies to that installation.
+    pub fn applies_to(
+        &self,
+        source: &audio::source::Soundscape,
+        installation: &installation::Id,
+    ) -> bool {
+        source.installations.contains(installation)
+            && (self.installation == *installation
+                || !source.installations.contains(&self.installation))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use audio::source::{self, Id};
+    use installation;
+    use std::time;
+    use super::{FollowOn, Rule};
+    use time_calc::Ms;
+
+    const A: Id = Id(0);
+    const B: Id = Id(1);
+    const C: Id = Id(2);
+
+    #[test]
+    fn exclusive_prevents_both_directions() {
+        let rule = Rule::Exclusive { a: A, b: B };
+        assert!(rule.prevents(A, |id| id == B, None));
+        assert!(rule.prevents(B, |id| id == A, None));
+        assert!(!rule.prevents(A, |_| false, None));
+        assert!(!rule.prevents(B, |_| false, None));
+        // The rule only relates its two sources.
+        assert!(!rule.prevents(C, |_| true, None));
+        assert!(!rule.prevents(A, |id| id == C, None));
+    }
+
+    #[test]
+    fn no_repeat_within_interval() {
+        let rule = Rule::NoRepeat { source: A, interval: Ms(1_000.0) };
+        assert!(!rule.prevents(A, |_| false, None));
+        assert!(rule.prevents(A, |_| false, Some(Ms(0.0))));
+        assert!(rule.prevents(A, |_| false, Some(Ms(999.0))));
+        assert!(!rule.prevents(A, |_| false, Some(Ms(1_000.0))));
+        assert!(!rule.prevents(A, |_| false, Some(Ms(5_000.0))));
+        assert!(!rule.prevents(B, |_| false, Some(Ms(0.0))));
+    }
+
+    #[test]
+    fn follow_on_applies_to_installation() {
+        let (one, two, three) = (installation::Id(1), installation::Id(2), installation::Id(3));
+        let follow_on = FollowOn {
+            source: B,
+            installation: one,
+            deadline: time::Duration::from_secs(10),
+        };
+
+        // When assigned to the preceding sound's installation, only that installation applies.
+        let mut source = source::Soundscape::default();
+        source.installations.insert(one);
+        source.installations.insert(two);
+        assert!(follow_on.applies_to(&source, &one));
+        assert!(!follow_on.applies_to(&source, &two));
+        assert!(!follow_on.applies_to(&source, &three));
+
+        // Otherwise, fall back to any of the following source's installations.
+        let mut source = source::Soundscape::default();
+        source.installations.insert(two);
+        source.installations.insert(three);
+        assert!(!follow_on.applies_to(&source, &one));
+        assert!(follow_on.applies_to(&source, &two));
+        assert!(follow_on.applies_to(&source, &three));
+    }
+}
@@ -0,0 +1,125 @@
+//! Crossfading the soundscape constraints of groups and installations between scenes.
+
+use fxhash::FxHashMap;
+use installation;
+use soundscape::{group, Group};
+use std::time;
+use time_calc::Ms;
+use utils::{duration_to_secs, Range};
+
+/// The constraints of a soundscape group captured by a scene.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct GroupConstraints {
+    pub occurrence_rate: Range<Ms>,
+    pub simultaneous_sounds: Range<usize>,
+}
+
+/// A crossfade of group and installation constraints from their current values to those of a
+/// recalled scene.
+#[derive(Clone, Debug)]
+pub struct Fade {
+    duration: Ms,
+    elapsed: Ms,
+    groups: FxHashMap<group::Id, (GroupConstraints, GroupConstraints)>,
+    installations: FxHashMap<installation::Id, (Range<usize>, Range<usize>)>,
+}
+
+impl Fade {
+    /// Create a fade from the current constraints towards the given targets.
+    ///
+    /// Groups and installations that do not currently exist are ignored.
+    pub fn new(
+        duration: Ms,
+        groups: &FxHashMap<group::Id, Group>,
+        installations: &FxHashMap<installation::Id, installation::Soundscape>,
+        target_groups: &FxHashMap<group::Id, GroupConstraints>,
+        target_installations: &FxHashMap<installation::Id, Range<usize>>,
+    ) -> Self {
+        let groups = target_groups
+            .iter()
+            .filter_map(|(id, target)| {
+                groups.get(id).map(|group| {
+                    let start = GroupConstraints::from(group);
+                    (*id, (start, target.clone()))
+                })
+            })
+            .collect();
+        let installations = target_installations
+            .iter()
+            .filter_map(|(id, &target)| {
+                installations
+                    .get(id)
+                    .map(|installation| (*id, (installation.simultaneous_sounds, target)))
+            })
+            .collect();
+        Fade {
+            duration,
+            elapsed: Ms(0.0),
+            groups,
+            installations,
+        }
+    }
+
+    /// Step the fade forward by the given duration.
+    pub fn advance(&mut self, duration: &time::Duration) {
+        self.elapsed = self.elapsed + Ms(duration_to_secs(duration) * 1_000.0);
+    }
+
+    /// Whether or not the fade has reached its target.
+    pub fn is_complete(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    /// Apply the constraints at the current position through the fade.
+    pub fn apply(
+        &self,
+        groups: &mut FxHashMap<group::Id, Group>,
+        installations: &mut FxHashMap<installation::Id, installation::Soundscape>,
+    ) {
+        let progress = self.progress();
+        for (id, &(ref start, ref end)) in &self.groups {
+            if let Some(group) = groups.get_mut(id) {
+                group.occurrence_rate = Range {
+                    min: lerp_ms(start.occurrence_rate.min, end.occurrence_rate.min, progress),
+                    max: lerp_ms(start.occurrence_rate.max, end.occurrence_rate.max, progress),
+                };
+                group.simultaneous_sounds =
+                    lerp_range(&start.simultaneous_sounds, &end.simultaneous_sounds, progress);
+            }
+        }
+        for (id, &(ref start, ref end)) in &self.installations {
+            if let Some(installation) = installations.get_mut(id) {
+                installation.simultaneous_sounds = lerp_range(start, end, progress);
+            }
+        }
+    }
+
+    // The normalised progress through the fade.
+    fn progress(&self) -> f64 {
+        if self.duration.ms() <= 0.0 {
+            return 1.0;
+        }
+        (self.elapsed.ms() / self.duration.ms()).min(1.0)
+    }
+}
+
+impl<'a> From<&'a Group> for GroupConstraints {
+    fn from(group: &'a Group) -> Self {
+        GroupConstraints {
+            occurrence_rate: group.occurrence_rate,
+            simultaneous_sounds: group.simultaneous_sounds,
+        }
+    }
+}
+
+fn lerp_ms(a: Ms, b: Ms, progress: f64) -> Ms {
+    Ms(a.ms() + (b.ms() - a.ms()) * progress)
+}
+
+fn lerp_range(a: &Range<usize>, b: &Range<usize>, progress: f64) -> Range<usize> {
+    let lerp = |a: usize, b: usize| (a as f64 + (b as f64 - a as f64) * progress).round() as usize;
+    Range {
+        min: lerp(a.min, b.min),
+        max: lerp(a.max, b.max),
+    }
+}
@@ -0,0 +1,216 @@
+//! Time-of-day and calendar schedules for soundscape groups and sources.
+//!
+//! A `Schedule` describes when a group or source is enabled in terms of wall-clock time. Each
+//! `Period` may also scale the `simultaneous_sounds` range of its group or source, allowing the
+//! character of the soundscape to change over the course of a day.
+//!
+//! Schedules are evaluated against the local time upon each soundscape `Tick`.
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+use utils::Range;
+
+/// Describes when a group or source is enabled.
+///
+/// The default schedule is always enabled.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct Schedule {
+    /// The periods during which the group or source is enabled.
+    ///
+    /// If empty, the group or source is enabled at all times other than during `closures`.
+    #[serde(default)]
+    pub periods: Vec<Period>,
+    /// Ranges of dates during which the group or source is disabled, e.g. holiday closures.
+    ///
+    /// Closures take precedence over `periods`.
+    #[serde(default)]
+    pub closures: Vec<Closure>,
+}
+
+/// A recurring period of time during which a group or source is enabled.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Period {
+    /// The days of the week on which the period begins.
+    ///
+    /// If empty, the period applies to every day.
+    #[serde(default)]
+    pub weekdays: Vec<Weekday>,
+    /// The time of day at which the period begins.
+    #[serde(default = "default::start")]
+    pub start: NaiveTime,
+    /// The time of day at which the period ends.
+    ///
+    /// If `end` is earlier than `start`, the period continues past midnight into the next day. If
+    /// `end` is equal to `start`, the period lasts the whole day.
+    #[serde(default = "default::end")]
+    pub end: NaiveTime,
+    /// Scales the `simultaneous_sounds` range of the group or source during the period.
+    #[serde(default = "default::simultaneous_sounds_scale")]
+    pub simultaneous_sounds_scale: f32,
+}
+
+/// An inclusive range of dates during which a group or source is disabled.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Closure {
+    pub first: NaiveDate,
+    pub last: NaiveDate,
+}
+
+impl Schedule {
+    /// Evaluate the schedule at the given local date and time.
+    ///
+    /// Returns `None` if the group or source is disabled, otherwise returns the amount by which
+    /// its `simultaneous_sounds` range should be scaled. Where multiple periods overlap, the
+    /// greatest scale is used.
+    pub fn evaluate(&self, now: &NaiveDateTime) -> Option<f32> {
+        let date = now.date();
+        if self.closures.iter().any(|c| c.first <= date && date <= c.last) {
+            return None;
+        }
+        if self.periods.is_empty() {
+            return Some(1.0);
+        }
+        self.periods
+            .iter()
+            .filter(|period| period.contains(now))
+            .map(|period| period.simultaneous_sounds_scale)
+            .fold(None, |max, scale| Some(max.map_or(scale, |max: f32| max.max(scale))))
+    }
+}
+
+impl Period {
+    /// Whether or not the given local date and time falls within the period.
+    pub fn contains(&self, now: &NaiveDateTime) -> bool {
+        let time = now.time();
+        let weekday = now.weekday();
+        if self.start == self.end {
+            self.applies_on(weekday)
+        } else if self.start < self.end {
+            self.applies_on(weekday) && self.start <= time && time < self.end
+        } else {
+            // The period wraps past midnight, so the hours after midnight belong to the period
+            // that began the previous day.
+            (self.applies_on(weekday) && self.start <= time)
+                || (self.applies_on(weekday.pred()) && time < self.end)
+        }
+    }
+
+    // Whether or not the period begins on the given day of the week.
+    fn applies_on(&self, weekday: Weekday) -> bool {
+        self.weekdays.is_empty() || self.weekdays.contains(&weekday)
+    }
+}
+
+impl Default for Period {
+    fn default() -> Self {
+        let weekdays = vec![];
+        let start = default::start();
+        let end = default::end();
+        let simultaneous_sounds_scale = default::simultaneous_sounds_scale();
+        Period { weekdays, start, end, simultaneous_sounds_scale }
+    }
+}
+
+/// Scale the given `simultaneous_sounds` range by the given amount, rounding to the nearest sound.
+pub fn scale_simultaneous_sounds(range: &Range<usize>, scale: f32) -> Range<usize> {
+    let scale = scale.max(0.0);
+    let min = (range.min as f32 * scale).round() as usize;
+    let max = (range.max as f32 * scale).round() as usize;
+    Range { min, max }
+}
+
+pub mod default {
+    use chrono::NaiveTime;
+
+    pub const SIMULTANEOUS_SOUNDS_SCALE: f32 = 1.0;
+
+    pub fn start() -> NaiveTime {
+        NaiveTime::from_hms_opt(0, 0, 0).expect("midnight is a valid time")
+    }
+
+    pub fn end() -> NaiveTime {
+        NaiveTime::from_hms_opt(0, 0, 0).expect("midnight is a valid time")
+    }
+
+    pub fn simultaneous_sounds_scale() -> f32 {
+        SIMULTANEOUS_SOUNDS_SCALE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+    use super::{Closure, Period, Schedule};
+
+    // 2019-03-18 is a Monday.
+    fn at(day: u32, h: u32, m: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2019, 3, day).unwrap().and_hms_opt(h, m, 0).unwrap()
+    }
+
+    fn period(weekdays: Vec<Weekday>, start: (u32, u32), end: (u32, u32), scale: f32) -> Period {
+        Period {
+            weekdays,
+            start: NaiveTime::from_hms_opt(start.0, start.1, 0).unwrap(),
+            end: NaiveTime::from_hms_opt(end.0, end.1, 0).unwrap(),
+            simultaneous_sounds_scale: scale,
+        }
+    }
+
+    #[test]
+    fn wrapped_period_continues_into_next_day() {
+        let p = period(vec![Weekday::Mon], (22, 0), (2, 0), 1.0);
+        assert!(!p.contains(&at(18, 21, 59)));
+        assert!(p.contains(&at(18, 22, 0)));
+        assert!(p.contains(&at(18, 23, 30)));
+        assert!(p.contains(&at(19, 0, 0)));
+        assert!(p.contains(&at(19, 1, 59)));
+        assert!(!p.contains(&at(19, 2, 0)));
+        // The early hours of Monday belong to a period beginning on Sunday.
+        assert!(!p.contains(&at(18, 1, 0)));
+        // The period does not begin on Tuesday.
+        assert!(!p.contains(&at(19, 23, 0)));
+    }
+
+    #[test]
+    fn closure_overlapping_wrapped_period() {
+        let schedule = Schedule {
+            periods: vec![period(vec![], (22, 0), (2, 0), 1.0)],
+            closures: vec![Closure {
+                first: NaiveDate::from_ymd_opt(2019, 3, 19).unwrap(),
+                last: NaiveDate::from_ymd_opt(2019, 3, 19).unwrap(),
+            }],
+        };
+        assert_eq!(schedule.evaluate(&at(18, 23, 0)), Some(1.0));
+        // Closures apply to the date of the given time, even after midnight within a period that
+        // began on an open day.
+        assert_eq!(schedule.evaluate(&at(19, 1, 0)), None);
+        assert_eq!(schedule.evaluate(&at(19, 23, 0)), None);
+        assert_eq!(schedule.evaluate(&at(20, 1, 0)), Some(1.0));
+    }
+
+    #[test]
+    fn equal_start_and_end_lasts_whole_day() {
+        let p = period(vec![Weekday::Mon], (9, 0), (9, 0), 1.0);
+        assert!(p.contains(&at(18, 0, 0)));
+        assert!(p.contains(&at(18, 8, 59)));
+        assert!(p.contains(&at(18, 9, 0)));
+        assert!(p.contains(&at(18, 23, 59)));
+        assert!(!p.contains(&at(19, 0, 0)));
+        assert!(!p.contains(&at(17, 23, 59)));
+    }
+
+    #[test]
+    fn overlapping_periods_use_greatest_scale() {
+        let schedule = Schedule {
+            periods: vec![
+                period(vec![], (9, 0), (17, 0), 0.5),
+                period(vec![], (12, 0), (14, 0), 2.0),
+            ],
+            closures: vec![],
+        };
+        assert_eq!(schedule.evaluate(&at(18, 10, 0)), Some(0.5));
+        assert_eq!(schedule.evaluate(&at(18, 13, 0)), Some(2.0));
+        assert_eq!(schedule.evaluate(&at(18, 14, 0)), Some(0.5));
+        assert_eq!(schedule.evaluate(&at(18, 18, 0)), None);
+        assert_eq!(Schedule::default().evaluate(&at(18, 18, 0)), Some(1.0));
+    }
+}
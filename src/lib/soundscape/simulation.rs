@@ -0,0 +1,382 @@
+//! Accelerated simulation of the soundscape against a virtual clock.
+//!
+//! A `Simulation` drives a soundscape `Model` with `Tick`s generated from a virtual clock rather
+//! than the ticker thread, so that many hours of soundscape may be generated within seconds. No
+//! audio is rendered. Instead, every spawned sound is written to an event log as JSON lines,
+//! allowing the constraints of a project to be checked before an exhibition goes live.
+//!
+//! The log contains one event per line:
+//!
+//! - `spawn`: the sound, source, group and installation along with the duration, playback rate,
+//!   movement kind and initial position of the sound.
+//! - `end`: the sound along with the path that it travelled, sampled once every `PATH_INTERVAL`.
+
+use audio;
+use chrono::{self, NaiveDateTime};
+use fxhash::FxHashMap;
+use installation;
+use serde_json;
+use soundscape::{group, Group, Model, Movement, Output, Rule, Source, Speaker, Tick};
+use std::hash::Hash;
+use std::io::{self, Write};
+use std::sync::atomic::AtomicUsize;
+use std::sync::Arc;
+use std::time;
+use time_calc::{Ms, Samples};
+use utils::{duration_to_secs, Seed};
+
+/// The interval of virtual time at which the path of each sound is sampled.
+pub const PATH_INTERVAL: time::Duration = time::Duration::from_secs(1);
+
+/// Parameters describing the virtual clock against which a simulation is run.
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// The local date and time at which the simulation begins.
+    ///
+    /// Used to evaluate the schedules of groups and sources.
+    pub start: NaiveDateTime,
+    /// The duration of virtual time to simulate.
+    pub duration: time::Duration,
+    /// The virtual time between each tick.
+    pub tick_interval: time::Duration,
+}
+
+/// An accelerated, silent run of the soundscape.
+pub struct Simulation {
+    model: Model,
+    config: Config,
+}
+
+/// A summary of a completed simulation.
+#[derive(Clone, Debug)]
+pub struct Summary {
+    /// The total number of sounds spawned.
+    pub sounds: usize,
+    /// The greatest number of sounds that were active at once.
+    pub max_simultaneous_sounds: usize,
+}
+
+/// An event written to the log of a simulation.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    Spawn(Spawn),
+    End(End),
+}
+
+/// A sound was spawned.
+#[derive(Clone, Debug, Serialize)]
+pub struct Spawn {
+    /// A unique index for the sound within the simulation.
+    pub sound: u64,
+    /// Seconds of virtual time since the start of the simulation.
+    pub secs: f64,
+    /// The virtual local date and time.
+    pub local_time: NaiveDateTime,
+    pub source_id: audio::source::Id,
+    pub source: String,
+    pub group_id: group::Id,
+    pub group: String,
+    pub installation_id: installation::Id,
+    pub installation: String,
+    /// The duration for which the sound is expected to play in seconds.
+    pub duration_secs: f64,
+    pub playback_rate: f64,
+    /// The kind of movement assigned to the sound.
+    pub movement: &'static str,
+    pub position: audio::sound::Position,
+}
+
+/// A sound ended.
+#[derive(Clone, Debug, Serialize)]
+pub struct End {
+    /// The index of the sound given in its `Spawn` event.
+    pub sound: u64,
+    /// Seconds of virtual time since the start of the simulation.
+    pub secs: f64,
+    /// The virtual local date and time.
+    pub local_time: NaiveDateTime,
+    /// The path of the sound sampled once every `PATH_INTERVAL`.
+    pub path: Vec<PathPoint>,
+}
+
+/// The position of a sound at some moment during a simulation.
+#[derive(Clone, Debug, Serialize)]
+pub struct PathPoint {
+    /// Seconds of virtual time since the start of the simulation.
+    pub secs: f64,
+    /// The position in metres.
+    pub x: f64,
+    pub y: f64,
+}
+
+/// Records the sounds spawned by a simulated soundscape in place of the audio streams.
+pub struct Recorder {
+    names: Names,
+    next_index: u64,
+    sounds: FxHashMap<audio::sound::Id, Record>,
+    events: Vec<Event>,
+}
+
+// Human-readable names for the log.
+#[derive(Default)]
+struct Names {
+    installations: FxHashMap<installation::Id, String>,
+    groups: FxHashMap<group::Id, String>,
+    sources: FxHashMap<audio::source::Id, String>,
+}
+
+// A sound that is currently playing within the simulation.
+struct Record {
+    index: u64,
+    ends_at: time::Duration,
+    path: Vec<PathPoint>,
+    next_path_point: time::Duration,
+}
+
+impl Simulation {
+    /// Create a simulation with no installations, groups, speakers or sources.
+    pub fn new(seed: Seed, config: Config) -> Self {
+        let frame_count = Arc::new(AtomicUsize::new(0));
+        let sound_id_gen = audio::sound::IdGenerator::new();
+        let recorder = Recorder {
+            names: Default::default(),
+            next_index: 0,
+            sounds: Default::default(),
+            events: vec![],
+        };
+        let output = Output::Simulation(recorder);
+        let model = Model::new(frame_count, seed, sound_id_gen, output, None);
+        Simulation { model, config }
+    }
+
+    /// Insert an installation along with its name.
+    pub fn insert_installation(
+        &mut self,
+        id: installation::Id,
+        name: String,
+        installation: installation::Soundscape,
+    ) {
+        self.recorder_mut().names.installations.insert(id, name);
+        self.model.insert_installation(id, installation);
+    }
+
+    /// Insert a group along with its name.
+    pub fn insert_group(&mut self, id: group::Id, name: String, group: Group) {
+        self.recorder_mut().names.groups.insert(id, name);
+        self.model.insert_group(id, group);
+    }
+
+    /// Insert a speaker.
+    pub fn insert_speaker(&mut self, id: audio::speaker::Id, speaker: Speaker) {
+        self.model.insert_speaker(id, speaker);
+    }
+
+    /// Replace the rules constraining when the sounds of sources may play.
+    pub fn set_rules(&mut self, rules: Vec<Rule>) {
+        self.model.set_rules(rules);
+    }
+
+    /// Insert a source along with its name.
+    pub fn insert_source(&mut self, id: audio::source::Id, name: String, source: Source) {
+        self.recorder_mut().names.sources.insert(id, name);
+        self.model.insert_source(id, source);
+    }
+
+    /// Run the simulation to completion, writing each event to the given writer as a JSON line.
+    pub fn run<W>(mut self, writer: W) -> io::Result<Summary>
+    where
+        W: Write,
+    {
+        let mut writer = io::BufWriter::new(writer);
+        let start_instant = time::Instant::now();
+        let mut elapsed = time::Duration::from_secs(0);
+        let mut summary = Summary { sounds: 0, max_simultaneous_sounds: 0 };
+        let mut ended = vec![];
+        while elapsed < self.config.duration {
+            elapsed += self.config.tick_interval;
+            let tick = self.tick(start_instant, elapsed);
+
+            // End all sounds that have finished playing.
+            self.recorder_mut().end_sounds(&tick, &mut ended);
+            for id in ended.drain(..) {
+                self.model.remove_active_sound(&id);
+            }
+
+            super::tick(&mut self.model, tick);
+            let active = self.model.active_sounds.len();
+            summary.max_simultaneous_sounds = summary.max_simultaneous_sounds.max(active);
+            summary.sounds += self.write_events(&mut writer)?;
+        }
+
+        // End all sounds that are still playing at the end of the simulation.
+        let tick = self.tick(start_instant, elapsed);
+        self.recorder_mut().end_all_sounds(&tick);
+        self.write_events(&mut writer)?;
+        writer.flush()?;
+        Ok(summary)
+    }
+
+    // Create the tick for the given moment of virtual time.
+    fn tick(&self, start_instant: time::Instant, elapsed: time::Duration) -> Tick {
+        let offset = chrono::Duration::from_std(elapsed).expect("simulation duration too long");
+        Tick {
+            instant: start_instant + elapsed,
+            since_last_tick: self.config.tick_interval,
+            playback_duration: elapsed,
+            local_time: self.config.start + offset,
+        }
+    }
+
+    // Write all pending events, returning the number of spawned sounds.
+    fn write_events<W>(&mut self, writer: &mut W) -> io::Result<usize>
+    where
+        W: Write,
+    {
+        let mut spawned = 0;
+        for event in self.recorder_mut().events.drain(..) {
+            if let Event::Spawn(_) = event {
+                spawned += 1;
+            }
+            serde_json::to_writer(&mut *writer, &event)?;
+            writeln!(writer)?;
+        }
+        Ok(spawned)
+    }
+
+    fn recorder_mut(&mut self) -> &mut Recorder {
+        match self.model.output {
+            Output::Simulation(ref mut recorder) => recorder,
+            Output::Audio { .. } => unreachable!("simulation model must output to a recorder"),
+        }
+    }
+}
+
+impl Recorder {
+    /// Record a newly spawned sound, returning a handle that is not rendered by any audio stream.
+    pub fn spawn(
+        &mut self,
+        sound_id: audio::sound::Id,
+        source_id: audio::source::Id,
+        source: &audio::Source,
+        installation: installation::Id,
+        group: group::Id,
+        tick: &Tick,
+        position: audio::sound::Position,
+        playback_rate: f64,
+        duration_frames: Samples,
+        movement: &Movement,
+    ) -> audio::sound::Handle {
+        // Non-looping WAVs end early if they run out of frames.
+        let mut duration = duration_frames.to_ms(audio::SAMPLE_RATE);
+        if let audio::source::Kind::Wav(ref wav) = source.kind {
+            if !wav.should_loop {
+                let wav_duration = Ms(wav.duration.to_ms(wav.sample_hz).ms() / playback_rate);
+                if wav_duration < duration {
+                    duration = wav_duration;
+                }
+            }
+        }
+
+        let index = self.next_index;
+        self.next_index += 1;
+        let spawn = Spawn {
+            sound: index,
+            secs: duration_to_secs(&tick.playback_duration),
+            local_time: tick.local_time,
+            source_id,
+            source: name(&self.names.sources, source_id),
+            group_id: group,
+            group: name(&self.names.groups, group),
+            installation_id: installation,
+            installation: name(&self.names.installations, installation),
+            duration_secs: duration.ms() / 1_000.0,
+            playback_rate,
+            movement: movement.kind(),
+            position,
+        };
+        self.events.push(Event::Spawn(spawn));
+
+        let duration = time::Duration::from_millis(duration.ms().max(0.0) as u64);
+        let record = Record {
+            index,
+            ends_at: tick.playback_duration + duration,
+            path: vec![],
+            next_path_point: tick.playback_duration,
+        };
+        self.sounds.insert(sound_id, record);
+        audio::sound::Handle::detached(sound_id, source_id)
+    }
+
+    /// Record the latest position of a sound, sampling it into its path at the `PATH_INTERVAL`.
+    pub fn update_position(
+        &mut self,
+        sound_id: audio::sound::Id,
+        tick: &Tick,
+        position: audio::sound::Position,
+    ) {
+        let record = match self.sounds.get_mut(&sound_id) {
+            None => return,
+            Some(record) => record,
+        };
+        if tick.playback_duration < record.next_path_point {
+            return;
+        }
+        record.next_path_point = tick.playback_duration + PATH_INTERVAL;
+        let point = PathPoint {
+            secs: duration_to_secs(&tick.playback_duration),
+            x: position.point.x.0,
+            y: position.point.y.0,
+        };
+        record.path.push(point);
+    }
+
+    // Collect the sounds that have finished playing by the given tick.
+    fn end_sounds(&mut self, tick: &Tick, ended: &mut Vec<audio::sound::Id>) {
+        ended.extend(
+            self.sounds
+                .iter()
+                .filter(|&(_, record)| record.ends_at <= tick.playback_duration)
+                .map(|(&id, _)| id),
+        );
+        for id in ended.iter() {
+            let record = self.sounds.remove(id).expect("no record for ended sound");
+            self.end(record, tick);
+        }
+    }
+
+    // End all sounds that are still playing.
+    fn end_all_sounds(&mut self, tick: &Tick) {
+        let records: Vec<_> = self.sounds.drain().map(|(_, record)| record).collect();
+        for record in records {
+            self.end(record, tick);
+        }
+    }
+
+    fn end(&mut self, record: Record, tick: &Tick) {
+        let end = End {
+            sound: record.index,
+            secs: duration_to_secs(&tick.playback_duration),
+            local_time: tick.local_time,
+            path: record.path,
+        };
+        self.events.push(Event::End(end));
+    }
+}
+
+impl Config {
+    /// Simulate the given duration starting from the current local time.
+    pub fn from_now(duration: time::Duration) -> Self {
+        let start = chrono::Local::now().naive_local();
+        let tick_interval = time::Duration::from_millis(super::TICK_RATE_MS);
+        Config { start, duration, tick_interval }
+    }
+}
+
+// The name of the item with the given id, or an empty string if it is unnamed.
+fn name<K>(names: &FxHashMap<K, String>, id: K) -> String
+where
+    K: Eq + Hash,
+{
+    names.get(&id).cloned().unwrap_or_default()
+}